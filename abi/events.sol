@@ -0,0 +1,3 @@
+// Events of the shares contract. build.rs turns each declaration into an EthEvent
+// struct with its signature, topic0 and ABI JSON, see src/block_chain/utils.rs
+event Trade(address trader, address subject, bool isBuy, uint256 shareAmount, uint256 ethAmount, uint256 protocolEthAmount, uint256 subjectEthAmount, uint256 supply);
@@ -0,0 +1,158 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use sha3::{Digest, Keccak256};
+
+// Solidity declarations of the contract events the EVM sync decodes
+const EVENTS_FILE: &str = "abi/events.sol";
+
+struct Param {
+    kind: String,
+    name: String,
+    indexed: bool,
+}
+
+struct Event {
+    name: String,
+    params: Vec<Param>,
+}
+
+impl Event {
+    // Canonical signature hashed into topic0, e.g. `Trade(address,bool)`
+    fn signature(&self) -> String {
+        let kinds: Vec<&str> = self.params.iter().map(|param| param.kind.as_str()).collect();
+        format!("{}({})", self.name, kinds.join(","))
+    }
+}
+
+// Parse `event Name(type [indexed] name, ...);` declarations, `//` comments ignored
+fn parse_events(source: &str) -> Vec<Event> {
+    let source: String = source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    source
+        .split(';')
+        .map(str::trim)
+        .filter(|declaration| !declaration.is_empty())
+        .map(|declaration| {
+            let body = declaration
+                .strip_prefix("event ")
+                .unwrap_or_else(|| panic!("{}: expected an event declaration: {}", EVENTS_FILE, declaration));
+            let (name, params) = body
+                .trim_end()
+                .strip_suffix(')')
+                .and_then(|body| body.split_once('('))
+                .unwrap_or_else(|| panic!("{}: malformed event: {}", EVENTS_FILE, declaration));
+            let params = params
+                .split(',')
+                .map(str::trim)
+                .filter(|param| !param.is_empty())
+                .map(|param| match param.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    [kind, name] => Param { kind: kind.to_string(), name: name.to_string(), indexed: false },
+                    [kind, "indexed", name] => Param { kind: kind.to_string(), name: name.to_string(), indexed: true },
+                    _ => panic!("{}: parameters need a type and a name: {}", EVENTS_FILE, param),
+                })
+                .collect();
+            Event { name: name.trim().to_string(), params }
+        })
+        .collect()
+}
+
+// Rust type ethers decodes a Solidity type into
+fn rust_type(kind: &str) -> &'static str {
+    match kind {
+        "address" => "Address",
+        "bool" => "bool",
+        "string" => "String",
+        "bytes" => "Bytes",
+        "bytes32" => "[u8; 32]",
+        "uint256" => "U256",
+        "int256" => "I256",
+        "uint8" => "u8",
+        "uint16" => "u16",
+        "uint32" => "u32",
+        "uint64" => "u64",
+        "uint128" => "u128",
+        _ => panic!("{}: unsupported parameter type {}", EVENTS_FILE, kind),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !snake.is_empty() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn abi_json(event: &Event) -> String {
+    let inputs: Vec<String> = event
+        .params
+        .iter()
+        .map(|param| {
+            format!(
+                r#"{{"indexed":{},"internalType":"{}","name":"{}","type":"{}"}}"#,
+                param.indexed, param.kind, param.name, param.kind
+            )
+        })
+        .collect();
+    format!(r#"[{{"anonymous":false,"inputs":[{}],"name":"{}","type":"event"}}]"#, inputs.join(","), event.name)
+}
+
+// Struct, signature, topic0 and ABI constants of one event
+fn generate(event: &Event) -> String {
+    let signature = event.signature();
+    let topic = Keccak256::digest(signature.as_bytes());
+    let prefix = snake_case(&event.name).to_uppercase();
+    let declaration: Vec<String> = event
+        .params
+        .iter()
+        .map(|param| match param.indexed {
+            true => format!("{} indexed {}", param.kind, param.name),
+            false => format!("{} {}", param.kind, param.name),
+        })
+        .collect();
+
+    let mut code = String::new();
+    writeln!(code, "/// `{}` event of the shares contract", event.name).unwrap();
+    writeln!(code, "#[derive(Clone, Debug, PartialEq, Eq, EthEvent)]").unwrap();
+    writeln!(code, "#[ethevent(name = \"{}\", abi = \"{}({})\")]", event.name, event.name, declaration.join(", ")).unwrap();
+    writeln!(code, "pub struct {}Event {{", event.name).unwrap();
+    for param in &event.params {
+        if param.indexed {
+            writeln!(code, "    #[ethevent(indexed)]").unwrap();
+        }
+        writeln!(code, "    pub {}: {},", snake_case(&param.name), rust_type(&param.kind)).unwrap();
+    }
+    writeln!(code, "}}\n").unwrap();
+    writeln!(code, "/// Canonical signature of [`{}Event`]", event.name).unwrap();
+    writeln!(code, "pub const {}_SIGNATURE: &str = \"{}\";\n", prefix, signature).unwrap();
+    writeln!(code, "/// topic0 of [`{}Event`] logs, keccak256 of its signature", event.name).unwrap();
+    writeln!(code, "pub const {}_TOPIC: [u8; 32] = {:?};\n", prefix, topic.as_slice()).unwrap();
+    writeln!(code, "/// ABI JSON of [`{}Event`]", event.name).unwrap();
+    writeln!(code, "pub const {}_ABI: &str = r#\"{}\"#;\n", prefix, abi_json(event)).unwrap();
+    code
+}
+
+fn main() {
+    // Rebuild when a migration is added, sqlx::migrate! embeds them at compile time
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed={}", EVENTS_FILE);
+
+    let source = fs::read_to_string(EVENTS_FILE).unwrap_or_else(|e| panic!("Failed to read {}: {}", EVENTS_FILE, e));
+    let code: String = parse_events(&source).iter().map(generate).collect();
+    let out = Path::new(&env::var("OUT_DIR").expect("OUT_DIR not set")).join("events.rs");
+    fs::write(&out, code).unwrap_or_else(|e| panic!("Failed to write {}: {}", out.display(), e));
+}
@@ -9,6 +9,11 @@ CREATE TABLE IF NOT EXISTS sync_status (
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
 );
 
+-- Databases created by the old init_db have these tables without the multi-chain columns
+ALTER TABLE sync_status ADD COLUMN IF NOT EXISTS chain_type VARCHAR(20) NOT NULL DEFAULT 'monad';
+ALTER TABLE sync_status ADD COLUMN IF NOT EXISTS created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE sync_status ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+
 -- 创建索引
 CREATE INDEX IF NOT EXISTS idx_sync_status_chain_type ON sync_status(chain_type);
 
@@ -24,6 +29,11 @@ CREATE TABLE IF NOT EXISTS trades (
     UNIQUE(trader, subject, chain_type)
 );
 
+ALTER TABLE trades ADD COLUMN IF NOT EXISTS chain_type VARCHAR(20) NOT NULL DEFAULT 'monad';
+ALTER TABLE trades ADD COLUMN IF NOT EXISTS created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE trades ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+CREATE UNIQUE INDEX IF NOT EXISTS trades_trader_subject_chain_type_key ON trades(trader, subject, chain_type);
+
 -- 创建索引
 CREATE INDEX IF NOT EXISTS idx_trades_trader ON trades(trader);
 CREATE INDEX IF NOT EXISTS idx_trades_subject ON trades(subject);
@@ -41,6 +51,11 @@ CREATE TABLE IF NOT EXISTS user_mappings (
     UNIQUE(address, chain_type)
 );
 
+ALTER TABLE user_mappings ADD COLUMN IF NOT EXISTS chain_type VARCHAR(20) NOT NULL DEFAULT 'monad';
+ALTER TABLE user_mappings ADD COLUMN IF NOT EXISTS created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE user_mappings ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+CREATE UNIQUE INDEX IF NOT EXISTS user_mappings_address_chain_type_key ON user_mappings(address, chain_type);
+
 -- 创建索引
 CREATE INDEX IF NOT EXISTS idx_user_mappings_address ON user_mappings(address);
 CREATE INDEX IF NOT EXISTS idx_user_mappings_telegram_id ON user_mappings(telegram_id);
@@ -58,6 +73,8 @@ CREATE TABLE IF NOT EXISTS telegram_bots (
     created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
 );
 
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS chain_type VARCHAR(20) NOT NULL DEFAULT 'monad';
+
 -- 创建索引
 CREATE INDEX IF NOT EXISTS idx_telegram_bots_subject_address ON telegram_bots(subject_address);
 CREATE INDEX IF NOT EXISTS idx_telegram_bots_chain_type ON telegram_bots(chain_type);
@@ -72,16 +89,19 @@ END;
 $$ language 'plpgsql';
 
 -- Add triggers to automatically update the modified time for tables that need it
+DROP TRIGGER IF EXISTS update_sync_status_modtime ON sync_status;
 CREATE TRIGGER update_sync_status_modtime
     BEFORE UPDATE ON sync_status
     FOR EACH ROW
     EXECUTE PROCEDURE update_modified_column();
 
+DROP TRIGGER IF EXISTS update_trades_modtime ON trades;
 CREATE TRIGGER update_trades_modtime
     BEFORE UPDATE ON trades
     FOR EACH ROW
     EXECUTE PROCEDURE update_modified_column();
 
+DROP TRIGGER IF EXISTS update_user_mappings_modtime ON user_mappings;
 CREATE TRIGGER update_user_mappings_modtime
     BEFORE UPDATE ON user_mappings
     FOR EACH ROW
@@ -0,0 +1,14 @@
+-- Verification sessions let a desktop page hand off signing to a mobile wallet via QR code
+CREATE TABLE IF NOT EXISTS verification_sessions (
+    id VARCHAR(64) PRIMARY KEY,
+    telegram_id VARCHAR(50) NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL DEFAULT 'monad',
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    completed_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_verification_sessions_status ON verification_sessions(status);
+CREATE INDEX IF NOT EXISTS idx_verification_sessions_created_at ON verification_sessions(created_at);
@@ -0,0 +1,17 @@
+-- Agent lifecycle status: active, suspended or archived
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'active';
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS group_closed BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Log of moderation actions taken on behalf of an agent
+CREATE TABLE IF NOT EXISTS moderation_events (
+    id BIGSERIAL PRIMARY KEY,
+    agent_name VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    telegram_id VARCHAR(50),
+    action VARCHAR(50) NOT NULL,
+    details TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_moderation_events_agent_name ON moderation_events(agent_name);
+CREATE INDEX IF NOT EXISTS idx_moderation_events_created_at ON moderation_events(created_at);
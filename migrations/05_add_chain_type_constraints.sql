@@ -0,0 +1,15 @@
+-- Restrict chain_type columns to the chains known to ChainType
+ALTER TABLE sync_status DROP CONSTRAINT IF EXISTS chk_sync_status_chain_type;
+ALTER TABLE sync_status ADD CONSTRAINT chk_sync_status_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana'));
+
+ALTER TABLE trades DROP CONSTRAINT IF EXISTS chk_trades_chain_type;
+ALTER TABLE trades ADD CONSTRAINT chk_trades_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana'));
+
+ALTER TABLE user_mappings DROP CONSTRAINT IF EXISTS chk_user_mappings_chain_type;
+ALTER TABLE user_mappings ADD CONSTRAINT chk_user_mappings_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana'));
+
+ALTER TABLE telegram_bots DROP CONSTRAINT IF EXISTS chk_telegram_bots_chain_type;
+ALTER TABLE telegram_bots ADD CONSTRAINT chk_telegram_bots_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana'));
+
+ALTER TABLE verification_sessions DROP CONSTRAINT IF EXISTS chk_verification_sessions_chain_type;
+ALTER TABLE verification_sessions ADD CONSTRAINT chk_verification_sessions_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana'));
@@ -0,0 +1,14 @@
+-- Per-agent option to remove join/leave service messages and expired bot prompts
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS delete_service_messages BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Bot messages scheduled for deletion by the cleanup task
+CREATE TABLE IF NOT EXISTS bot_messages (
+    id BIGSERIAL PRIMARY KEY,
+    agent_name VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    message_id INTEGER NOT NULL,
+    delete_after TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_bot_messages_delete_after ON bot_messages(delete_after);
@@ -0,0 +1,2 @@
+-- Disabled bots stay registered but their Telegram task is not started
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS enabled BOOLEAN NOT NULL DEFAULT TRUE;
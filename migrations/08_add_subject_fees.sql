@@ -0,0 +1,11 @@
+-- Daily fee totals per subject, in the chain's smallest native unit
+CREATE TABLE IF NOT EXISTS subject_fees (
+    subject VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL DEFAULT 'monad' CHECK (chain_type IN ('monad', 'sui', 'solana')),
+    day DATE NOT NULL,
+    protocol_fee NUMERIC NOT NULL DEFAULT 0,
+    subject_fee NUMERIC NOT NULL DEFAULT 0,
+    trade_count BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (subject, chain_type, day)
+);
@@ -0,0 +1,5 @@
+-- Trigram indexes backing fuzzy agent search
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_telegram_bots_agent_name_trgm ON telegram_bots USING GIN (agent_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_telegram_bots_bio_trgm ON telegram_bots USING GIN (bio gin_trgm_ops);
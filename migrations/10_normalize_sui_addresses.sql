@@ -0,0 +1,55 @@
+-- Rewrite Sui addresses to the full 32-byte zero-padded lowercase hex form (no 0x prefix)
+
+-- trades: merge balances of rows that normalize to the same trader/subject
+CREATE TEMP TABLE sui_trades AS
+SELECT lpad(lower(regexp_replace(trader, '^0[xX]', '')), 64, '0') AS trader,
+       lpad(lower(regexp_replace(subject, '^0[xX]', '')), 64, '0') AS subject,
+       SUM(share_amount) AS share_amount,
+       MIN(created_at) AS created_at,
+       MAX(updated_at) AS updated_at
+FROM trades
+WHERE chain_type = 'sui'
+GROUP BY 1, 2;
+
+DELETE FROM trades WHERE chain_type = 'sui';
+
+INSERT INTO trades (trader, subject, share_amount, chain_type, created_at, updated_at)
+SELECT trader, subject, share_amount, 'sui', created_at, updated_at FROM sui_trades;
+
+DROP TABLE sui_trades;
+
+-- user_mappings: keep the most recent binding when duplicates collapse
+DELETE FROM user_mappings a
+USING user_mappings b
+WHERE a.chain_type = 'sui' AND b.chain_type = 'sui'
+  AND a.id < b.id
+  AND lpad(lower(regexp_replace(a.address, '^0[xX]', '')), 64, '0')
+    = lpad(lower(regexp_replace(b.address, '^0[xX]', '')), 64, '0');
+
+UPDATE user_mappings
+SET address = lpad(lower(regexp_replace(address, '^0[xX]', '')), 64, '0')
+WHERE chain_type = 'sui';
+
+-- telegram_bots
+UPDATE telegram_bots
+SET subject_address = lpad(lower(regexp_replace(subject_address, '^0[xX]', '')), 64, '0')
+WHERE chain_type = 'sui';
+
+-- subject_fees: merge daily totals that collapse onto the same subject
+CREATE TEMP TABLE sui_subject_fees AS
+SELECT lpad(lower(regexp_replace(subject, '^0[xX]', '')), 64, '0') AS subject,
+       day,
+       SUM(protocol_fee) AS protocol_fee,
+       SUM(subject_fee) AS subject_fee,
+       SUM(trade_count) AS trade_count,
+       MAX(updated_at) AS updated_at
+FROM subject_fees
+WHERE chain_type = 'sui'
+GROUP BY 1, 2;
+
+DELETE FROM subject_fees WHERE chain_type = 'sui';
+
+INSERT INTO subject_fees (subject, chain_type, day, protocol_fee, subject_fee, trade_count, updated_at)
+SELECT subject, 'sui', day, protocol_fee, subject_fee, trade_count, updated_at FROM sui_subject_fees;
+
+DROP TABLE sui_subject_fees;
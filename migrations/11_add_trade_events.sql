@@ -0,0 +1,22 @@
+-- Every decoded trade event, kept for audits and for rebuilding balances
+CREATE TABLE IF NOT EXISTS trade_events (
+    id BIGSERIAL PRIMARY KEY,
+    chain_type VARCHAR(20) NOT NULL DEFAULT 'monad' CHECK (chain_type IN ('monad', 'sui', 'solana')),
+    block_number BIGINT,
+    tx_hash VARCHAR(128) NOT NULL,
+    log_index BIGINT NOT NULL,
+    trader VARCHAR(66) NOT NULL,
+    subject VARCHAR(66) NOT NULL,
+    is_buy BOOLEAN NOT NULL,
+    share_amount NUMERIC NOT NULL,
+    eth_amount NUMERIC NOT NULL,
+    protocol_fee NUMERIC NOT NULL,
+    subject_fee NUMERIC NOT NULL,
+    supply NUMERIC NOT NULL,
+    block_time TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_trade_events_trader_subject ON trade_events(chain_type, trader, subject);
+CREATE INDEX IF NOT EXISTS idx_trade_events_subject ON trade_events(chain_type, subject);
+CREATE INDEX IF NOT EXISTS idx_trade_events_tx ON trade_events(chain_type, tx_hash, log_index);
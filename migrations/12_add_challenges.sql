@@ -0,0 +1,12 @@
+-- One-time nonces a wallet signs to prove ownership, consumed on verification
+CREATE TABLE IF NOT EXISTS challenges (
+    nonce VARCHAR(64) PRIMARY KEY,
+    telegram_id VARCHAR(50) NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL DEFAULT 'monad',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    consumed_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_challenges_expires_at ON challenges(expires_at);
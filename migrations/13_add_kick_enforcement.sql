@@ -0,0 +1,27 @@
+-- How holders who sell out are removed: 'mute' restricts them, 'kick' removes them from the group
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS enforcement_mode VARCHAR(10) NOT NULL DEFAULT 'mute';
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'telegram_bots_enforcement_mode_check') THEN
+        ALTER TABLE telegram_bots ADD CONSTRAINT telegram_bots_enforcement_mode_check
+            CHECK (enforcement_mode IN ('mute', 'kick'));
+    END IF;
+END $$;
+
+-- Kicked members waiting to be sent a single-use invite once they hold shares again
+CREATE TABLE IF NOT EXISTS rejoin_tokens (
+    token VARCHAR(64) PRIMARY KEY,
+    telegram_id VARCHAR(50) NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL DEFAULT 'monad',
+    address VARCHAR NOT NULL,
+    invite_link VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMP WITH TIME ZONE,
+    used_at TIMESTAMP WITH TIME ZONE
+);
+
+-- At most one open token per member and group
+CREATE UNIQUE INDEX IF NOT EXISTS idx_rejoin_tokens_open
+    ON rejoin_tokens(telegram_id, chat_id) WHERE used_at IS NULL;
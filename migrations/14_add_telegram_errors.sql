@@ -0,0 +1,9 @@
+-- Per-agent summary of failed Telegram API calls by error class
+CREATE TABLE IF NOT EXISTS telegram_errors (
+    agent_name VARCHAR NOT NULL,
+    kind VARCHAR(30) NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT NOT NULL,
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (agent_name, kind)
+);
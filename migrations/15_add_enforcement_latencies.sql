@@ -0,0 +1,11 @@
+-- Time from an on-chain trade to the Telegram action it triggered, our core SLA
+CREATE TABLE IF NOT EXISTS enforcement_latencies (
+    id BIGSERIAL PRIMARY KEY,
+    chain_type VARCHAR(20) NOT NULL,
+    agent_name VARCHAR NOT NULL,
+    action VARCHAR(20) NOT NULL,
+    latency_ms BIGINT NOT NULL,
+    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_enforcement_latencies_recorded_at ON enforcement_latencies(recorded_at);
@@ -0,0 +1,7 @@
+-- High-water mark of the last batch applied from each external indexer
+CREATE TABLE IF NOT EXISTS ingest_sources (
+    source_id VARCHAR(100) PRIMARY KEY,
+    high_water_mark BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,39 @@
+-- Decimals the stored share amounts of each contract have already been scaled by
+CREATE TABLE IF NOT EXISTS share_decimals (
+    chain_type VARCHAR(20) NOT NULL,
+    contract_address VARCHAR NOT NULL,
+    decimals INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_type, contract_address)
+);
+
+-- Rescale the stored share amounts and supplies of a chain from the decimals recorded for its
+-- contract to p_decimals. Runs at startup with the configured *_SHARE_DECIMALS and does nothing
+-- when they did not change, so rows stored as raw units are converted exactly once.
+CREATE OR REPLACE FUNCTION rescale_share_decimals(p_chain_type VARCHAR, p_contract_address VARCHAR, p_decimals INTEGER)
+RETURNS BOOLEAN AS $$
+DECLARE
+    applied INTEGER;
+    factor NUMERIC;
+BEGIN
+    INSERT INTO share_decimals (chain_type, contract_address)
+    VALUES (p_chain_type, p_contract_address)
+    ON CONFLICT (chain_type, contract_address) DO NOTHING;
+
+    SELECT decimals INTO applied FROM share_decimals
+    WHERE chain_type = p_chain_type AND contract_address = p_contract_address
+    FOR UPDATE;
+
+    IF applied = p_decimals THEN
+        RETURN FALSE;
+    END IF;
+
+    factor := power(10::NUMERIC, p_decimals - applied);
+    UPDATE trades SET share_amount = share_amount / factor WHERE chain_type = p_chain_type;
+    UPDATE trade_events SET share_amount = share_amount / factor, supply = supply / factor WHERE chain_type = p_chain_type;
+
+    UPDATE share_decimals SET decimals = p_decimals, updated_at = NOW()
+    WHERE chain_type = p_chain_type AND contract_address = p_contract_address;
+    RETURN TRUE;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,31 @@
+-- Messages a bot sends to a member after verification, in position order
+CREATE TABLE IF NOT EXISTS onboarding_steps (
+    id BIGSERIAL PRIMARY KEY,
+    agent_name VARCHAR NOT NULL,
+    position INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    -- Wait after the previous step (or the verification for the first one)
+    delay_secs INTEGER NOT NULL DEFAULT 0,
+    -- Pin the message in the member's chat with the bot, e.g. the rules link
+    pin BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (agent_name, position)
+);
+
+-- One row per step and verified member, tracks whether the step was delivered
+CREATE TABLE IF NOT EXISTS onboarding_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    step_id BIGINT NOT NULL,
+    agent_name VARCHAR NOT NULL,
+    telegram_id VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'failed')),
+    due_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    sent_at TIMESTAMP WITH TIME ZONE,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (step_id, telegram_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_onboarding_deliveries_due ON onboarding_deliveries(due_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_onboarding_deliveries_agent ON onboarding_deliveries(agent_name);
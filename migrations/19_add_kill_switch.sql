@@ -0,0 +1,23 @@
+-- Runtime switches shared by every server instance
+CREATE TABLE IF NOT EXISTS service_flags (
+    name VARCHAR(50) PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    reason TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Restrict/kick actions that were not sent to Telegram because the kill switch was engaged
+CREATE TABLE IF NOT EXISTS held_enforcement_actions (
+    id BIGSERIAL PRIMARY KEY,
+    chain_type VARCHAR(20) NOT NULL,
+    agent_name VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    telegram_id VARCHAR NOT NULL,
+    address VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    action VARCHAR(20) NOT NULL,
+    balance NUMERIC NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_held_enforcement_actions_created_at ON held_enforcement_actions(created_at);
@@ -0,0 +1,14 @@
+-- Members who joined a gated group and have not verified yet
+CREATE TABLE IF NOT EXISTS pending_verifications (
+    telegram_id VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    agent_name VARCHAR NOT NULL,
+    -- pending until the member verifies, leaves or is removed after the timeout
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'verified', 'left', 'removed')),
+    joined_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    warned_at TIMESTAMP WITH TIME ZONE,
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (telegram_id, chat_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_verifications_joined_at ON pending_verifications(joined_at) WHERE status = 'pending';
@@ -0,0 +1,16 @@
+-- Wallet bindings proven by sending a transaction that carries a memo, for wallets that cannot sign messages
+CREATE TABLE IF NOT EXISTS pending_bindings (
+    memo VARCHAR(64) PRIMARY KEY,
+    telegram_id VARCHAR(50) NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL DEFAULT 'monad',
+    address VARCHAR NOT NULL,
+    -- pending until sync sees the transaction, bound once it did
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'bound')),
+    tx_hash VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    bound_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_bindings_chain ON pending_bindings(chain_type, expires_at) WHERE status = 'pending';
@@ -0,0 +1,4 @@
+-- Hash of the last synced block, EVM sync compares it with the chain to detect reorgs
+ALTER TABLE sync_status ADD COLUMN IF NOT EXISTS block_hash VARCHAR(66);
+
+CREATE INDEX IF NOT EXISTS idx_sync_status_block_hash ON sync_status(chain_type, last_synced_block DESC) WHERE block_hash IS NOT NULL;
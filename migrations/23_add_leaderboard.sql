@@ -0,0 +1,9 @@
+-- Telegram usernames last seen by the bots, shown next to holders on leaderboards
+CREATE TABLE IF NOT EXISTS telegram_users (
+    telegram_id VARCHAR PRIMARY KEY,
+    username VARCHAR(64),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Top holders of a subject
+CREATE INDEX IF NOT EXISTS idx_trades_subject_share_amount ON trades(chain_type, subject, share_amount DESC) WHERE share_amount > 0;
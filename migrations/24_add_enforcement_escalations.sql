@@ -0,0 +1,25 @@
+-- Escalation ladder of an agent as [{"action": "warn|read_only|kick", "delay_secs": n}],
+-- NULL restricts holders who sold out right away following enforcement_mode
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS escalation_ladder TEXT;
+
+-- Holders who sold out and are being walked up their agent's ladder
+CREATE TABLE IF NOT EXISTS enforcement_escalations (
+    id BIGSERIAL PRIMARY KEY,
+    agent_name VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    telegram_id VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL CHECK (chain_type IN ('monad', 'sui', 'solana')),
+    address VARCHAR(66) NOT NULL,
+    subject VARCHAR(66) NOT NULL,
+    -- Index of the ladder step applied next
+    next_step INT NOT NULL DEFAULT 0,
+    next_run_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- active until the last step ran (completed) or the balance recovered (cancelled)
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'completed', 'cancelled')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_enforcement_escalations_active ON enforcement_escalations(chat_id, telegram_id) WHERE status = 'active';
+CREATE INDEX IF NOT EXISTS idx_enforcement_escalations_due ON enforcement_escalations(next_run_at) WHERE status = 'active';
+CREATE INDEX IF NOT EXISTS idx_enforcement_escalations_holding ON enforcement_escalations(chain_type, address, subject) WHERE status = 'active';
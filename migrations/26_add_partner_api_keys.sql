@@ -0,0 +1,6 @@
+-- Partner keys only reach the introspection endpoint, limited to the listed subjects (NULL for every subject)
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS role VARCHAR(20) NOT NULL DEFAULT 'admin';
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS subjects TEXT[];
+
+ALTER TABLE api_keys DROP CONSTRAINT IF EXISTS chk_api_keys_role;
+ALTER TABLE api_keys ADD CONSTRAINT chk_api_keys_role CHECK (role IN ('admin', 'partner'));
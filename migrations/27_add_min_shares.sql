@@ -0,0 +1,5 @@
+-- Shares a member must hold to chat in an agent's group
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS min_shares NUMERIC NOT NULL DEFAULT 1;
+
+ALTER TABLE telegram_bots DROP CONSTRAINT IF EXISTS chk_telegram_bots_min_shares;
+ALTER TABLE telegram_bots ADD CONSTRAINT chk_telegram_bots_min_shares CHECK (min_shares > 0);
@@ -0,0 +1,8 @@
+-- Why a trade event is suspected to be a wash trade, NULL when it looks organic:
+-- self_round_trip (its trader traded the subject the other way shortly before or after) or
+-- mirrored_pair (two wallets passed the same amount back and forth between them)
+ALTER TABLE trade_events ADD COLUMN IF NOT EXISTS wash_reason VARCHAR(30);
+
+-- Trades of a subject near a new one, searched when tagging it
+CREATE INDEX IF NOT EXISTS idx_trade_events_subject_block ON trade_events(chain_type, subject, block_number);
+CREATE INDEX IF NOT EXISTS idx_trade_events_wash_traders ON trade_events(chain_type, subject, trader) WHERE wash_reason IS NOT NULL;
@@ -0,0 +1,22 @@
+-- Whether members need shares of 'any' or 'all' of the subjects gating their group
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS subject_rule VARCHAR(10) NOT NULL DEFAULT 'any';
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'telegram_bots_subject_rule_check') THEN
+        ALTER TABLE telegram_bots ADD CONSTRAINT telegram_bots_subject_rule_check
+            CHECK (subject_rule IN ('any', 'all'));
+    END IF;
+END $$;
+
+-- Subjects gating an agent's group besides its subject_address, on the agent's chain
+CREATE TABLE IF NOT EXISTS group_subjects (
+    agent_name VARCHAR NOT NULL REFERENCES telegram_bots(agent_name) ON DELETE CASCADE,
+    chain_type VARCHAR(20) NOT NULL,
+    subject_address VARCHAR NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (agent_name, subject_address)
+);
+
+-- Trades of a subject look up the groups it gates
+CREATE INDEX IF NOT EXISTS idx_group_subjects_subject ON group_subjects(chain_type, subject_address);
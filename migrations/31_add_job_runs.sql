@@ -0,0 +1,22 @@
+-- Long running jobs such as `alice_ai_server backfill`, so their progress is visible to the API
+CREATE TABLE IF NOT EXISTS job_runs (
+    id BIGSERIAL PRIMARY KEY,
+    -- Job type, backfill
+    kind VARCHAR(30) NOT NULL,
+    chain_type VARCHAR(20) NOT NULL,
+    -- First and last position of the job's range, block numbers for a backfill
+    start_position BIGINT NOT NULL,
+    end_position BIGINT NOT NULL,
+    -- Last position whose work is done, NULL until the first batch is
+    current_position BIGINT,
+    events_stored BIGINT NOT NULL DEFAULT 0,
+    events_skipped BIGINT NOT NULL DEFAULT 0,
+    -- running until the job finishes (completed) or gives up (failed)
+    status VARCHAR(20) NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'failed')),
+    error TEXT,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_runs_kind_started ON job_runs(kind, started_at DESC);
@@ -0,0 +1,3 @@
+-- Daily window without non-urgent DMs as {"start_hour": n, "end_hour": n, "utc_offset_minutes": n},
+-- NULL sends them at any time
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS quiet_hours TEXT;
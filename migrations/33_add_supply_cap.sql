@@ -0,0 +1,2 @@
+-- Most shares the subject's contract lets exist, in whole shares; NULL when supply is not capped
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS supply_cap NUMERIC;
@@ -0,0 +1,8 @@
+-- Id assigned to each chain event (or other cause of enforcement), carried over to the
+-- escalations and moderation events it leads to so a Telegram action can be traced back to it
+ALTER TABLE trade_events ADD COLUMN IF NOT EXISTS trace_id VARCHAR(32);
+ALTER TABLE enforcement_escalations ADD COLUMN IF NOT EXISTS trace_id VARCHAR(32);
+ALTER TABLE moderation_events ADD COLUMN IF NOT EXISTS trace_id VARCHAR(32);
+
+CREATE INDEX IF NOT EXISTS idx_trade_events_trace ON trade_events(trace_id) WHERE trace_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_moderation_events_trace ON moderation_events(trace_id) WHERE trace_id IS NOT NULL;
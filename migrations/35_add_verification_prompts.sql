@@ -0,0 +1,7 @@
+-- Sign-link prompt sent to each pending member, so prompts lost to a restart can be sent again.
+-- A NULL prompt_sent_at means the member joined but their prompt never went out; rows that
+-- predate this column are taken as prompted
+ALTER TABLE pending_verifications ADD COLUMN IF NOT EXISTS prompt_sent_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+ALTER TABLE pending_verifications ALTER COLUMN prompt_sent_at DROP DEFAULT;
+ALTER TABLE pending_verifications ADD COLUMN IF NOT EXISTS prompt_session_id VARCHAR(64);
+ALTER TABLE pending_verifications ADD COLUMN IF NOT EXISTS prompt_message_id INTEGER;
@@ -0,0 +1,5 @@
+-- 'view_only' groups let members below the threshold read (and invite others) without posting,
+-- and never remove them
+ALTER TABLE telegram_bots DROP CONSTRAINT IF EXISTS telegram_bots_enforcement_mode_check;
+ALTER TABLE telegram_bots ADD CONSTRAINT telegram_bots_enforcement_mode_check
+    CHECK (enforcement_mode IN ('mute', 'kick', 'view_only'));
@@ -0,0 +1,10 @@
+-- Each chain event is applied once: a sync loop restarting mid-batch replays the overlapping
+-- blocks, and the replayed events must not be counted twice. Duplicates stored before this
+-- key existed are dropped, keeping the first copy; the balance reconciliation corrects the
+-- share amounts they added
+DELETE FROM trade_events a
+    USING trade_events b
+    WHERE a.chain_type = b.chain_type AND a.tx_hash = b.tx_hash AND a.log_index = b.log_index AND a.id > b.id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_trade_events_event ON trade_events(chain_type, tx_hash, log_index);
+DROP INDEX IF EXISTS idx_trade_events_tx;
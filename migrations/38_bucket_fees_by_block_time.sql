@@ -0,0 +1,32 @@
+-- subject_fees were totalled under the day a trade was stored, so backfilled events and those
+-- synced after midnight landed on the wrong day. Totals are now kept under the UTC day of the
+-- trade's block: move the logged trades whose block falls on another day than they were stored
+CREATE TEMP TABLE moved_fees AS
+SELECT subject, chain_type, (created_at AT TIME ZONE 'UTC')::date AS stored_day, (block_time AT TIME ZONE 'UTC')::date AS block_day,
+       protocol_fee, subject_fee
+FROM trade_events
+WHERE block_time IS NOT NULL AND (created_at AT TIME ZONE 'UTC')::date <> (block_time AT TIME ZONE 'UTC')::date;
+
+UPDATE subject_fees f
+SET protocol_fee = f.protocol_fee - m.protocol_fee,
+    subject_fee = f.subject_fee - m.subject_fee,
+    trade_count = f.trade_count - m.trades,
+    updated_at = CURRENT_TIMESTAMP
+FROM (SELECT subject, chain_type, stored_day, SUM(protocol_fee) AS protocol_fee,
+             SUM(subject_fee) AS subject_fee, COUNT(*) AS trades
+      FROM moved_fees GROUP BY subject, chain_type, stored_day) m
+WHERE f.subject = m.subject AND f.chain_type = m.chain_type AND f.day = m.stored_day;
+
+INSERT INTO subject_fees (subject, chain_type, day, protocol_fee, subject_fee, trade_count)
+SELECT subject, chain_type, block_day, SUM(protocol_fee), SUM(subject_fee), COUNT(*)
+FROM moved_fees
+GROUP BY subject, chain_type, block_day
+ON CONFLICT (subject, chain_type, day)
+DO UPDATE SET protocol_fee = subject_fees.protocol_fee + EXCLUDED.protocol_fee,
+              subject_fee = subject_fees.subject_fee + EXCLUDED.subject_fee,
+              trade_count = subject_fees.trade_count + EXCLUDED.trade_count,
+              updated_at = CURRENT_TIMESTAMP;
+
+DELETE FROM subject_fees WHERE trade_count <= 0;
+
+DROP TABLE moved_fees;
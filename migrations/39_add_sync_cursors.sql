@@ -0,0 +1,16 @@
+-- Position of chains indexed by event cursor rather than block (Sui), as the cursor JSON the
+-- chain's RPC takes back. Replaces the digest prefix and metadata kept in sync_status
+CREATE TABLE IF NOT EXISTS sync_cursors (
+    chain_type VARCHAR(20) PRIMARY KEY CHECK (chain_type IN ('monad', 'sui', 'solana')),
+    cursor_json TEXT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO sync_cursors (chain_type, cursor_json, updated_at)
+SELECT DISTINCT ON (chain_type) chain_type, metadata, COALESCE(updated_at, CURRENT_TIMESTAMP)
+FROM sync_status
+WHERE chain_type = 'sui' AND metadata LIKE '{%'
+ORDER BY chain_type, id DESC
+ON CONFLICT (chain_type) DO NOTHING;
+
+DELETE FROM sync_status WHERE chain_type = 'sui';
@@ -0,0 +1,28 @@
+-- Allow Aptos in every chain_type column
+ALTER TABLE sync_status DROP CONSTRAINT IF EXISTS chk_sync_status_chain_type;
+ALTER TABLE sync_status ADD CONSTRAINT chk_sync_status_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE trades DROP CONSTRAINT IF EXISTS chk_trades_chain_type;
+ALTER TABLE trades ADD CONSTRAINT chk_trades_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE user_mappings DROP CONSTRAINT IF EXISTS chk_user_mappings_chain_type;
+ALTER TABLE user_mappings ADD CONSTRAINT chk_user_mappings_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE telegram_bots DROP CONSTRAINT IF EXISTS chk_telegram_bots_chain_type;
+ALTER TABLE telegram_bots ADD CONSTRAINT chk_telegram_bots_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE verification_sessions DROP CONSTRAINT IF EXISTS chk_verification_sessions_chain_type;
+ALTER TABLE verification_sessions ADD CONSTRAINT chk_verification_sessions_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+-- Constraints declared inline with their tables, under the names Postgres gave them
+ALTER TABLE subject_fees DROP CONSTRAINT IF EXISTS subject_fees_chain_type_check;
+ALTER TABLE subject_fees ADD CONSTRAINT subject_fees_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE trade_events DROP CONSTRAINT IF EXISTS trade_events_chain_type_check;
+ALTER TABLE trade_events ADD CONSTRAINT trade_events_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE enforcement_escalations DROP CONSTRAINT IF EXISTS enforcement_escalations_chain_type_check;
+ALTER TABLE enforcement_escalations ADD CONSTRAINT enforcement_escalations_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
+
+ALTER TABLE sync_cursors DROP CONSTRAINT IF EXISTS sync_cursors_chain_type_check;
+ALTER TABLE sync_cursors ADD CONSTRAINT sync_cursors_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos'));
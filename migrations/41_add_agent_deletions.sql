@@ -0,0 +1,22 @@
+-- Audit log of agent deletions. Deleting an agent removes its bot right away and opens an export
+-- window in which the creator can download the agent's members, trades and analytics; once it
+-- ends the retention job purges the agent's remaining rows and records when it did
+CREATE TABLE IF NOT EXISTS agent_deletions (
+    id BIGSERIAL PRIMARY KEY,
+    agent_name VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL,
+    chat_group_id VARCHAR NOT NULL,
+    -- Every subject that gated the group, its subject_address first
+    subjects TEXT[] NOT NULL,
+    requested_by VARCHAR NOT NULL,
+    requested_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    export_until TIMESTAMP WITH TIME ZONE NOT NULL,
+    export_count INTEGER NOT NULL DEFAULT 0,
+    last_exported_by VARCHAR,
+    last_exported_at TIMESTAMP WITH TIME ZONE,
+    purged_at TIMESTAMP WITH TIME ZONE,
+    purged_rows BIGINT
+);
+
+-- An agent name is held by at most one deletion until it is purged
+CREATE UNIQUE INDEX IF NOT EXISTS idx_agent_deletions_open ON agent_deletions(agent_name) WHERE purged_at IS NULL;
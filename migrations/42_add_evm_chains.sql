@@ -0,0 +1,42 @@
+-- Allow Base and Arbitrum in every chain_type column
+ALTER TABLE sync_status DROP CONSTRAINT IF EXISTS chk_sync_status_chain_type;
+ALTER TABLE sync_status ADD CONSTRAINT chk_sync_status_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE trades DROP CONSTRAINT IF EXISTS chk_trades_chain_type;
+ALTER TABLE trades ADD CONSTRAINT chk_trades_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE user_mappings DROP CONSTRAINT IF EXISTS chk_user_mappings_chain_type;
+ALTER TABLE user_mappings ADD CONSTRAINT chk_user_mappings_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE telegram_bots DROP CONSTRAINT IF EXISTS chk_telegram_bots_chain_type;
+ALTER TABLE telegram_bots ADD CONSTRAINT chk_telegram_bots_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE verification_sessions DROP CONSTRAINT IF EXISTS chk_verification_sessions_chain_type;
+ALTER TABLE verification_sessions ADD CONSTRAINT chk_verification_sessions_chain_type CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+-- Constraints declared inline with their tables, under the names Postgres gave them
+ALTER TABLE subject_fees DROP CONSTRAINT IF EXISTS subject_fees_chain_type_check;
+ALTER TABLE subject_fees ADD CONSTRAINT subject_fees_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE trade_events DROP CONSTRAINT IF EXISTS trade_events_chain_type_check;
+ALTER TABLE trade_events ADD CONSTRAINT trade_events_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE enforcement_escalations DROP CONSTRAINT IF EXISTS enforcement_escalations_chain_type_check;
+ALTER TABLE enforcement_escalations ADD CONSTRAINT enforcement_escalations_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+ALTER TABLE sync_cursors DROP CONSTRAINT IF EXISTS sync_cursors_chain_type_check;
+ALTER TABLE sync_cursors ADD CONSTRAINT sync_cursors_chain_type_check CHECK (chain_type IN ('monad', 'sui', 'solana', 'aptos', 'base', 'arbitrum'));
+
+-- EVM networks synced besides the Monad network configured by the environment, a row
+-- for monad replaces that configuration
+CREATE TABLE IF NOT EXISTS evm_chains (
+    chain_type VARCHAR(20) PRIMARY KEY CHECK (chain_type IN ('monad', 'base', 'arbitrum')),
+    rpc_url TEXT NOT NULL,
+    ws_url TEXT,
+    contract_address TEXT NOT NULL,
+    start_block BIGINT NOT NULL CHECK (start_block >= 0),
+    confirmations BIGINT NOT NULL DEFAULT 3 CHECK (confirmations >= 0),
+    share_decimals INTEGER NOT NULL DEFAULT 0 CHECK (share_decimals >= 0),
+    price_id TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,50 @@
+-- Verified wallets whose balances currently satisfy their agent's gate, kept in step by
+-- the enforcement engine. Unbinding a wallet removes it, rebinding moves it along
+CREATE TABLE IF NOT EXISTS gated_members (
+    agent_name VARCHAR NOT NULL REFERENCES telegram_bots(agent_name) ON DELETE CASCADE,
+    address VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL,
+    telegram_id VARCHAR NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (agent_name, address, chain_type),
+    FOREIGN KEY (address, chain_type) REFERENCES user_mappings(address, chain_type) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_gated_members_address ON gated_members(address, chain_type);
+
+-- Number of gated_members rows of the agent, so listing agents needs no count per row
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS active_members INTEGER NOT NULL DEFAULT 0;
+
+CREATE OR REPLACE FUNCTION count_gated_members()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE telegram_bots SET active_members = active_members + 1 WHERE agent_name = NEW.agent_name;
+    ELSIF TG_OP = 'DELETE' THEN
+        UPDATE telegram_bots SET active_members = active_members - 1 WHERE agent_name = OLD.agent_name;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS gated_members_count ON gated_members;
+CREATE TRIGGER gated_members_count
+AFTER INSERT OR DELETE ON gated_members
+FOR EACH ROW EXECUTE FUNCTION count_gated_members();
+
+-- Members verified before the table existed, by the same rule the engine applies
+INSERT INTO gated_members (agent_name, address, chain_type, telegram_id)
+SELECT b.agent_name, u.address, u.chain_type, u.telegram_id
+FROM telegram_bots b
+JOIN user_mappings u ON u.chain_type = b.chain_type
+CROSS JOIN LATERAL (
+    SELECT COUNT(*) AS subjects, COUNT(*) FILTER (WHERE COALESCE(t.share_amount, 0) >= b.min_shares) AS held
+    FROM (
+        SELECT b.subject_address AS subject
+        UNION
+        SELECT g.subject_address FROM group_subjects g WHERE g.agent_name = b.agent_name
+    ) s
+    LEFT JOIN trades t ON t.trader = u.address AND t.subject = s.subject AND t.chain_type = b.chain_type
+) h
+WHERE (b.subject_rule = 'any' AND h.held > 0) OR (b.subject_rule = 'all' AND h.held = h.subjects)
+ON CONFLICT DO NOTHING;
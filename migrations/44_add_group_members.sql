@@ -0,0 +1,22 @@
+-- Telegram users seen in an agent's group, the roster admins page through
+CREATE TABLE IF NOT EXISTS group_members (
+    agent_name VARCHAR NOT NULL REFERENCES telegram_bots(agent_name) ON DELETE CASCADE,
+    telegram_id VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    -- unknown for members who verified before joins were recorded
+    joined_at TIMESTAMP WITH TIME ZONE,
+    left_at TIMESTAMP WITH TIME ZONE,
+    verified_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (agent_name, telegram_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_group_members_joined_at ON group_members(agent_name, joined_at DESC);
+
+-- Joins recorded so far only live on as verification timeouts
+INSERT INTO group_members (agent_name, telegram_id, chat_id, joined_at, left_at, verified_at)
+SELECT p.agent_name, p.telegram_id, p.chat_id, p.joined_at,
+       CASE WHEN p.status IN ('left', 'removed') THEN p.resolved_at END,
+       CASE WHEN p.status = 'verified' THEN p.resolved_at END
+FROM pending_verifications p
+JOIN telegram_bots b ON b.agent_name = p.agent_name
+ON CONFLICT DO NOTHING;
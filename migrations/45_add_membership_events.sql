@@ -0,0 +1,14 @@
+-- Every join and leave seen by an agent's bot, for audits of who was in a group when
+CREATE TABLE IF NOT EXISTS membership_events (
+    id BIGSERIAL PRIMARY KEY,
+    agent_name VARCHAR NOT NULL,
+    chat_id VARCHAR NOT NULL,
+    telegram_id VARCHAR NOT NULL,
+    username VARCHAR,
+    kind VARCHAR(10) NOT NULL CHECK (kind IN ('join', 'leave')),
+    -- time of the service message in Telegram
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_membership_events_chat ON membership_events(chat_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_membership_events_user ON membership_events(telegram_id, created_at DESC);
@@ -0,0 +1,5 @@
+-- Raw payload of Sui trade events as delivered by the RPC, so stored events can be decoded
+-- again after a parser fix
+ALTER TABLE trade_events ADD COLUMN IF NOT EXISTS raw_event_id TEXT;
+ALTER TABLE trade_events ADD COLUMN IF NOT EXISTS raw_bcs TEXT;
+ALTER TABLE trade_events ADD COLUMN IF NOT EXISTS raw_bcs_encoding VARCHAR(10);
@@ -0,0 +1,26 @@
+-- Immutable id of an agent, agent_name stays its unique but renamable name
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS agent_id VARCHAR(36) NOT NULL DEFAULT gen_random_uuid()::text;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_telegram_bots_agent_id ON telegram_bots(agent_id);
+
+-- Names agents went by before being renamed, old URLs are redirected to the current name
+CREATE TABLE IF NOT EXISTS agent_aliases (
+    alias VARCHAR NOT NULL PRIMARY KEY,
+    agent_id VARCHAR(36) NOT NULL REFERENCES telegram_bots(agent_id) ON DELETE CASCADE,
+    renamed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_agent_aliases_agent_id ON agent_aliases(agent_id);
+
+-- Rows referencing the bot row follow a rename
+ALTER TABLE webhooks DROP CONSTRAINT IF EXISTS webhooks_agent_name_fkey;
+ALTER TABLE webhooks ADD CONSTRAINT webhooks_agent_name_fkey
+    FOREIGN KEY (agent_name) REFERENCES telegram_bots(agent_name) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE group_subjects DROP CONSTRAINT IF EXISTS group_subjects_agent_name_fkey;
+ALTER TABLE group_subjects ADD CONSTRAINT group_subjects_agent_name_fkey
+    FOREIGN KEY (agent_name) REFERENCES telegram_bots(agent_name) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE gated_members DROP CONSTRAINT IF EXISTS gated_members_agent_name_fkey;
+ALTER TABLE gated_members ADD CONSTRAINT gated_members_agent_name_fkey
+    FOREIGN KEY (agent_name) REFERENCES telegram_bots(agent_name) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE group_members DROP CONSTRAINT IF EXISTS group_members_agent_name_fkey;
+ALTER TABLE group_members ADD CONSTRAINT group_members_agent_name_fkey
+    FOREIGN KEY (agent_name) REFERENCES telegram_bots(agent_name) ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,24 @@
+-- Discord server an agent gates besides its Telegram group. Holders get role_id,
+-- members who sell out lose it; invites are created in invite_channel_id
+CREATE TABLE IF NOT EXISTS discord_servers (
+    agent_name VARCHAR NOT NULL PRIMARY KEY REFERENCES telegram_bots(agent_name) ON DELETE CASCADE ON UPDATE CASCADE,
+    bot_token VARCHAR NOT NULL,
+    guild_id VARCHAR(32) NOT NULL,
+    role_id VARCHAR(32) NOT NULL,
+    invite_channel_id VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Wallets Discord users proved to own, on the chain of the agent whose server they joined.
+-- has_role mirrors whether the gated role was last granted, so it is only changed on a crossing
+CREATE TABLE IF NOT EXISTS discord_links (
+    agent_name VARCHAR NOT NULL REFERENCES discord_servers(agent_name) ON DELETE CASCADE ON UPDATE CASCADE,
+    discord_id VARCHAR(32) NOT NULL,
+    address VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL,
+    has_role BOOLEAN NOT NULL DEFAULT FALSE,
+    linked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (agent_name, discord_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_discord_links_address ON discord_links(address, chain_type);
@@ -0,0 +1,3 @@
+-- Agents opting into optimistic verification unmute members as soon as their signature
+-- checks out and confirm the balance afterwards, restricting them again if it falls short
+ALTER TABLE telegram_bots ADD COLUMN IF NOT EXISTS optimistic_verify BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- Raw payload of trade events the parser could not decode, kept until a replay with a fixed
+-- parser stores them in trade_events
+CREATE TABLE IF NOT EXISTS undecoded_trade_events (
+    chain_type VARCHAR(20) NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    block_time TIMESTAMP WITH TIME ZONE,
+    raw_event_id TEXT,
+    raw_bcs TEXT NOT NULL,
+    raw_bcs_encoding VARCHAR(10) NOT NULL,
+    error TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain_type, tx_hash, log_index)
+);
@@ -0,0 +1,29 @@
+-- Groups a verified wallet lost access to, so a holder gated by several groups is restricted and
+-- restored in each on its own. user_mappings.is_banned stays set while the wallet has any ban
+CREATE TABLE IF NOT EXISTS group_bans (
+    agent_name VARCHAR NOT NULL REFERENCES telegram_bots(agent_name) ON DELETE CASCADE,
+    address VARCHAR NOT NULL,
+    chain_type VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (agent_name, address, chain_type),
+    FOREIGN KEY (address, chain_type) REFERENCES user_mappings(address, chain_type) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_group_bans_address ON group_bans(address, chain_type);
+
+-- Wallets banned before the table existed lost access to the groups of the subjects they traded
+-- whose gate they no longer satisfy
+INSERT INTO group_bans (agent_name, address, chain_type)
+SELECT DISTINCT b.agent_name, u.address, u.chain_type
+FROM user_mappings u
+JOIN telegram_bots b ON b.chain_type = u.chain_type
+WHERE u.is_banned
+  AND EXISTS (
+      SELECT 1 FROM trades t
+      WHERE t.trader = u.address AND t.chain_type = u.chain_type
+        AND (t.subject = b.subject_address OR t.subject IN (SELECT g.subject_address FROM group_subjects g WHERE g.agent_name = b.agent_name))
+  )
+  AND NOT EXISTS (
+      SELECT 1 FROM gated_members m WHERE m.agent_name = b.agent_name AND m.address = u.address AND m.chain_type = u.chain_type
+  )
+ON CONFLICT DO NOTHING;
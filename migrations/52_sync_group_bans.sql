@@ -0,0 +1,32 @@
+-- Bans follow a renamed agent like the other rows referencing the bot row
+ALTER TABLE group_bans DROP CONSTRAINT IF EXISTS group_bans_agent_name_fkey;
+ALTER TABLE group_bans ADD CONSTRAINT group_bans_agent_name_fkey
+    FOREIGN KEY (agent_name) REFERENCES telegram_bots(agent_name) ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- user_mappings.is_banned is set while the wallet has any group ban, also when bans go with
+-- a deleted agent or are lifted by a verification
+CREATE OR REPLACE FUNCTION sync_wallet_ban()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE user_mappings SET is_banned = TRUE
+        WHERE address = NEW.address AND chain_type = NEW.chain_type AND NOT is_banned;
+    ELSIF TG_OP = 'DELETE' THEN
+        UPDATE user_mappings m
+        SET is_banned = EXISTS (SELECT 1 FROM group_bans g WHERE g.address = m.address AND g.chain_type = m.chain_type)
+        WHERE m.address = OLD.address AND m.chain_type = OLD.chain_type AND m.is_banned;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS group_bans_sync ON group_bans;
+CREATE TRIGGER group_bans_sync
+AFTER INSERT OR DELETE ON group_bans
+FOR EACH ROW EXECUTE FUNCTION sync_wallet_ban();
+
+-- Wallets whose bans already went with their agent
+UPDATE user_mappings m
+SET is_banned = FALSE
+WHERE m.is_banned
+  AND NOT EXISTS (SELECT 1 FROM group_bans g WHERE g.address = m.address AND g.chain_type = m.chain_type);
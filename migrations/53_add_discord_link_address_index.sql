@@ -0,0 +1,10 @@
+-- A wallet is linked to one Discord user per agent, like it is bound to one Telegram user.
+-- Wallets linked to several users keep their latest link
+DELETE FROM discord_links l
+WHERE EXISTS (
+    SELECT 1 FROM discord_links n
+    WHERE n.agent_name = l.agent_name AND n.address = l.address AND n.chain_type = l.chain_type
+      AND (n.linked_at, n.discord_id) > (l.linked_at, l.discord_id)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_discord_links_agent_address ON discord_links(agent_name, address, chain_type);
@@ -0,0 +1,232 @@
+//! `alice_ai_server backfill` subcommand.
+//!
+//! Replays historical trade events of a block range into `trade_events` and
+//! `trades` to bootstrap a fresh database from chain history, e.g.
+//! `alice_ai_server backfill --chain monad --from 10000 --to 20000`. Nothing is
+//! sent to Telegram or to webhooks and sync progress is not moved, so the
+//! server can run alongside. Events already stored are skipped, a failed or
+//! interrupted backfill is resumed by running it again. Only the EVM chains have
+//! block ranges to replay; Sui, Solana and Aptos history is read by their sync loops.
+//!
+//! Every run is recorded in `job_runs` with its position after each batch, so
+//! `GET /admin/backfills` can report its rate and estimate when it finishes.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use tracing::{info, warn};
+
+use crate::block_chain::evm::EvmBlockchain;
+use crate::block_chain::ChainType;
+use crate::db::models::JobRun;
+use crate::db::operations::{finish_job_run, start_job_run};
+use crate::AppConfig;
+
+/// `job_runs.kind` of backfills
+pub const JOB_KIND: &str = "backfill";
+// A running backfill without progress for this long most likely lost its process
+const STALLED_AFTER_SECS: i64 = 900;
+
+const DEFAULT_BATCH_SIZE: u64 = 2000;
+const DEFAULT_CONCURRENCY: usize = 4;
+// RPCs cap the block range and the number of logs of a single query
+const MAX_BATCH_SIZE: u64 = 10_000;
+const MAX_CONCURRENCY: usize = 32;
+
+pub const USAGE: &str = "Usage: alice_ai_server backfill --chain <monad|base|arbitrum> --from <block> --to <block> [--batch-size <blocks>] [--concurrency <requests>]";
+
+/// Arguments of the backfill subcommand
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackfillArgs {
+    pub chain: ChainType,
+    pub from: u64,
+    pub to: u64,
+    /// Blocks fetched per log query
+    pub batch_size: u64,
+    /// Log queries in flight at once
+    pub concurrency: usize,
+}
+
+fn flag_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value.parse().map_err(|_| format!("Invalid {} value {}", flag, value))
+}
+
+impl BackfillArgs {
+    /// Parse the arguments following `backfill`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let (mut chain, mut from, mut to) = (None, None, None);
+        let mut batch_size = DEFAULT_BATCH_SIZE;
+        let mut concurrency = DEFAULT_CONCURRENCY;
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--chain" => chain = Some(flag_value::<ChainType>(flag, args.next())?),
+                "--from" => from = Some(flag_value(flag, args.next())?),
+                "--to" => to = Some(flag_value(flag, args.next())?),
+                "--batch-size" => batch_size = flag_value(flag, args.next())?,
+                "--concurrency" => concurrency = flag_value(flag, args.next())?,
+                _ => return Err(format!("Unknown argument {}", flag)),
+            }
+        }
+
+        let chain = chain.ok_or("--chain is required")?;
+        let from = from.ok_or("--from is required")?;
+        let to = to.ok_or("--to is required")?;
+        if !chain.is_evm() {
+            return Err(format!("Backfill by block range is not supported on {}", chain));
+        }
+        if from > to {
+            return Err("--from must not be after --to".to_string());
+        }
+        if !(1..=MAX_BATCH_SIZE).contains(&batch_size) {
+            return Err(format!("--batch-size must be between 1 and {}", MAX_BATCH_SIZE));
+        }
+        if !(1..=MAX_CONCURRENCY).contains(&concurrency) {
+            return Err(format!("--concurrency must be between 1 and {}", MAX_CONCURRENCY));
+        }
+        Ok(Self { chain, from, to, batch_size, concurrency })
+    }
+}
+
+/// Run a backfill to completion
+pub async fn run_backfill(config: AppConfig, pool: &PgPool, args: &BackfillArgs) -> Result<()> {
+    let chain = EvmBlockchain::new(&config, args.chain).map_err(|e| anyhow!("{}", e))?;
+
+    let job_id = start_job_run(pool, JOB_KIND, args.chain, args.from as i64, args.to as i64).await?;
+    info!("Backfilling {} blocks {} to {} as job {}", args.chain, args.from, args.to, job_id);
+    let result = chain.backfill(pool, job_id, args.from, args.to, args.batch_size, args.concurrency).await;
+    let error = result.as_ref().err().map(|e| e.to_string());
+    if let Err(e) = finish_job_run(pool, job_id, error.as_deref()).await {
+        warn!("Failed to record the end of backfill job {}: {}", job_id, e);
+    }
+
+    let stats = result?;
+    info!("Backfill of {} finished: {} events stored, {} already present", args.chain, stats.stored, stats.skipped);
+    Ok(())
+}
+
+/// Rates and expected completion of a backfill, derived from its job run
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BackfillEstimate {
+    /// Share of the block range replayed, 0 to 100
+    pub percent_complete: f64,
+    pub blocks_per_sec: f64,
+    /// Stored and skipped events per second
+    pub events_per_sec: f64,
+    /// Seconds until the backfill is expected to finish, only while it is making progress
+    pub eta_secs: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub estimated_completion_at: Option<OffsetDateTime>,
+    /// Still running but without progress for a while, e.g. its process was killed
+    pub stalled: bool,
+}
+
+/// Estimate a backfill's progress at `now`. Rates are averaged since it started, up to `now`
+/// while it runs so a backfill that slows down sees its estimate move out
+pub fn estimate(run: &JobRun, now: OffsetDateTime) -> BackfillEstimate {
+    let total = (run.end_position - run.start_position + 1).max(1);
+    let done = run.current_position.map_or(0, |position| (position - run.start_position + 1).clamp(0, total));
+    let running = run.status == "running";
+
+    let until = if running { now } else { run.finished_at.unwrap_or(run.updated_at) };
+    let elapsed = (until - run.started_at).as_seconds_f64();
+    let rate = |count: i64| if elapsed > 0.0 { count as f64 / elapsed } else { 0.0 };
+    let blocks_per_sec = rate(done);
+    let events_per_sec = rate(run.events_stored + run.events_skipped);
+
+    let stalled = running && (now - run.updated_at).whole_seconds() >= STALLED_AFTER_SECS;
+    let eta_secs = (running && !stalled && blocks_per_sec > 0.0)
+        .then(|| ((total - done) as f64 / blocks_per_sec).ceil() as i64);
+
+    BackfillEstimate {
+        percent_complete: (done as f64 * 1000.0 / total as f64).round() / 10.0,
+        blocks_per_sec,
+        events_per_sec,
+        eta_secs,
+        estimated_completion_at: eta_secs.map(|secs| now + Duration::seconds(secs)),
+        stalled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_backfill_args() {
+        let parsed = BackfillArgs::parse(&args("--chain monad --from 10000 --to 20000 --concurrency 8")).unwrap();
+        assert_eq!(parsed, BackfillArgs {
+            chain: ChainType::Monad,
+            from: 10000,
+            to: 20000,
+            batch_size: DEFAULT_BATCH_SIZE,
+            concurrency: 8,
+        });
+    }
+
+    #[test]
+    fn test_invalid_backfill_args() {
+        assert!(BackfillArgs::parse(&args("--chain monad --from 10")).is_err());
+        assert!(BackfillArgs::parse(&args("--chain monad --from 20 --to 10")).is_err());
+        assert!(BackfillArgs::parse(&args("--chain sui --from 1 --to 10")).is_err());
+        assert!(BackfillArgs::parse(&args("--chain base --from 1 --to 10")).is_ok());
+        assert!(BackfillArgs::parse(&args("--chain monad --from 1 --to 10 --batch-size 0")).is_err());
+        assert!(BackfillArgs::parse(&args("--chain monad --from 1 --to 10 --verbose")).is_err());
+        assert!(BackfillArgs::parse(&args("--chain monad --from x --to 10")).is_err());
+    }
+
+    fn run(status: &str, current_position: Option<i64>, updated_secs_ago: i64) -> JobRun {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(365);
+        JobRun {
+            id: 1,
+            kind: JOB_KIND.to_string(),
+            chain_type: ChainType::Monad,
+            start_position: 1000,
+            end_position: 1999,
+            current_position,
+            events_stored: 400,
+            events_skipped: 100,
+            status: status.to_string(),
+            error: None,
+            started_at: now - Duration::seconds(100),
+            updated_at: now - Duration::seconds(updated_secs_ago),
+            finished_at: (status != "running").then_some(now - Duration::seconds(updated_secs_ago)),
+        }
+    }
+
+    #[test]
+    fn test_backfill_estimate() {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(365);
+
+        // A quarter of the range in 100 seconds leaves 300 seconds
+        let running = estimate(&run("running", Some(1249), 5), now);
+        assert_eq!(running.percent_complete, 25.0);
+        assert_eq!(running.blocks_per_sec, 2.5);
+        assert_eq!(running.events_per_sec, 5.0);
+        assert_eq!(running.eta_secs, Some(300));
+        assert_eq!(running.estimated_completion_at, Some(now + Duration::seconds(300)));
+        assert!(!running.stalled);
+
+        let starting = estimate(&run("running", None, 5), now);
+        assert_eq!(starting.percent_complete, 0.0);
+        assert_eq!(starting.eta_secs, None);
+
+        let stalled = estimate(&run("running", Some(1249), STALLED_AFTER_SECS), now);
+        assert!(stalled.stalled);
+        assert_eq!(stalled.eta_secs, None);
+
+        // Rates of a finished backfill stop at its end
+        let completed = estimate(&run("completed", Some(1999), 50), now);
+        assert_eq!(completed.percent_complete, 100.0);
+        assert_eq!(completed.blocks_per_sec, 20.0);
+        assert_eq!(completed.eta_secs, None);
+        assert!(!completed.stalled);
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use ethers::utils::hex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Sha3_256};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, Instrument};
+
+use crate::block_chain::{head, Blockchain, ChainType};
+use crate::block_chain::trade::{apply_trade_event, scale_shares};
+use crate::bot::api::TelegramBotApi;
+use crate::bot::lanes::ChatLanes;
+use crate::db::models::{EventLocation, NewTradeEvent};
+use crate::db::operations::{get_cursor, set_cursor};
+use crate::error::AppError;
+use crate::shutdown::sleep_or_shutdown;
+use crate::AppConfig;
+
+// Authentication key scheme of single ed25519 key accounts, appended to the public key
+// before hashing it into the account address
+const ED25519_SCHEME: u8 = 0x00;
+
+// Most events fetched per request, the fullnode caps pages at 100
+const MAX_EVENT_PAGE_LIMIT: u64 = 100;
+
+/// Aptos blockchain implementation
+pub struct AptosBlockchain {
+    rpc_url: String,
+    contract_address: String,
+    config: Arc<AppConfig>,
+}
+
+/// Trade event emitted by the shares module, u64 fields are JSON strings
+#[derive(Debug, Deserialize)]
+struct AptosTradeEvent {
+    trader: String,
+    subject: String,
+    is_buy: bool,
+    share_amount: String,
+    apt_amount: String,
+    protocol_fee: String,
+    subject_fee: String,
+    supply: String,
+}
+
+/// An event of the trade event handle as listed by the fullnode
+#[derive(Debug, Deserialize)]
+struct HandleEvent {
+    /// Version of the transaction that emitted it
+    version: String,
+    sequence_number: String,
+    data: AptosTradeEvent,
+}
+
+/// Position in the trade event handle sync resumes from
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct EventCursor {
+    /// Sequence number of the next event to fetch
+    start: u64,
+}
+
+/// Hash, time and event position of a transaction that emitted trade events
+struct TransactionInfo {
+    hash: String,
+    timestamp: Option<OffsetDateTime>,
+    /// Sequence numbers of the trade events it emitted by their index in the transaction
+    event_indexes: HashMap<u64, i64>,
+}
+
+/// Account address of a single ed25519 key: sha3-256(public_key || scheme)
+fn public_key_address(public_key: &[u8; 32]) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(public_key);
+    hasher.update([ED25519_SCHEME]);
+    hex::encode(hasher.finalize())
+}
+
+/// Message Petra-style wallets sign for `signMessage({ message, nonce })`
+fn full_message(message: &str, nonce: &str) -> String {
+    format!("APTOS\nmessage: {}\nnonce: {}", message, nonce)
+}
+
+/// Check a `signMessage` signature, given as hex of the 32 byte public key followed by the
+/// 64 byte signature, and return the account address of the key
+fn verify_signed_message(message: &str, nonce: &str, signature: &str) -> Result<String, String> {
+    let bytes = hex::decode(signature.trim().trim_start_matches("0x")).map_err(|e| format!("Invalid signature hex: {}", e))?;
+    if bytes.len() != 96 {
+        return Err("Signature must be a 32 byte public key followed by a 64 byte signature".to_string());
+    }
+    let public_key: [u8; 32] = bytes[..32].try_into().unwrap();
+    let sig_bytes: [u8; 64] = bytes[32..].try_into().unwrap();
+
+    let verifying_key = VerifyingKey::from_bytes(&public_key).map_err(|e| format!("Invalid public key: {}", e))?;
+    verifying_key
+        .verify(full_message(message, nonce).as_bytes(), &Signature::from_bytes(&sig_bytes))
+        .map_err(|e| e.to_string())?;
+    Ok(public_key_address(&public_key))
+}
+
+fn parse_u64(value: &str, field: &str) -> Result<u64> {
+    value.parse().map_err(|_| anyhow!("Cannot parse Aptos {} {}", field, value))
+}
+
+impl AptosBlockchain {
+    pub fn new(config: Arc<AppConfig>) -> Self {
+        let rpc_url = config.aptos_rpc.clone().unwrap_or_else(|| "https://fullnode.mainnet.aptoslabs.com/v1".to_string());
+        let contract_address = config.aptos_contract.clone().unwrap_or_default();
+
+        Self {
+            rpc_url: rpc_url.trim_end_matches('/').to_string(),
+            contract_address,
+            config,
+        }
+    }
+
+    /// GET a fullnode REST endpoint
+    async fn get(&self, path: &str) -> Result<Value> {
+        let response = Client::new().get(format!("{}{}", self.rpc_url, path)).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Aptos request {} failed: {}", path, response.status()));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Current block height of the chain
+    async fn get_block_height(&self) -> Result<u64> {
+        self.get("/").await?
+            .get("block_height")
+            .and_then(|h| h.as_str())
+            .and_then(|h| h.parse().ok())
+            .ok_or_else(|| anyhow!("Cannot parse Aptos block height"))
+    }
+
+    /// Trade events of the shares module from sequence number `start`, oldest first
+    async fn get_events(&self, start: u64) -> Result<Vec<HandleEvent>> {
+        let handle = format!("{}::shares_trading::SharesTrading", self.contract_address);
+        let path = format!(
+            "/accounts/{}/events/{}/trade_events?start={}&limit={}",
+            self.contract_address, handle, start, self.config.sync_batch_size.min(MAX_EVENT_PAGE_LIMIT)
+        );
+        Ok(serde_json::from_value(self.get(&path).await?)?)
+    }
+
+    /// Hash, time and trade event positions of the transaction at `version`
+    async fn get_transaction(&self, version: &str) -> Result<TransactionInfo> {
+        let transaction = self.get(&format!("/transactions/by_version/{}", version)).await?;
+
+        let hash = transaction.get("hash")
+            .and_then(|h| h.as_str())
+            .ok_or_else(|| anyhow!("Cannot parse Aptos transaction {}", version))?
+            .to_string();
+        // Microseconds since the epoch
+        let timestamp = transaction.get("timestamp")
+            .and_then(|t| t.as_str())
+            .and_then(|t| t.parse::<i128>().ok())
+            .and_then(|us| OffsetDateTime::from_unix_timestamp_nanos(us * 1_000).ok());
+
+        let trade_type = format!("{}::shares_trading::Trade", self.contract_address);
+        let event_indexes = transaction.get("events")
+            .and_then(|e| e.as_array())
+            .map(|events| events.iter()
+                .enumerate()
+                .filter(|(_, e)| e.get("type").and_then(|t| t.as_str()) == Some(trade_type.as_str()))
+                .filter_map(|(index, e)| {
+                    let sequence_number = e.get("sequence_number")?.as_str()?.parse().ok()?;
+                    Some((sequence_number, index as i64))
+                })
+                .collect())
+            .unwrap_or_default();
+
+        Ok(TransactionInfo { hash, timestamp, event_indexes })
+    }
+
+    /// Process Aptos trade event
+    async fn process_trade_event(&self, event: &AptosTradeEvent, location: &EventLocation, pool: &sqlx::PgPool) -> Result<()> {
+        debug!("Processing Aptos Trade event: {:?}", event);
+
+        let record = NewTradeEvent {
+            trader: self.chain_type().normalize_address(&event.trader),
+            subject: self.chain_type().normalize_address(&event.subject),
+            is_buy: event.is_buy,
+            share_amount: BigDecimal::from(parse_u64(&event.share_amount, "share_amount")?),
+            eth_amount: BigDecimal::from(parse_u64(&event.apt_amount, "apt_amount")?),
+            protocol_fee: BigDecimal::from(parse_u64(&event.protocol_fee, "protocol_fee")?),
+            subject_fee: BigDecimal::from(parse_u64(&event.subject_fee, "subject_fee")?),
+            supply: BigDecimal::from(parse_u64(&event.supply, "supply")?),
+        };
+        apply_trade_event(pool, &TelegramBotApi, self.chain_type(), location, &record, None, self.config.share_decimals(self.chain_type())).await
+    }
+}
+
+#[async_trait]
+impl Blockchain for AptosBlockchain {
+    fn chain_type(&self) -> ChainType {
+        ChainType::Aptos
+    }
+
+    async fn sync_events(&self, pool: &PgPool, _lanes: &ChatLanes, shutdown: &CancellationToken) -> Result<()> {
+        // Aptos is indexed by the sequence number of the trade event handle
+        let mut cursor: EventCursor = match get_cursor(pool, self.chain_type()).await? {
+            Some(saved) => serde_json::from_str(&saved.cursor_json)?,
+            None => EventCursor::default(),
+        };
+
+        info!("Starting sync from event {} for {}", cursor.start, self.get_name());
+
+        while !shutdown.is_cancelled() {
+            match self.get_block_height().await {
+                Ok(height) => head::record_head(self.chain_type(), height),
+                Err(e) => debug!("Failed to get current {} block height: {:?}", self.get_name(), e),
+            }
+
+            match self.get_events(cursor.start).await {
+                Ok(events) if events.is_empty() => {
+                    debug!("No new events for {}, waiting...", self.get_name());
+                    sleep_or_shutdown(shutdown, Duration::from_secs(self.config.sync_idle_interval_secs)).await;
+                },
+                Ok(events) => {
+                    let span = info_span!("sync_batch", chain = %self.chain_type(), events = events.len());
+                    async {
+                        let mut transactions: HashMap<String, TransactionInfo> = HashMap::new();
+                        for event in &events {
+                            // Progress is saved after every event, stop between two of them
+                            if shutdown.is_cancelled() {
+                                break;
+                            }
+                            let sequence_number = match parse_u64(&event.sequence_number, "sequence_number") {
+                                Ok(sequence_number) => sequence_number,
+                                Err(e) => {
+                                    error!("Skipping Aptos event: {:?}", e);
+                                    continue;
+                                }
+                            };
+                            if !transactions.contains_key(&event.version) {
+                                match self.get_transaction(&event.version).await {
+                                    Ok(transaction) => {
+                                        transactions.insert(event.version.clone(), transaction);
+                                    }
+                                    Err(e) => {
+                                        // Stop here and retry this event on the next round
+                                        error!("Failed to load Aptos transaction {}: {:?}", event.version, e);
+                                        break;
+                                    }
+                                }
+                            }
+                            let transaction = &transactions[&event.version];
+
+                            let location = EventLocation {
+                                block_number: None,
+                                tx_hash: transaction.hash.clone(),
+                                log_index: transaction.event_indexes.get(&sequence_number).copied().unwrap_or(0),
+                                block_time: transaction.timestamp,
+                            };
+                            if let Err(e) = self.process_trade_event(&event.data, &location, pool).await {
+                                error!("Error processing Aptos trade event: {:?}", e);
+                            }
+
+                            cursor.start = sequence_number + 1;
+                            let cursor_json = serde_json::to_string(&cursor).unwrap_or_default();
+                            if let Err(e) = set_cursor(pool, self.chain_type(), &cursor_json).await {
+                                error!("Failed to update last synced event: {:?}", e);
+                            }
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                },
+                Err(e) => {
+                    error!("Failed to query Aptos events: {:?}", e);
+                    sleep_or_shutdown(shutdown, Duration::from_secs(self.config.sync_error_backoff_secs)).await;
+                }
+            }
+
+            sleep_or_shutdown(shutdown, Duration::from_secs(1)).await;
+        }
+
+        info!("Stopped {} sync at event {}", self.get_name(), cursor.start);
+        Ok(())
+    }
+
+    fn verify_signature(&self, challenge: &str, signature: &str, user: &str) -> Result<String, AppError> {
+        // The wallet is asked to sign with the challenge's nonce, which ends the challenge
+        let nonce = challenge
+            .rsplit_once("Nonce: ")
+            .map(|(_, nonce)| nonce)
+            .ok_or_else(|| AppError::BadRequest("Challenge has no nonce".to_string()))?;
+        let address = verify_signed_message(challenge, nonce, signature).map_err(AppError::InvalidSignature)?;
+
+        // Accounts whose key was rotated no longer match the address derived from their key
+        if address != self.chain_type().normalize_address(user) {
+            return Err(AppError::InvalidSignature("Public key does not belong to the claimed account".to_string()));
+        }
+        Ok(address)
+    }
+
+    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<BigDecimal> {
+        let payload = json!({
+            "function": format!("{}::shares_trading::shares_balance", self.contract_address),
+            "type_arguments": [],
+            "arguments": [
+                format!("0x{}", self.chain_type().normalize_address(subject)),
+                format!("0x{}", self.chain_type().normalize_address(user)),
+            ]
+        });
+
+        let response = Client::new().post(format!("{}/view", self.rpc_url))
+            .json(&payload)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Aptos view function call failed: {}", response.status()));
+        }
+
+        // View functions return their values as a JSON array, u64 as a string
+        let result: Value = response.json().await?;
+        let balance = result.get(0)
+            .and_then(|b| b.as_str())
+            .ok_or_else(|| anyhow!("Cannot parse Aptos shares balance"))?;
+        let balance = BigDecimal::from(parse_u64(balance, "shares balance")?);
+        Ok(scale_shares(&balance, self.config.share_decimals(self.chain_type())))
+    }
+
+    async fn get_gas_price(&self) -> Result<u128> {
+        // Gas unit price in octas
+        self.get("/estimate_gas_price").await?
+            .get("gas_estimate")
+            .and_then(|g| g.as_u64())
+            .map(u128::from)
+            .ok_or_else(|| anyhow!("Cannot parse Aptos gas estimate"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_signed_message_recovers_account() {
+        let message = "Verify Telegram user 1 for group -100\nNonce: abc";
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = key.verifying_key().to_bytes();
+        let signed = key.sign(full_message(message, "abc").as_bytes());
+        let signature = format!("0x{}{}", hex::encode(public_key), hex::encode(signed.to_bytes()));
+
+        let address = public_key_address(&public_key);
+        assert_eq!(address.len(), 64);
+        assert_eq!(verify_signed_message(message, "abc", &signature), Ok(address));
+        assert!(verify_signed_message(message, "other", &signature).is_err());
+        assert!(verify_signed_message("another message", "abc", &signature).is_err());
+        assert!(verify_signed_message(message, "abc", &signature[..128]).is_err());
+    }
+}
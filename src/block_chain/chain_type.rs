@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Supported chains, stored as lowercase text in every `chain_type` column
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum ChainType {
+    #[default]
+    Monad,
+    Sui,
+    Solana,
+    Aptos,
+    Base,
+    Arbitrum,
+}
+
+impl ChainType {
+    pub const ALL: [ChainType; 6] = [
+        ChainType::Monad,
+        ChainType::Sui,
+        ChainType::Solana,
+        ChainType::Aptos,
+        ChainType::Base,
+        ChainType::Arbitrum,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChainType::Monad => "monad",
+            ChainType::Sui => "sui",
+            ChainType::Solana => "solana",
+            ChainType::Aptos => "aptos",
+            ChainType::Base => "base",
+            ChainType::Arbitrum => "arbitrum",
+        }
+    }
+
+    /// Whether the chain runs the EVM shares contract, synced by [`EvmBlockchain`](crate::block_chain::evm::EvmBlockchain)
+    pub fn is_evm(&self) -> bool {
+        matches!(self, ChainType::Monad | ChainType::Base | ChainType::Arbitrum)
+    }
+
+    /// Canonical form addresses of this chain are stored and compared in
+    pub fn normalize_address(&self, address: &str) -> String {
+        let address = address.trim();
+        match self {
+            ChainType::Monad | ChainType::Base | ChainType::Arbitrum => address.to_lowercase().trim_start_matches("0x").to_owned(),
+            // Sui and Aptos addresses are 32 bytes, short forms drop leading zeros
+            ChainType::Sui | ChainType::Aptos => format!("{:0>64}", address.to_lowercase().trim_start_matches("0x")),
+            // Base58 is case sensitive
+            ChainType::Solana => address.to_owned(),
+        }
+    }
+
+    /// Whether a normalized address has the shape of an account of this chain: 20 bytes of hex
+    /// on EVM chains, 32 bytes of hex on Sui and Aptos, a base58 public key on Solana
+    pub fn is_valid_address(&self, normalized: &str) -> bool {
+        let is_hex = |len: usize| normalized.len() == len && normalized.chars().all(|c| c.is_ascii_hexdigit());
+        match self {
+            ChainType::Monad | ChainType::Base | ChainType::Arbitrum => is_hex(40),
+            ChainType::Sui | ChainType::Aptos => is_hex(64),
+            ChainType::Solana => bs58::decode(normalized).into_vec().is_ok_and(|bytes| bytes.len() == 32),
+        }
+    }
+
+    /// Decimals of the native token (wei, MIST, lamports, octas)
+    pub fn native_decimals(&self) -> u32 {
+        match self {
+            ChainType::Monad | ChainType::Base | ChainType::Arbitrum => 18,
+            ChainType::Sui => 9,
+            ChainType::Solana => 9,
+            ChainType::Aptos => 8,
+        }
+    }
+
+    /// Symbol of the native currency
+    pub fn native_symbol(&self) -> &'static str {
+        match self {
+            ChainType::Monad => "MON",
+            ChainType::Sui => "SUI",
+            ChainType::Solana => "SOL",
+            ChainType::Aptos => "APT",
+            ChainType::Base | ChainType::Arbitrum => "ETH",
+        }
+    }
+}
+
+impl fmt::Display for ChainType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ChainType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChainType::ALL
+            .into_iter()
+            .find(|chain| chain.as_str() == s)
+            .ok_or_else(|| format!("Unsupported chain type: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_type_round_trip() {
+        for chain in ChainType::ALL {
+            assert_eq!(chain.as_str().parse::<ChainType>(), Ok(chain));
+            assert_eq!(serde_json::to_string(&chain).unwrap(), format!("\"{}\"", chain));
+        }
+        assert!("Monad".parse::<ChainType>().is_err());
+    }
+
+    #[test]
+    fn test_normalize_sui_address() {
+        let full = "0000000000000000000000000000000000000000000000000000000000000abc";
+        assert_eq!(ChainType::Sui.normalize_address("0xABC"), full);
+        assert_eq!(ChainType::Sui.normalize_address("abc"), full);
+        assert_eq!(ChainType::Sui.normalize_address(&format!("0x{}", full)), full);
+        assert_eq!(ChainType::Monad.normalize_address("0xAbC"), "abc");
+        assert_eq!(ChainType::Aptos.normalize_address("0x1"), format!("{:0>64}", "1"));
+        assert_eq!(ChainType::Base.normalize_address("0xAbC"), "abc");
+    }
+
+    #[test]
+    fn test_valid_addresses() {
+        let evm = ChainType::Monad.normalize_address("0x52908400098527886E0F7030069857D2E4169EE7");
+        assert!(ChainType::Monad.is_valid_address(&evm));
+        assert!(ChainType::Arbitrum.is_valid_address(&evm));
+        assert!(!ChainType::Monad.is_valid_address("abc"));
+        assert!(!ChainType::Base.is_valid_address(&"g".repeat(40)));
+        assert!(ChainType::Sui.is_valid_address(&ChainType::Sui.normalize_address("0x2")));
+        assert!(!ChainType::Aptos.is_valid_address(&ChainType::Aptos.normalize_address(&"1".repeat(65))));
+        assert!(ChainType::Solana.is_valid_address("11111111111111111111111111111111"));
+        assert!(!ChainType::Solana.is_valid_address("0OIl"));
+        assert!(!ChainType::Solana.is_valid_address("1111"));
+    }
+}
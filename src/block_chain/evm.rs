@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ethers::{
+    prelude::*,
+    contract::Contract,
+};
+use ethers::utils::{hash_message, hex};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::block_chain::{head, Blockchain, ChainType};
+use crate::block_chain::rpc::RpcEndpoints;
+use crate::block_chain::trade::{apply_trade_event, backfill_trade_event, scale_shares, BackfillStats};
+use crate::block_chain::tx_binding::{match_pending_bindings, ObservedTransaction};
+use crate::block_chain::utils::{TradeEvent, TRADE_ABI, ABI};
+use crate::bot::api::TelegramBotApi;
+use crate::bot::lanes::ChatLanes;
+use crate::db::models::{EventLocation, NewTradeEvent};
+use crate::db::operations::{
+    get_last_synced_block, get_open_pending_bindings, get_synced_block_hashes, record_synced_block, rollback_trade_events,
+    update_job_run_progress,
+};
+use crate::enforcement::handle_balance_change;
+use crate::error::AppError;
+use crate::shutdown::sleep_or_shutdown;
+use crate::config::{AppConfig, EvmChainConfig};
+
+// Seconds to poll over HTTP before retrying the WebSocket
+const WS_RECONNECT_SECS: u64 = 60;
+
+// Seconds between syncs while streaming when no trade is pushed
+const STREAM_POLL_SECS: u64 = 5;
+
+// Recorded block hashes compared against the chain to find where a reorg forked
+const REORG_LOOKBACK: i64 = 256;
+
+/// Outcome of one HTTP polling step
+enum PollStep {
+    CaughtUp(u64),
+    Advanced,
+    Failed,
+}
+
+/// Trade event in the chain-independent form, raw amounts
+fn trade_record(event: &TradeEvent) -> Result<NewTradeEvent> {
+    Ok(NewTradeEvent {
+        trader: hex::encode(event.trader.as_bytes()),
+        subject: hex::encode(event.subject.as_bytes()),
+        is_buy: event.is_buy,
+        share_amount: BigDecimal::from_str(&event.share_amount.to_string())?,
+        eth_amount: BigDecimal::from_str(&event.eth_amount.to_string())?,
+        protocol_fee: BigDecimal::from_str(&event.protocol_eth_amount.to_string())?,
+        subject_fee: BigDecimal::from_str(&event.subject_eth_amount.to_string())?,
+        supply: BigDecimal::from_str(&event.supply.to_string())?,
+    })
+}
+
+/// Location of a log emitted in a block with the given timestamp
+fn located(meta: &LogMeta, block_time: Option<OffsetDateTime>) -> EventLocation {
+    EventLocation {
+        block_number: Some(meta.block_number.as_u64() as i64),
+        tx_hash: format!("{:?}", meta.transaction_hash),
+        log_index: meta.log_index.as_u64() as i64,
+        block_time,
+    }
+}
+
+/// HTTP transport of a network sending each request to its healthiest RPC endpoint, failing over
+/// to the next one when an endpoint cannot be reached, see [`RpcEndpoints`]
+#[derive(Debug)]
+pub struct FailoverHttp {
+    endpoints: RpcEndpoints,
+    transports: Vec<Http>,
+}
+
+impl FailoverHttp {
+    fn new(chain_type: ChainType, urls: &str) -> Result<Self, AppError> {
+        let endpoints = RpcEndpoints::parse(chain_type, urls)?;
+        let transports = endpoints
+            .urls()
+            .map(|url| Http::from_str(url).map_err(|e| AppError::Config(format!("Invalid {} RPC URL {}: {}", chain_type, url, e))))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { endpoints, transports })
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverHttp {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, HttpClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = &params;
+        // Errors answered by the node are final, another endpoint would give the same answer
+        self.endpoints
+            .call(move |index| async move {
+                match self.transports[index].request::<_, R>(method, params).await {
+                    Err(HttpClientError::JsonRpcError(e)) => Ok(Err(HttpClientError::JsonRpcError(e))),
+                    result => result.map(Ok),
+                }
+            })
+            .await?
+    }
+}
+
+/// Shares contract on an EVM network (Monad, Base, Arbitrum), each network synced by its own instance
+pub struct EvmBlockchain {
+    provider: Arc<Provider<FailoverHttp>>,
+    contract_address: Address,
+    chain: EvmChainConfig,
+    // Blocks synced per batch and the waits of the polling loop, see AppConfig
+    batch_size: u64,
+    idle_interval: Duration,
+    error_backoff: Duration,
+}
+
+impl EvmBlockchain {
+    pub fn new(config: &AppConfig, chain_type: ChainType) -> Result<Self, AppError> {
+        let chain = config.evm_chain(chain_type)
+            .ok_or_else(|| AppError::Config(format!("No EVM network configured for {}", chain_type)))?
+            .clone();
+        
+        let provider = Arc::new(Provider::new(FailoverHttp::new(chain_type, &chain.rpc_url)?));
+        
+        let contract_address = Address::from_str(&chain.contract_address)
+            .map_err(|e| AppError::Config(format!("Invalid {} shares contract {}: {}", chain_type, chain.contract_address, e)))?;
+        
+        Ok(Self {
+            provider,
+            contract_address,
+            chain,
+            batch_size: config.sync_batch_size,
+            idle_interval: Duration::from_secs(config.sync_idle_interval_secs),
+            error_backoff: Duration::from_secs(config.sync_error_backoff_secs),
+        })
+    }
+    
+    /// Process trade event
+    async fn process_trade_event(&self, event: &TradeEvent, location: &EventLocation, pool: &sqlx::PgPool) -> Result<()> {
+        debug!("Processing {} Trade event: {:?}", self.get_name(), event);
+        
+        let record = trade_record(event)?;
+        apply_trade_event(pool, &TelegramBotApi, self.chain_type(), location, &record, None, self.chain.share_decimals).await
+    }
+    
+    /// Location of a log, with the timestamp of its block
+    async fn event_location(&self, meta: &LogMeta) -> EventLocation {
+        let block_time = self.block_time(meta.block_number).await;
+        located(meta, block_time)
+    }
+    
+    /// Timestamp of a block, None if it cannot be fetched
+    async fn block_time(&self, number: U64) -> Option<OffsetDateTime> {
+        match self.provider.get_block(number).await {
+            Ok(Some(block)) => OffsetDateTime::from_unix_timestamp(block.timestamp.as_u64() as i64).ok(),
+            _ => None,
+        }
+    }
+    
+    /// Replay the trade events of blocks `from..=to` into the database without enforcing group
+    /// access. Up to `concurrency` batches of `batch_size` blocks are fetched at once, their
+    /// events are applied in block order and recorded as the progress of job run `job_id`. Sync
+    /// progress is left alone
+    pub async fn backfill(&self, pool: &PgPool, job_id: i64, from: u64, to: u64, batch_size: u64, concurrency: usize) -> Result<BackfillStats> {
+        let abi: ethers::abi::Abi = serde_json::from_str(TRADE_ABI).expect("Invalid ABI");
+        let contract = Contract::new(self.contract_address, abi, self.provider.clone());
+        let share_decimals = self.chain.share_decimals;
+        
+        let batches = (from..=to)
+            .step_by(batch_size as usize)
+            .map(|start| (start, (start + batch_size - 1).min(to)));
+        let mut fetched = futures::stream::iter(batches)
+            .map(|(start, end)| self.fetch_batch(&contract, start, end))
+            .buffered(concurrency);
+        
+        let mut stats = BackfillStats::default();
+        while let Some(batch) = fetched.next().await {
+            let (end, events) = batch.map_err(|e| anyhow!("{} (backfilled through {:?})", e, stats.last_block))?;
+            for (event, location) in &events {
+                let record = trade_record(event)?;
+                if backfill_trade_event(pool, self.chain_type(), location, &record, share_decimals).await? {
+                    stats.stored += 1;
+                } else {
+                    stats.skipped += 1;
+                }
+            }
+            stats.last_block = Some(end);
+            // Progress is only reported, losing an update must not stop the backfill
+            if let Err(e) = update_job_run_progress(pool, job_id, end as i64, stats.stored as i64, stats.skipped as i64).await {
+                warn!("Failed to record progress of backfill job {}: {}", job_id, e);
+            }
+            info!("Backfilled {} through block {}: {} events stored, {} already present", self.get_name(), end, stats.stored, stats.skipped);
+        }
+        Ok(stats)
+    }
+    
+    /// Trade events of blocks `from..=to` with their locations, block times fetched once per block
+    async fn fetch_batch(&self, contract: &Contract<Provider<FailoverHttp>>, from: u64, to: u64) -> Result<(u64, Vec<(TradeEvent, EventLocation)>)> {
+        let events = contract
+            .event::<TradeEvent>()
+            .from_block(from)
+            .to_block(to)
+            .query_with_meta()
+            .await?;
+        
+        let mut block_times = HashMap::new();
+        let mut located_events = Vec::with_capacity(events.len());
+        for (event, meta) in events {
+            let block_time = match block_times.get(&meta.block_number) {
+                Some(block_time) => *block_time,
+                None => {
+                    let block_time = self.block_time(meta.block_number).await;
+                    block_times.insert(meta.block_number, block_time);
+                    block_time
+                }
+            };
+            located_events.push((event, located(&meta, block_time)));
+        }
+        Ok((to, located_events))
+    }
+    
+    /// Sync the next batch of blocks over HTTP
+    async fn poll_step(&self, contract: &Contract<Provider<FailoverHttp>>, pool: &PgPool, lanes: &ChatLanes, last_synced_block: &mut u64) -> PollStep {
+        // Get the current chain's latest block
+        let current_block = match self.provider.get_block_number().await {
+            Ok(block) => {
+                head::record_head(self.chain_type(), block.as_u64());
+                block.as_u64()
+            },
+            Err(e) => {
+                error!("Failed to get current block number: {:?}", e);
+                return PollStep::Failed;
+            }
+        };
+        
+        if let Err(e) = self.check_reorg(pool, last_synced_block).await {
+            error!("Failed to check {} for a reorg: {:?}", self.get_name(), e);
+            return PollStep::Failed;
+        }
+        
+        // Only sync blocks with enough confirmations to be unlikely to reorg
+        let confirmed_block = current_block.saturating_sub(self.chain.confirmations);
+        if *last_synced_block >= confirmed_block {
+            return PollStep::CaughtUp(current_block);
+        }
+        
+        // Calculate the end block for this sync
+        let end_block = std::cmp::min(*last_synced_block + self.batch_size, confirmed_block);
+        
+        let span = info_span!("sync_batch", chain = %self.chain_type(), from = *last_synced_block, to = end_block);
+        self.sync_batch(contract, pool, lanes, last_synced_block, end_block).instrument(span).await
+    }
+    
+    /// Apply the trade events of blocks `last_synced_block..=end_block`
+    async fn sync_batch(&self, contract: &Contract<Provider<FailoverHttp>>, pool: &PgPool, lanes: &ChatLanes, last_synced_block: &mut u64, end_block: u64) -> PollStep {
+        debug!("Syncing blocks {} to {} for {}", last_synced_block, end_block, self.get_name());
+        
+        // Create a filter to query historical events
+        let filter = contract
+            .event::<TradeEvent>()
+            .from_block(*last_synced_block)
+            .to_block(end_block);
+        
+        // Query events
+        match filter.query_with_meta().await {
+            Ok(events) => {
+                debug!("Found {} events in blocks {} to {} for {}", events.len(), last_synced_block, end_block, self.get_name());
+                
+                // Process each event
+                for (event, meta) in events {
+                    let location = self.event_location(&meta).await;
+                    if let Err(e) = self.process_trade_event(&event, &location, pool).await {
+                        error!("Error processing trade event: {:?}", e);
+                    }
+                }
+                self.watch_bindings(pool, lanes, *last_synced_block, end_block).await;
+                
+                // Record the last synced block with its hash for reorg checks
+                let recorded = match self.block_hash(end_block).await {
+                    Ok(Some(hash)) => record_synced_block(pool, end_block, &hash, self.chain_type()).await.map_err(anyhow::Error::from),
+                    Ok(None) => Err(anyhow!("Block {} not found", end_block)),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = recorded {
+                    error!("Failed to update last synced block: {:?}", e);
+                    return PollStep::Failed;
+                }
+                *last_synced_block = end_block;
+                PollStep::Advanced
+            },
+            Err(e) => {
+                error!("Failed to query events: {:?}", e);
+                PollStep::Failed
+            }
+        }
+    }
+    
+    /// Hash of a block, None if the chain has no such block (any more)
+    async fn block_hash(&self, number: u64) -> Result<Option<String>> {
+        let block = self.provider.get_block(number).await?;
+        Ok(block.and_then(|block| block.hash).map(|hash| format!("{:?}", hash)))
+    }
+    
+    /// Roll back to the last block still on the canonical chain if the last synced
+    /// block was reorged away: trade events of the orphaned blocks are undone, the
+    /// affected balances enforced again and sync resumes from the fork
+    async fn check_reorg(&self, pool: &PgPool, last_synced_block: &mut u64) -> Result<()> {
+        let recorded = get_synced_block_hashes(pool, self.chain_type(), REORG_LOOKBACK).await?;
+        // Nothing to compare when progress was saved without a hash
+        let Some((tip, tip_hash)) = recorded.first() else {
+            return Ok(());
+        };
+        if *tip != *last_synced_block || self.block_hash(*tip).await?.as_ref() == Some(tip_hash) {
+            return Ok(());
+        }
+        
+        let mut fork = None;
+        for (number, hash) in recorded.iter().skip(1) {
+            if self.block_hash(*number).await?.as_ref() == Some(hash) {
+                fork = Some((*number, hash.clone()));
+                break;
+            }
+        }
+        // Deeper than the recorded hashes, go back one more batch before the oldest
+        let (fork_block, fork_hash) = match fork {
+            Some(fork) => fork,
+            None => {
+                let oldest = recorded.last().map(|(number, _)| *number).unwrap_or(*tip);
+                let number = oldest.saturating_sub(self.batch_size).max(self.chain.start_block);
+                let hash = self.block_hash(number).await?.ok_or_else(|| anyhow!("Block {} not found", number))?;
+                (number, hash)
+            }
+        };
+        warn!("Reorg on {} below block {}, rolling back to block {}", self.get_name(), tip, fork_block);
+        
+        let balances = rollback_trade_events(pool, self.chain_type(), fork_block, &fork_hash).await?;
+        *last_synced_block = fork_block;
+        info!("Rolled back {} to block {}, {} balances changed", self.get_name(), fork_block, balances.len());
+        
+        for (trader, subject, balance) in balances {
+            if let Err(e) = handle_balance_change(pool, &TelegramBotApi, self.chain_type(), &trader, &subject, &balance, None).await {
+                error!("Failed to enforce rolled back balance of {} on {}: {:?}", trader, subject, e);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Complete pending wallet bindings proven by a transaction in blocks `from..=to`
+    async fn watch_bindings(&self, pool: &PgPool, lanes: &ChatLanes, from: u64, to: u64) {
+        let bindings = match get_open_pending_bindings(pool, self.chain_type()).await {
+            Ok(bindings) if bindings.is_empty() => return,
+            Ok(bindings) => bindings,
+            Err(e) => {
+                error!("Failed to load pending bindings: {:?}", e);
+                return;
+            }
+        };
+        
+        // Only pull full blocks while someone is waiting for their proof
+        let mut transactions = Vec::new();
+        for number in from..=to {
+            let block = match self.provider.get_block_with_txs(number).await {
+                Ok(Some(block)) => block,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Failed to load block {} for binding proofs: {:?}", number, e);
+                    continue;
+                }
+            };
+            for tx in block.transactions {
+                let from = hex::encode(tx.from.as_bytes());
+                if bindings.iter().any(|binding| binding.address == from) {
+                    transactions.push(ObservedTransaction {
+                        from,
+                        payload: tx.input.to_vec(),
+                        tx_hash: format!("{:?}", tx.hash),
+                    });
+                }
+            }
+        }
+        
+        let bound = match_pending_bindings(pool, self, lanes, &bindings, &transactions).await;
+        if bound > 0 {
+            info!("Bound {} wallets from transactions in blocks {} to {}", bound, from, to);
+        }
+    }
+    
+    /// One polling iteration, sleeping as the original polling loop did
+    async fn poll_and_wait(&self, contract: &Contract<Provider<FailoverHttp>>, pool: &PgPool, lanes: &ChatLanes, last_synced_block: &mut u64, shutdown: &CancellationToken) {
+        let wait = match self.poll_step(contract, pool, lanes, last_synced_block).await {
+            PollStep::CaughtUp(current_block) => {
+                // Already synced to the latest block, wait for a while before continuing
+                debug!("Synced to current block {} for {}, waiting for new blocks...", current_block, self.get_name());
+                self.idle_interval
+            },
+            PollStep::Advanced => Duration::from_secs(1),
+            PollStep::Failed => self.error_backoff,
+        };
+        sleep_or_shutdown(shutdown, wait).await;
+    }
+    
+    /// Sync whenever trade events are pushed over a WebSocket subscription, until the stream ends.
+    /// Pushed events only wake the sync up: they are applied through [`Self::poll_step`]
+    /// once confirmed, so reorg checks and binding proofs cover them like polled ones
+    async fn stream_events(
+        &self,
+        ws_url: &str,
+        contract: &Contract<Provider<FailoverHttp>>,
+        pool: &PgPool,
+        lanes: &ChatLanes,
+        last_synced_block: &mut u64,
+        shutdown: &CancellationToken,
+    ) -> Result<()> {
+        let ws_provider = Arc::new(Provider::<Ws>::connect(ws_url).await?);
+        let abi: ethers::abi::Abi = serde_json::from_str(TRADE_ABI).expect("Invalid ABI");
+        let ws_contract = Contract::new(self.contract_address, abi, ws_provider);
+        let trade_events = ws_contract.event::<TradeEvent>();
+        let mut stream = trade_events.subscribe_with_meta().await?;
+        
+        info!("Subscribed to trade events over WebSocket for {}", self.get_name());
+        
+        // Blocks without trades still confirm earlier ones and may carry binding proofs
+        let mut tick = tokio::time::interval(Duration::from_secs(STREAM_POLL_SECS));
+        
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = tick.tick() => {},
+                item = stream.next() => match item {
+                    Some(item) => {
+                        item?;
+                    },
+                    None => break,
+                },
+            }
+            
+            // Catch up to the confirmed head, pushed events are buffered by the subscription meanwhile
+            loop {
+                let wait = match self.poll_step(contract, pool, lanes, last_synced_block).await {
+                    PollStep::CaughtUp(_) => break,
+                    PollStep::Advanced => Duration::from_secs(1),
+                    PollStep::Failed => self.error_backoff,
+                };
+                if sleep_or_shutdown(shutdown, wait).await {
+                    return Ok(());
+                }
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Blockchain for EvmBlockchain {
+    fn chain_type(&self) -> ChainType {
+        self.chain.chain_type
+    }
+    
+    async fn sync_events(&self, pool: &PgPool, lanes: &ChatLanes, shutdown: &CancellationToken) -> Result<()> {
+        let abi: ethers::abi::Abi = serde_json::from_str(TRADE_ABI).expect("Invalid ABI");
+        let contract = Contract::new(self.contract_address, abi, self.provider.clone());
+        
+        // Get the last synced block number
+        let mut last_synced_block = get_last_synced_block(pool, self.chain.start_block, self.chain_type()).await?;
+        
+        info!("Starting sync from block {} for {}", last_synced_block, self.get_name());
+        
+        while !shutdown.is_cancelled() {
+            let Some(ws_url) = &self.chain.ws_url else {
+                self.poll_and_wait(&contract, pool, lanes, &mut last_synced_block, shutdown).await;
+                continue;
+            };
+            
+            match self.stream_events(ws_url, &contract, pool, lanes, &mut last_synced_block, shutdown).await {
+                Ok(()) if shutdown.is_cancelled() => break,
+                Ok(()) => warn!("WebSocket stream closed for {}, falling back to HTTP polling", self.get_name()),
+                Err(e) => error!("WebSocket stream failed for {}: {:?}, falling back to HTTP polling", self.get_name(), e),
+            }
+            
+            // Poll over HTTP until it is time to try the WebSocket again
+            let reconnect_at = Instant::now() + Duration::from_secs(WS_RECONNECT_SECS);
+            while Instant::now() < reconnect_at && !shutdown.is_cancelled() {
+                self.poll_and_wait(&contract, pool, lanes, &mut last_synced_block, shutdown).await;
+            }
+        }
+        
+        info!("Stopped {} sync at block {}", self.get_name(), last_synced_block);
+        Ok(())
+    }
+    
+    fn verify_signature(&self, challenge: &str, signature: &str, _user: &str) -> Result<String, AppError> {
+        let sig_bytes = hex::decode(signature)
+            .map_err(|e| AppError::InvalidSignature(format!("Invalid signature hex: {}", e)))?;
+
+        if sig_bytes.len() != 65 {
+            return Err(AppError::InvalidSignature("Signature must be 65 bytes".into()));
+        }
+
+        let message_hash = hash_message(challenge);
+        let signature = Signature::try_from(sig_bytes.as_slice())
+            .map_err(|e| AppError::InvalidSignature(e.to_string()))?;
+        let recovered_address = signature
+            .recover(message_hash)
+            .map_err(|e| AppError::InvalidSignature(format!("Recovery failed: {}", e)))?;
+        
+        Ok(hex::encode(recovered_address.as_bytes()))
+    }
+    
+    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<BigDecimal> {
+        let subject_address = Address::from_str(subject).map_err(|e| anyhow!("Invalid subject address: {}", e))?;
+        let user_address = Address::from_str(user).map_err(|e| anyhow!("Invalid user address: {}", e))?;
+        
+        let abi: ethers::abi::Abi = serde_json::from_str(ABI).expect("Invalid abi");
+        let contract = ethers::contract::Contract::new(
+            self.contract_address,
+            abi,
+            self.provider.clone()
+        );
+
+        let balance: U256 = contract
+            .method::<_, U256>("sharesBalance", (subject_address, user_address))
+            .map_err(|e| anyhow!("Failed to get sharesBalance method: {}", e))?
+            .call()
+            .await
+            .map_err(|e| anyhow!("Failed to call sharesBalance: {}", e))?;
+            
+        let balance = BigDecimal::from_str(&balance.to_string())?;
+        Ok(scale_shares(&balance, self.chain.share_decimals))
+    }
+    
+    async fn get_gas_price(&self) -> Result<u128> {
+        let gas_price = self.provider
+            .get_gas_price()
+            .await
+            .map_err(|e| anyhow!("Failed to get gas price: {}", e))?;
+        
+        Ok(gas_price.as_u128())
+    }
+    
+    fn supports_tx_binding(&self) -> bool {
+        true
+    }
+}
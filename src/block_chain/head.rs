@@ -0,0 +1,83 @@
+//! Latest chain heads seen by the sync loops.
+//!
+//! Every sync loop reports the head it polled through [`record_head`], so
+//! `GET /chains` can show how far behind indexing is without calling the RPCs
+//! itself. The average block time is a moving average of the head advances
+//! observed, it starts once the head has moved twice.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::block_chain::ChainType;
+use crate::metrics;
+
+// Weight of the newest sample in the block time average
+const BLOCK_TIME_SMOOTHING: f64 = 0.2;
+
+/// Last head height observed for a chain (block, checkpoint or slot)
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ChainHead {
+    pub height: u64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub observed_at: OffsetDateTime,
+    pub avg_block_time_ms: Option<f64>,
+}
+
+static HEADS: LazyLock<RwLock<HashMap<ChainType, ChainHead>>> = LazyLock::new(Default::default);
+
+/// Remember the head a sync loop just polled
+pub fn record_head(chain_type: ChainType, height: u64) {
+    metrics::CHAIN_HEAD_BLOCK.with_label_values(&[chain_type.as_str()]).set(height as i64);
+    let now = OffsetDateTime::now_utc();
+    let mut heads = HEADS.write().unwrap();
+    let previous = heads.get(&chain_type).copied();
+    heads.insert(chain_type, advance(previous, height, now));
+}
+
+/// Last head recorded for a chain, `None` until its sync loop polled one
+pub fn get_head(chain_type: ChainType) -> Option<ChainHead> {
+    HEADS.read().unwrap().get(&chain_type).copied()
+}
+
+fn advance(previous: Option<ChainHead>, height: u64, now: OffsetDateTime) -> ChainHead {
+    let Some(previous) = previous else {
+        return ChainHead { height, observed_at: now, avg_block_time_ms: None };
+    };
+    // Keep the old observation time while the head stands still, so the next advance
+    // is measured over the whole interval
+    if height <= previous.height {
+        return previous;
+    }
+
+    let sample = (now - previous.observed_at).as_seconds_f64() * 1000.0 / (height - previous.height) as f64;
+    let avg_block_time_ms = match previous.avg_block_time_ms {
+        Some(avg) => avg + BLOCK_TIME_SMOOTHING * (sample - avg),
+        None => sample,
+    };
+    ChainHead { height, observed_at: now, avg_block_time_ms: Some(avg_block_time_ms) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    #[test]
+    fn test_block_time_average() {
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let head = advance(None, 100, start);
+        assert_eq!(head.avg_block_time_ms, None);
+
+        let head = advance(Some(head), 110, start + Duration::seconds(5));
+        assert_eq!(head.avg_block_time_ms, Some(500.0));
+
+        // A poll without a new block keeps the previous observation
+        let stalled = advance(Some(head), 110, start + Duration::seconds(6));
+        assert_eq!(stalled.observed_at, head.observed_at);
+
+        let head = advance(Some(stalled), 115, start + Duration::seconds(10));
+        assert_eq!(head.avg_block_time_ms, Some(600.0));
+    }
+}
@@ -1,33 +1,101 @@
-pub mod monad;
+pub mod aptos;
+pub mod chain_type;
+pub mod evm;
+pub mod head;
+pub mod reconcile;
+pub mod rpc;
 pub mod utils;
 pub mod sui;
+pub mod solana;
+pub mod trade;
+pub mod tx_binding;
 
 use anyhow::Result;
+use sqlx::types::BigDecimal;
 use sqlx::PgPool;
 use std::sync::Arc;
 use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::bot::lanes::ChatLanes;
+use crate::error::AppError;
+
+pub use chain_type::ChainType;
 
 /// Blockchain interface abstraction
 #[async_trait]
 pub trait Blockchain: Send + Sync {
+    /// Get the chain this implementation syncs
+    fn chain_type(&self) -> ChainType;
+    
     /// Get blockchain name
-    fn get_name(&self) -> &'static str;
+    fn get_name(&self) -> &'static str {
+        self.chain_type().as_str()
+    }
     
-    /// Sync transaction events
-    async fn sync_events(&self, pool: &PgPool) -> Result<()>;
+    /// Sync transaction events until `shutdown` is cancelled, stopping only after
+    /// the progress of the batch in flight has been saved. Members admitted by sync
+    /// are unmuted during their group's turn on `lanes`
+    async fn sync_events(&self, pool: &PgPool, lanes: &ChatLanes, shutdown: &CancellationToken) -> Result<()>;
     
-    /// Verify user signature
-    fn verify_signature(&self, challenge: &str, signature: &str) -> Result<String, String>;
+    /// Verify user signature over the challenge and return the signing address.
+    /// `user` is the address the client claims to sign with, needed by chains
+    /// whose signatures cannot recover the public key
+    fn verify_signature(&self, challenge: &str, signature: &str, user: &str) -> Result<String, AppError>;
     
-    /// Get user's shares balance
-    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<u64>;
+    /// Get user's shares balance in whole shares, scaled by the contract's configured decimals
+    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<BigDecimal>;
+    
+    /// Get current gas price in the chain's smallest native unit
+    async fn get_gas_price(&self) -> Result<u128>;
+    
+    /// Symbol of the chain's native currency
+    fn native_symbol(&self) -> &'static str {
+        self.chain_type().native_symbol()
+    }
+    
+    /// Whether sync watches for wallet binding proof transactions, see [`tx_binding`]
+    fn supports_tx_binding(&self) -> bool {
+        false
+    }
 }
 
 // Factory function to create different chain implementations
-pub fn create_blockchain(chain_type: &str, config: Arc<crate::AppConfig>) -> Box<dyn Blockchain> {
-    match chain_type {
-        "monad" => Box::new(monad::MonadBlockchain::new(config)),
-        "sui" => Box::new(sui::SuiBlockchain::new(config)),
-        _ => panic!("Unsupported blockchain type: {}", chain_type),
+pub fn create_blockchain(chain_type: ChainType, config: Arc<crate::AppConfig>) -> Result<Box<dyn Blockchain>, AppError> {
+    Ok(match chain_type {
+        ChainType::Monad | ChainType::Base | ChainType::Arbitrum => Box::new(evm::EvmBlockchain::new(&config, chain_type)?),
+        ChainType::Sui => Box::new(sui::SuiBlockchain::new(config)?),
+        ChainType::Solana => Box::new(solana::SolanaBlockchain::new(config)),
+        ChainType::Aptos => Box::new(aptos::AptosBlockchain::new(config)),
+    })
+} 
+
+// Sync trade events of every chain enabled in ENABLED_CHAINS until all sync loops end
+pub async fn sync_trade_events(config: crate::AppConfig, pool: PgPool, lanes: ChatLanes, shutdown: CancellationToken) {
+    let config = Arc::new(config);
+    
+    let mut sync_tasks = Vec::new();
+    for chain_type in &config.enabled_chains {
+        let blockchain = match create_blockchain(*chain_type, config.clone()) {
+            Ok(blockchain) => blockchain,
+            Err(e) => {
+                warn!("{} sync disabled: {}", chain_type, e);
+                continue;
+            }
+        };
+        
+        let pool = pool.clone();
+        let lanes = lanes.clone();
+        let shutdown = shutdown.clone();
+        let span = info_span!("sync", chain = %chain_type);
+        sync_tasks.push(async move {
+            match blockchain.sync_events(&pool, &lanes, &shutdown).await {
+                Ok(()) => info!("{} sync stopped", blockchain.get_name()),
+                Err(e) => error!("Error syncing {} events: {:?}", blockchain.get_name(), e),
+            }
+        }.instrument(span));
     }
-} 
\ No newline at end of file
+    
+    futures::future::join_all(sync_tasks).await;
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::time::Duration;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::block_chain::{create_blockchain, Blockchain};
+use crate::bot::api::TelegramBotApi;
+use crate::db::operations::{get_reconcile_targets, set_trade_balance};
+use crate::enforcement::handle_balance_change;
+use crate::shutdown::sleep_or_shutdown;
+use crate::AppConfig;
+
+/// Outcome of one reconciliation pass over a chain
+#[derive(Clone, Debug, Default)]
+pub struct ReconcileStats {
+    pub checked: u64,
+    pub corrected: u64,
+    pub failed: u64,
+}
+
+// Compare every verified holder's on-chain balance with the trades table and fix drift
+pub async fn reconcile_chain(pool: &PgPool, blockchain: &dyn Blockchain) -> Result<ReconcileStats, sqlx::Error> {
+    let chain_type = blockchain.chain_type();
+    let mut stats = ReconcileStats::default();
+
+    for target in get_reconcile_targets(pool, chain_type).await? {
+        stats.checked += 1;
+
+        let balance = match blockchain.get_shares_balance(&target.subject, &target.trader).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                error!("Failed to get {} balance of {} for {}: {:?}", chain_type, target.trader, target.subject, e);
+                stats.failed += 1;
+                continue;
+            }
+        };
+
+        if balance == target.share_amount {
+            continue;
+        }
+
+        warn!(
+            "Balance drift for {} on {} ({}): local {}, on-chain {}",
+            target.trader, target.subject, chain_type, target.share_amount, balance
+        );
+        set_trade_balance(pool, &target.trader, &target.subject, chain_type, &balance).await?;
+        stats.corrected += 1;
+
+        if let Err(e) = handle_balance_change(pool, &TelegramBotApi, chain_type, &target.trader, &target.subject, &balance, None).await {
+            error!("Failed to enforce corrected balance of {} on {}: {:?}", target.trader, target.subject, e);
+            stats.failed += 1;
+        }
+    }
+
+    Ok(stats)
+}
+
+// Periodically reconcile local balances of every enabled chain against the chain
+pub async fn reconcile_loop(pool: PgPool, config: AppConfig, shutdown: CancellationToken) {
+    let config = Arc::new(config);
+
+    while !sleep_or_shutdown(&shutdown, Duration::from_secs(config.reconcile_interval_secs)).await {
+        for chain_type in &config.enabled_chains {
+            let blockchain = match create_blockchain(*chain_type, config.clone()) {
+                Ok(blockchain) => blockchain,
+                Err(e) => {
+                    warn!("Skipping reconciliation of {}: {}", chain_type, e);
+                    continue;
+                }
+            };
+
+            match reconcile_chain(&pool, blockchain.as_ref()).await {
+                Ok(stats) => info!(
+                    "Reconciled {}: {} checked, {} corrected, {} failed",
+                    chain_type, stats.checked, stats.corrected, stats.failed
+                ),
+                Err(e) => error!("Reconciliation of {} failed: {:?}", chain_type, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Failover between the RPC endpoints configured for a chain.
+//!
+//! `CHAIN_RPC`, `SUI_RPC` and the `rpc_url` of `evm_chains` rows take a comma-separated list
+//! of URLs. Every call goes to the healthiest endpoint first and moves on to the next one when
+//! it cannot be reached, so one node going down does not stall sync or balance checks. Each
+//! endpoint keeps a health score, raised by successful calls and cut by failures, and a failing
+//! endpoint is benched for a cooldown growing with its consecutive failures before it is
+//! preferred again. Benched endpoints are still tried, last, when all the others failed.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::block_chain::ChainType;
+use crate::error::AppError;
+use crate::metrics::{RPC_ENDPOINT_HEALTH, RPC_FAILOVERS};
+
+// Score of a healthy endpoint, every endpoint starts there
+const MAX_SCORE: u32 = 100;
+// Score given back by a successful call and taken by a failed one
+const SUCCESS_CREDIT: u32 = 10;
+const FAILURE_PENALTY: u32 = 40;
+// Cooldown after the first consecutive failure, doubled by each further one up to the cap
+const BASE_COOLDOWN: Duration = Duration::from_secs(5);
+const MAX_COOLDOWN: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+struct Health {
+    score: u32,
+    consecutive_failures: u32,
+    benched_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    // Scheme and host only, URLs of hosted nodes often carry an API key in their path or query
+    label: String,
+    health: Mutex<Health>,
+}
+
+/// RPC endpoints of one chain, in their configured order, with their health
+#[derive(Debug)]
+pub struct RpcEndpoints {
+    chain: ChainType,
+    endpoints: Vec<Endpoint>,
+}
+
+fn endpoint_label(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => match (parsed.host_str(), parsed.port()) {
+            (Some(host), Some(port)) => format!("{}://{}:{}", parsed.scheme(), host, port),
+            (Some(host), None) => format!("{}://{}", parsed.scheme(), host),
+            _ => parsed.scheme().to_string(),
+        },
+        Err(_) => "invalid".to_string(),
+    }
+}
+
+fn cooldown(consecutive_failures: u32) -> Duration {
+    let doublings = consecutive_failures.saturating_sub(1).min(16);
+    (BASE_COOLDOWN * 2u32.pow(doublings)).min(MAX_COOLDOWN)
+}
+
+impl RpcEndpoints {
+    /// Endpoints of a comma-separated list of URLs, the first one preferred while all are healthy
+    pub fn parse(chain: ChainType, urls: &str) -> Result<Self, AppError> {
+        let endpoints: Vec<Endpoint> = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| Endpoint {
+                url: url.to_string(),
+                label: endpoint_label(url),
+                health: Mutex::new(Health { score: MAX_SCORE, consecutive_failures: 0, benched_until: None }),
+            })
+            .collect();
+        if endpoints.is_empty() {
+            return Err(AppError::Config(format!("No {} RPC URL configured", chain)));
+        }
+        for endpoint in &endpoints {
+            RPC_ENDPOINT_HEALTH.with_label_values(&[chain.as_str(), &endpoint.label]).set(MAX_SCORE as i64);
+        }
+        Ok(Self { chain, endpoints })
+    }
+
+    pub fn url(&self, index: usize) -> &str {
+        &self.endpoints[index].url
+    }
+
+    /// URLs in their configured order, the index of each is the one [`Self::call`] hands out
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.endpoints.iter().map(|endpoint| endpoint.url.as_str())
+    }
+
+    /// Indexes of the endpoints in the order a call tries them: endpoints that are not benched
+    /// by score, ties in configured order, then benched ones by the end of their cooldown
+    pub fn attempt_order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let mut available = Vec::new();
+        let mut benched = Vec::new();
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            let health = endpoint.health.lock().unwrap();
+            match health.benched_until {
+                Some(until) if until > now => benched.push((index, until)),
+                _ => available.push((index, health.score)),
+            }
+        }
+        available.sort_by(|a, b| b.1.cmp(&a.1));
+        benched.sort_by_key(|(_, until)| *until);
+        available.into_iter().map(|(index, _)| index).chain(benched.into_iter().map(|(index, _)| index)).collect()
+    }
+
+    /// Credit an endpoint that answered, ending its cooldown
+    pub fn record_success(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let mut health = endpoint.health.lock().unwrap();
+        if health.consecutive_failures > 0 {
+            info!("{} RPC endpoint {} recovered", self.chain, endpoint.label);
+        }
+        health.score = (health.score + SUCCESS_CREDIT).min(MAX_SCORE);
+        health.consecutive_failures = 0;
+        health.benched_until = None;
+        RPC_ENDPOINT_HEALTH.with_label_values(&[self.chain.as_str(), &endpoint.label]).set(health.score as i64);
+    }
+
+    /// Penalize an endpoint that could not be reached and bench it for its cooldown
+    pub fn record_failure(&self, index: usize, error: &dyn Display) {
+        let endpoint = &self.endpoints[index];
+        let mut health = endpoint.health.lock().unwrap();
+        health.score = health.score.saturating_sub(FAILURE_PENALTY);
+        health.consecutive_failures += 1;
+        let cooldown = cooldown(health.consecutive_failures);
+        health.benched_until = Some(Instant::now() + cooldown);
+        RPC_ENDPOINT_HEALTH.with_label_values(&[self.chain.as_str(), &endpoint.label]).set(health.score as i64);
+        warn!(
+            "{} RPC endpoint {} failed ({} in a row), benched for {:?}: {}",
+            self.chain, endpoint.label, health.consecutive_failures, cooldown, error
+        );
+    }
+
+    /// Run `call` against the endpoints in [`Self::attempt_order`] until one succeeds, returning
+    /// the error of the last one when all failed. `call` gets the index of the endpoint to use and
+    /// only fails for errors worth trying another endpoint for, answers of the node belong in `T`
+    pub async fn call<T, E, F, Fut>(&self, mut call: F) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut(usize) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let order = self.attempt_order();
+        let mut last_error = None;
+        for (attempt, index) in order.into_iter().enumerate() {
+            if attempt > 0 {
+                RPC_FAILOVERS.with_label_values(&[self.chain.as_str()]).inc();
+            }
+            match call(index).await {
+                Ok(value) => {
+                    self.record_success(index);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.record_failure(index, &e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.expect("RpcEndpoints always holds an endpoint"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failover_prefers_healthy_endpoints() {
+        let endpoints = RpcEndpoints::parse(ChainType::Sui, " https://a.example/key, ,https://b.example:8443 ").unwrap();
+        assert_eq!(endpoints.endpoints.len(), 2);
+        assert_eq!(endpoints.url(0), "https://a.example/key");
+        assert_eq!(endpoints.endpoints[0].label, "https://a.example");
+        assert_eq!(endpoints.endpoints[1].label, "https://b.example:8443");
+        assert_eq!(endpoints.attempt_order(), vec![0, 1]);
+
+        // The first endpoint is down, the call fails over and the first one is benched
+        let mut tried = Vec::new();
+        let result: Result<usize, String> = endpoints
+            .call(|index| {
+                tried.push(index);
+                async move { if index == 0 { Err("connection refused".to_string()) } else { Ok(index) } }
+            })
+            .await;
+        assert_eq!(result, Ok(1));
+        assert_eq!(tried, vec![0, 1]);
+        assert_eq!(endpoints.attempt_order(), vec![1, 0]);
+
+        // Benched endpoints are still tried when every other one failed
+        let result: Result<usize, String> = endpoints.call(|index| async move { Err(format!("down {}", index)) }).await;
+        assert_eq!(result, Err("down 0".to_string()));
+
+        // Recovery lifts the bench, the score is earned back call by call
+        endpoints.record_success(0);
+        endpoints.record_success(1);
+        assert_eq!(endpoints.attempt_order(), vec![1, 0]);
+        for _ in 0..8 {
+            endpoints.record_success(0);
+        }
+        assert_eq!(endpoints.attempt_order(), vec![0, 1]);
+
+        assert!(RpcEndpoints::parse(ChainType::Monad, " , ").is_err());
+    }
+
+    #[test]
+    fn test_cooldown_grows_to_cap() {
+        assert_eq!(cooldown(1), BASE_COOLDOWN);
+        assert_eq!(cooldown(3), BASE_COOLDOWN * 4);
+        assert_eq!(cooldown(50), MAX_COOLDOWN);
+    }
+}
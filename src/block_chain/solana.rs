@@ -0,0 +1,424 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use base64::prelude::*;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, Instrument};
+
+use crate::block_chain::{head, Blockchain, ChainType};
+use crate::block_chain::trade::{apply_trade_event, scale_shares};
+use crate::bot::api::TelegramBotApi;
+use crate::bot::lanes::ChatLanes;
+use crate::db::models::{EventLocation, NewTradeEvent};
+use crate::db::operations::{get_last_synced_block_with_metadata, update_last_synced_block_with_metadata};
+use crate::error::AppError;
+use crate::shutdown::sleep_or_shutdown;
+use crate::AppConfig;
+
+/// Solana blockchain implementation
+pub struct SolanaBlockchain {
+    rpc_url: String,
+    program_id: String,
+    config: Arc<AppConfig>,
+}
+
+/// Trade event emitted by the shares program (Anchor `emit!` layout)
+#[derive(Debug)]
+struct SolanaTradeEvent {
+    trader: String,
+    subject: String,
+    is_buy: bool,
+    share_amount: u64,
+    sol_amount: u64,
+    protocol_fee: u64,
+    subject_fee: u64,
+    supply: u64,
+}
+
+// Page size for getSignaturesForAddress
+const SIGNATURE_PAGE_LIMIT: usize = 1000;
+
+// Minimal reader for the borsh encoded event payload
+struct BorshReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BorshReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.offset + len > self.data.len() {
+            return Err(anyhow!("Unexpected end of event data"));
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn read_pubkey(&mut self) -> Result<String> {
+        Ok(bs58::encode(self.take(32)?).into_string())
+    }
+
+    fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.take(1)?[0] != 0)
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let bytes: [u8; 8] = self.take(8)?.try_into()?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+/// Anchor discriminator: first 8 bytes of sha256("<namespace>:<name>")
+fn discriminator(preimage: &str) -> [u8; 8] {
+    let hash = Sha256::digest(preimage.as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+/// Derive a program address the same way `Pubkey::find_program_address` does
+fn find_program_address(seeds: &[&[u8]], program_id: &[u8; 32]) -> Option<[u8; 32]> {
+    for bump in (0..=u8::MAX).rev() {
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update([bump]);
+        hasher.update(program_id);
+        hasher.update(b"ProgramDerivedAddress");
+        let hash: [u8; 32] = hasher.finalize().into();
+
+        // A valid program address must not be a point on the ed25519 curve
+        if CompressedEdwardsY(hash).decompress().is_none() {
+            return Some(hash);
+        }
+    }
+    None
+}
+
+fn decode_pubkey(address: &str) -> Result<[u8; 32]> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|e| anyhow!("Invalid Solana address {}: {}", address, e))?;
+    bytes.try_into().map_err(|_| anyhow!("Solana address {} is not 32 bytes", address))
+}
+
+impl SolanaBlockchain {
+    pub fn new(config: Arc<AppConfig>) -> Self {
+        let rpc_url = config.solana_rpc.clone().unwrap_or_else(|| "https://api.mainnet-beta.solana.com".to_string());
+        let program_id = config.solana_program_id.clone().unwrap_or_default();
+
+        Self {
+            rpc_url,
+            program_id,
+            config,
+        }
+    }
+
+    /// Call a Solana JSON-RPC method and return its result
+    async fn rpc_call(&self, method: &str, params: Value) -> Result<Value> {
+        let client = Client::new();
+
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params
+        });
+
+        let response = client.post(&self.rpc_url)
+            .json(&payload)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Solana RPC request failed: {}", response.status()));
+        }
+
+        let response_json: Value = response.json().await?;
+
+        if let Some(error) = response_json.get("error") {
+            return Err(anyhow!("Solana RPC returned error: {}", error));
+        }
+
+        response_json.get("result")
+            .cloned()
+            .ok_or_else(|| anyhow!("Cannot parse Solana RPC response"))
+    }
+
+    /// Current slot of the cluster
+    async fn get_slot(&self) -> Result<u64> {
+        self.rpc_call("getSlot", json!([])).await?
+            .as_u64()
+            .ok_or_else(|| anyhow!("Cannot parse Solana slot"))
+    }
+
+    /// Signatures of successful program transactions after `until`, oldest first
+    async fn get_new_signatures(&self, until: Option<String>) -> Result<Vec<(String, u64)>> {
+        let mut signatures = Vec::new();
+        let mut before: Option<String> = None;
+
+        loop {
+            let mut options = json!({ "limit": SIGNATURE_PAGE_LIMIT, "commitment": "finalized" });
+            if let Some(until) = &until {
+                options["until"] = json!(until);
+            }
+            if let Some(before) = &before {
+                options["before"] = json!(before);
+            }
+
+            let result = self.rpc_call("getSignaturesForAddress", json!([self.program_id, options])).await?;
+            let page = result.as_array().cloned().unwrap_or_default();
+
+            for entry in &page {
+                let signature = entry.get("signature").and_then(|s| s.as_str());
+                let slot = entry.get("slot").and_then(|s| s.as_u64());
+                let failed = entry.get("err").map(|e| !e.is_null()).unwrap_or(false);
+
+                if let (Some(signature), Some(slot)) = (signature, slot) {
+                    before = Some(signature.to_string());
+                    if !failed {
+                        signatures.push((signature.to_string(), slot));
+                    }
+                }
+            }
+
+            if page.len() < SIGNATURE_PAGE_LIMIT {
+                break;
+            }
+        }
+
+        // RPC returns newest first
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    /// Decode the Trade events logged by one transaction, with its block time
+    async fn get_trade_events(&self, signature: &str) -> Result<(Vec<SolanaTradeEvent>, Option<OffsetDateTime>)> {
+        let result = self.rpc_call(
+            "getTransaction",
+            json!([signature, { "encoding": "json", "maxSupportedTransactionVersion": 0, "commitment": "finalized" }]),
+        ).await?;
+
+        let logs = result.get("meta")
+            .and_then(|m| m.get("logMessages"))
+            .and_then(|l| l.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let block_time = result.get("blockTime")
+            .and_then(|t| t.as_i64())
+            .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok());
+
+        let trade_discriminator = discriminator("event:Trade");
+        let mut events = Vec::new();
+
+        for log in logs.iter().filter_map(|l| l.as_str()) {
+            let Some(encoded) = log.strip_prefix("Program data: ") else {
+                continue;
+            };
+            let data = match BASE64_STANDARD.decode(encoded) {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+            if data.len() < 8 || data[..8] != trade_discriminator {
+                continue;
+            }
+
+            let mut reader = BorshReader::new(&data[8..]);
+            events.push(SolanaTradeEvent {
+                trader: reader.read_pubkey()?,
+                subject: reader.read_pubkey()?,
+                is_buy: reader.read_bool()?,
+                share_amount: reader.read_u64()?,
+                sol_amount: reader.read_u64()?,
+                protocol_fee: reader.read_u64()?,
+                subject_fee: reader.read_u64()?,
+                supply: reader.read_u64()?,
+            });
+        }
+
+        Ok((events, block_time))
+    }
+
+    /// Process Solana trade event
+    async fn process_trade_event(&self, event: &SolanaTradeEvent, location: &EventLocation, pool: &sqlx::PgPool) -> Result<()> {
+        debug!("Processing Solana Trade event: {:?}", event);
+
+        let share_amount = BigDecimal::from(event.share_amount);
+        // Base58 addresses are case sensitive, keep them as emitted
+        let trader = event.trader.clone();
+        let subject = event.subject.clone();
+
+        let protocol_fee = BigDecimal::from(event.protocol_fee);
+        let subject_fee = BigDecimal::from(event.subject_fee);
+
+        let record = NewTradeEvent {
+            trader,
+            subject,
+            is_buy: event.is_buy,
+            share_amount,
+            eth_amount: BigDecimal::from(event.sol_amount),
+            protocol_fee,
+            subject_fee,
+            supply: BigDecimal::from(event.supply),
+        };
+        apply_trade_event(pool, &TelegramBotApi, self.chain_type(), location, &record, None, self.config.share_decimals(self.chain_type())).await
+    }
+}
+
+#[async_trait]
+impl Blockchain for SolanaBlockchain {
+    fn chain_type(&self) -> ChainType {
+        ChainType::Solana
+    }
+
+    async fn sync_events(&self, pool: &PgPool, _lanes: &ChatLanes, shutdown: &CancellationToken) -> Result<()> {
+        // Solana progress is the last processed transaction signature, kept in metadata
+        let (last_slot, metadata) = get_last_synced_block_with_metadata(pool, 0, self.chain_type()).await?;
+        let mut last_signature = metadata;
+
+        info!("Starting sync from slot {} (signature {:?}) for {}", last_slot, last_signature, self.get_name());
+
+        while !shutdown.is_cancelled() {
+            match self.get_slot().await {
+                Ok(slot) => head::record_head(self.chain_type(), slot),
+                Err(e) => debug!("Failed to get current {} slot: {:?}", self.get_name(), e),
+            }
+
+            match self.get_new_signatures(last_signature.clone()).await {
+                Ok(signatures) if signatures.is_empty() => {
+                    debug!("No new transactions for {}, waiting...", self.get_name());
+                    sleep_or_shutdown(shutdown, Duration::from_secs(self.config.sync_idle_interval_secs)).await;
+                },
+                Ok(signatures) => {
+                    debug!("Found {} new transactions for {}", signatures.len(), self.get_name());
+
+                    let span = info_span!("sync_batch", chain = %self.chain_type(), transactions = signatures.len());
+                    async {
+                        for (signature, slot) in signatures {
+                            // Progress is saved after every transaction, stop between two of them
+                            if shutdown.is_cancelled() {
+                                break;
+                            }
+                            let (events, block_time) = match self.get_trade_events(&signature).await {
+                                Ok(result) => result,
+                                Err(e) => {
+                                    // Stop here and retry this transaction on the next round
+                                    error!("Failed to load Solana transaction {}: {:?}", signature, e);
+                                    break;
+                                }
+                            };
+
+                            for (index, event) in events.iter().enumerate() {
+                                let location = EventLocation {
+                                    block_number: Some(slot as i64),
+                                    tx_hash: signature.clone(),
+                                    log_index: index as i64,
+                                    block_time,
+                                };
+                                if let Err(e) = self.process_trade_event(event, &location, pool).await {
+                                    error!("Error processing Solana trade event: {:?}", e);
+                                }
+                            }
+
+                            if let Err(e) = update_last_synced_block_with_metadata(pool, slot, signature.clone(), self.chain_type()).await {
+                                error!("Failed to update last synced signature: {:?}", e);
+                            }
+                            last_signature = Some(signature);
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                },
+                Err(e) => {
+                    error!("Failed to query Solana signatures: {:?}", e);
+                    sleep_or_shutdown(shutdown, Duration::from_secs(self.config.sync_error_backoff_secs)).await;
+                }
+            }
+
+            sleep_or_shutdown(shutdown, Duration::from_secs(1)).await;
+        }
+
+        info!("Stopped {} sync at signature {:?}", self.get_name(), last_signature);
+        Ok(())
+    }
+
+    fn verify_signature(&self, challenge: &str, signature: &str, user: &str) -> Result<String, AppError> {
+        // ed25519 signatures cannot be recovered, verify against the claimed wallet
+        let public_key = decode_pubkey(user).map_err(|e| AppError::BadRequest(e.to_string()))?;
+        let verifying_key = VerifyingKey::from_bytes(&public_key)
+            .map_err(|e| AppError::BadRequest(format!("Invalid public key: {}", e)))?;
+
+        let sig_bytes = bs58::decode(signature)
+            .into_vec()
+            .map_err(|e| AppError::InvalidSignature(format!("Invalid signature base58: {}", e)))?;
+        let sig_bytes: [u8; 64] = sig_bytes
+            .try_into()
+            .map_err(|_| AppError::InvalidSignature("Signature must be 64 bytes".to_string()))?;
+
+        verifying_key
+            .verify(challenge.as_bytes(), &Signature::from_bytes(&sig_bytes))
+            .map_err(|e| AppError::InvalidSignature(e.to_string()))?;
+
+        Ok(user.to_string())
+    }
+
+    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<BigDecimal> {
+        let program_id = decode_pubkey(&self.program_id)?;
+        let subject_key = decode_pubkey(subject)?;
+        let user_key = decode_pubkey(user)?;
+
+        // Holder balances live in a PDA seeded by ("shares", subject, holder)
+        let balance_account = find_program_address(&[b"shares", &subject_key, &user_key], &program_id)
+            .ok_or_else(|| anyhow!("Cannot derive shares balance account"))?;
+
+        let result = self.rpc_call(
+            "getAccountInfo",
+            json!([bs58::encode(balance_account).into_string(), { "encoding": "base64" }]),
+        ).await?;
+
+        let data = match result.get("value").filter(|v| !v.is_null()) {
+            Some(value) => value.get("data")
+                .and_then(|d| d.get(0))
+                .and_then(|d| d.as_str())
+                .ok_or_else(|| anyhow!("Cannot parse shares balance account"))?,
+            // No account means the user never bought
+            None => return Ok(BigDecimal::from(0)),
+        };
+
+        let bytes = BASE64_STANDARD.decode(data)?;
+        // Skip the 8 byte account discriminator
+        let mut reader = BorshReader::new(bytes.get(8..).unwrap_or_default());
+        let balance = BigDecimal::from(reader.read_u64()?);
+        Ok(scale_shares(&balance, self.config.share_decimals(self.chain_type())))
+    }
+
+    async fn get_gas_price(&self) -> Result<u128> {
+        let result = self.rpc_call("getRecentPrioritizationFees", json!([])).await?;
+
+        let mut fees: Vec<u128> = result.as_array()
+            .map(|entries| entries.iter()
+                .filter_map(|e| e.get("prioritizationFee").and_then(|f| f.as_u64()))
+                .map(u128::from)
+                .collect())
+            .unwrap_or_default();
+
+        // Median priority fee in micro-lamports per compute unit
+        fees.sort_unstable();
+        Ok(fees.get(fees.len() / 2).copied().unwrap_or(0))
+    }
+}
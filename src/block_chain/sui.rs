@@ -7,21 +7,102 @@ use sqlx::PgPool;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use teloxide::Bot;
-use teloxide::prelude::{Requester, UserId};
-use teloxide::types::ChatPermissions;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
 use async_trait::async_trait;
 use base64::prelude::*;
-use sui_sdk::types::crypto::{Signature, SignatureScheme};
-use sui_sdk::types::base_types::SuiAddress;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use ed25519_dalek::Verifier;
+use ethers::core::k256::ecdsa;
+use ethers::utils::hex;
+use tracing::{debug, error, info, info_span, Instrument};
 
-use crate::block_chain::Blockchain;
-use crate::db::operations::{get_last_synced_block, get_last_synced_block_with_metadata, process_buy_trade, process_sell_trade, update_last_synced_block, update_last_synced_block_with_metadata};
+use crate::block_chain::{head, Blockchain, ChainType};
+use crate::block_chain::rpc::RpcEndpoints;
+use crate::block_chain::trade::{apply_trade_event, scale_shares};
+use crate::bot::api::TelegramBotApi;
+use crate::bot::lanes::ChatLanes;
+use crate::db::models::{EventLocation, NewTradeEvent, RawEventPayload};
+use crate::db::operations::{get_cursor, record_undecoded_trade_event, set_cursor};
+use crate::error::AppError;
+use crate::shutdown::sleep_or_shutdown;
 use crate::AppConfig;
 
+// Scheme flags, the first byte of a serialized signature and of the hashed public key
+const ED25519_FLAG: u8 = 0x00;
+const SECP256K1_FLAG: u8 = 0x01;
+// Intent prefix of personal messages: scope PersonalMessage, version 0, app Sui
+const PERSONAL_MESSAGE_INTENT: [u8; 3] = [3, 0, 0];
+
+type Blake2b256 = Blake2b<U32>;
+
+// BCS encoding of a byte vector, ULEB128 length then the bytes
+fn bcs_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + 5);
+    let mut len = bytes.len();
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Digest a Sui wallet signs for `signPersonalMessage(message)`
+pub fn personal_message_digest(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Blake2b256::new();
+    hasher.update(PERSONAL_MESSAGE_INTENT);
+    hasher.update(bcs_bytes(message));
+    hasher.finalize().into()
+}
+
+/// Sui address of a public key, the BLAKE2b-256 of its scheme flag and bytes
+pub fn public_key_address(flag: u8, public_key: &[u8]) -> String {
+    let mut hasher = Blake2b256::new();
+    hasher.update([flag]);
+    hasher.update(public_key);
+    hex::encode(hasher.finalize())
+}
+
+/// Check a serialized Sui signature (flag, signature, public key) over a personal
+/// message and return the signer's address. Ed25519 and Secp256k1 keys are supported.
+pub fn verify_personal_message(message: &[u8], serialized: &[u8]) -> Result<String, String> {
+    let (&flag, rest) = serialized.split_first().ok_or("Empty signature")?;
+    if rest.len() < 64 {
+        return Err("Signature is too short".to_string());
+    }
+    let (signature, public_key) = rest.split_at(64);
+    let digest = personal_message_digest(message);
+
+    match flag {
+        ED25519_FLAG => {
+            let public_key: [u8; 32] = public_key.try_into().map_err(|_| "Ed25519 public key must be 32 bytes")?;
+            let key = ed25519_dalek::VerifyingKey::from_bytes(&public_key).map_err(|e| format!("Invalid public key: {}", e))?;
+            let signature = ed25519_dalek::Signature::from_slice(signature).map_err(|e| e.to_string())?;
+            key.verify(&digest, &signature).map_err(|e| e.to_string())?;
+        }
+        // Secp256k1 signs the SHA-256 of the digest, with a normalized (low) s
+        SECP256K1_FLAG => {
+            let key = ecdsa::VerifyingKey::from_sec1_bytes(public_key).map_err(|e| format!("Invalid public key: {}", e))?;
+            let signature = ecdsa::Signature::from_slice(signature).map_err(|e| e.to_string())?;
+            ecdsa::signature::Verifier::verify(&key, &digest, &signature).map_err(|e| e.to_string())?;
+        }
+        _ => return Err(format!("Unsupported signature scheme {:#04x}", flag)),
+    }
+
+    Ok(public_key_address(flag, public_key))
+}
+
 /// Sui blockchain implementation
 pub struct SuiBlockchain {
-    rpc_url: String,
+    // Fullnodes of SUI_RPC, failed over between by health
+    endpoints: RpcEndpoints,
     contract_address: String,
     shares_trading_object_id: String,
     config: Arc<AppConfig>,
@@ -77,159 +158,178 @@ struct SuiEvent {
     sender: String,
     #[serde(rename = "packageId")]
     package_id: String,
+    // Decoded after the event is read, so a payload the parser trips on is still kept
     #[serde(rename = "parsedJson")]
-    parsed_json: SuiTradeEvent,
+    parsed_json: Value,
     bcs: String,
     #[serde(rename = "bcsEncoding")]
     bcs_encoding: String,
 }
 
+// Minimal reader for the bcs encoded event payload
+struct BcsReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BcsReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.offset + len > self.data.len() {
+            return Err(anyhow!("Unexpected end of event data"));
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn read_address(&mut self) -> Result<String> {
+        Ok(format!("0x{}", hex::encode(self.take(32)?)))
+    }
+
+    fn read_bool(&mut self) -> Result<bool> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(anyhow!("Invalid bcs bool {}", other)),
+        }
+    }
+
+    fn read_u64(&mut self) -> Result<String> {
+        let bytes: [u8; 8] = self.take(8)?.try_into()?;
+        Ok(u64::from_le_bytes(bytes).to_string())
+    }
+}
+
+impl SuiTradeEvent {
+    // Decode the bcs payload of a Trade event, fields in the order of the Move struct
+    fn from_bcs(data: &[u8]) -> Result<Self> {
+        let mut reader = BcsReader::new(data);
+        let event = Self {
+            trader: reader.read_address()?,
+            subject: reader.read_address()?,
+            is_buy: reader.read_bool()?,
+            amount: reader.read_u64()?,
+            price: reader.read_u64()?,
+            protocol_fee: reader.read_u64()?,
+            subject_fee: reader.read_u64()?,
+            supply: reader.read_u64()?,
+        };
+        if reader.offset != data.len() {
+            return Err(anyhow!("{} unexpected bytes after the Trade event", data.len() - reader.offset));
+        }
+        Ok(event)
+    }
+
+    // Trade record with normalized addresses, amounts still in raw units
+    fn to_record(&self) -> Result<NewTradeEvent> {
+        // Raw amounts may exceed u64 when the contract uses share decimals
+        let share_amount = BigDecimal::from_str(&self.amount)
+            .map_err(|e| anyhow!("Cannot parse transaction amount {}: {:?}", self.amount, e))?;
+
+        // Normalize to the full zero-padded form used in every table
+        Ok(NewTradeEvent {
+            trader: ChainType::Sui.normalize_address(&self.trader),
+            subject: ChainType::Sui.normalize_address(&self.subject),
+            is_buy: self.is_buy,
+            share_amount,
+            eth_amount: BigDecimal::from_str(&self.price)?,
+            protocol_fee: BigDecimal::from_str(&self.protocol_fee)?,
+            subject_fee: BigDecimal::from_str(&self.subject_fee)?,
+            supply: BigDecimal::from_str(&self.supply)?,
+        })
+    }
+}
+
+/// Decode a Trade event from the raw bcs stored with it, `encoding` as reported by the RPC.
+/// Amounts are in raw units like those of the live sync
+pub fn decode_raw_trade_event(bcs: &str, encoding: &str) -> Result<NewTradeEvent> {
+    let data = match encoding {
+        "base64" => BASE64_STANDARD.decode(bcs)?,
+        "base58" => bs58::decode(bcs).into_vec()?,
+        other => return Err(anyhow!("Unsupported bcs encoding {}", other)),
+    };
+    SuiTradeEvent::from_bcs(&data)?.to_record()
+}
+
 impl SuiBlockchain {
-    pub fn new(config: Arc<AppConfig>) -> Self {
-        let rpc_url = config.sui_rpc.clone().unwrap_or_else(|| "https://fullnode.mainnet.sui.io:443".to_string());
+    pub fn new(config: Arc<AppConfig>) -> Result<Self, AppError> {
+        let endpoints = RpcEndpoints::parse(ChainType::Sui, config.sui_rpc.as_deref().unwrap_or("https://fullnode.mainnet.sui.io:443"))?;
         let contract_address = config.sui_contract.clone().unwrap_or_else(|| "0x000".to_string());
         let shares_trading_object_id = config.sui_shares_trading_object_id.clone().unwrap_or_else(|| "0x000".to_string());
         
-        Self {
-            rpc_url,
+        Ok(Self {
+            endpoints,
             contract_address,
             shares_trading_object_id,
             config,
-        }
+        })
     }
     
-    /// Remove 0x prefix from address
-    fn remove_0x_prefix(&self, address: &str) -> String {
-        if address.starts_with("0x") {
-            address[2..].to_string()
-        } else {
-            address.to_string()
+    /// Send a JSON-RPC request to the healthiest fullnode and return its result. Only endpoints
+    /// that cannot be reached are failed over, errors answered by the node are returned as they are
+    async fn rpc_call(&self, payload: &Value) -> Result<Value> {
+        let client = Client::new();
+        let response_json: Value = self.endpoints
+            .call(move |index| {
+                let request = client.post(self.endpoints.url(index)).json(payload);
+                async move { anyhow::Ok(request.send().await?.error_for_status()?.json().await?) }
+            })
+            .await?;
+        
+        if let Some(error) = response_json.get("error") {
+            return Err(anyhow!("Sui RPC returned error: {}", error));
         }
+        response_json.get("result")
+            .cloned()
+            .ok_or_else(|| anyhow!("Cannot parse Sui RPC response"))
     }
     
-    /// Process Sui trade event
-    async fn process_trade_event(&self, event: &SuiTradeEvent, pool: &sqlx::PgPool) -> Result<()> {
-        println!("Processing Sui Trade event: {:?}", event);
-        
-        // Parse string to u64
-        let share_amount = match event.amount.parse::<u64>() {
-            Ok(amount) => BigDecimal::from(amount),
+    /// Process Sui trade event, its raw bcs is stored with it so a parser fix can be replayed.
+    /// An event that cannot be decoded is kept undecoded for the replay instead
+    async fn process_trade_event(&self, event: &SuiEvent, location: &EventLocation, pool: &sqlx::PgPool) -> Result<()> {
+        debug!("Processing Sui Trade event: {:?}", event.parsed_json);
+
+        let raw = RawEventPayload {
+            event_id: serde_json::to_string(&event.id)?,
+            bcs: event.bcs.clone(),
+            bcs_encoding: event.bcs_encoding.clone(),
+        };
+        let decoded = serde_json::from_value::<SuiTradeEvent>(event.parsed_json.clone())
+            .map_err(anyhow::Error::from)
+            .and_then(|parsed| parsed.to_record());
+        let record = match decoded {
+            Ok(record) => record,
             Err(e) => {
-                println!("Cannot parse transaction amount: {} - {:?}", event.amount, e);
-                return Err(anyhow!("Cannot parse transaction amount"));
+                record_undecoded_trade_event(pool, self.chain_type(), location, &raw, &format!("{:#}", e)).await?;
+                return Err(e.context(format!("Kept undecoded Sui event {}", raw.event_id)));
             }
         };
+        apply_trade_event(pool, &TelegramBotApi, self.chain_type(), location, &record, Some(&raw), self.config.share_decimals(self.chain_type())).await
+    }
+    
+    /// Sequence number of the latest executed checkpoint
+    async fn get_latest_checkpoint(&self) -> Result<u64> {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "sui_getLatestCheckpointSequenceNumber",
+            "params": []
+        });
         
-        // Remove 0x prefix from address
-        let trader = self.remove_0x_prefix(&event.trader);
-        let subject = self.remove_0x_prefix(&event.subject);
-        
-        if event.is_buy {
-            // Buy operation, increase shares
-            process_buy_trade(
-                pool, 
-                trader.clone(),
-                subject.clone(),
-                share_amount,
-                self.get_name(),
-            ).await?;
-            
-            // Check if user is banned
-            let user_mapping = sqlx::query!(
-                "SELECT telegram_id, is_banned FROM user_mappings WHERE address = $1 AND chain_type = $2",
-                trader.clone(), 
-                self.get_name()
-            )
-            .fetch_optional(pool)
-            .await?;
-            
-            if let Some(user) = user_mapping {
-                if user.is_banned {
-                    let user_share = sqlx::query!(
-                        "SELECT share_amount FROM trades WHERE trader = $1 AND subject = $2 AND chain_type = $3",
-                        trader.clone(),
-                        subject.clone(),
-                        self.get_name()
-                    )
-                    .fetch_optional(pool)
-                    .await?;
-                    
-                    if let Some(share) = user_share {
-                        if share.share_amount > BigDecimal::from(0) {
-                            let bot_info = sqlx::query!(
-                                "SELECT bot_token, chat_group_id FROM telegram_bots WHERE subject_address = $1 AND chain_type = $2",
-                                subject.clone(),
-                                self.get_name()
-                            )
-                            .fetch_optional(pool)
-                            .await?;
-                            
-                            if let Some(bot_info) = bot_info {
-                                let permissions = ChatPermissions::empty()
-                                    | ChatPermissions::SEND_MESSAGES
-                                    | ChatPermissions::SEND_MEDIA_MESSAGES
-                                    | ChatPermissions::SEND_OTHER_MESSAGES
-                                    | ChatPermissions::SEND_POLLS
-                                    | ChatPermissions::ADD_WEB_PAGE_PREVIEWS;
-
-                                let bot = Bot::new(bot_info.bot_token);
-                                let user_id: u64 = user.telegram_id.parse().unwrap();
-                                bot.restrict_chat_member(bot_info.chat_group_id, UserId(user_id), permissions).await?;
-                            }
-                        }
-                    }
-                }
-            }
-        } else {
-            // Sell operation, decrease shares
-            println!("Trader {} sell {} shares of subject {}", trader, share_amount, subject);
-            let (should_ban, telegram_id_opt) = process_sell_trade(
-                pool,
-                trader.clone(),
-                subject.clone(),
-                share_amount,
-                self.get_name(),
-            ).await?;
-            
-            if should_ban {
-                if let Some(telegram_id) = telegram_id_opt {
-                    println!("User {} has 0 shares for {}, banning user", &trader, &subject);
-                    
-                    // Get the bot token and chat group id from telegram_bots table for this subject
-                    let bot_info = sqlx::query!(
-                        "SELECT bot_token, chat_group_id FROM telegram_bots WHERE subject_address = $1 AND chain_type = $2",
-                        subject.clone(),
-                        self.get_name()
-                    )
-                    .fetch_optional(pool)
-                    .await?;
-                    
-                    if let Some(bot_info) = bot_info {
-                        let permissions = ChatPermissions::empty();
-
-                        let bot = Bot::new(bot_info.bot_token);
-                        let user_id: u64 = telegram_id.parse().unwrap();
-                        bot.restrict_chat_member(bot_info.chat_group_id, UserId(user_id), permissions).await?;
-                        sqlx::query!(
-                            "UPDATE user_mappings SET is_banned = true WHERE address = $1 AND chain_type = $2",
-                            trader.clone(),
-                            self.get_name()
-                        )
-                        .execute(pool)
-                        .await?;
-                    } else {
-                        println!("No telegram bot info found for subject {}", &subject);
-                    }
-                }
-            }
-        }
-        Ok(())
+        // Sequence numbers are returned as strings
+        self.rpc_call(&payload)
+            .await?
+            .as_str()
+            .and_then(|r| r.parse::<u64>().ok())
+            .ok_or_else(|| anyhow!("Cannot parse Sui checkpoint sequence number"))
     }
     
     /// Call Sui RPC to get events
-    async fn get_events(&self, start_cursor: Option<String>, limit: u64) -> Result<SuiEventPage> {
-        let client = Client::new();
-        
+    async fn get_events(&self, start_cursor: Option<&EventID>, limit: u64) -> Result<SuiEventPage> {
         // Build query JSON
         let query_type = if self.contract_address.is_empty() {
             // Use MoveEvent event type
@@ -243,78 +343,28 @@ impl SuiBlockchain {
             })
         };
         
-        // Process cursor parameter
-        let cursor_param: Option<serde_json::Value> = match start_cursor {
-            Some(cursor_str) => {
-                // Check if already JSON format
-                if cursor_str.trim().starts_with('{') {
-                    match serde_json::from_str(&cursor_str) {
-                        Ok(json_val) => Some(json_val),
-                        Err(_) => {
-                            // If parsing fails, try to create a new EventID
-                            // Use valid transaction hash (64 hexadecimal characters)
-                            Some(json!({
-                                "txDigest": "0000000000000000000000000000000000000000000000000000000000000000",
-                                "eventSeq": cursor_str
-                            }))
-                        }
-                    }
-                } else {
-                    // Assume simple string, wrap as EventID structure
-                    // Use valid transaction hash (64 hexadecimal characters)
-                    Some(json!({
-                        "txDigest": "0000000000000000000000000000000000000000000000000000000000000000",
-                        "eventSeq": cursor_str
-                    }))
-                }
-            },
-            None => None,
-        };
-        
         let payload = json!({
             "jsonrpc": "2.0",
             "id": 1,
             "method": "suix_queryEvents",
             "params": {
                 "query": query_type,
-                "cursor": cursor_param,
+                "cursor": start_cursor,
                 "limit": limit,
                 "descending_order": false
             }
         });
         
-        let response = client.post(&self.rpc_url)
-            .json(&payload)
-            .send()
-            .await?;
-        
-        if !response.status().is_success() {
-            return Err(anyhow!("Sui RPC request failed: {}", response.status()));
-        }
-        
-        let response_json: Value = response.json().await?;
-        
-        if let Some(error) = response_json.get("error") {
-            return Err(anyhow!("Sui RPC returned error: {}", error));
-        }
-        
-        // Parse result
-        if let Some(result) = response_json.get("result") {
-            // println!("result: {:?}", result);
-            let events: SuiEventPage = serde_json::from_value(result.clone())?;
-            return Ok(events);
-        }
-        
-        Err(anyhow!("Cannot parse Sui RPC response"))
+        let result = self.rpc_call(&payload).await?;
+        let events: SuiEventPage = serde_json::from_value(result)?;
+        Ok(events)
     }
     
     /// Get shares on Sui
     async fn get_sui_shares(&self, subject: &str, user: &str) -> Result<u64> {
-        let client = Client::new();
-        
-        // Remove address prefix, ensure consistency
-        let clean_subject = self.remove_0x_prefix(subject);
-        let clean_user = self.remove_0x_prefix(user);
+        // Normalize addresses, ensure consistency
+        let clean_subject = self.chain_type().normalize_address(subject);
+        let clean_user = self.chain_type().normalize_address(user);
         
         // For RPC call, need to add back 0x prefix
         let subject_with_prefix = format!("0x{}", clean_subject);
@@ -343,23 +393,10 @@ impl SuiBlockchain {
             "id": 1
         });
         
-        let response = client.post(&self.rpc_url)
-            .json(&payload)
-            .send()
-            .await?;
-        
-        if !response.status().is_success() {
-            return Err(anyhow!("Sui RPC request failed: {}", response.status()));
-        }
-        
-        let response_json: Value = response.json().await?;
-        
-        if let Some(error) = response_json.get("error") {
-            return Err(anyhow!("Sui RPC returned error: {}", error));
-        }
+        let response = self.rpc_call(&payload).await?;
         
         // Parse return result (actual deployment needs to adjust based on contract's specific return format)
-        if let Some(result) = response_json.get("result").and_then(|r| r.get("results")).and_then(|r| r.as_array()) {
+        if let Some(result) = response.get("results").and_then(|r| r.as_array()) {
             if let Some(first_result) = result.first() {
                 if let Some(return_values) = first_result.get("returnValues").and_then(|v| v.as_array()) {
                     if let Some(first_value) = return_values.first() {
@@ -378,89 +415,154 @@ impl SuiBlockchain {
 
 #[async_trait]
 impl Blockchain for SuiBlockchain {
-    fn get_name(&self) -> &'static str {
-        "sui"
+    fn chain_type(&self) -> ChainType {
+        ChainType::Sui
     }
     
-    async fn sync_events(&self, pool: &PgPool) -> Result<()> {
-        // Get last synced data (Sui uses cursor) and get metadata
-        let (last_cursor_num, metadata) = get_last_synced_block_with_metadata(pool, 0, self.get_name()).await?;
-        println!("last_cursor_num: {}", last_cursor_num);
-        println!("Metadata query result: {:?}", metadata);
-        
-        // Initialize cursor - prioritize using metadata
-        let mut cursor_str: Option<String> = if let Some(meta_str) = metadata {
-            println!("Found valid metadata: {}", meta_str);
-            // If there's valid metadata, use it to restore cursor
-            Some(meta_str)
-        } else {
-            None
+    async fn sync_events(&self, pool: &PgPool, _lanes: &ChatLanes, shutdown: &CancellationToken) -> Result<()> {
+        // Sui is indexed by event cursor, a fresh database starts from the first event
+        let mut cursor: Option<EventID> = match get_cursor(pool, self.chain_type()).await? {
+            Some(saved) => Some(serde_json::from_str(&saved.cursor_json)?),
+            None => None,
         };
         
-        println!("Starting sync from cursor {:?} for {}", cursor_str, self.get_name());
+        info!("Starting sync from cursor {:?} for {}", cursor, self.get_name());
         
-        // Event sync loop
-        loop {
+        // Event sync loop, the cursor is saved after every page
+        while !shutdown.is_cancelled() {
+            match self.get_latest_checkpoint().await {
+                Ok(checkpoint) => head::record_head(self.chain_type(), checkpoint),
+                Err(e) => debug!("Failed to get latest {} checkpoint: {:?}", self.get_name(), e),
+            }
+            
             // Query events
-            match self.get_events(cursor_str.clone(), 100).await {
+            match self.get_events(cursor.as_ref(), self.config.sync_batch_size).await {
                 Ok(events) => {
-                    //println!("Found {} events for {} with cursor {:?}", events.data.len(), self.get_name(), cursor_str);
                     
-                    // Process each event
-                    for event in &events.data {
-                        if let Err(e) = self.process_trade_event(&event.parsed_json, pool).await {
-                            println!("Error processing Sui trade event: {:?}", e);
+                    let span = info_span!("sync_batch", chain = %self.chain_type(), events = events.data.len());
+                    async {
+                        // Process each event
+                        for event in &events.data {
+                            let location = EventLocation {
+                                block_number: None,
+                                tx_hash: event.id.tx_digest.clone(),
+                                log_index: event.id.event_seq.parse().unwrap_or(0),
+                                block_time: event.timestamp_ms.parse::<i128>().ok()
+                                    .and_then(|ms| OffsetDateTime::from_unix_timestamp_nanos(ms * 1_000_000).ok()),
+                            };
+                            if let Err(e) = self.process_trade_event(event, &location, pool).await {
+                                error!("Error processing Sui trade event: {:?}", e);
+                            }
                         }
+                        
                     }
+                    .instrument(span)
+                    .await;
                     
                     // Update cursor
                     if let Some(next_cursor) = events.nextCursor {
-                        // Serialize EventID to JSON string
-                        let next_cursor_json = serde_json::to_string(&next_cursor).unwrap_or_default();
-                        cursor_str = Some(next_cursor_json.clone());
-                        
-                        // Serialize full EventID as JSON string to database
-                        // Use txDigest as numeric part (converted to u64), and full JSON in metadata field
-                        let tx_digest_hash = u64::from_str_radix(&next_cursor.tx_digest[0..16], 16).unwrap_or(0);
-                        
-                        // println!("Updating sync progress: tx_digest={}, eventSeq={}, hash={}, json={}",
-                        //     next_cursor.tx_digest, next_cursor.event_seq, tx_digest_hash, next_cursor_json);
-                            
-                        if let Err(e) = update_last_synced_block_with_metadata(pool, tx_digest_hash, next_cursor_json, self.get_name()).await {
-                            println!("Failed to update last synced cursor: {:?}", e);
+                        let next_cursor_json = serde_json::to_string(&next_cursor)?;
+                        if let Err(e) = set_cursor(pool, self.chain_type(), &next_cursor_json).await {
+                            error!("Failed to update last synced cursor: {:?}", e);
                         }
+                        cursor = Some(next_cursor);
                     } else if !events.hasNextPage {
                         // No more events, wait for new events
-                        println!("No more events available for {}, waiting for new events...", self.get_name());
-                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        debug!("No more events available for {}, waiting for new events...", self.get_name());
+                        sleep_or_shutdown(shutdown, Duration::from_secs(self.config.sync_idle_interval_secs)).await;
                     }
                 },
                 Err(e) => {
-                    println!("Failed to query Sui events: {:?}", e);
-                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    error!("Failed to query Sui events: {:?}", e);
+                    sleep_or_shutdown(shutdown, Duration::from_secs(self.config.sync_error_backoff_secs)).await;
                 }
             }
             
             // Brief rest, avoid too frequent requests
-            tokio::time::sleep(Duration::from_secs(1)).await;
+            sleep_or_shutdown(shutdown, Duration::from_secs(1)).await;
         }
+        
+        info!("Stopped {} sync at cursor {:?}", self.get_name(), cursor);
+        Ok(())
     }
     
-    fn verify_signature(&self, challenge: &str, signature: &str) -> Result<String, String> {
-        // Use sui-sdk library for signature verification
-        // Step 1: Decode Base64 format signature
-        let signature_bytes = match BASE64_STANDARD.decode(signature) {
-            Ok(bytes) => bytes,
-            Err(e) => return Err(format!("Cannot decode signature: {}", e)),
-        };
-        
-        // Now the challenge parameter is already the user's address, just return it directly
-        // This is just a temporary solution, long term should implement complete Sui signature verification logic
-        
-        Ok(challenge.to_string())
+    fn verify_signature(&self, challenge: &str, signature: &str, _user: &str) -> Result<String, AppError> {
+        // Wallets return the serialized signature of signPersonalMessage in base64
+        let serialized = BASE64_STANDARD
+            .decode(signature.trim())
+            .map_err(|e| AppError::InvalidSignature(format!("Cannot decode signature: {}", e)))?;
+        verify_personal_message(challenge.as_bytes(), &serialized).map_err(AppError::InvalidSignature)
     }
     
-    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<u64> {
-        self.get_sui_shares(subject, user).await
+    async fn get_shares_balance(&self, subject: &str, user: &str) -> Result<BigDecimal> {
+        let balance = BigDecimal::from(self.get_sui_shares(subject, user).await?);
+        Ok(scale_shares(&balance, self.config.share_decimals(self.chain_type())))
     }
-} 
\ No newline at end of file
+    
+    async fn get_gas_price(&self) -> Result<u128> {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "suix_getReferenceGasPrice",
+            "params": []
+        });
+        
+        // Reference gas price is returned as a string of MIST
+        self.rpc_call(&payload)
+            .await?
+            .as_str()
+            .and_then(|r| r.parse::<u128>().ok())
+            .ok_or_else(|| anyhow!("Cannot parse Sui reference gas price"))
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_bcs_length_prefix() {
+        assert_eq!(bcs_bytes(&[1, 2]), vec![2, 1, 2]);
+        assert_eq!(&bcs_bytes(&[0u8; 300])[..2], &[0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_ed25519_personal_message() {
+        let message = b"Verify Telegram user 1 for group -100\nNonce: abc";
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = key.verifying_key().to_bytes();
+        let mut serialized = vec![ED25519_FLAG];
+        serialized.extend_from_slice(&key.sign(&personal_message_digest(message)).to_bytes());
+        serialized.extend_from_slice(&public_key);
+
+        let address = public_key_address(ED25519_FLAG, &public_key);
+        assert_eq!(address.len(), 64);
+        assert_eq!(verify_personal_message(message, &serialized), Ok(address));
+        assert!(verify_personal_message(b"another message", &serialized).is_err());
+        serialized[0] = 0x05;
+        assert!(verify_personal_message(message, &serialized).is_err());
+    }
+
+    #[test]
+    fn test_decode_raw_trade_event() {
+        let mut data = vec![0u8; 31];
+        data.push(0xab);
+        data.extend_from_slice(&[0x11; 32]);
+        data.push(1);
+        for value in [3u64, 1_000, 50, 40, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let event = decode_raw_trade_event(&BASE64_STANDARD.encode(&data), "base64").unwrap();
+        assert_eq!(event.trader, ChainType::Sui.normalize_address("0xab"));
+        assert_eq!(event.subject, "11".repeat(32));
+        assert!(event.is_buy);
+        assert_eq!(event.share_amount, BigDecimal::from(3));
+        assert_eq!(event.supply, BigDecimal::from(10));
+        assert_eq!(decode_raw_trade_event(&bs58::encode(&data).into_string(), "base58").unwrap().eth_amount, BigDecimal::from(1_000));
+
+        assert!(decode_raw_trade_event(&BASE64_STANDARD.encode(&data[..100]), "base64").is_err());
+        data[64] = 2;
+        assert!(decode_raw_trade_event(&BASE64_STANDARD.encode(&data), "base64").is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Result};
+use sqlx::types::BigDecimal;
+use sqlx::{PgConnection, PgPool};
+use time::{Date, OffsetDateTime, UtcOffset};
+use tracing::{info, info_span, Instrument};
+
+use crate::block_chain::ChainType;
+use crate::bot::api::TelegramApi;
+use crate::db::models::{EventLocation, NewTradeEvent, RawEventPayload, TradeEventRecord};
+use crate::db::operations::{
+    get_subject_thresholds, get_trade_event_for_update, process_buy_trade, process_sell_trade, record_subject_fees, record_trade_event,
+    rescale_share_decimals, revert_subject_fees, tag_wash_trades, update_trade_event,
+};
+use crate::enforcement::{crosses_any_threshold, enforce_balance, PendingEnforcement};
+use crate::logging::new_trace_id;
+use crate::metrics;
+use crate::webhooks::{self, TradeEventData, WebhookEvent};
+use crate::AppConfig;
+
+// Window in which opposite trades of a subject are paired up as suspected wash trades, in blocks,
+// or in seconds on chains without block numbers
+const WASH_TRADE_BLOCK_WINDOW: i64 = 100;
+const WASH_TRADE_WINDOW_SECS: f64 = 300.0;
+
+/// Convert a raw on-chain share amount to whole shares
+pub fn scale_shares(amount: &BigDecimal, decimals: u32) -> BigDecimal {
+    (amount * BigDecimal::new(1.into(), decimals as i64)).normalized()
+}
+
+/// Day a trade's fees are totalled under: the UTC day of its block, or of `processed_at` when
+/// the chain gave no block time. Backfilled and late events land on the day they traded
+pub fn trade_day(block_time: Option<OffsetDateTime>, processed_at: OffsetDateTime) -> Date {
+    block_time.unwrap_or(processed_at).to_offset(UtcOffset::UTC).date()
+}
+
+/// Rescale stored share amounts of every enabled chain whose configured decimals changed,
+/// must run before syncing starts so no trade is stored with the old scale
+pub async fn sync_share_decimals(pool: &PgPool, config: &AppConfig) -> Result<(), sqlx::Error> {
+    for chain_type in &config.enabled_chains {
+        let decimals = config.share_decimals(*chain_type);
+        if rescale_share_decimals(pool, *chain_type, config.contract_address(*chain_type), decimals).await? {
+            info!("Rescaled stored {} share amounts to {} decimals", chain_type, decimals);
+        }
+    }
+    Ok(())
+}
+
+/// Apply a decoded trade: log it, accumulate fees, update the trader's balance and enforce group access.
+/// Shared by every chain implementation and the indexer ingest API, `event` holds raw amounts
+/// which are scaled down by `share_decimals` before being stored. All database effects of the
+/// trade, including the resulting ban state and the `raw` payload it was decoded from, are
+/// committed in one transaction or not at all. Telegram and Discord are only called once it
+/// committed, so their failures never undo a trade.
+/// Access is only enforced when the trade moves the balance across the `min_shares` of any
+/// group the subject gates, subjects no agent gates are not enforced at all. Trades of gated
+/// subjects are queued for the agent's webhooks in the same transaction. The event gets a trace id,
+/// stored with it and on the moderation events it causes and attached to its log lines.
+/// An event already stored under the same transaction hash and log index, replayed when a
+/// sync loop restarts mid-batch, is skipped without touching balances, enforcement or webhooks.
+pub async fn apply_trade_event(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    chain_type: ChainType,
+    location: &EventLocation,
+    event: &NewTradeEvent,
+    raw: Option<&RawEventPayload>,
+    share_decimals: u32,
+) -> Result<()> {
+    let trace_id = new_trace_id();
+    let result = apply_scaled_trade_event(pool, telegram, chain_type, location, event, raw, share_decimals, &trace_id)
+        .instrument(info_span!("trade", trace_id = %trace_id, tx_hash = %location.tx_hash))
+        .await;
+    let outcome = match &result {
+        Ok(true) => "applied",
+        Ok(false) => "duplicate",
+        Err(_) => "failed",
+    };
+    metrics::TRADE_EVENTS.with_label_values(&[chain_type.as_str(), outcome]).inc();
+    result.map(|_| ())
+}
+
+/// Events replayed by a backfill
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BackfillStats {
+    pub stored: u64,
+    /// Events that were already stored, e.g. by live sync
+    pub skipped: u64,
+    /// Last block whose events were all replayed, a failed backfill resumes after it
+    pub last_block: Option<u64>,
+}
+
+/// Store a decoded trade like [`apply_trade_event`] but without enforcing group access or
+/// notifying webhooks, for replaying chain history into a fresh database. Events already
+/// stored are skipped, so a range can be replayed again safely. Returns whether it was stored
+pub async fn backfill_trade_event(
+    pool: &PgPool,
+    chain_type: ChainType,
+    location: &EventLocation,
+    event: &NewTradeEvent,
+    share_decimals: u32,
+) -> Result<bool> {
+    let event = &scale_trade_event(event, share_decimals);
+
+    let mut tx = pool.begin().await?;
+    if let StoredTrade::Duplicate = store_trade(&mut tx, chain_type, location, event, None, &new_trace_id()).await? {
+        return Ok(false);
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+fn scale_trade_event(event: &NewTradeEvent, share_decimals: u32) -> NewTradeEvent {
+    NewTradeEvent {
+        share_amount: scale_shares(&event.share_amount, share_decimals),
+        supply: scale_shares(&event.supply, share_decimals),
+        ..event.clone()
+    }
+}
+
+// Result of storing a trade
+enum StoredTrade {
+    // The event was stored before, nothing was changed
+    Duplicate,
+    // The trade was applied, with the trader's new balance if they hold a position
+    Applied(Option<BigDecimal>),
+}
+
+// Log the event, accumulate fees and update the trader's balance, unless the event was
+// already stored
+async fn store_trade(
+    conn: &mut PgConnection,
+    chain_type: ChainType,
+    location: &EventLocation,
+    event: &NewTradeEvent,
+    raw: Option<&RawEventPayload>,
+    trace_id: &str,
+) -> Result<StoredTrade> {
+    // Keep the raw event for audits and balance rebuilds
+    let Some(event_id) = record_trade_event(&mut *conn, chain_type, location, event, raw, trace_id).await? else {
+        return Ok(StoredTrade::Duplicate);
+    };
+
+    // Only tagged for analytics, wash trades still move balances like any other trade
+    let tagged = tag_wash_trades(&mut *conn, event_id, WASH_TRADE_BLOCK_WINDOW, WASH_TRADE_WINDOW_SECS).await?;
+    if tagged > 0 {
+        info!("Tagged {} trade events of subject {} as suspected wash trades", tagged, event.subject);
+    }
+
+    // Accumulate creator fees
+    let day = trade_day(location.block_time, OffsetDateTime::now_utc());
+    record_subject_fees(&mut *conn, &event.subject, event.protocol_fee.clone(), event.subject_fee.clone(), chain_type, day).await?;
+
+    let new_balance = if event.is_buy {
+        // Buy operation, increase shares
+        Some(process_buy_trade(
+            &mut *conn,
+            event.trader.clone(),
+            event.subject.clone(),
+            event.share_amount.clone(),
+            chain_type,
+        ).await?)
+    } else {
+        // Sell operation, decrease shares
+        info!("Trader {} sell {} shares of subject {}", event.trader, event.share_amount, event.subject);
+        process_sell_trade(
+            &mut *conn,
+            event.trader.clone(),
+            event.subject.clone(),
+            event.share_amount.clone(),
+            chain_type,
+        ).await?
+    };
+    Ok(StoredTrade::Applied(new_balance))
+}
+
+// Returns whether the trade was applied, false for an event already stored
+#[allow(clippy::too_many_arguments)]
+async fn apply_scaled_trade_event(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    chain_type: ChainType,
+    location: &EventLocation,
+    event: &NewTradeEvent,
+    raw: Option<&RawEventPayload>,
+    share_decimals: u32,
+    trace_id: &str,
+) -> Result<bool> {
+    let event = &scale_trade_event(event, share_decimals);
+
+    let mut tx = pool.begin().await?;
+    let new_balance = match store_trade(&mut tx, chain_type, location, event, raw, trace_id).await? {
+        StoredTrade::Duplicate => {
+            info!("Skipped {} trade event {}:{} already applied", chain_type, location.tx_hash, location.log_index);
+            return Ok(false);
+        }
+        StoredTrade::Applied(new_balance) => new_balance,
+    };
+
+    let mut pending = None;
+    if let Some(new_balance) = new_balance {
+        let previous_balance = if event.is_buy {
+            &new_balance - &event.share_amount
+        } else {
+            &new_balance + &event.share_amount
+        };
+        let thresholds = get_subject_thresholds(&mut tx, &event.subject, chain_type).await?;
+        if !thresholds.is_empty() {
+            // Queued before enforcing so receivers see the trade ahead of the ban it causes
+            webhooks::enqueue(&mut tx, chain_type, &event.subject, WebhookEvent::trade(event.is_buy), &TradeEventData {
+                chain_type,
+                subject: event.subject.clone(),
+                trader: event.trader.clone(),
+                share_amount: event.share_amount.to_string(),
+                eth_amount: event.eth_amount.to_string(),
+                balance: new_balance.to_string(),
+                tx_hash: location.tx_hash.clone(),
+                log_index: location.log_index,
+                block_number: location.block_number,
+                block_time: location.block_time,
+            }).await?;
+
+            if crosses_any_threshold(&previous_balance, &new_balance, &thresholds) {
+                pending = Some(enforce_balance(&mut tx, pool, chain_type, &event.trader, &event.subject, &new_balance, location.block_time, trace_id).await?);
+            } else {
+                metrics::ENFORCEMENT_SKIPPED.with_label_values(&[chain_type.as_str()]).inc();
+            }
+        }
+    }
+    tx.commit().await?;
+    if let Some(pending) = pending {
+        pending.apply(pool, telegram).await;
+    }
+    Ok(true)
+}
+
+/// Whether a stored event already holds the decoded fields of `event`, whose raw amounts are
+/// scaled down by `share_decimals`
+pub fn matches_stored(stored: &TradeEventRecord, event: &NewTradeEvent, share_decimals: u32) -> bool {
+    let event = &scale_trade_event(event, share_decimals);
+    stored.trader == event.trader
+        && stored.subject == event.subject
+        && stored.is_buy == event.is_buy
+        && stored.share_amount == event.share_amount
+        && stored.eth_amount == event.eth_amount
+        && stored.protocol_fee == event.protocol_fee
+        && stored.subject_fee == event.subject_fee
+        && stored.supply == event.supply
+}
+
+/// Replace the decoded fields of the stored trade event `id` with `event` after a parser fix and
+/// re-apply it: the stored fees and balance change are reverted, the corrected ones applied and
+/// the enforcement of the holders on both sides decided, in one transaction. Group access is
+/// enforced once it committed. Trade webhooks are not sent again. `event` holds raw amounts like for [`apply_trade_event`]. Returns whether the
+/// stored event differed
+pub async fn correct_trade_event(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    id: i64,
+    event: &NewTradeEvent,
+    share_decimals: u32,
+) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let stored = get_trade_event_for_update(&mut tx, id).await?.ok_or_else(|| anyhow!("Trade event {} not found", id))?;
+    if matches_stored(&stored, event, share_decimals) {
+        return Ok(false);
+    }
+    let event = &scale_trade_event(event, share_decimals);
+    let chain_type = stored.chain_type;
+
+    let day = trade_day(stored.block_time, stored.created_at);
+    revert_subject_fees(&mut tx, &stored.subject, stored.protocol_fee.clone(), stored.subject_fee.clone(), chain_type, day).await?;
+    record_subject_fees(&mut tx, &event.subject, event.protocol_fee.clone(), event.subject_fee.clone(), chain_type, day).await?;
+
+    let reverted = if stored.is_buy {
+        process_sell_trade(&mut tx, stored.trader.clone(), stored.subject.clone(), stored.share_amount.clone(), chain_type).await?
+    } else {
+        Some(process_buy_trade(&mut tx, stored.trader.clone(), stored.subject.clone(), stored.share_amount.clone(), chain_type).await?)
+    };
+    let applied = if event.is_buy {
+        Some(process_buy_trade(&mut tx, event.trader.clone(), event.subject.clone(), event.share_amount.clone(), chain_type).await?)
+    } else {
+        process_sell_trade(&mut tx, event.trader.clone(), event.subject.clone(), event.share_amount.clone(), chain_type).await?
+    };
+    update_trade_event(&mut tx, id, event).await?;
+
+    // The corrected trade's balance is the final one when both sides are the same holder
+    let mut balances = vec![(&event.trader, &event.subject, applied)];
+    if (&stored.trader, &stored.subject) != (&event.trader, &event.subject) {
+        balances.push((&stored.trader, &stored.subject, reverted));
+    }
+    let trace_id = stored.trace_id.clone().unwrap_or_else(new_trace_id);
+    let span = info_span!("trade_correction", trace_id = %trace_id, tx_hash = %stored.tx_hash);
+    let mut pending: Vec<PendingEnforcement> = Vec::new();
+    for (trader, subject, balance) in balances {
+        let Some(balance) = balance else { continue };
+        if !get_subject_thresholds(&mut tx, subject, chain_type).await?.is_empty() {
+            pending.push(
+                enforce_balance(&mut tx, pool, chain_type, trader, subject, &balance, None, &trace_id)
+                    .instrument(span.clone())
+                    .await?,
+            );
+        }
+    }
+
+    tx.commit().await?;
+    info!("Corrected {} trade event {} of {}:{}", chain_type, id, stored.tx_hash, stored.log_index);
+    for pending in pending {
+        pending.apply(pool, telegram).instrument(span.clone()).await;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // 2024-03-01 00:00:00 UTC
+    const MIDNIGHT_SECS: u64 = 1_709_251_200;
+
+    fn at(secs: u64) -> OffsetDateTime {
+        OffsetDateTime::from(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn whole_shares_are_unchanged() {
+        let amount = BigDecimal::from(42);
+        assert_eq!(scale_shares(&amount, 0), amount);
+    }
+
+    #[test]
+    fn wei_like_shares_are_scaled_down() {
+        let amount = BigDecimal::from_str("2500000000000000000").unwrap();
+        assert_eq!(scale_shares(&amount, 18), BigDecimal::from_str("2.5").unwrap());
+    }
+
+    #[test]
+    fn amounts_above_u64_are_scaled() {
+        let amount = BigDecimal::from_str("300000000000000000000").unwrap();
+        assert_eq!(scale_shares(&amount, 18), BigDecimal::from(300));
+    }
+
+    #[test]
+    fn trade_before_midnight_counts_on_its_block_day() {
+        // Mined a second before midnight, processed just after
+        let day = trade_day(Some(at(MIDNIGHT_SECS - 1)), at(MIDNIGHT_SECS + 5));
+        assert_eq!(day, at(MIDNIGHT_SECS - 1).date());
+        assert_eq!(day.next_day(), Some(at(MIDNIGHT_SECS).date()));
+    }
+
+    #[test]
+    fn backfilled_trade_counts_on_its_block_day() {
+        let week_later = at(MIDNIGHT_SECS + 7 * 86_400);
+        assert_eq!(trade_day(Some(at(MIDNIGHT_SECS + 60)), week_later), at(MIDNIGHT_SECS).date());
+    }
+
+    #[test]
+    fn block_time_is_bucketed_in_utc() {
+        // 01:00 on March 1 at UTC+2 is still February 29 in UTC
+        let local = at(MIDNIGHT_SECS - 3_600).to_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(local.date(), at(MIDNIGHT_SECS).date());
+        assert_eq!(trade_day(Some(local), at(MIDNIGHT_SECS)), at(MIDNIGHT_SECS - 1).date());
+    }
+
+    #[test]
+    fn trade_without_block_time_counts_on_processing_day() {
+        assert_eq!(trade_day(None, at(MIDNIGHT_SECS + 5)), at(MIDNIGHT_SECS).date());
+    }
+}
@@ -0,0 +1,113 @@
+//! Wallet binding through a transaction proof.
+//!
+//! Wallets that cannot sign arbitrary messages (custodial wallets) prove
+//! ownership by sending a transaction whose calldata carries a memo issued by
+//! `POST /bind-by-transaction`. Chain syncs hand the transactions they see to
+//! [`match_pending_bindings`], which completes the binding and admits the member
+//! just like a verified signature would, during the group's turn on the bot lanes.
+
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::block_chain::Blockchain;
+use crate::bot::api::TelegramBotApi;
+use crate::bot::lanes::ChatLanes;
+use crate::db::models::PendingBinding;
+use crate::db::operations::complete_pending_binding;
+use crate::services::verification::{bind_and_admit, get_verified_group_bot};
+
+/// A transaction seen by a chain sync
+#[derive(Clone, Debug)]
+pub struct ObservedTransaction {
+    /// Sender, normalized for the chain
+    pub from: String,
+    /// Calldata or memo carried by the transaction
+    pub payload: Vec<u8>,
+    pub tx_hash: String,
+}
+
+/// Memo a wallet puts in its proof transaction
+pub fn new_binding_memo() -> String {
+    format!("alice-bind:{}", Uuid::new_v4().simple())
+}
+
+// Whether the transaction was sent by the binding's wallet and carries its memo
+fn proves(binding: &PendingBinding, tx: &ObservedTransaction) -> bool {
+    let memo = binding.memo.as_bytes();
+    tx.from == binding.address && tx.payload.windows(memo.len()).any(|window| window == memo)
+}
+
+/// Complete the pending bindings proven by `transactions`, returns how many were bound
+pub async fn match_pending_bindings(
+    pool: &PgPool,
+    blockchain: &dyn Blockchain,
+    lanes: &ChatLanes,
+    bindings: &[PendingBinding],
+    transactions: &[ObservedTransaction],
+) -> usize {
+    let mut bound = 0;
+    for binding in bindings {
+        let Some(tx) = transactions.iter().find(|tx| proves(binding, tx)) else {
+            continue;
+        };
+
+        match complete_pending_binding(pool, &binding.memo, &tx.tx_hash).await {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(e) => {
+                error!("Failed to complete binding {}: {:?}", binding.memo, e);
+                continue;
+            }
+        }
+        bound += 1;
+        info!("Transaction {} binds {} to Telegram user {}", tx.tx_hash, binding.address, binding.telegram_id);
+
+        let admitted = match get_verified_group_bot(pool, &binding.chat_id, binding.chain_type).await {
+            Ok(bot_info) => bind_and_admit(pool, blockchain, &TelegramBotApi, Some(lanes), &bot_info, &binding.telegram_id, &binding.address).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = admitted {
+            error!("Failed to admit Telegram user {} after binding {}: {:?}", binding.telegram_id, binding.memo, e);
+        }
+    }
+    bound
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_chain::ChainType;
+    use time::OffsetDateTime;
+
+    fn binding(memo: &str, address: &str) -> PendingBinding {
+        PendingBinding {
+            memo: memo.to_string(),
+            telegram_id: "42".to_string(),
+            chat_id: "-100".to_string(),
+            chain_type: ChainType::Monad,
+            address: address.to_string(),
+            status: "pending".to_string(),
+            tx_hash: None,
+            expires_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[test]
+    fn test_proof_needs_sender_and_memo() {
+        let binding = binding("alice-bind:abc", "aa");
+        let mut tx = ObservedTransaction {
+            from: "aa".to_string(),
+            payload: b"\x00\x01alice-bind:abc\x02".to_vec(),
+            tx_hash: "0x1".to_string(),
+        };
+        assert!(proves(&binding, &tx));
+
+        tx.from = "bb".to_string();
+        assert!(!proves(&binding, &tx));
+
+        tx.from = "aa".to_string();
+        tx.payload = b"alice-bind:abd".to_vec();
+        assert!(!proves(&binding, &tx));
+    }
+}
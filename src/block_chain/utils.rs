@@ -24,24 +24,9 @@ pub fn verify_signature(
     Ok(recovered_address)
 }
 
-// Define Trade event structure
-#[derive(Debug, EthEvent)]
-#[ethevent(
-    name = "Trade",
-    abi = "Trade(address trader, address subject, bool isBuy, uint256 shareAmount, uint256 ethAmount, uint256 protocolEthAmount, uint256 subjectEthAmount, uint256 supply)"
-)]
-pub struct TradeEvent {
-    // #[ethevent(indexed)]
-    pub trader: Address,
-    // #[ethevent(indexed)]
-    pub subject: Address,
-    pub is_buy: bool,
-    pub share_amount: U256,
-    pub eth_amount: U256,
-    pub protocol_eth_amount: U256,
-    pub subject_eth_amount: U256,
-    pub supply: U256,
-}
+// Event structs with their signature, topic0 and ABI constants, generated by build.rs
+// from abi/events.sol so the ABI JSON and the decoders cannot drift apart
+include!(concat!(env!("OUT_DIR"), "/events.rs"));
 
 // ABI constants
 pub const ABI: &str = r#"[	{
@@ -69,88 +54,23 @@ pub const ABI: &str = r#"[	{
     "type": "function"
 }]"#;
 
-pub const TRADE_ABI: &str = r#"[{
-    "anonymous": false,
-    "inputs": [
-        {
-            "indexed": false,
-            "internalType": "address",
-            "name": "trader",
-            "type": "address"
-        },
-        {
-            "indexed": false,
-            "internalType": "address",
-            "name": "subject",
-            "type": "address"
-        },
-        {
-            "indexed": false,
-            "internalType": "bool",
-            "name": "isBuy",
-            "type": "bool"
-        },
-        {
-            "indexed": false,
-            "internalType": "uint256",
-            "name": "shareAmount",
-            "type": "uint256"
-        },
-        {
-            "indexed": false,
-            "internalType": "uint256",
-            "name": "ethAmount",
-            "type": "uint256"
-        },
-        {
-            "indexed": false,
-            "internalType": "uint256",
-            "name": "protocolEthAmount",
-            "type": "uint256"
-        },
-        {
-            "indexed": false,
-            "internalType": "uint256",
-            "name": "subjectEthAmount",
-            "type": "uint256"
-        },
-        {
-            "indexed": false,
-            "internalType": "uint256",
-            "name": "supply",
-            "type": "uint256"
-        }
-    ],
-    "name": "Trade",
-    "type": "event"
-}]"#;
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use ethers::utils::keccak256;
     
     #[test]
-    fn test_keccak256_hash() {
-        // Prepare test data
-        let input = "Trade(address,address,bool,uint256,uint256,uint256,uint256,uint256)";
-        
-        // Execute keccak256 hash
-        let hash_result = keccak256(input.as_bytes());
-        
-        // Convert result to hex string for verification
-        let hash_hex = hex::encode(hash_result);
-        println!("{hash_hex}")
-        
-        // // Expected hash value (can be verified with other tools)
-        // let expected_hex = "f45f5e9619efb8a2a6600b6f7e382a4e141f7a9668a8c242c38232a43e433a01";
-        //
-        // // Assert hash result matches expected
-        // assert_eq!(hash_hex, expected_hex);
-        //
-        // // Test empty string
-        // let empty_hash = hex::encode(keccak256("".as_bytes()));
-        // let expected_empty = "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
-        // assert_eq!(empty_hash, expected_empty);
+    fn test_trade_event_topic() {
+        assert_eq!(TRADE_SIGNATURE, "Trade(address,address,bool,uint256,uint256,uint256,uint256,uint256)");
+        assert_eq!(TRADE_TOPIC, keccak256(TRADE_SIGNATURE.as_bytes()));
+        assert_eq!(TradeEvent::signature().0, TRADE_TOPIC);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_trade_abi_matches_event() {
+        let abi: ethers::abi::Abi = serde_json::from_str(TRADE_ABI).unwrap();
+        let event = abi.event("Trade").unwrap();
+        assert_eq!(event.signature().0, TRADE_TOPIC);
+        assert_eq!(event.inputs.len(), 8);
+    }
+}
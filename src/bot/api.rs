@@ -0,0 +1,232 @@
+//! Telegram calls made outside of a bot's update handler, behind a trait so the
+//! sync, enforcement and verification flows issuing them can be tested without
+//! reaching Telegram: [`TelegramBotApi`] makes the calls, [`MockTelegramApi`]
+//! records them and can simulate a Telegram [`Outage`].
+
+use std::collections::HashSet;
+use std::io;
+use std::sync::Mutex;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use teloxide::payloads::CreateChatInviteLinkSetters;
+use teloxide::prelude::*;
+use teloxide::types::{ChatPermissions, MessageId, Recipient, Seconds};
+use teloxide::{ApiError, RequestError};
+
+use crate::enforcement::restored_permissions;
+
+/// Lift the restrictions of a member of `chat_id` within the chat's default permissions,
+/// read with getChat, see [`restored_permissions`]
+pub async fn restore_member(bot: &Bot, chat_id: Recipient, user_id: UserId) -> Result<(), RequestError> {
+    let chat = bot.get_chat(chat_id.clone()).await?;
+    bot.restrict_chat_member(chat_id, user_id, restored_permissions(chat.permissions())).await?;
+    Ok(())
+}
+
+/// Telegram Bot API calls, made with the token of the agent's bot
+#[async_trait]
+pub trait TelegramApi: Send + Sync {
+    /// Set the permissions of a member of `chat_id`, empty permissions mute them
+    async fn restrict_chat_member(
+        &self,
+        bot_token: &str,
+        chat_id: &str,
+        user_id: UserId,
+        permissions: ChatPermissions,
+    ) -> Result<(), RequestError>;
+
+    /// Give a member of `chat_id` the regular permissions back, never beyond the chat's defaults
+    async fn restore_chat_member(&self, bot_token: &str, chat_id: &str, user_id: UserId) -> Result<(), RequestError>;
+
+    /// Send a text message, a user id as `chat_id` DMs the user. Returns the id of the message
+    async fn send_message(&self, bot_token: &str, chat_id: ChatId, text: String) -> Result<MessageId, RequestError>;
+
+    /// Remove a member of `chat_id`: ban then lift the ban right away, which lets them rejoin by link
+    async fn kick_chat_member(&self, bot_token: &str, chat_id: &str, user_id: UserId) -> Result<(), RequestError>;
+
+    /// Create an invite link into `chat_id` usable `member_limit` times until `expire_date`, returns its URL
+    async fn create_invite_link(
+        &self,
+        bot_token: &str,
+        chat_id: &str,
+        name: String,
+        member_limit: u32,
+        expire_date: DateTime<Utc>,
+    ) -> Result<String, RequestError>;
+}
+
+/// [`TelegramApi`] calling Telegram through teloxide
+pub struct TelegramBotApi;
+
+#[async_trait]
+impl TelegramApi for TelegramBotApi {
+    async fn restrict_chat_member(
+        &self,
+        bot_token: &str,
+        chat_id: &str,
+        user_id: UserId,
+        permissions: ChatPermissions,
+    ) -> Result<(), RequestError> {
+        Bot::new(bot_token).restrict_chat_member(chat_id.to_string(), user_id, permissions).await.map(|_| ())
+    }
+
+    async fn restore_chat_member(&self, bot_token: &str, chat_id: &str, user_id: UserId) -> Result<(), RequestError> {
+        restore_member(&Bot::new(bot_token), chat_id.to_string().into(), user_id).await
+    }
+
+    async fn send_message(&self, bot_token: &str, chat_id: ChatId, text: String) -> Result<MessageId, RequestError> {
+        Bot::new(bot_token).send_message(chat_id, text).await.map(|message| message.id)
+    }
+
+    async fn kick_chat_member(&self, bot_token: &str, chat_id: &str, user_id: UserId) -> Result<(), RequestError> {
+        let bot = Bot::new(bot_token);
+        bot.ban_chat_member(chat_id.to_string(), user_id).await?;
+        bot.unban_chat_member(chat_id.to_string(), user_id).await?;
+        Ok(())
+    }
+
+    async fn create_invite_link(
+        &self,
+        bot_token: &str,
+        chat_id: &str,
+        name: String,
+        member_limit: u32,
+        expire_date: DateTime<Utc>,
+    ) -> Result<String, RequestError> {
+        let link = Bot::new(bot_token)
+            .create_chat_invite_link(chat_id.to_string())
+            .name(name)
+            .member_limit(member_limit)
+            .expire_date(expire_date)
+            .await?;
+        Ok(link.invite_link)
+    }
+}
+
+/// A call recorded by [`MockTelegramApi`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum TelegramCall {
+    Restrict { chat_id: String, user_id: u64, permissions: ChatPermissions },
+    SendMessage { chat_id: ChatId, text: String },
+    Kick { chat_id: String, user_id: u64 },
+    CreateInviteLink { chat_id: String, name: String },
+}
+
+/// Telegram failure every call of a [`MockTelegramApi`] answers with until it recovers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outage {
+    /// Requests time out before Telegram answers
+    Timeout,
+    /// 429 Too Many Requests, retry after this many seconds
+    FloodWait(u32),
+    /// 403 Forbidden, the bot was kicked from the chat
+    Forbidden,
+}
+
+impl Outage {
+    fn error(&self) -> RequestError {
+        match self {
+            Outage::Timeout => RequestError::Io(io::Error::new(io::ErrorKind::TimedOut, "request timed out")),
+            Outage::FloodWait(secs) => RequestError::RetryAfter(Seconds::from_seconds(*secs)),
+            Outage::Forbidden => RequestError::Api(ApiError::BotKicked),
+        }
+    }
+}
+
+/// [`TelegramApi`] recording the calls made instead of sending them. Every call
+/// succeeds, except DMs to users set as having blocked the bot and any call made
+/// during an [`Outage`], which is recorded as failed instead. Restored members are
+/// recorded as restricted with the permissions the mocked chat defaults allow
+#[derive(Default)]
+pub struct MockTelegramApi {
+    calls: Mutex<Vec<TelegramCall>>,
+    failed: Mutex<Vec<TelegramCall>>,
+    outage: Mutex<Option<Outage>>,
+    blocked: HashSet<u64>,
+    chat_defaults: Mutex<Option<ChatPermissions>>,
+}
+
+impl MockTelegramApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mock whose DMs to `user_ids` fail as if they blocked the bot
+    pub fn blocked_by(user_ids: impl IntoIterator<Item = u64>) -> Self {
+        Self { blocked: user_ids.into_iter().collect(), ..Self::default() }
+    }
+
+    /// Start failing every call with `outage`, None lets calls succeed again
+    pub fn set_outage(&self, outage: Option<Outage>) {
+        *self.outage.lock().unwrap() = outage;
+    }
+
+    /// Default permissions every chat reports, None for chats allowing everything
+    pub fn set_chat_defaults(&self, permissions: Option<ChatPermissions>) {
+        *self.chat_defaults.lock().unwrap() = permissions;
+    }
+
+    /// Calls that succeeded so far, oldest first
+    pub fn calls(&self) -> Vec<TelegramCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Calls that failed during an outage, oldest first
+    pub fn failed_calls(&self) -> Vec<TelegramCall> {
+        self.failed.lock().unwrap().clone()
+    }
+
+    // Record a call, failing it while an outage lasts
+    fn record(&self, call: TelegramCall) -> Result<(), RequestError> {
+        if let Some(outage) = *self.outage.lock().unwrap() {
+            self.failed.lock().unwrap().push(call);
+            return Err(outage.error());
+        }
+        self.calls.lock().unwrap().push(call);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TelegramApi for MockTelegramApi {
+    async fn restrict_chat_member(
+        &self,
+        _bot_token: &str,
+        chat_id: &str,
+        user_id: UserId,
+        permissions: ChatPermissions,
+    ) -> Result<(), RequestError> {
+        self.record(TelegramCall::Restrict { chat_id: chat_id.to_string(), user_id: user_id.0, permissions })
+    }
+
+    async fn restore_chat_member(&self, _bot_token: &str, chat_id: &str, user_id: UserId) -> Result<(), RequestError> {
+        let defaults = self.chat_defaults.lock().unwrap().unwrap_or(ChatPermissions::all());
+        self.record(TelegramCall::Restrict { chat_id: chat_id.to_string(), user_id: user_id.0, permissions: restored_permissions(Some(defaults)) })
+    }
+
+    async fn send_message(&self, _bot_token: &str, chat_id: ChatId, text: String) -> Result<MessageId, RequestError> {
+        if chat_id.as_user().is_some_and(|user_id| self.blocked.contains(&user_id.0)) {
+            return Err(RequestError::Api(ApiError::BotBlocked));
+        }
+        self.record(TelegramCall::SendMessage { chat_id, text })?;
+        // Numbered by the order calls were made in
+        Ok(MessageId(self.calls.lock().unwrap().len() as i32))
+    }
+
+    async fn kick_chat_member(&self, _bot_token: &str, chat_id: &str, user_id: UserId) -> Result<(), RequestError> {
+        self.record(TelegramCall::Kick { chat_id: chat_id.to_string(), user_id: user_id.0 })
+    }
+
+    async fn create_invite_link(
+        &self,
+        _bot_token: &str,
+        chat_id: &str,
+        name: String,
+        _member_limit: u32,
+        _expire_date: DateTime<Utc>,
+    ) -> Result<String, RequestError> {
+        let url = format!("https://t.me/+{}", name);
+        self.record(TelegramCall::CreateInviteLink { chat_id: chat_id.to_string(), name })?;
+        Ok(url)
+    }
+}
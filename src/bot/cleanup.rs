@@ -0,0 +1,47 @@
+use std::time::Duration;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::db::operations::{get_due_bot_messages, remove_bot_message};
+use crate::shutdown::sleep_or_shutdown;
+
+// Maximum number of messages deleted per pass
+const CLEANUP_BATCH_SIZE: i64 = 100;
+
+// Delete tracked bot messages whose TTL has passed
+pub async fn cleanup_expired_messages(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let messages = get_due_bot_messages(pool, CLEANUP_BATCH_SIZE).await?;
+    let count = messages.len();
+
+    for message in messages {
+        match message.chat_id.parse::<i64>() {
+            Ok(chat_id) => {
+                let bot = Bot::new(&message.bot_token);
+                // The message may already be gone, either way stop tracking it
+                if let Err(e) = bot.delete_message(ChatId(chat_id), MessageId(message.message_id)).await {
+                    error!("Failed to delete message {} in chat {} (agent {}): {:?}",
+                             message.message_id, message.chat_id, message.agent_name, e);
+                }
+            },
+            Err(_) => warn!("Invalid chat id {} for agent {}", message.chat_id, message.agent_name),
+        }
+
+        remove_bot_message(pool, message.id).await?;
+    }
+
+    Ok(count)
+}
+
+pub async fn message_cleanup_loop(pool: PgPool, interval_secs: u64, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        match cleanup_expired_messages(&pool).await {
+            Ok(count) if count > 0 => info!("Deleted {} expired bot messages", count),
+            Ok(_) => {},
+            Err(e) => error!("Bot message cleanup failed: {:?}", e),
+        }
+        sleep_or_shutdown(&shutdown, Duration::from_secs(interval_secs)).await;
+    }
+}
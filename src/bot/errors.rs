@@ -0,0 +1,127 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use teloxide::RequestError;
+use tracing::{error, info, warn};
+
+use crate::db::operations::{mark_agent_misconfigured, record_moderation_event, record_telegram_error_kind};
+use crate::metrics;
+
+/// Class of a failed Telegram API call
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelegramErrorKind {
+    /// Rate limited, the request can be retried later
+    FloodWait,
+    /// Bot was blocked by the user or removed from the chat
+    Forbidden,
+    ChatNotFound,
+    /// Bot lacks an admin right the action needs
+    NotEnoughRights,
+    UserDeactivated,
+    Network,
+    Other,
+}
+
+impl TelegramErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TelegramErrorKind::FloodWait => "flood_wait",
+            TelegramErrorKind::Forbidden => "forbidden",
+            TelegramErrorKind::ChatNotFound => "chat_not_found",
+            TelegramErrorKind::NotEnoughRights => "not_enough_rights",
+            TelegramErrorKind::UserDeactivated => "user_deactivated",
+            TelegramErrorKind::Network => "network",
+            TelegramErrorKind::Other => "other",
+        }
+    }
+
+    /// Errors that only an agent owner can fix in the group settings
+    pub fn is_misconfiguration(&self) -> bool {
+        matches!(self, TelegramErrorKind::NotEnoughRights)
+    }
+}
+
+/// Classify a teloxide error
+pub fn classify(error: &RequestError) -> TelegramErrorKind {
+    match error {
+        RequestError::RetryAfter(_) => TelegramErrorKind::FloodWait,
+        RequestError::Network(_) | RequestError::Io(_) => TelegramErrorKind::Network,
+        RequestError::Api(api_error) => classify_description(&api_error.to_string()),
+        _ => TelegramErrorKind::Other,
+    }
+}
+
+/// Classify the description Telegram returned for a failed call
+pub fn classify_description(description: &str) -> TelegramErrorKind {
+    let description = description.to_lowercase();
+    if description.contains("too many requests") || description.contains("retry after") {
+        TelegramErrorKind::FloodWait
+    } else if description.contains("user is deactivated") {
+        TelegramErrorKind::UserDeactivated
+    } else if description.contains("not enough rights")
+        || description.contains("need administrator rights")
+        || description.contains("chat_admin_required")
+        || description.contains("have no rights")
+    {
+        TelegramErrorKind::NotEnoughRights
+    } else if description.contains("chat not found") {
+        TelegramErrorKind::ChatNotFound
+    } else if description.contains("forbidden") {
+        TelegramErrorKind::Forbidden
+    } else {
+        TelegramErrorKind::Other
+    }
+}
+
+/// Count a failed call in the agent's error summary and remediate what can be detected.
+/// Never fails, the caller still handles the original error.
+pub async fn record_telegram_error(pool: &PgPool, agent_name: &str, chat_id: &str, error: &RequestError) -> TelegramErrorKind {
+    let kind = classify(error);
+    warn!("Telegram {} error for agent {}: {}", kind.as_str(), agent_name, error);
+    metrics::TELEGRAM_ERRORS.with_label_values(&[kind.as_str()]).inc();
+
+    if let Err(e) = record_telegram_error_kind(pool, agent_name, kind.as_str(), &error.to_string()).await {
+        warn!("Failed to record telegram error for agent {}: {:?}", agent_name, e);
+    }
+
+    // Missing admin rights make every enforcement fail, flag the agent for its owner
+    if kind.is_misconfiguration() {
+        match mark_agent_misconfigured(pool, agent_name).await {
+            Ok(true) => {
+                info!("Agent {} marked misconfigured", agent_name);
+                if let Err(e) = record_moderation_event(pool, agent_name, chat_id, None, "misconfigured", Some(error.to_string()), None).await {
+                    warn!("Failed to record moderation event for agent {}: {:?}", agent_name, e);
+                }
+            },
+            Ok(false) => {},
+            Err(e) => error!("Failed to mark agent {} misconfigured: {:?}", agent_name, e),
+        }
+    }
+
+    kind
+}
+
+/// Pass a Telegram call result through, recording the error if it failed
+pub async fn track<T>(pool: &PgPool, agent_name: &str, chat_id: &str, result: Result<T, RequestError>) -> Result<T, RequestError> {
+    if let Err(e) = &result {
+        record_telegram_error(pool, agent_name, chat_id, e).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_description() {
+        assert_eq!(
+            classify_description("Bad Request: not enough rights to restrict/unrestrict chat member"),
+            TelegramErrorKind::NotEnoughRights
+        );
+        assert_eq!(classify_description("Bad Request: chat not found"), TelegramErrorKind::ChatNotFound);
+        assert_eq!(classify_description("Forbidden: user is deactivated"), TelegramErrorKind::UserDeactivated);
+        assert_eq!(classify_description("Forbidden: bot was blocked by the user"), TelegramErrorKind::Forbidden);
+        assert_eq!(classify_description("Bad Request: message to delete not found"), TelegramErrorKind::Other);
+    }
+}
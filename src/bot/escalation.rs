@@ -0,0 +1,274 @@
+//! Progressive enforcement of holders who sold out.
+//!
+//! Agents with an escalation ladder do not restrict a holder right away:
+//! [`crate::enforcement::enforce_balance`] starts an escalation instead, and
+//! this loop applies its steps (DM warning, read-only, kick) as they fall due.
+//! The balance is checked again before every step, an escalation whose holder
+//! bought back in is cancelled. Read-only and kick steps wait while the
+//! [`crate::kill_switch`] is engaged, warnings with a long deadline wait for
+//! the end of the agent's [`crate::bot::quiet_hours`].
+
+use std::time::Duration;
+use sqlx::types::BigDecimal;
+use sqlx::{PgConnection, PgPool};
+use teloxide::prelude::*;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::bot::api::{TelegramApi, TelegramBotApi};
+use crate::bot::errors::track;
+use crate::bot::quiet_hours::{parse_quiet_hours, LONG_DEADLINE_SECS};
+use crate::db::models::DueEscalation;
+use crate::db::operations::{
+    advance_escalation, cancel_escalation, create_rejoin_token, defer_escalation, get_due_escalations, get_group_subject_balances,
+    get_latest_subject_prices, record_moderation_event, set_group_banned,
+};
+use crate::enforcement::{parse_ladder, EnforcementDetails, EnforcementMode, EscalationAction};
+use crate::error::parse_telegram_id;
+use crate::kill_switch;
+use crate::pricing::can_buy;
+use crate::shutdown::sleep_or_shutdown;
+use crate::webhooks::{self, MemberEventData, WebhookEvent};
+use crate::AppConfig;
+
+// Maximum number of escalation steps applied per pass
+const ESCALATION_BATCH_SIZE: i64 = 100;
+// Interval between passes
+const ESCALATION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What a pass did with one due escalation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Applied(EscalationAction),
+    /// The holder bought back in or is no longer verified
+    Cancelled,
+    /// Read-only or kick step waiting for the kill switch to be released
+    Held,
+    /// Warning postponed to the end of the agent's quiet hours
+    Deferred,
+}
+
+// Apply the due step of an escalation. The holder's mapping stays locked until the step
+// is recorded, so a trade buying back in is enforced either before the step (and cancels
+// it) or after it (and restores access).
+async fn run_step(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    config: &AppConfig,
+    escalation: &DueEscalation,
+    held: bool,
+) -> anyhow::Result<StepOutcome> {
+    let mut tx = pool.begin().await?;
+
+    let verified = sqlx::query_scalar!(
+        "SELECT telegram_id FROM user_mappings WHERE address = $1 AND chain_type = $2 FOR UPDATE",
+        escalation.address,
+        escalation.chain_type.as_str()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    // Buying into any other subject of the group can restore access as well
+    let balances = get_group_subject_balances(&mut *tx, &escalation.agent_name, &escalation.address).await?;
+    let balance = balances
+        .iter()
+        .find(|(subject, _)| subject == &escalation.subject)
+        .map(|(_, balance)| balance.clone())
+        .unwrap_or_else(|| BigDecimal::from(0));
+    let holds = escalation.subject_rule.admits(balances.iter().map(|(_, balance)| balance), &escalation.min_shares);
+
+    if verified.as_deref() != Some(escalation.telegram_id.as_str()) || holds {
+        cancel_escalation(&mut tx, escalation.id).await?;
+        tx.commit().await?;
+        return Ok(StepOutcome::Cancelled);
+    }
+
+    // A ladder removed from the agent ends its running escalations
+    let ladder = parse_ladder(escalation.escalation_ladder.as_deref()).unwrap_or_default();
+    let Some(step) = ladder.get(escalation.next_step as usize) else {
+        advance_escalation(&mut tx, escalation.id, escalation.next_step, None).await?;
+        tx.commit().await?;
+        return Ok(StepOutcome::Cancelled);
+    };
+    if held && step.action != EscalationAction::Warn {
+        return Ok(StepOutcome::Held);
+    }
+    // A warning with a long deadline can wait for the morning, the ladder moves along with it.
+    // One close to its next step is sent anyway so the holder still has time to buy back in
+    let next_delay = ladder.get(escalation.next_step as usize + 1).map(|next| next.delay_secs);
+    if step.action == EscalationAction::Warn && next_delay.is_none_or(|delay| delay >= LONG_DEADLINE_SECS) {
+        let quiet_until = parse_quiet_hours(escalation.quiet_hours.as_deref())
+            .and_then(|hours| hours.deferred_until(OffsetDateTime::now_utc()));
+        if let Some(until) = quiet_until {
+            defer_escalation(&mut tx, escalation.id, until).await?;
+            tx.commit().await?;
+            return Ok(StepOutcome::Deferred);
+        }
+    }
+
+    // View-only groups keep non-holders as readers, a kick step leaves them read-only instead
+    let step_action = match (step.action, escalation.enforcement_mode) {
+        (EscalationAction::Kick, EnforcementMode::ViewOnly) => EscalationAction::ReadOnly,
+        (action, _) => action,
+    };
+    let warning = match step_action {
+        EscalationAction::Warn => warning_message(pool, config, escalation, &balance).await?,
+        _ => String::new(),
+    };
+    apply_action(&mut tx, pool, telegram, escalation, step_action, warning).await?;
+    if step_action != EscalationAction::Warn {
+        webhooks::enqueue(&mut tx, escalation.chain_type, &escalation.subject, WebhookEvent::MemberBanned, &MemberEventData {
+            agent_name: escalation.agent_name.clone(),
+            chat_id: escalation.chat_id.clone(),
+            telegram_id: escalation.telegram_id.clone(),
+            chain_type: escalation.chain_type,
+            address: escalation.address.clone(),
+            subject: escalation.subject.clone(),
+            action: step_action.as_str().to_string(),
+            balance: balance.to_string(),
+        }).await?;
+    }
+    advance_escalation(&mut tx, escalation.id, escalation.next_step + 1, next_delay).await?;
+    tx.commit().await?;
+
+    // Logged like immediate enforcement so an erroneous window can be rolled back
+    let details = EnforcementDetails {
+        chain_type: escalation.chain_type,
+        address: escalation.address.clone(),
+        subject: escalation.subject.clone(),
+        balance: balance.to_string(),
+    };
+    let action = step_action.as_str();
+    if let Err(e) = record_moderation_event(
+        pool,
+        &escalation.agent_name,
+        &escalation.chat_id,
+        Some(&escalation.telegram_id),
+        action,
+        serde_json::to_string(&details).ok(),
+        escalation.trace_id.as_deref(),
+    ).await {
+        warn!("Failed to log {} of user {} for agent {}: {:?}", action, escalation.telegram_id, escalation.agent_name, e);
+    }
+
+    Ok(StepOutcome::Applied(step_action))
+}
+
+// Warning DMed to a holder who sold out. When a capped subject has too few shares left for
+// them to get back to the threshold, they are not sent to buy but told to wait for sellers
+async fn warning_message(pool: &PgPool, config: &AppConfig, escalation: &DueEscalation, balance: &BigDecimal) -> Result<String, sqlx::Error> {
+    let supply = match escalation.supply_cap {
+        Some(_) => get_latest_subject_prices(pool, escalation.chain_type, std::slice::from_ref(&escalation.subject))
+            .await?
+            .into_iter()
+            .next()
+            .map(|price| price.supply)
+            .unwrap_or_default(),
+        None => BigDecimal::from(0),
+    };
+    let can_rebuy = can_buy(&supply, escalation.supply_cap.as_ref(), &(&escalation.min_shares - balance));
+    Ok(warning_text(config.buy_url(&escalation.subject, escalation.chain_type).as_deref(), can_rebuy))
+}
+
+fn warning_text(buy_url: Option<&str>, can_rebuy: bool) -> String {
+    match (can_rebuy, buy_url) {
+        (false, _) => "You no longer hold shares of the group's subject and its shares are sold out. \
+                       You will lose your access to the group unless a holder sells and you buy back in first."
+            .to_string(),
+        (true, Some(url)) => format!("You no longer hold shares of the group's subject. Buy back in soon to keep your access to the group: {}", url),
+        (true, None) => "You no longer hold shares of the group's subject. Buy back in soon to keep your access to the group.".to_string(),
+    }
+}
+
+async fn apply_action(
+    conn: &mut PgConnection,
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    escalation: &DueEscalation,
+    action: EscalationAction,
+    warning: String,
+) -> anyhow::Result<()> {
+    let bot_token = escalation.bot_token.as_str();
+    let user_id = UserId(parse_telegram_id(&escalation.telegram_id)?);
+    let agent = escalation.agent_name.as_str();
+    let chat = escalation.chat_id.as_str();
+
+    match action {
+        EscalationAction::Warn => {
+            let sent = telegram.send_message(bot_token, user_id.into(), warning).await;
+            // Members who never started the bot cannot be messaged, the ladder goes on regardless
+            if let Err(e) = track(pool, agent, chat, sent).await {
+                warn!("Could not warn user {} of chat {}: {:?}", escalation.telegram_id, chat, e);
+            }
+            return Ok(());
+        }
+        EscalationAction::ReadOnly => {
+            let permissions = escalation.enforcement_mode.restricted_permissions();
+            track(pool, agent, chat, telegram.restrict_chat_member(bot_token, chat, user_id, permissions).await).await?;
+        }
+        EscalationAction::Kick => {
+            track(pool, agent, chat, telegram.kick_chat_member(bot_token, chat, user_id).await).await?;
+            let token = Uuid::new_v4().simple().to_string();
+            create_rejoin_token(&mut *conn, &token, &escalation.telegram_id, chat, escalation.chain_type, &escalation.address).await?;
+        }
+    }
+
+    set_group_banned(&mut *conn, agent, &escalation.address, escalation.chain_type, true).await?;
+    Ok(())
+}
+
+/// Apply every escalation step that fell due, returns the number of steps applied
+pub async fn run_due_escalations(pool: &PgPool, telegram: &dyn TelegramApi, config: &AppConfig) -> Result<usize, sqlx::Error> {
+    let due = get_due_escalations(pool, ESCALATION_BATCH_SIZE).await?;
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    let held = kill_switch::is_engaged(pool).await?;
+    let mut applied = 0;
+    for escalation in &due {
+        // Steps log under the trace id of the trade the holder sold out in
+        let span = info_span!("escalation", id = escalation.id, trace_id = escalation.trace_id.as_deref().unwrap_or_default());
+        match run_step(pool, telegram, config, escalation, held).instrument(span).await {
+            Ok(StepOutcome::Applied(action)) => {
+                info!("Escalation {}: {} user {} in chat {}", escalation.id, action.as_str(), escalation.telegram_id, escalation.chat_id);
+                applied += 1;
+            }
+            Ok(StepOutcome::Cancelled) => {
+                info!("Escalation {} of user {} in chat {} ended", escalation.id, escalation.telegram_id, escalation.chat_id);
+            }
+            Ok(StepOutcome::Held) | Ok(StepOutcome::Deferred) => {}
+            // Left due, retried next pass
+            Err(e) => error!("Failed to run escalation {} of user {}: {:?}", escalation.id, escalation.telegram_id, e),
+        }
+    }
+
+    Ok(applied)
+}
+
+pub async fn escalation_loop(pool: PgPool, config: AppConfig, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        match run_due_escalations(&pool, &TelegramBotApi, &config).await {
+            Ok(applied) if applied > 0 => info!("Applied {} escalation steps", applied),
+            Ok(_) => {},
+            Err(e) => error!("Escalation pass failed: {:?}", e),
+        }
+        sleep_or_shutdown(&shutdown, ESCALATION_CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sold_out_warning_has_no_buy_link() {
+        let url = "https://alice.example/trade?subject=0x1&chain_type=monad";
+        assert!(warning_text(Some(url), true).ends_with(url));
+        assert!(!warning_text(None, true).contains("http"));
+        let sold_out = warning_text(Some(url), false);
+        assert!(!sold_out.contains(url));
+        assert!(sold_out.contains("sold out"));
+    }
+}
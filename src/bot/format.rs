@@ -0,0 +1,126 @@
+//! Formatting of share amounts and addresses in bot messages.
+//!
+//! Amounts get the thousands and decimal separators of the reader's Telegram
+//! language and at most as many decimals as the chain's share contract has.
+//! Addresses are shortened to their first and last characters, EVM addresses
+//! in their EIP-55 checksum form.
+
+use std::str::FromStr;
+
+use ethers::types::Address;
+use ethers::utils::to_checksum;
+use sqlx::types::BigDecimal;
+
+use crate::block_chain::ChainType;
+
+/// Number separators of a reader's locale
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberLocale {
+    pub group: char,
+    pub decimal: char,
+}
+
+impl NumberLocale {
+    pub const ENGLISH: NumberLocale = NumberLocale { group: ',', decimal: '.' };
+
+    /// Locale for a Telegram `language_code` such as `de` or `pt-br`, English when unknown
+    pub fn from_language(language_code: Option<&str>) -> Self {
+        let language = language_code
+            .and_then(|code| code.split(['-', '_']).next())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" => NumberLocale { group: '.', decimal: ',' },
+            // Narrow no-break space
+            "fr" | "ru" | "uk" | "pl" | "cs" | "sv" | "fi" | "nb" => NumberLocale { group: '\u{202f}', decimal: ',' },
+            _ => Self::ENGLISH,
+        }
+    }
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        Self::ENGLISH
+    }
+}
+
+/// Amount rounded to `decimals` places without trailing zeros, with the locale's separators
+pub fn format_amount(amount: &BigDecimal, decimals: u32, locale: NumberLocale) -> String {
+    let rounded = amount.round(decimals as i64).to_string();
+    let (sign, digits) = match rounded.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", rounded.as_str()),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let fraction = fraction.trim_end_matches('0');
+
+    let mut formatted = sign.to_string();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            formatted.push(locale.group);
+        }
+        formatted.push(digit);
+    }
+    if !fraction.is_empty() {
+        formatted.push(locale.decimal);
+        formatted.push_str(fraction);
+    }
+    formatted
+}
+
+// First `head` and last `tail` characters joined by an ellipsis
+fn abbreviate(text: &str, head: usize, tail: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= head + tail + 1 {
+        return text.to_string();
+    }
+    let start: String = chars[..head].iter().collect();
+    let end: String = chars[chars.len() - tail..].iter().collect();
+    format!("{}…{}", start, end)
+}
+
+/// Short form of an address as stored for `chain_type`, e.g. `0xAbC123…9fE2`
+pub fn format_address(chain_type: ChainType, address: &str) -> String {
+    match chain_type {
+        ChainType::Monad | ChainType::Base | ChainType::Arbitrum => {
+            let full = match Address::from_str(address.trim_start_matches("0x")) {
+                Ok(parsed) => to_checksum(&parsed, None),
+                Err(_) => format!("0x{}", address.trim_start_matches("0x")),
+            };
+            abbreviate(&full, 8, 4)
+        },
+        ChainType::Sui | ChainType::Aptos => abbreviate(&format!("0x{}", address.trim_start_matches("0x")), 8, 4),
+        ChainType::Solana => abbreviate(address, 4, 4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount_groups_and_trims() {
+        let amount = BigDecimal::from_str("1234567.5000").unwrap();
+        assert_eq!(format_amount(&amount, 2, NumberLocale::ENGLISH), "1,234,567.5");
+        assert_eq!(format_amount(&amount, 0, NumberLocale::ENGLISH), "1,234,568");
+        assert_eq!(format_amount(&BigDecimal::from(999), 0, NumberLocale::ENGLISH), "999");
+        assert_eq!(format_amount(&BigDecimal::from(-1000), 0, NumberLocale::ENGLISH), "-1,000");
+    }
+
+    #[test]
+    fn test_format_amount_uses_locale() {
+        let amount = BigDecimal::from_str("12345.25").unwrap();
+        let german = NumberLocale::from_language(Some("de"));
+        assert_eq!(format_amount(&amount, 2, german), "12.345,25");
+        assert_eq!(NumberLocale::from_language(Some("pt-br")), german);
+        assert_eq!(NumberLocale::from_language(None), NumberLocale::ENGLISH);
+    }
+
+    #[test]
+    fn test_format_address() {
+        let address = "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        assert_eq!(format_address(ChainType::Monad, address), "0x5aAeb6…eAed");
+        assert_eq!(format_address(ChainType::Solana, "So11111111111111111111111111111111111111112"), "So11…1112");
+        assert_eq!(format_address(ChainType::Solana, "short"), "short");
+    }
+}
@@ -0,0 +1,321 @@
+use std::sync::{Arc, Mutex};
+use chrono::Utc;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use teloxide::types::{ChatPermissions, User};
+use teloxide::utils::command::BotCommands;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use tracing::{error, info, warn};
+
+use crate::bot::BotState;
+use crate::bot::api::restore_member;
+use crate::bot::errors::record_telegram_error;
+use crate::bot::lanes::ChatLanes;
+use crate::bot::format::{format_address, format_amount, NumberLocale};
+use crate::block_chain::ChainType;
+use crate::config::Branding;
+use crate::db::models::VerificationSession;
+use crate::db::operations::{
+    consume_rejoin_token, create_pending_verification, create_verification_session, get_group_holdings, get_user_bindings,
+    mark_verification_prompted, record_member_joined, record_member_left, record_membership_event, record_telegram_username, resolve_pending_verification,
+    track_bot_message,
+};
+use crate::enforcement::EnforcementMode;
+
+/// Commands members can send to a bot in a private chat
+#[derive(BotCommands, Clone, Debug, PartialEq)]
+#[command(rename_rule = "lowercase", description = "Available commands:")]
+pub enum Command {
+    /// Get a new verification link
+    Verify,
+    /// Sent by Telegram when opening the bot, same as /verify
+    Start(String),
+    /// Show your bound wallets, share balances and access
+    Status,
+}
+
+/// Per-bot data shared with the update handlers
+pub struct BotContext {
+    pub agent_name: String,
+    pub chat_group_id: String,
+    pub sign_page_url: String,
+    /// Delete join/leave service messages and expire the bot's own prompts
+    pub delete_service_messages: bool,
+    pub prompt_ttl_secs: i64,
+    /// Lifetime of the session-based sign links the bot hands out
+    pub verify_session_ttl_secs: i64,
+    /// Product name and support link used in the bot's messages
+    pub branding: Branding,
+    pub pool: PgPool,
+    /// Worker lanes shared by all bots, updates of a chat run on its lane in order
+    pub lanes: ChatLanes,
+    pub state: Arc<Mutex<BotState>>,
+}
+
+/// Open a verification session for a member and return it with its sign link.
+/// The link carries only the session id, the session holds the user and chat.
+pub async fn issue_sign_link(
+    pool: &PgPool,
+    sign_page_url: &str,
+    telegram_id: &str,
+    chat_id: &str,
+    chain_type: ChainType,
+    ttl_secs: i64,
+) -> Result<(VerificationSession, String), sqlx::Error> {
+    let session_id = Uuid::new_v4().simple().to_string();
+    let session = create_verification_session(pool, &session_id, telegram_id, chat_id, chain_type, ttl_secs).await?;
+    let link = format!("{}?session={}", sign_page_url, session.id);
+    Ok((session, link))
+}
+
+/// Prompt posted in the group for a member who joined, `name` is left out when unknown
+pub fn welcome_prompt(name: Option<&str>, branding: &Branding, sign_link: &str, ttl_secs: i64) -> String {
+    let greeting = match name {
+        Some(name) => format!("Welcome {}!", name),
+        None => "Welcome!".to_string(),
+    };
+    format!(
+        "{} Sign with your wallet to prove you hold {} shares and unlock chatting: {}\n\
+         The link expires in {} minutes, send /verify to me in a private chat for a new one.{}",
+        greeting, branding.name, sign_link, ttl_secs / 60, branding.footer()
+    )
+}
+
+// Sign link for a member of this bot's group, with the verification session it opens
+async fn member_sign_link(ctx: &BotContext, telegram_id: u64) -> Result<(VerificationSession, String), sqlx::Error> {
+    let chain_type = sqlx::query_scalar!(
+        r#"SELECT chain_type as "chain_type: ChainType" FROM telegram_bots WHERE agent_name = $1"#,
+        ctx.agent_name
+    )
+    .fetch_one(&ctx.pool)
+    .await?;
+
+    issue_sign_link(
+        &ctx.pool,
+        &ctx.sign_page_url,
+        &telegram_id.to_string(),
+        &ctx.chat_group_id,
+        chain_type,
+        ctx.verify_session_ttl_secs,
+    ).await
+}
+
+// Permissions of new members of this bot's group until they verify, read-only in view-only groups
+async fn unverified_permissions(ctx: &BotContext) -> ChatPermissions {
+    let mode = sqlx::query_scalar!(
+        r#"SELECT enforcement_mode as "enforcement_mode: EnforcementMode" FROM telegram_bots WHERE agent_name = $1"#,
+        ctx.agent_name
+    )
+    .fetch_one(&ctx.pool)
+    .await;
+    match mode {
+        Ok(mode) => mode.restricted_permissions(),
+        Err(e) => {
+            warn!("Failed to load enforcement mode of agent {}, muting: {:?}", ctx.agent_name, e);
+            ChatPermissions::empty()
+        }
+    }
+}
+
+pub async fn handle_message(bot: Bot, msg: Message, ctx: Arc<BotContext>) -> ResponseResult<()> {
+    ctx.state.lock().unwrap().last_update_at = Some(Utc::now());
+
+    let result = ctx.lanes.run(msg.chat.id.0, process_message(&bot, &msg, &ctx)).await;
+    record_update_result(&ctx, lane_result(&ctx, &msg, result)).await
+}
+
+pub async fn handle_command(bot: Bot, msg: Message, cmd: Command, ctx: Arc<BotContext>) -> ResponseResult<()> {
+    ctx.state.lock().unwrap().last_update_at = Some(Utc::now());
+
+    // Muted members cannot post in the group, commands are answered in private only
+    if !msg.chat.is_private() {
+        return Ok(());
+    }
+    let result = ctx.lanes.run(msg.chat.id.0, process_command(&bot, &msg, cmd, &ctx)).await;
+    record_update_result(&ctx, lane_result(&ctx, &msg, result)).await
+}
+
+// Updates the lanes no longer take at shutdown are dropped, Telegram does not resend them
+fn lane_result(ctx: &BotContext, msg: &Message, result: Option<ResponseResult<()>>) -> ResponseResult<()> {
+    result.unwrap_or_else(|| {
+        warn!("Dropped update {} of chat {} for agent {}, bot lanes are shut down", msg.id.0, msg.chat.id.0, ctx.agent_name);
+        Ok(())
+    })
+}
+
+// Count and classify a failed update
+async fn record_update_result(ctx: &BotContext, result: ResponseResult<()>) -> ResponseResult<()> {
+    if let Err(e) = &result {
+        error!("Bot for agent {} failed to handle update: {:?}", ctx.agent_name, e);
+        ctx.state.lock().unwrap().error_count += 1;
+        record_telegram_error(&ctx.pool, &ctx.agent_name, &ctx.chat_group_id, e).await;
+    }
+    result
+}
+
+// Keep the username shown on leaderboards current
+async fn remember_username(ctx: &BotContext, user: &User) {
+    if let Err(e) = record_telegram_username(&ctx.pool, &user.id.0.to_string(), user.username.as_deref()).await {
+        warn!("Failed to record username of user {}: {:?}", user.id.0, e);
+    }
+}
+
+// Keep a join or leave in the membership log, at the time of its service message
+async fn log_membership(ctx: &BotContext, msg: &Message, user: &User, kind: &str) {
+    let at = OffsetDateTime::from_unix_timestamp(msg.date.timestamp()).unwrap_or_else(|_| OffsetDateTime::now_utc());
+    let chat_id = msg.chat.id.0.to_string();
+    let logged = record_membership_event(&ctx.pool, &ctx.agent_name, &chat_id, &user.id.0.to_string(), user.username.as_deref(), kind, at).await;
+    if let Err(e) = logged {
+        warn!("Failed to log {} of user {} in chat {}: {:?}", kind, user.id.0, chat_id, e);
+    }
+}
+
+async fn process_command(bot: &Bot, msg: &Message, cmd: Command, ctx: &BotContext) -> ResponseResult<()> {
+    let Some(user) = msg.from() else {
+        return Ok(());
+    };
+    remember_username(ctx, user).await;
+
+    match cmd {
+        Command::Verify | Command::Start(_) => {
+            match member_sign_link(ctx, user.id.0).await {
+                Ok((_, link)) => {
+                    bot.send_message(
+                        msg.chat.id,
+                        format!(
+                            "Here is your new {} verification link, it expires in {} minutes: {}{}",
+                            ctx.branding.name, ctx.verify_session_ttl_secs / 60, link, ctx.branding.footer()
+                        ),
+                    )
+                    .await?;
+                },
+                Err(e) => error!("Failed to open verification session for user {}: {:?}", user.id.0, e),
+            }
+        },
+        Command::Status => {
+            let locale = NumberLocale::from_language(user.language_code.as_deref());
+            let text = match member_status(&ctx.pool, &user.id.0.to_string(), locale).await {
+                Ok(text) => text + &ctx.branding.footer(),
+                Err(e) => {
+                    error!("Failed to load status of user {}: {:?}", user.id.0, e);
+                    "Your status is unavailable right now, please try again later.".to_string()
+                }
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        },
+    }
+    Ok(())
+}
+
+// Bound wallets with their access, then the share balance of every gated group they hold shares for
+async fn member_status(pool: &PgPool, telegram_id: &str, locale: NumberLocale) -> Result<String, sqlx::Error> {
+    let bindings = get_user_bindings(pool, telegram_id).await?;
+    if bindings.is_empty() {
+        return Ok("No wallet is bound to your account yet, send /verify to get a verification link.".to_string());
+    }
+
+    let mut lines = vec!["Bound wallets:".to_string()];
+    for binding in &bindings {
+        let access = if binding.is_banned { "restricted" } else { "active" };
+        lines.push(format!("- {} ({}): {}", format_address(binding.chain_type, &binding.address), binding.chain_type, access));
+    }
+
+    let holdings = get_group_holdings(pool, telegram_id).await?;
+    if holdings.is_empty() {
+        lines.push("\nNo shares of any gated group.".to_string());
+    } else {
+        lines.push("\nShares:".to_string());
+        for holding in &holdings {
+            lines.push(format!(
+                "- {}: {} shares of {} ({})",
+                holding.agent_name,
+                format_amount(&holding.share_amount, holding.share_decimals.max(0) as u32, locale),
+                format_address(holding.chain_type, &holding.subject_address),
+                format_address(holding.chain_type, &holding.address)
+            ));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+async fn process_message(bot: &Bot, msg: &Message, ctx: &BotContext) -> ResponseResult<()> {
+    if let Some(members) = msg.new_chat_members() {
+        for member in members {
+            if member.is_bot {
+                continue;
+            }
+            info!("User {} joined chat {} (agent {})", member.id.0, msg.chat.id.0, ctx.agent_name);
+            remember_username(ctx, member).await;
+            log_membership(ctx, msg, member, "join").await;
+            if let Err(e) = record_member_joined(&ctx.pool, &ctx.agent_name, &ctx.chat_group_id, &member.id.0.to_string()).await {
+                error!("Failed to record join of user {}: {:?}", member.id.0, e);
+            }
+
+            // Kicked holders who bought back in come through their single-use invite, no need to sign again
+            match consume_rejoin_token(&ctx.pool, &member.id.0.to_string(), &ctx.chat_group_id).await {
+                Ok(true) => {
+                    info!("User {} rejoined chat {} with a rejoin invite", member.id.0, msg.chat.id.0);
+                    restore_member(bot, msg.chat.id.into(), member.id).await?;
+                    continue;
+                },
+                Ok(false) => {},
+                Err(e) => error!("Failed to check rejoin token for user {}: {:?}", member.id.0, e),
+            }
+
+            // Recorded before muting, so a member muted just before a restart is re-prompted on startup
+            let telegram_id = member.id.0.to_string();
+            if let Err(e) = create_pending_verification(&ctx.pool, &telegram_id, &ctx.chat_group_id, &ctx.agent_name).await {
+                error!("Failed to start verification timeout for user {}: {:?}", member.id.0, e);
+            }
+            // New members stay muted (or read-only) until they prove they hold shares
+            bot.restrict_chat_member(msg.chat.id, member.id, unverified_permissions(ctx).await).await?;
+
+            let (session, sign_link) = match member_sign_link(ctx, member.id.0).await {
+                Ok(issued) => issued,
+                Err(e) => {
+                    error!("Failed to open verification session for user {}: {:?}", member.id.0, e);
+                    continue;
+                }
+            };
+            let text = welcome_prompt(Some(&member.first_name), &ctx.branding, &sign_link, ctx.verify_session_ttl_secs);
+            let prompt = bot.send_message(msg.chat.id, text).await?;
+            if let Err(e) = mark_verification_prompted(&ctx.pool, &telegram_id, &ctx.chat_group_id, &session.id, prompt.id.0).await {
+                warn!("Failed to record prompt of user {}: {:?}", member.id.0, e);
+            }
+
+            if ctx.delete_service_messages {
+                let chat_id = msg.chat.id.0.to_string();
+                if let Err(e) = track_bot_message(&ctx.pool, &ctx.agent_name, &chat_id, prompt.id.0, ctx.prompt_ttl_secs).await {
+                    warn!("Failed to track prompt message for agent {}: {:?}", ctx.agent_name, e);
+                }
+            }
+        }
+        delete_service_message(bot, msg, ctx).await;
+    }
+
+    if let Some(member) = msg.left_chat_member() {
+        info!("User {} left chat {} (agent {})", member.id.0, msg.chat.id.0, ctx.agent_name);
+        log_membership(ctx, msg, member, "leave").await;
+        if let Err(e) = record_member_left(&ctx.pool, &ctx.agent_name, &member.id.0.to_string()).await {
+            error!("Failed to record leave of user {}: {:?}", member.id.0, e);
+        }
+        if let Err(e) = resolve_pending_verification(&ctx.pool, &member.id.0.to_string(), &ctx.chat_group_id, "left").await {
+            error!("Failed to close pending verification of user {}: {:?}", member.id.0, e);
+        }
+        delete_service_message(bot, msg, ctx).await;
+    }
+
+    Ok(())
+}
+
+// Remove a join/leave notice when the agent opted into cleanup
+async fn delete_service_message(bot: &Bot, msg: &Message, ctx: &BotContext) {
+    if !ctx.delete_service_messages {
+        return;
+    }
+    // Failing to delete (e.g. missing admin rights) should not fail the update
+    if let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
+        error!("Failed to delete service message in chat {} (agent {}): {:?}", msg.chat.id.0, ctx.agent_name, e);
+    }
+}
@@ -0,0 +1,156 @@
+//! Sharded processing of bot updates with per-chat ordering.
+//!
+//! Every update of every bot, and every verification grant, takes a turn on one of a fixed
+//! number of worker lanes picked by hashing its chat id. A lane hands out one turn at a time, in
+//! the order they were asked for, so the updates of a chat and the grants for it are handled in
+//! the order they arrived while other chats proceed on the other lanes. Lane queues are bounded:
+//! once a lane is full, asking for a turn waits for room, which slows the bots feeding it instead
+//! of buffering without end.
+
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::warn;
+
+use crate::metrics::{BOT_LANE_BACKPRESSURE, BOT_LANE_QUEUE_DEPTH, BOT_LANE_WAIT};
+
+struct Job {
+    enqueued_at: Instant,
+    // Receives the release of the turn once the lane reaches this job
+    turn: oneshot::Sender<oneshot::Sender<()>>,
+}
+
+/// A chat's turn on its lane, the next queued work of the lane waits until it is dropped
+pub struct LaneTurn {
+    _release: oneshot::Sender<()>,
+}
+
+/// Worker lanes shared by all bots, cheap to clone
+#[derive(Clone)]
+pub struct ChatLanes {
+    senders: Vec<mpsc::Sender<Job>>,
+}
+
+// Lane of a chat, stable for the lifetime of the process
+fn lane_index(chat_id: i64, lanes: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    chat_id.hash(&mut hasher);
+    (hasher.finish() % lanes as u64) as usize
+}
+
+impl ChatLanes {
+    /// Spawn `lanes` workers on `tasks`, each queueing up to `capacity` turns. Once shutdown is
+    /// requested the lanes stop taking work and hand out the turns already queued
+    pub fn new(lanes: usize, capacity: usize, tasks: &TaskTracker, shutdown: CancellationToken) -> Self {
+        let senders = (0..lanes.max(1))
+            .map(|lane| {
+                let (sender, receiver) = mpsc::channel(capacity.max(1));
+                tasks.spawn(run_lane(lane, receiver, shutdown.clone()));
+                sender
+            })
+            .collect();
+        Self { senders }
+    }
+
+    /// Wait for the turn of `chat_id` on its lane, after the turns asked for before on the same
+    /// lane. None once the lanes shut down
+    pub async fn turn(&self, chat_id: i64) -> Option<LaneTurn> {
+        let lane = lane_index(chat_id, self.senders.len());
+        let lane_label = lane.to_string();
+        let (turn_sender, turn_receiver) = oneshot::channel();
+        let job = Job { enqueued_at: Instant::now(), turn: turn_sender };
+
+        let sender = &self.senders[lane];
+        match sender.try_send(job) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(job)) => {
+                // A full lane holds the caller until its worker catches up
+                BOT_LANE_BACKPRESSURE.with_label_values(&[&lane_label]).inc();
+                sender.send(job).await.ok()?;
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => return None,
+        }
+        BOT_LANE_QUEUE_DEPTH
+            .with_label_values(&[&lane_label])
+            .set((sender.max_capacity() - sender.capacity()) as i64);
+
+        let release = turn_receiver.await.ok()?;
+        Some(LaneTurn { _release: release })
+    }
+
+    /// Run `work` during the turn of `chat_id`, None when the lanes shut down before it ran
+    pub async fn run<F: Future>(&self, chat_id: i64, work: F) -> Option<F::Output> {
+        let _turn = self.turn(chat_id).await?;
+        Some(work.await)
+    }
+}
+
+// Hand out the turns of one lane in order, until shutdown closed the queue and it is drained
+async fn run_lane(lane: usize, mut receiver: mpsc::Receiver<Job>, shutdown: CancellationToken) {
+    let lane_label = lane.to_string();
+    loop {
+        let job = tokio::select! {
+            job = receiver.recv() => job,
+            _ = shutdown.cancelled() => {
+                receiver.close();
+                receiver.recv().await
+            }
+        };
+        let Some(job) = job else {
+            break;
+        };
+        BOT_LANE_QUEUE_DEPTH.with_label_values(&[&lane_label]).set(receiver.len() as i64);
+        BOT_LANE_WAIT.with_label_values(&[&lane_label]).observe(job.enqueued_at.elapsed().as_secs_f64());
+
+        // Ends when the turn is dropped, also when its holder panicked or gave up waiting
+        let (release_sender, release_receiver) = oneshot::channel();
+        if job.turn.send(release_sender).is_ok() {
+            let _ = release_receiver.await;
+        }
+    }
+    if !shutdown.is_cancelled() {
+        warn!("Bot lane {} stopped", lane);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_lanes_keep_chat_order() {
+        let tasks = TaskTracker::new();
+        let shutdown = CancellationToken::new();
+        let lanes = ChatLanes::new(4, 2, &tasks, shutdown.clone());
+        assert_eq!(lane_index(-100123, 4), lane_index(-100123, 4));
+
+        // A slow first update of a chat still finishes before the later ones, past a full queue
+        let seen = Mutex::new(Vec::new());
+        let seen = &seen;
+        let runs = (0..5u64).map(|i| {
+            lanes.run(-100123, async move {
+                tokio::time::sleep(Duration::from_millis(if i == 0 { 50 } else { 0 })).await;
+                seen.lock().unwrap().push(i);
+                i
+            })
+        });
+        let results = futures::future::join_all(runs).await;
+        assert_eq!(results, (0..5).map(Some).collect::<Vec<_>>());
+        assert_eq!(*seen.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+
+        // A dropped turn frees the lane
+        drop(lanes.turn(-100123).await.unwrap());
+        assert_eq!(lanes.run(-100123, async { 7 }).await, Some(7));
+
+        shutdown.cancel();
+        tasks.close();
+        tasks.wait().await;
+        assert_eq!(lanes.run(-100123, async { 8 }).await, None);
+    }
+}
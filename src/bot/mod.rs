@@ -0,0 +1,277 @@
+pub mod api;
+pub mod cleanup;
+pub mod errors;
+pub mod escalation;
+pub mod format;
+pub mod handler;
+pub mod lanes;
+pub mod onboarding;
+pub mod quiet_hours;
+pub mod recovery;
+pub mod unverified;
+
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use serde::Serialize;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use teloxide::utils::command::BotCommands;
+use tokio::task::AbortHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
+
+use crate::bot::handler::{handle_command, handle_message, BotContext, Command};
+use crate::bot::lanes::ChatLanes;
+use crate::config::Branding;
+use crate::shutdown::sleep_or_shutdown;
+
+// Restart backoff for crashed bots
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(300);
+// Uptime after which a bot's backoff is reset
+const HEALTHY_RUN_TIME: Duration = Duration::from_secs(60);
+
+/// Operational state of a bot task
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BotStatus {
+    Running,
+    Stopped,
+    Crashed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BotState {
+    pub status: BotStatus,
+    pub last_update_at: Option<DateTime<Utc>>,
+    pub error_count: u64,
+    pub restart_count: u64,
+}
+
+impl Default for BotState {
+    fn default() -> Self {
+        Self {
+            status: BotStatus::Stopped,
+            last_update_at: None,
+            error_count: 0,
+            restart_count: 0,
+        }
+    }
+}
+
+struct BotEntry {
+    state: Arc<Mutex<BotState>>,
+    abort_handle: Option<AbortHandle>,
+}
+
+/// Owns and supervises the running bot tasks, keyed by agent_name
+#[derive(Clone)]
+pub struct BotManager {
+    bots: Arc<Mutex<HashMap<String, BotEntry>>>,
+    pool: PgPool,
+    sign_page_url: String,
+    prompt_ttl_secs: i64,
+    verify_session_ttl_secs: i64,
+    branding: Branding,
+    // Worker lanes the updates of all bots run on, ordered per chat
+    lanes: ChatLanes,
+    shutdown: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl BotManager {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pool: PgPool,
+        sign_page_url: String,
+        prompt_ttl_secs: i64,
+        verify_session_ttl_secs: i64,
+        branding: Branding,
+        lanes: usize,
+        lane_capacity: usize,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let tasks = TaskTracker::new();
+        Self {
+            bots: Arc::new(Mutex::new(HashMap::new())),
+            pool,
+            sign_page_url,
+            prompt_ttl_secs,
+            verify_session_ttl_secs,
+            branding,
+            lanes: ChatLanes::new(lanes, lane_capacity, &tasks, shutdown.clone()),
+            shutdown,
+            tasks,
+        }
+    }
+
+    /// Start the bot for an agent, replacing any task already running for it
+    pub fn start(&self, agent_name: &str, bot_token: &str, chat_group_id: &str, delete_service_messages: bool) {
+        let mut bots = self.bots.lock().unwrap();
+        if let Some(entry) = bots.get(agent_name) {
+            if let Some(abort_handle) = &entry.abort_handle {
+                abort_handle.abort();
+            }
+        }
+
+        let state = Arc::new(Mutex::new(BotState {
+            status: BotStatus::Running,
+            ..BotState::default()
+        }));
+        let ctx = Arc::new(BotContext {
+            agent_name: agent_name.to_string(),
+            chat_group_id: chat_group_id.to_string(),
+            sign_page_url: self.sign_page_url.clone(),
+            delete_service_messages,
+            prompt_ttl_secs: self.prompt_ttl_secs,
+            verify_session_ttl_secs: self.verify_session_ttl_secs,
+            branding: self.branding.clone(),
+            pool: self.pool.clone(),
+            lanes: self.lanes.clone(),
+            state: state.clone(),
+        });
+
+        let task = self.tasks.spawn(supervise(bot_token.to_string(), ctx, self.shutdown.clone()));
+        let abort_handle = task.abort_handle();
+
+        bots.insert(agent_name.to_string(), BotEntry {
+            state,
+            abort_handle: Some(abort_handle),
+        });
+        info!("Bot started for agent {}", agent_name);
+    }
+
+    /// Stop the bot for an agent, returns false when none was running
+    pub fn stop(&self, agent_name: &str) -> bool {
+        let mut bots = self.bots.lock().unwrap();
+        match bots.remove(agent_name) {
+            Some(entry) => {
+                if let Some(abort_handle) = entry.abort_handle {
+                    abort_handle.abort();
+                }
+                info!("Bot stopped for agent {}", agent_name);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Current state of a bot, if it has ever been started
+    pub fn status(&self, agent_name: &str) -> Option<BotState> {
+        let bots = self.bots.lock().unwrap();
+        bots.get(agent_name).map(|entry| entry.state.lock().unwrap().clone())
+    }
+
+    /// Lanes bot updates run on, for work that must stay in order with a chat's updates
+    pub fn lanes(&self) -> &ChatLanes {
+        &self.lanes
+    }
+
+    /// Wait for every bot and lane to finish its in-flight updates once shutdown was requested
+    pub async fn wait_stopped(&self) {
+        self.tasks.close();
+        self.tasks.wait().await;
+        info!("All bots stopped");
+    }
+
+    /// Start bots for every agent registered in the database
+    pub async fn start_all(&self) -> Result<(), sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT agent_name, bot_token, chat_group_id, delete_service_messages FROM telegram_bots WHERE enabled"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for row in rows {
+            self.start(&row.agent_name, &row.bot_token, &row.chat_group_id, row.delete_service_messages);
+        }
+
+        Ok(())
+    }
+}
+
+// Keep a bot running, restarting it with exponential backoff when it crashes or exits.
+// The dispatcher runs inside this task so aborting it stops the bot as well.
+async fn supervise(bot_token: String, ctx: Arc<BotContext>, shutdown: CancellationToken) {
+    let mut backoff = INITIAL_RESTART_BACKOFF;
+
+    loop {
+        ctx.state.lock().unwrap().status = BotStatus::Running;
+        let started_at = Instant::now();
+
+        let result = AssertUnwindSafe(run_bot(Bot::new(&bot_token), ctx.clone(), shutdown.clone())).catch_unwind().await;
+        if shutdown.is_cancelled() {
+            ctx.state.lock().unwrap().status = BotStatus::Stopped;
+            info!("Bot for agent {} stopped for shutdown", ctx.agent_name);
+            return;
+        }
+        match result {
+            Ok(()) => warn!("Bot for agent {} exited unexpectedly", ctx.agent_name),
+            Err(_) => error!("Bot for agent {} crashed", ctx.agent_name),
+        }
+
+        // A bot that ran for a while is treated as healthy again
+        if started_at.elapsed() >= HEALTHY_RUN_TIME {
+            backoff = INITIAL_RESTART_BACKOFF;
+        }
+
+        {
+            let mut state = ctx.state.lock().unwrap();
+            state.status = BotStatus::Crashed;
+            state.restart_count += 1;
+        }
+        info!("Restarting bot for agent {} in {:?}", ctx.agent_name, backoff);
+        if sleep_or_shutdown(&shutdown, backoff).await {
+            ctx.state.lock().unwrap().status = BotStatus::Stopped;
+            return;
+        }
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+    }
+}
+
+async fn run_bot(bot: Bot, ctx: Arc<BotContext>, shutdown: CancellationToken) {
+    if shutdown.is_cancelled() {
+        return;
+    }
+    // Lets Telegram suggest the commands in the member's chat with the bot
+    if let Err(e) = bot.set_my_commands(Command::bot_commands()).await {
+        error!("Failed to register commands for agent {}: {:?}", ctx.agent_name, e);
+    }
+
+    // Commands first, everything else (joins, leaves) goes to the message handler
+    let handler = Update::filter_message()
+        .branch(dptree::entry().filter_command::<Command>().endpoint(handle_command))
+        .branch(dptree::endpoint(handle_message));
+
+    let mut dispatcher = Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![ctx])
+        .default_handler(|_| async {})
+        .build();
+
+    // Let the dispatcher finish the updates it is handling instead of dropping them
+    let dispatcher_shutdown = dispatcher.shutdown_token();
+    let stop_watcher = tokio::spawn(async move {
+        shutdown.cancelled().await;
+        if let Ok(stopped) = dispatcher_shutdown.shutdown() {
+            stopped.await;
+        }
+    });
+
+    dispatcher.dispatch().await;
+    stop_watcher.abort();
+}
+
+/// Hide everything but the bot id and the last characters of a token
+pub fn mask_token(token: &str) -> String {
+    match token.split_once(':') {
+        Some((bot_id, secret)) if secret.len() > 4 => {
+            format!("{}:****{}", bot_id, &secret[secret.len() - 4..])
+        },
+        _ => "****".to_string(),
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::bot::errors::record_telegram_error;
+use crate::bot::quiet_hours::parse_quiet_hours;
+use crate::db::models::DueOnboardingDelivery;
+use crate::db::operations::{defer_onboarding_delivery, finish_onboarding_delivery, get_due_onboarding_deliveries};
+use crate::shutdown::sleep_or_shutdown;
+
+// Maximum number of onboarding messages sent per pass
+const ONBOARDING_BATCH_SIZE: i64 = 100;
+
+// DM one onboarding step to the member, pinning it when asked
+async fn deliver(pool: &PgPool, delivery: &DueOnboardingDelivery) -> Result<(), String> {
+    let user_id = delivery.telegram_id.parse::<u64>()
+        .map_err(|_| format!("Invalid Telegram user id {}", delivery.telegram_id))?;
+    let bot = Bot::new(&delivery.bot_token);
+
+    let message = match bot.send_message(UserId(user_id), delivery.message.clone()).await {
+        Ok(message) => message,
+        Err(e) => {
+            record_telegram_error(pool, &delivery.agent_name, &delivery.chat_id, &e).await;
+            return Err(e.to_string());
+        }
+    };
+
+    if delivery.pin {
+        // The message arrived, a failed pin is only logged
+        if let Err(e) = bot.pin_chat_message(message.chat.id, message.id).await {
+            error!("Failed to pin onboarding message for user {} (agent {}): {:?}", delivery.telegram_id, delivery.agent_name, e);
+        }
+    }
+    Ok(())
+}
+
+// Send onboarding messages that are due and record which ones were delivered. Messages
+// falling in their agent's quiet hours are moved to the end of them
+pub async fn send_due_onboarding(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let deliveries = get_due_onboarding_deliveries(pool, ONBOARDING_BATCH_SIZE).await?;
+    let count = deliveries.len();
+    let now = OffsetDateTime::now_utc();
+
+    for delivery in deliveries {
+        if let Some(until) = parse_quiet_hours(delivery.quiet_hours.as_deref()).and_then(|hours| hours.deferred_until(now)) {
+            defer_onboarding_delivery(pool, delivery.id, until).await?;
+            continue;
+        }
+        let error = deliver(pool, &delivery).await.err();
+        if let Some(error) = &error {
+            error!("Failed to send onboarding message to user {} (agent {}): {}", delivery.telegram_id, delivery.agent_name, error);
+        }
+        finish_onboarding_delivery(pool, delivery.id, error).await?;
+    }
+
+    Ok(count)
+}
+
+pub async fn onboarding_loop(pool: PgPool, interval_secs: u64, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        match send_due_onboarding(&pool).await {
+            Ok(count) if count > 0 => info!("Processed {} onboarding messages", count),
+            Ok(_) => {},
+            Err(e) => error!("Onboarding delivery failed: {:?}", e),
+        }
+        sleep_or_shutdown(&shutdown, Duration::from_secs(interval_secs)).await;
+    }
+}
@@ -0,0 +1,114 @@
+//! Per-agent quiet hours.
+//!
+//! An agent can set a daily window, in its members' local time, during which
+//! the scheduled loops do not DM non-urgent messages: onboarding steps and
+//! escalation warnings with a long deadline are moved to the end of the window
+//! instead. Restricting and removing members is never deferred, it does not
+//! DM anyone, and neither are warnings whose deadline would pass first.
+
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime, Time, UtcOffset};
+
+/// Escalation warnings followed by a step at least this far out are deferred, shorter
+/// ones are sent right away so the member still has time to act
+pub const LONG_DEADLINE_SECS: i64 = 12 * 3600;
+
+/// Daily window without non-urgent DMs, from `start_hour` to `end_hour` at UTC offset
+/// `utc_offset_minutes`. The window wraps around midnight when it ends before it starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start_hour: u8,
+    pub end_hour: u8,
+    #[serde(default)]
+    pub utc_offset_minutes: i16,
+}
+
+impl QuietHours {
+    /// Check hours submitted for an agent
+    pub fn validate(&self) -> Result<(), String> {
+        if self.start_hour > 23 || self.end_hour > 23 {
+            return Err("Hours must be between 0 and 23".to_string());
+        }
+        if !(-14 * 60..=14 * 60).contains(&self.utc_offset_minutes) {
+            return Err("utc_offset_minutes must be between -840 and 840".to_string());
+        }
+        Ok(())
+    }
+
+    /// A window starting where it ends is no window, it turns quiet hours off
+    pub fn is_empty(&self) -> bool {
+        self.start_hour == self.end_hour
+    }
+
+    /// End of the quiet window `now` falls in, `None` outside of it
+    pub fn deferred_until(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        if self.is_empty() {
+            return None;
+        }
+        let offset = UtcOffset::from_whole_seconds(self.utc_offset_minutes as i32 * 60).ok()?;
+        let local = now.to_offset(offset);
+
+        let hour = local.hour();
+        let quiet = if self.start_hour < self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        };
+        if !quiet {
+            return None;
+        }
+
+        let mut end = local.replace_time(Time::from_hms(self.end_hour, 0, 0).ok()?);
+        if end <= local {
+            end += Duration::days(1);
+        }
+        Some(end.to_offset(UtcOffset::UTC))
+    }
+}
+
+/// Quiet hours stored for an agent, `None` when it has none
+pub fn parse_quiet_hours(stored: Option<&str>) -> Option<QuietHours> {
+    let hours: QuietHours = serde_json::from_str(stored?).ok()?;
+    (!hours.is_empty()).then_some(hours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(day: i64, hour: i64, minute: i64) -> OffsetDateTime {
+        OffsetDateTime::UNIX_EPOCH + Duration::days(day) + Duration::hours(hour) + Duration::minutes(minute)
+    }
+
+    #[test]
+    fn test_quiet_hours_window() {
+        // 22:00 to 08:00 at UTC+2
+        let hours = QuietHours { start_hour: 22, end_hour: 8, utc_offset_minutes: 120 };
+
+        // 03:00 local, deferred to 08:00 local the same morning
+        assert_eq!(hours.deferred_until(utc(0, 1, 0)), Some(utc(0, 6, 0)));
+        // 23:30 local, deferred to the next morning
+        assert_eq!(hours.deferred_until(utc(0, 21, 30)), Some(utc(1, 6, 0)));
+        // 08:00 and 21:59 local are outside the window
+        assert_eq!(hours.deferred_until(utc(0, 6, 0)), None);
+        assert_eq!(hours.deferred_until(utc(0, 19, 59)), None);
+
+        let afternoon = QuietHours { start_hour: 13, end_hour: 15, utc_offset_minutes: 0 };
+        assert_eq!(afternoon.deferred_until(utc(0, 14, 10)), Some(utc(0, 15, 0)));
+        assert_eq!(afternoon.deferred_until(utc(0, 15, 0)), None);
+    }
+
+    #[test]
+    fn test_stored_quiet_hours() {
+        assert_eq!(
+            parse_quiet_hours(Some(r#"{"start_hour":22,"end_hour":7}"#)),
+            Some(QuietHours { start_hour: 22, end_hour: 7, utc_offset_minutes: 0 })
+        );
+        assert_eq!(parse_quiet_hours(Some(r#"{"start_hour":9,"end_hour":9}"#)), None);
+        assert_eq!(parse_quiet_hours(None), None);
+
+        assert!(QuietHours { start_hour: 24, end_hour: 7, utc_offset_minutes: 0 }.validate().is_err());
+        assert!(QuietHours { start_hour: 22, end_hour: 7, utc_offset_minutes: 900 }.validate().is_err());
+        assert!(QuietHours { start_hour: 22, end_hour: 7, utc_offset_minutes: -300 }.validate().is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+//! Startup recovery of verifications in flight when the server stopped.
+//!
+//! A member who joined just before a crash or restart may have been muted
+//! without ever getting their sign-link prompt, or may have signed while the
+//! server went down before unmuting them. On startup every member still
+//! pending verification is checked again: one with a bound wallet holding the
+//! group's shares is admitted, one whose prompt never went out is muted and
+//! prompted again. Members who were prompted and have not signed are left to
+//! the verification timeout. Admissions and re-prompts take the group's turn on
+//! the bot lanes, so they stay in order with the updates of the bots starting
+//! meanwhile.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use sqlx::PgPool;
+use teloxide::types::{ChatId, UserId};
+use tracing::{error, info, warn};
+
+use crate::block_chain::{create_blockchain, Blockchain, ChainType};
+use crate::bot::api::{TelegramApi, TelegramBotApi};
+use crate::bot::errors::track;
+use crate::bot::handler::{issue_sign_link, welcome_prompt};
+use crate::bot::lanes::ChatLanes;
+use crate::db::models::UnsettledVerification;
+use crate::db::operations::{get_unsettled_verifications, get_user_bindings, mark_verification_prompted, track_bot_message};
+use crate::error::parse_telegram_id;
+use crate::services::verification::{bind_and_admit, get_verified_group_bot};
+use crate::AppConfig;
+
+// Maximum number of pending members checked on startup
+const RECOVERY_BATCH_SIZE: i64 = 1000;
+
+/// Outcome of checking the pending members on startup
+#[derive(Debug, Default)]
+pub struct RecoveryStats {
+    /// Pending members checked
+    pub checked: usize,
+    /// Members whose bound wallet holds the shares, unmuted
+    pub admitted: usize,
+    /// Members whose prompt never went out, prompted again
+    pub reprompted: usize,
+    /// Members whose prompt could not be sent again
+    pub failed: usize,
+}
+
+// Admit the member if one of their wallets on the group's chain already holds the shares
+async fn admit_bound_member(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    blockchain: &dyn Blockchain,
+    lanes: &ChatLanes,
+    member: &UnsettledVerification,
+) -> anyhow::Result<bool> {
+    let addresses: Vec<String> = get_user_bindings(pool, &member.telegram_id)
+        .await?
+        .into_iter()
+        .filter(|binding| binding.chain_type == member.chain_type)
+        .map(|binding| binding.address)
+        .collect();
+    if addresses.is_empty() {
+        return Ok(false);
+    }
+
+    let bot_info = get_verified_group_bot(pool, &member.chat_id, member.chain_type).await?;
+    for address in &addresses {
+        if bind_and_admit(pool, blockchain, telegram, Some(lanes), &bot_info, &member.telegram_id, address).await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Mute the member again, they may have joined right before the restart, and post a fresh prompt
+async fn reprompt_member(pool: &PgPool, telegram: &dyn TelegramApi, config: &AppConfig, member: &UnsettledVerification) -> anyhow::Result<()> {
+    let user_id = UserId(parse_telegram_id(&member.telegram_id)?);
+    let chat = member.chat_id.as_str();
+    let muted = telegram.restrict_chat_member(&member.bot_token, chat, user_id, member.enforcement_mode.restricted_permissions()).await;
+    track(pool, &member.agent_name, chat, muted).await?;
+
+    let (session, link) = issue_sign_link(
+        pool,
+        &config.sign_page_url,
+        &member.telegram_id,
+        chat,
+        member.chain_type,
+        config.verify_session_ttl_secs,
+    ).await?;
+    let name = member.username.as_ref().map(|username| format!("@{}", username));
+    let text = welcome_prompt(name.as_deref(), &config.branding, &link, config.verify_session_ttl_secs);
+    let sent = telegram.send_message(&member.bot_token, ChatId(chat.parse()?), text).await;
+    let message_id = track(pool, &member.agent_name, chat, sent).await?;
+
+    mark_verification_prompted(pool, &member.telegram_id, chat, &session.id, message_id.0).await?;
+    if member.delete_service_messages {
+        if let Err(e) = track_bot_message(pool, &member.agent_name, chat, message_id.0, config.prompt_ttl_secs).await {
+            warn!("Failed to track prompt message for agent {}: {:?}", member.agent_name, e);
+        }
+    }
+    Ok(())
+}
+
+/// Check every member still pending verification: admit those whose bound wallet holds
+/// the shares and re-prompt those whose prompt was never sent
+pub async fn recover_pending_verifications(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    lanes: &ChatLanes,
+    config: Arc<AppConfig>,
+) -> Result<RecoveryStats, sqlx::Error> {
+    let members = get_unsettled_verifications(pool, RECOVERY_BATCH_SIZE).await?;
+    let mut stats = RecoveryStats { checked: members.len(), ..RecoveryStats::default() };
+    // Chains that cannot be reached are skipped, their members can still be re-prompted
+    let mut chains: HashMap<ChainType, Option<Box<dyn Blockchain>>> = HashMap::new();
+
+    for member in &members {
+        let blockchain = chains.entry(member.chain_type).or_insert_with(|| {
+            create_blockchain(member.chain_type, config.clone())
+                .map_err(|e| warn!("Cannot check {} wallets of pending members: {}", member.chain_type, e))
+                .ok()
+        });
+
+        if let Some(blockchain) = blockchain {
+            match admit_bound_member(pool, telegram, blockchain.as_ref(), lanes, member).await {
+                Ok(true) => {
+                    info!("Admitted pending user {} of chat {} holding shares with a bound wallet", member.telegram_id, member.chat_id);
+                    stats.admitted += 1;
+                    continue;
+                }
+                Ok(false) => {},
+                Err(e) => error!("Failed to check bound wallets of pending user {}: {:?}", member.telegram_id, e),
+            }
+        }
+
+        if member.prompt_sent_at.is_some() {
+            continue;
+        }
+        // Taken only now, admitting takes the turn on its own
+        let _turn = match member.chat_id.parse() {
+            Ok(chat_id) => lanes.turn(chat_id).await,
+            Err(_) => None,
+        };
+        match reprompt_member(pool, telegram, &config, member).await {
+            Ok(()) => {
+                info!("Re-sent the lost prompt of user {} in chat {}", member.telegram_id, member.chat_id);
+                stats.reprompted += 1;
+            }
+            Err(e) => {
+                error!("Failed to re-prompt pending user {} in chat {}: {:?}", member.telegram_id, member.chat_id, e);
+                stats.failed += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Run [`recover_pending_verifications`] once, for the startup task
+pub async fn recover_verifications_on_startup(pool: PgPool, lanes: ChatLanes, config: AppConfig) {
+    match recover_pending_verifications(&pool, &TelegramBotApi, &lanes, Arc::new(config)).await {
+        Ok(stats) if stats.admitted + stats.reprompted + stats.failed > 0 => info!(
+            "Recovered pending verifications: {} checked, {} admitted, {} re-prompted, {} failed",
+            stats.checked, stats.admitted, stats.reprompted, stats.failed
+        ),
+        Ok(_) => {},
+        Err(e) => error!("Verification recovery failed: {:?}", e),
+    }
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+use serde::Serialize;
+use sqlx::PgPool;
+use teloxide::prelude::*;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::bot::api::{TelegramApi, TelegramBotApi};
+use crate::bot::errors::track;
+use crate::bot::handler::issue_sign_link;
+use crate::db::models::PendingVerification;
+use crate::db::operations::{get_overdue_verifications, get_unverified_members, mark_verification_warned, resolve_pending_verification};
+use crate::enforcement::restrict_member;
+use crate::kill_switch;
+use crate::shutdown::sleep_or_shutdown;
+use crate::AppConfig;
+
+// Maximum number of members warned or removed per pass
+const UNVERIFIED_BATCH_SIZE: i64 = 100;
+// Interval between passes
+const UNVERIFIED_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// Pause between re-prompt DMs, well below Telegram's limit of 30 messages per second
+const REPROMPT_PACING: Duration = Duration::from_millis(100);
+
+/// Members DMed by one re-prompt request, the rest are left to the next one
+pub const MAX_REPROMPT_MEMBERS: i64 = 300;
+
+/// Outcome of re-prompting the unverified members of a group
+#[derive(Debug, Default, Serialize)]
+pub struct RepromptStats {
+    /// Unverified members found
+    pub members: usize,
+    /// Members the bot could DM a fresh link
+    pub reachable: usize,
+    /// Members who never started the bot, blocked it or left Telegram
+    pub unreachable: usize,
+}
+
+// DM a member that they will be removed soon, with a fresh sign link
+async fn warn_member(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    config: &AppConfig,
+    member: &PendingVerification,
+    minutes_left: i64,
+) -> anyhow::Result<()> {
+    let (_, link) = issue_sign_link(
+        pool,
+        &config.sign_page_url,
+        &member.telegram_id,
+        &member.chat_id,
+        member.chain_type,
+        config.verify_session_ttl_secs,
+    ).await?;
+
+    let user_id = UserId(member.telegram_id.parse()?);
+    let sent = telegram.send_message(
+        &member.bot_token,
+        user_id.into(),
+        format!(
+            "You joined a group that requires holding {} shares but have not verified yet. \
+             Verify within {} minutes or you will be removed: {}{}",
+            config.branding.name, minutes_left, link, config.branding.footer()
+        ),
+    )
+    .await;
+    track(pool, &member.agent_name, &member.chat_id, sent).await?;
+    Ok(())
+}
+
+/// DM every pending member of an agent's group without a bound wallet a fresh sign link,
+/// paced so a large group does not hit Telegram's rate limits
+pub async fn reprompt_unverified(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    config: &AppConfig,
+    agent_name: &str,
+) -> Result<RepromptStats, sqlx::Error> {
+    let members = get_unverified_members(pool, agent_name, MAX_REPROMPT_MEMBERS).await?;
+    let mut stats = RepromptStats { members: members.len(), ..RepromptStats::default() };
+
+    for (i, member) in members.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(REPROMPT_PACING).await;
+        }
+        let (_, link) = issue_sign_link(
+            pool,
+            &config.sign_page_url,
+            &member.telegram_id,
+            &member.chat_id,
+            member.chain_type,
+            config.verify_session_ttl_secs,
+        ).await?;
+
+        let Ok(user_id) = member.telegram_id.parse() else {
+            stats.unreachable += 1;
+            continue;
+        };
+        let sent = telegram.send_message(
+            &member.bot_token,
+            UserId(user_id).into(),
+            format!(
+                "You have not verified your wallet for the {} group yet. Here is a fresh link, it expires in {} minutes: {}{}",
+                config.branding.name, config.verify_session_ttl_secs / 60, link, config.branding.footer()
+            ),
+        )
+        .await;
+        match track(pool, agent_name, &member.chat_id, sent).await {
+            Ok(_) => stats.reachable += 1,
+            Err(e) => {
+                warn!("Could not re-prompt user {} of chat {}: {:?}", member.telegram_id, member.chat_id, e);
+                stats.unreachable += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+// Remove a member who never verified, following the group's enforcement mode: muted
+// members stay muted, kicked ones can join again and get a new prompt
+async fn remove_member(pool: &PgPool, telegram: &dyn TelegramApi, member: &PendingVerification) -> anyhow::Result<()> {
+    let user_id = UserId(member.telegram_id.parse()?);
+    let removed = restrict_member(telegram, &member.bot_token, &member.chat_id, user_id, member.enforcement_mode).await;
+    track(pool, &member.agent_name, &member.chat_id, removed).await?;
+    Ok(())
+}
+
+// Warn members close to the timeout, then remove those past it
+pub async fn enforce_verification_timeout(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    config: &AppConfig,
+) -> Result<(usize, usize), sqlx::Error> {
+    let timeout_secs = config.verify_timeout_minutes * 60;
+    let warning_secs = config.verify_warning_minutes.min(config.verify_timeout_minutes) * 60;
+
+    let to_warn = get_overdue_verifications(pool, timeout_secs - warning_secs, true, UNVERIFIED_BATCH_SIZE).await?;
+    for member in &to_warn {
+        if let Err(e) = warn_member(pool, telegram, config, member, config.verify_warning_minutes).await {
+            error!("Failed to warn unverified user {} in chat {}: {:?}", member.telegram_id, member.chat_id, e);
+        }
+        // Members who never started the bot cannot be messaged, do not retry every pass
+        mark_verification_warned(pool, &member.telegram_id, &member.chat_id).await?;
+    }
+
+    // Removal is an outbound restrict/kick, hold it like any other while the switch is engaged
+    if kill_switch::is_engaged(pool).await? {
+        return Ok((to_warn.len(), 0));
+    }
+
+    let to_remove = get_overdue_verifications(pool, timeout_secs, false, UNVERIFIED_BATCH_SIZE).await?;
+    let mut removed = 0;
+    for member in &to_remove {
+        match remove_member(pool, telegram, member).await {
+            Ok(()) => {
+                info!("Removed unverified user {} from chat {} ({})", member.telegram_id, member.chat_id, member.enforcement_mode.as_str());
+                resolve_pending_verification(pool, &member.telegram_id, &member.chat_id, "removed").await?;
+                removed += 1;
+            }
+            Err(e) => error!("Failed to remove unverified user {} from chat {}: {:?}", member.telegram_id, member.chat_id, e),
+        }
+    }
+
+    Ok((to_warn.len(), removed))
+}
+
+pub async fn verification_timeout_loop(pool: PgPool, config: AppConfig, shutdown: CancellationToken) {
+    if config.verify_timeout_minutes <= 0 {
+        warn!("VERIFY_TIMEOUT_MINUTES is 0, unverified members are never removed");
+        return;
+    }
+
+    while !shutdown.is_cancelled() {
+        match enforce_verification_timeout(&pool, &TelegramBotApi, &config).await {
+            Ok((warned, removed)) if warned + removed > 0 => {
+                info!("Warned {} and removed {} unverified members", warned, removed)
+            },
+            Ok(_) => {},
+            Err(e) => error!("Verification timeout pass failed: {:?}", e),
+        }
+        sleep_or_shutdown(&shutdown, UNVERIFIED_CHECK_INTERVAL).await;
+    }
+}
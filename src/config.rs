@@ -0,0 +1,362 @@
+use std::env;
+use std::str::FromStr;
+
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::block_chain::ChainType;
+use crate::db::operations::get_evm_chains;
+use crate::routes::concurrency::{parse_limits, DEFAULT_CONCURRENCY_LIMITS};
+
+/// Product name, logo, colors and support link of a deployment, shown in bot
+/// messages, on the sign page and by `GET /branding`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Branding {
+    pub name: String,
+    pub logo_url: Option<String>,
+    /// CSS hex colors, `#rgb` or `#rrggbb`
+    pub primary_color: String,
+    pub accent_color: String,
+    pub support_url: Option<String>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            name: "Alice".to_string(),
+            logo_url: None,
+            primary_color: "#1a1a1a".to_string(),
+            accent_color: "#1b7a1b".to_string(),
+            support_url: None,
+        }
+    }
+}
+
+impl Branding {
+    /// Load the BRAND_* variables, panicking on invalid colors
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let color = |key: &str, default: String| {
+            let color = env::var(key).ok().filter(|v| !v.is_empty()).unwrap_or(default);
+            if !valid_color(&color) {
+                panic!("Invalid {}: {} is not a #rgb or #rrggbb color", key, color);
+            }
+            color
+        };
+        Self {
+            name: env::var("BRAND_NAME").ok().filter(|v| !v.trim().is_empty()).unwrap_or(defaults.name),
+            logo_url: env::var("BRAND_LOGO_URL").ok().filter(|v| !v.is_empty()),
+            primary_color: color("BRAND_PRIMARY_COLOR", defaults.primary_color),
+            accent_color: color("BRAND_ACCENT_COLOR", defaults.accent_color),
+            support_url: env::var("BRAND_SUPPORT_URL").ok().filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// Closing line of bot messages pointing members to support, empty without a support link
+    pub fn footer(&self) -> String {
+        match &self.support_url {
+            Some(url) => format!("\n\nNeed help? Contact {} support: {}", self.name, url),
+            None => String::new(),
+        }
+    }
+}
+
+// Colors end up in the sign page's CSS, so only plain hex colors are accepted
+fn valid_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Network and shares contract of one EVM chain. Monad comes from the environment,
+/// rows of the `evm_chains` table add further networks or override it
+#[derive(Clone, Debug)]
+pub struct EvmChainConfig {
+    pub chain_type: ChainType,
+    pub rpc_url: String,
+    // Optional WebSocket endpoint for streaming trade events
+    pub ws_url: Option<String>,
+    pub contract_address: String,
+    pub start_block: u64,
+    // Blocks are only synced once this many blocks were built on top of them
+    pub confirmations: u64,
+    pub share_decimals: u32,
+    pub price_id: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    pub telegram_bot_token: String,
+    pub telegram_group_id: String,
+    // EVM networks synced by EvmBlockchain, one per chain type
+    pub evm_chains: Vec<EvmChainConfig>,
+    // Blocks (EVM) or events (Sui, Aptos) synced per step
+    pub sync_batch_size: u64,
+    // Wait of the sync loops once caught up with the chain, and after a failed step
+    pub sync_idle_interval_secs: u64,
+    pub sync_error_backoff_secs: u64,
+    pub database_url: String,
+    // Chains whose events are synced, oracled and reconciled
+    pub enabled_chains: Vec<ChainType>,
+    // HTTP listener, served over HTTPS when both TLS paths are set
+    pub http_bind_addr: String,
+    pub http_port: u16,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    // Sui chain configuration
+    pub sui_rpc: Option<String>,
+    pub sui_contract: Option<String>,
+    pub sui_shares_trading_object_id: Option<String>,
+    // Solana chain configuration
+    pub solana_rpc: Option<String>,
+    pub solana_program_id: Option<String>,
+    // Aptos chain configuration, the fullnode REST API and the account publishing the shares module
+    pub aptos_rpc: Option<String>,
+    pub aptos_contract: Option<String>,
+    // Decimals of share amounts emitted by each chain's contract, 0 for whole shares
+    pub sui_share_decimals: u32,
+    pub solana_share_decimals: u32,
+    pub aptos_share_decimals: u32,
+    // Bonding curve of share prices, the share at supply n costs n² / divisor native tokens
+    pub share_price_divisor: u64,
+    // Garbage collection configuration
+    pub gc_interval_secs: u64,
+    pub gc_batch_size: i64,
+    // Public URL of this server, used for links to the built-in pages
+    pub public_base_url: String,
+    // Page the bot links new members to for signing, the built-in /sign page unless set
+    pub sign_page_url: String,
+    // Trading page linked from access reports, e.g. https://your.host/trade
+    pub buy_page_url: Option<String>,
+    // Lifetime of QR verification sessions
+    pub verify_session_ttl_secs: i64,
+    // Members who have not verified this long after joining are removed, 0 disables it
+    pub verify_timeout_minutes: i64,
+    // How long before removal the member is warned by DM
+    pub verify_warning_minutes: i64,
+    // Lifetime of signature challenge nonces
+    pub challenge_ttl_secs: i64,
+    // How long a wallet has to send its binding proof transaction
+    pub tx_binding_ttl_secs: i64,
+    // Native price and gas oracle configuration
+    pub price_api_url: String,
+    pub sui_price_id: Option<String>,
+    pub solana_price_id: Option<String>,
+    pub aptos_price_id: Option<String>,
+    pub oracle_refresh_secs: u64,
+    // Interval between on-chain balance reconciliation passes
+    pub reconcile_interval_secs: u64,
+    // Cleanup of bot prompts in gated groups
+    pub prompt_ttl_secs: i64,
+    pub message_cleanup_interval_secs: u64,
+    // Worker lanes bot updates are sharded to by chat, and the updates each lane queues before bots wait
+    pub bot_lanes: usize,
+    pub bot_lane_capacity: usize,
+    // Hold every restrict/kick action from startup, see kill_switch
+    pub kill_switch: bool,
+    // Log one JSON object per line instead of text, LOG_FORMAT=json
+    pub log_json: bool,
+    // Interval between sends of due onboarding messages
+    pub onboarding_interval_secs: u64,
+    // Interval between passes over due webhook deliveries
+    pub webhook_interval_secs: u64,
+    // Token buckets of the rate limited routes per client IP and per Telegram id, a zero disables one
+    pub rate_limit_ip_burst: u32,
+    pub rate_limit_ip_per_minute: u32,
+    pub rate_limit_telegram_burst: u32,
+    pub rate_limit_telegram_per_minute: u32,
+    // Take the client IP from Forwarded / X-Forwarded-For, only behind a trusted proxy
+    pub trust_proxy_headers: bool,
+    // Requests each heavy route runs at once, and how long further requests queue before a 503
+    pub concurrency_limits: Vec<(String, usize)>,
+    pub concurrency_queue_ms: u64,
+    // Rate limit buckets shared between instances, needs the redis feature
+    pub redis_url: Option<String>,
+    // Key always accepted by the administrative routes, used to create the stored keys
+    pub admin_api_key: Option<String>,
+    // Discord application whose OAuth2 tokens prove the user linking a wallet, linking is off unless set
+    pub discord_client_id: Option<String>,
+    // Readiness fails once a chain's indexing lags its head by more than this
+    pub health_max_sync_lag_secs: u64,
+    // Product name, logo, colors and support link of white-label deployments
+    pub branding: Branding,
+}
+
+// Read an optional numeric setting, falling back to a default when unset or invalid
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+// Parse a comma separated chain list, ignoring blanks and duplicates
+fn parse_chain_list(value: &str) -> Result<Vec<ChainType>, String> {
+    let mut chains = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let chain_type = ChainType::from_str(name)?;
+        if !chains.contains(&chain_type) {
+            chains.push(chain_type);
+        }
+    }
+    Ok(chains)
+}
+
+impl AppConfig {
+    /// Load configuration from environment variables, panicking on missing required values
+    pub fn from_env() -> Self {
+        let http_port = env_or("HTTP_PORT", 8088);
+        let public_base_url = env::var("PUBLIC_BASE_URL")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| format!("http://localhost:{}", http_port))
+            .trim_end_matches('/')
+            .to_string();
+        Self {
+            telegram_bot_token: env::var("TELEGRAM_BOT_TOKEN")
+                .expect("TELEGRAM_BOT_TOKEN not set"),
+            telegram_group_id: env::var("TELEGRAM_GROUP_ID")
+                .expect("TELEGRAM_GROUP_ID not set"),
+            evm_chains: vec![EvmChainConfig {
+                chain_type: ChainType::Monad,
+                rpc_url: env::var("CHAIN_RPC")
+                    .expect("CHAIN_RPC not set"),
+                ws_url: env::var("CHAIN_WS_RPC").ok(),
+                contract_address: env::var("SHARES_CONTRACT_ADDRESS")
+                    .expect("SHARES_CONTRACT_ADDRESS not set"),
+                start_block: env::var("START_BLOCK")
+                    .expect("START_BLOCK not set")
+                    .parse()
+                    .expect("START_BLOCK must be a number"),
+                confirmations: env_or("MONAD_CONFIRMATIONS", 3),
+                share_decimals: env_or("MONAD_SHARE_DECIMALS", 0),
+                price_id: env::var("MONAD_PRICE_ID").ok(),
+            }],
+            sync_batch_size: env_or("SYNC_BATCH_SIZE", 100u64).max(1),
+            sync_idle_interval_secs: env_or("SYNC_IDLE_INTERVAL_SECS", 60),
+            sync_error_backoff_secs: env_or("SYNC_ERROR_BACKOFF_SECS", 10),
+            database_url: env::var("DATABASE_URL")
+                .expect("DATABASE_URL not set"),
+            enabled_chains: parse_chain_list(&env::var("ENABLED_CHAINS").unwrap_or_else(|_| "sui".to_string()))
+                .unwrap_or_else(|e| panic!("Invalid ENABLED_CHAINS: {}", e)),
+            http_bind_addr: env::var("HTTP_BIND_ADDR").unwrap_or_else(|_| "0.0.0.0".to_string()),
+            http_port,
+            tls_cert_path: env::var("TLS_CERT_PATH").ok().filter(|v| !v.is_empty()),
+            tls_key_path: env::var("TLS_KEY_PATH").ok().filter(|v| !v.is_empty()),
+            sui_rpc: env::var("SUI_RPC").ok(),
+            sui_contract: env::var("SUI_CONTRACT").ok(),
+            sui_shares_trading_object_id: env::var("SUI_SHARES_TRADING_OBJECT_ID").ok(),
+            solana_rpc: env::var("SOLANA_RPC").ok(),
+            solana_program_id: env::var("SOLANA_PROGRAM_ID").ok(),
+            aptos_rpc: env::var("APTOS_RPC").ok(),
+            aptos_contract: env::var("APTOS_CONTRACT").ok(),
+            sui_share_decimals: env_or("SUI_SHARE_DECIMALS", 0),
+            solana_share_decimals: env_or("SOLANA_SHARE_DECIMALS", 0),
+            aptos_share_decimals: env_or("APTOS_SHARE_DECIMALS", 0),
+            share_price_divisor: env_or("SHARE_PRICE_DIVISOR", 16000u64).max(1),
+            gc_interval_secs: env_or("GC_INTERVAL_SECS", 3600),
+            gc_batch_size: env_or("GC_BATCH_SIZE", 1000i64).max(1),
+            sign_page_url: env::var("SIGN_PAGE_URL")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| format!("{}/sign", public_base_url)),
+            public_base_url,
+            buy_page_url: env::var("BUY_PAGE_URL").ok(),
+            verify_session_ttl_secs: env_or("VERIFY_SESSION_TTL_SECS", 600),
+            verify_timeout_minutes: env_or("VERIFY_TIMEOUT_MINUTES", 0),
+            verify_warning_minutes: env_or("VERIFY_WARNING_MINUTES", 10),
+            challenge_ttl_secs: env_or("CHALLENGE_TTL_SECS", 300),
+            tx_binding_ttl_secs: env_or("TX_BINDING_TTL_SECS", 3600),
+            price_api_url: env::var("PRICE_API_URL")
+                .unwrap_or_else(|_| "https://api.coingecko.com/api/v3/simple/price".to_string()),
+            sui_price_id: Some(env::var("SUI_PRICE_ID").unwrap_or_else(|_| "sui".to_string())),
+            solana_price_id: Some(env::var("SOLANA_PRICE_ID").unwrap_or_else(|_| "solana".to_string())),
+            aptos_price_id: Some(env::var("APTOS_PRICE_ID").unwrap_or_else(|_| "aptos".to_string())),
+            oracle_refresh_secs: env_or("ORACLE_REFRESH_SECS", 60),
+            reconcile_interval_secs: env_or("RECONCILE_INTERVAL_SECS", 1800),
+            prompt_ttl_secs: env_or("PROMPT_TTL_SECS", 600),
+            message_cleanup_interval_secs: env_or("MESSAGE_CLEANUP_INTERVAL_SECS", 60),
+            bot_lanes: env_or("BOT_LANES", 16usize).max(1),
+            bot_lane_capacity: env_or("BOT_LANE_CAPACITY", 256usize).max(1),
+            kill_switch: env_or("KILL_SWITCH", false),
+            log_json: env::var("LOG_FORMAT").map(|format| format.eq_ignore_ascii_case("json")).unwrap_or(false),
+            onboarding_interval_secs: env_or("ONBOARDING_INTERVAL_SECS", 15),
+            webhook_interval_secs: env_or("WEBHOOK_INTERVAL_SECS", 5),
+            rate_limit_ip_burst: env_or("RATE_LIMIT_IP_BURST", 20),
+            rate_limit_ip_per_minute: env_or("RATE_LIMIT_IP_PER_MINUTE", 30),
+            rate_limit_telegram_burst: env_or("RATE_LIMIT_TELEGRAM_BURST", 5),
+            rate_limit_telegram_per_minute: env_or("RATE_LIMIT_TELEGRAM_PER_MINUTE", 10),
+            trust_proxy_headers: env_or("TRUST_PROXY_HEADERS", false),
+            concurrency_limits: parse_limits(&env::var("CONCURRENCY_LIMITS").unwrap_or_else(|_| DEFAULT_CONCURRENCY_LIMITS.to_string()))
+                .unwrap_or_else(|e| panic!("Invalid CONCURRENCY_LIMITS: {}", e)),
+            concurrency_queue_ms: env_or("CONCURRENCY_QUEUE_MS", 1000),
+            redis_url: env::var("REDIS_URL").ok().filter(|v| !v.is_empty()),
+            admin_api_key: env::var("ADMIN_API_KEY").ok().filter(|v| !v.is_empty()),
+            discord_client_id: env::var("DISCORD_CLIENT_ID").ok().filter(|v| !v.is_empty()),
+            health_max_sync_lag_secs: env_or("HEALTH_MAX_SYNC_LAG_SECS", 300),
+            branding: Branding::from_env(),
+        }
+    }
+
+    /// Add the EVM networks of the `evm_chains` table, a row for a chain replaces its
+    /// configuration from the environment
+    pub async fn load_evm_chains(&mut self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        for row in get_evm_chains(pool).await? {
+            self.evm_chains.retain(|existing| existing.chain_type != row.chain_type);
+            self.evm_chains.push(EvmChainConfig {
+                chain_type: row.chain_type,
+                rpc_url: row.rpc_url,
+                ws_url: row.ws_url,
+                contract_address: row.contract_address,
+                start_block: row.start_block as u64,
+                confirmations: row.confirmations as u64,
+                share_decimals: row.share_decimals as u32,
+                price_id: row.price_id,
+            });
+        }
+        Ok(())
+    }
+
+    /// Network and contract of an EVM chain, None if it is not configured
+    pub fn evm_chain(&self, chain_type: ChainType) -> Option<&EvmChainConfig> {
+        self.evm_chains.iter().find(|chain| chain.chain_type == chain_type)
+    }
+
+    /// Address of the shares contract (program on Solana, module account on Aptos) synced for a chain
+    pub fn contract_address(&self, chain_type: ChainType) -> &str {
+        match chain_type {
+            ChainType::Monad | ChainType::Base | ChainType::Arbitrum => {
+                self.evm_chain(chain_type).map(|chain| chain.contract_address.as_str()).unwrap_or_default()
+            }
+            ChainType::Sui => self.sui_contract.as_deref().unwrap_or_default(),
+            ChainType::Solana => self.solana_program_id.as_deref().unwrap_or_default(),
+            ChainType::Aptos => self.aptos_contract.as_deref().unwrap_or_default(),
+        }
+    }
+
+    /// Decimals share amounts of a chain's contract are expressed in
+    pub fn share_decimals(&self, chain_type: ChainType) -> u32 {
+        match chain_type {
+            ChainType::Monad | ChainType::Base | ChainType::Arbitrum => {
+                self.evm_chain(chain_type).map_or(0, |chain| chain.share_decimals)
+            }
+            ChainType::Sui => self.sui_share_decimals,
+            ChainType::Solana => self.solana_share_decimals,
+            ChainType::Aptos => self.aptos_share_decimals,
+        }
+    }
+
+    /// Trading page of a subject, when `BUY_PAGE_URL` is set
+    pub fn buy_url(&self, subject: &str, chain_type: ChainType) -> Option<String> {
+        self.buy_page_url.as_ref().map(|url| format!("{}?subject={}&chain_type={}", url, subject, chain_type))
+    }
+
+    /// Certificate and key paths when HTTPS is configured, panicking if only one of them is set
+    pub fn tls_paths(&self) -> Option<(&str, &str)> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => panic!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+        }
+    }
+}
@@ -1,40 +1,604 @@
 use serde::{Deserialize, Serialize};
 use sqlx::types::BigDecimal;
+use time::{Date, OffsetDateTime};
+
+use crate::block_chain::ChainType;
+use crate::enforcement::{EnforcementMode, SubjectRule};
+use crate::routes::auth::KeyRole;
 
 #[derive(Clone, Debug)]
-pub struct AppConfig {
-    pub telegram_bot_token: String,
-    pub telegram_group_id: String,
-    pub shares_contract: String,
-    pub chain_rpc: String,
-    pub database_url: String,
-    pub start_block: u64,
-    pub sui_rpc: Option<String>,
-    pub sui_contract: Option<String>,
-    pub sui_start_tx_digest: Option<String>,
-    pub sui_shares_trading_object_id: Option<String>,
+pub struct UserShares {
+    pub trader: String,
+    pub subject: String,
+    pub share_amount: BigDecimal,
+    pub chain_type: ChainType,
 }
 
 #[derive(Clone, Debug)]
-pub struct UserShares {
+pub struct VerificationSession {
+    pub id: String,
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub chain_type: ChainType,
+    pub status: String,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+impl VerificationSession {
+    /// Status as seen by clients, pending sessions past their expiry are reported as expired
+    pub fn effective_status(&self) -> &str {
+        if self.status == "pending" && self.expires_at < OffsetDateTime::now_utc() {
+            "expired"
+        } else {
+            &self.status
+        }
+    }
+}
+
+/// A tracked bot message that is due for deletion
+#[derive(Clone, Debug)]
+pub struct DueBotMessage {
+    pub id: i64,
+    pub agent_name: String,
+    pub chat_id: String,
+    pub message_id: i32,
+    pub bot_token: String,
+}
+
+/// A member who joined a gated group and has not verified in time, with the group's bot
+#[derive(Clone, Debug)]
+pub struct PendingVerification {
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub agent_name: String,
+    pub chain_type: ChainType,
+    pub enforcement_mode: EnforcementMode,
+    pub bot_token: String,
+}
+
+/// A member still pending verification, checked again at startup
+#[derive(Clone, Debug)]
+pub struct UnsettledVerification {
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub agent_name: String,
+    pub chain_type: ChainType,
+    pub bot_token: String,
+    pub enforcement_mode: EnforcementMode,
+    pub delete_service_messages: bool,
+    /// `None` while their sign-link prompt was never sent
+    pub prompt_sent_at: Option<OffsetDateTime>,
+    pub username: Option<String>,
+}
+
+/// A group whose share rule a wallet meets
+#[derive(Clone, Debug, Serialize)]
+pub struct QualifyingGroup {
+    pub agent_name: String,
+    pub invite_url: String,
+    pub chain_type: ChainType,
+}
+
+/// The bot gating a group and the subject whose shares grant access
+#[derive(Clone, Debug)]
+pub struct GroupBot {
+    pub agent_name: String,
+    pub bot_token: String,
+    pub chat_group_id: String,
+    pub subject_address: String,
+    /// Shares a member must hold to chat
+    pub min_shares: BigDecimal,
+    /// Whether shares of any or all of the group's subjects are needed
+    pub subject_rule: SubjectRule,
+    pub enforcement_mode: EnforcementMode,
+    /// Unmute members before their balance is confirmed
+    pub optimistic_verify: bool,
+}
+
+/// A wallet binding waiting for its proof transaction
+#[derive(Clone, Debug)]
+pub struct PendingBinding {
+    pub memo: String,
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub chain_type: ChainType,
+    pub address: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub expires_at: OffsetDateTime,
+}
+
+/// A step of an agent's onboarding sequence with its delivery counts
+#[derive(Clone, Debug)]
+pub struct OnboardingStep {
+    pub id: i64,
+    pub position: i32,
+    pub message: String,
+    pub delay_secs: i32,
+    pub pin: bool,
+    pub sent: i64,
+    pub failed: i64,
+    pub pending: i64,
+}
+
+/// A step of an onboarding sequence as configured through the API
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewOnboardingStep {
+    pub message: String,
+    #[serde(default)]
+    pub delay_secs: i32,
+    #[serde(default)]
+    pub pin: bool,
+}
+
+/// An onboarding message that is due, with the token of the bot that sends it
+#[derive(Clone, Debug)]
+pub struct DueOnboardingDelivery {
+    pub id: i64,
+    pub agent_name: String,
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub message: String,
+    pub pin: bool,
+    pub bot_token: String,
+    pub quiet_hours: Option<String>,
+}
+
+/// Fee totals of one subject for one day, in the chain's smallest native unit
+#[derive(Clone, Debug)]
+pub struct DailySubjectFees {
+    pub day: Date,
+    pub protocol_fee: BigDecimal,
+    pub subject_fee: BigDecimal,
+    pub trade_count: i64,
+}
+
+/// A gated group a Telegram user is bound to through one of their addresses
+#[derive(Clone, Debug)]
+pub struct GroupHolding {
+    pub agent_name: String,
+    pub chat_group_id: String,
+    pub chain_type: ChainType,
+    pub subject_address: String,
+    pub address: String,
+    pub share_amount: BigDecimal,
+    /// Decimals of the chain's share contract
+    pub share_decimals: i32,
+    /// Shares the group requires to chat
+    pub min_shares: BigDecimal,
+}
+
+/// An address bound to a Telegram user and whether it lost group access
+#[derive(Clone, Debug)]
+pub struct UserBinding {
+    pub address: String,
+    pub chain_type: ChainType,
+    pub is_banned: bool,
+}
+
+/// Where an event was emitted on chain
+#[derive(Clone, Debug)]
+pub struct EventLocation {
+    /// Block (Monad) or slot (Solana), Sui events are not tied to one
+    pub block_number: Option<i64>,
+    pub tx_hash: String,
+    /// Position of the event within its transaction
+    pub log_index: i64,
+    pub block_time: Option<OffsetDateTime>,
+}
+
+/// A decoded trade event, amounts in the chain's smallest units
+#[derive(Clone, Debug)]
+pub struct NewTradeEvent {
+    pub trader: String,
+    pub subject: String,
+    pub is_buy: bool,
+    pub share_amount: BigDecimal,
+    pub eth_amount: BigDecimal,
+    pub protocol_fee: BigDecimal,
+    pub subject_fee: BigDecimal,
+    pub supply: BigDecimal,
+}
+
+/// A stored row of trade_events
+#[derive(Clone, Debug)]
+pub struct TradeEventRecord {
+    pub id: i64,
+    pub chain_type: ChainType,
+    pub block_number: Option<i64>,
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub trader: String,
+    pub subject: String,
+    pub is_buy: bool,
+    pub share_amount: BigDecimal,
+    pub eth_amount: BigDecimal,
+    pub protocol_fee: BigDecimal,
+    pub subject_fee: BigDecimal,
+    pub supply: BigDecimal,
+    pub block_time: Option<OffsetDateTime>,
+    /// Why the trade is suspected to be a wash trade, `None` when it looks organic
+    pub wash_reason: Option<String>,
+    /// Id the event's enforcement is logged under, `None` for events stored before trace ids
+    pub trace_id: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+/// A trader holding shares of a subject, with the Telegram user bound to the address
+#[derive(Clone, Debug)]
+pub struct SubjectHolder {
+    pub trader: String,
+    pub share_amount: BigDecimal,
+    pub telegram_id: Option<String>,
+}
+
+/// Last recorded trade of a subject, the basis of its price
+#[derive(Clone, Debug)]
+pub struct SubjectPrice {
+    pub subject: String,
+    /// Supply after the trade, in whole shares
+    pub supply: BigDecimal,
+    pub share_amount: BigDecimal,
+    pub eth_amount: BigDecimal,
+    pub protocol_fee: BigDecimal,
+    pub subject_fee: BigDecimal,
+    pub traded_at: OffsetDateTime,
+}
+
+/// Trades of a subject over a time window, or over one bucket of a series
+#[derive(Clone, Debug)]
+pub struct SubjectTradeStats {
+    /// Start of the bucket, the start of the window for window totals
+    pub start: OffsetDateTime,
+    pub buy_count: i64,
+    pub sell_count: i64,
+    /// Native amount traded, in the chain's smallest unit
+    pub volume: BigDecimal,
+    pub unique_traders: i64,
+}
+
+/// A holder's position on a subject's leaderboard
+#[derive(Clone, Debug)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub trader: String,
+    pub share_amount: BigDecimal,
+    pub telegram_username: Option<String>,
+}
+
+/// Failed Telegram calls of one class for an agent
+#[derive(Clone, Debug, Serialize)]
+pub struct TelegramErrorSummary {
+    pub agent_name: String,
+    pub kind: String,
+    pub count: i64,
+    pub last_error: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen_at: OffsetDateTime,
+}
+
+/// A logged mute, kick or restore of a member, with the bot of the group
+#[derive(Clone, Debug)]
+pub struct EnforcementEvent {
+    pub id: i64,
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    pub action: String,
+    pub details: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub bot_token: String,
+    pub enforcement_mode: EnforcementMode,
+}
+
+/// A restrict/kick action to hold back while the kill switch is engaged
+#[derive(Clone, Debug)]
+pub struct NewHeldAction {
+    pub chain_type: ChainType,
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    pub address: String,
+    pub subject: String,
+    pub action: String,
+    pub balance: BigDecimal,
+}
+
+/// A holder who sold out, to be walked up their agent's escalation ladder
+#[derive(Clone, Debug)]
+pub struct NewEscalation {
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    pub chain_type: ChainType,
+    pub address: String,
+    pub subject: String,
+    /// Trace id of the event the holder sold out in
+    pub trace_id: String,
+}
+
+/// An active escalation whose next step is due, with its agent's bot and ladder
+#[derive(Clone, Debug)]
+pub struct DueEscalation {
+    pub id: i64,
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    pub chain_type: ChainType,
+    pub address: String,
+    pub subject: String,
+    pub next_step: i32,
+    pub bot_token: String,
+    pub escalation_ladder: Option<String>,
+    pub min_shares: BigDecimal,
+    pub subject_rule: SubjectRule,
+    pub quiet_hours: Option<String>,
+    pub supply_cap: Option<BigDecimal>,
+    pub trace_id: Option<String>,
+    pub enforcement_mode: EnforcementMode,
+}
+
+/// A restrict/kick action held back by the kill switch
+#[derive(Clone, Debug, Serialize)]
+pub struct HeldAction {
+    pub id: i64,
+    pub chain_type: ChainType,
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    pub address: String,
+    pub subject: String,
+    pub action: String,
+    pub balance: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// A verified holder's local balance of a gated subject
+#[derive(Clone, Debug)]
+pub struct ReconcileTarget {
     pub trader: String,
     pub subject: String,
     pub share_amount: BigDecimal,
-    pub chain_type: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ChallengeRequest {
-    pub challenge: String,
-    pub signature: String,
-    pub shares_subject: String,
-    pub user: String,
-    pub chain_type: Option<String>,
+/// Enforcement latency distribution of one agent, in milliseconds.
+/// `le_*` are cumulative histogram buckets.
+#[derive(Clone, Debug)]
+pub struct EnforcementLatencyStats {
+    pub chain_type: ChainType,
+    pub agent_name: String,
+    pub count: i64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: i64,
+    pub le_1s: i64,
+    pub le_5s: i64,
+    pub le_15s: i64,
+    pub le_60s: i64,
+    pub le_300s: i64,
+    pub le_900s: i64,
+}
+
+/// Latest saved sync progress of a chain
+#[derive(Clone, Debug)]
+pub struct SyncPosition {
+    /// Block or slot
+    pub last_synced_block: i64,
+    /// Chain specific cursor (Solana signature)
+    pub metadata: Option<String>,
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+/// Saved position of a chain indexed by event cursor (Sui)
+#[derive(Clone, Debug)]
+pub struct SyncCursor {
+    /// Cursor as the JSON the chain's RPC takes back
+    pub cursor_json: String,
+    pub updated_at: OffsetDateTime,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ChallengeResponse {
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
+/// EVM network configured in the `evm_chains` table
+#[derive(Clone, Debug)]
+pub struct EvmChain {
+    pub chain_type: ChainType,
+    pub rpc_url: String,
+    pub ws_url: Option<String>,
+    pub contract_address: String,
+    pub start_block: i64,
+    pub confirmations: i64,
+    pub share_decimals: i32,
+    pub price_id: Option<String>,
+}
+
+/// A registered webhook, without its secret, with the counts of its deliveries
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookInfo {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub delivered: i64,
+    pub pending: i64,
+    pub failed: i64,
+}
+
+/// A webhook delivery that is due, with the target and key it is signed with
+#[derive(Clone, Debug)]
+pub struct DueWebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub agent_name: String,
+    pub url: String,
+    pub secret: String,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub created_at: OffsetDateTime,
+}
+
+/// A stored API key, without the key itself
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiKeyInfo {
+    pub id: i64,
+    pub name: String,
+    pub role: KeyRole,
+    /// Subjects a partner key may look up, `None` for every subject
+    pub subjects: Option<Vec<String>>,
+    pub key_prefix: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+/// The key a request was authenticated with
+#[derive(Clone, Debug)]
+pub struct AuthenticatedKey {
+    pub name: String,
+    pub role: KeyRole,
+    pub subjects: Option<Vec<String>>,
+}
+
+/// A wallet bound to a Telegram user and its balance of one subject
+#[derive(Clone, Debug)]
+pub struct BoundHolding {
+    pub address: String,
+    pub telegram_id: String,
+    pub is_banned: bool,
+    pub share_amount: BigDecimal,
+}
+
+/// A deleted agent, with its export window and when its data was purged
+#[derive(Clone, Debug, Serialize)]
+pub struct AgentDeletion {
+    pub id: i64,
+    pub agent_name: String,
+    pub chain_type: ChainType,
+    pub chat_group_id: String,
+    /// Subjects that gated the group, its primary subject first
+    pub subjects: Vec<String>,
+    /// Name of the API key that deleted the agent
+    pub requested_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub requested_at: OffsetDateTime,
+    /// The agent's data can be exported until then, and is purged afterwards
+    #[serde(with = "time::serde::rfc3339")]
+    pub export_until: OffsetDateTime,
+    pub export_count: i32,
+    pub last_exported_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_exported_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub purged_at: Option<OffsetDateTime>,
+    pub purged_rows: Option<i64>,
+}
+
+/// Progress of a long running job, e.g. a backfill
+#[derive(Clone, Debug, Serialize)]
+pub struct JobRun {
+    pub id: i64,
+    pub kind: String,
+    pub chain_type: ChainType,
+    pub start_position: i64,
+    pub end_position: i64,
+    /// Last position whose work is done, `None` before the first batch
+    pub current_position: Option<i64>,
+    pub events_stored: i64,
+    pub events_skipped: i64,
+    pub status: String,
     pub error: Option<String>,
-}
\ No newline at end of file
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+/// A logged moderation action of an agent
+#[derive(Clone, Debug, Serialize)]
+pub struct ModerationEvent {
+    pub id: i64,
+    pub chat_id: String,
+    pub telegram_id: Option<String>,
+    pub action: String,
+    pub details: Option<String>,
+    /// Trace id of the chain event that caused the action, if one did
+    pub trace_id: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// A Telegram user seen in an agent's group, with the wallet they verified on the agent's chain
+#[derive(Clone, Debug)]
+pub struct GroupMember {
+    pub telegram_id: String,
+    pub username: Option<String>,
+    pub address: Option<String>,
+    /// Shares of the agent's subject held by `address`
+    pub share_amount: Option<BigDecimal>,
+    pub is_banned: bool,
+    pub joined_at: Option<OffsetDateTime>,
+    pub left_at: Option<OffsetDateTime>,
+    pub verified_at: Option<OffsetDateTime>,
+}
+
+/// Raw payload kept with a stored trade event
+#[derive(Clone, Debug)]
+pub struct RawTradeEvent {
+    pub id: i64,
+    /// Event id as reported by the chain, e.g. the JSON of a Sui `EventID`
+    pub event_id: Option<String>,
+    pub bcs: String,
+    pub bcs_encoding: String,
+}
+
+/// Raw payload of an event as delivered by the chain's RPC, stored with its decoding
+#[derive(Clone, Debug)]
+pub struct RawEventPayload {
+    /// Event id as reported by the chain, e.g. the JSON of a Sui `EventID`
+    pub event_id: String,
+    pub bcs: String,
+    pub bcs_encoding: String,
+}
+
+/// Raw payload of a trade event the parser could not decode
+#[derive(Clone, Debug)]
+pub struct UndecodedTradeEvent {
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_time: Option<OffsetDateTime>,
+    pub event_id: Option<String>,
+    pub bcs: String,
+    pub bcs_encoding: String,
+    /// Why decoding failed when the event was synced
+    pub error: String,
+}
+
+/// Discord server an agent gates, with the role given to holders
+#[derive(Clone, Debug)]
+pub struct DiscordServer {
+    pub agent_name: String,
+    pub bot_token: String,
+    pub guild_id: String,
+    pub role_id: String,
+    pub invite_channel_id: String,
+}
+
+/// A Discord user's linked wallet with the gate of the agent whose server they joined
+#[derive(Clone, Debug)]
+pub struct DiscordGate {
+    pub server: DiscordServer,
+    pub discord_id: String,
+    pub address: String,
+    pub has_role: bool,
+    pub min_shares: BigDecimal,
+    pub subject_rule: SubjectRule,
+}
@@ -1,14 +1,24 @@
-use sqlx::{PgPool, types::BigDecimal};
+use sqlx::{PgConnection, PgPool, types::BigDecimal};
 use std::str::FromStr;
 use ethers::prelude::*;
 use anyhow;
-use crate::db::models::UserShares;
+use time::{Date, OffsetDateTime};
+use tracing::warn;
+use crate::block_chain::ChainType;
+use crate::metrics;
+use crate::enforcement::{EnforcementMode, SubjectRule};
+use crate::db::retention::AGENT_TABLES;
+use crate::routes::auth::KeyRole;
+use crate::db::models::{
+    AgentDeletion, ApiKeyInfo, AuthenticatedKey, BoundHolding, DailySubjectFees, DiscordGate, DiscordServer, DueBotMessage, DueEscalation, DueOnboardingDelivery, DueWebhookDelivery, EnforcementEvent, EvmChain, EnforcementLatencyStats, EventLocation, GroupBot, GroupHolding, GroupMember, HeldAction, JobRun, LeaderboardEntry, ModerationEvent, NewEscalation, NewHeldAction, NewOnboardingStep, NewTradeEvent, OnboardingStep, PendingBinding, PendingVerification, QualifyingGroup, RawEventPayload, RawTradeEvent, ReconcileTarget, SubjectHolder, SubjectPrice, SubjectTradeStats, SyncCursor, SyncPosition, TelegramErrorSummary, TradeEventRecord, UndecodedTradeEvent, UnsettledVerification, UserBinding, UserShares,
+    VerificationSession, WebhookInfo,
+};
 
 // Get the last synchronized block number
-pub async fn get_last_synced_block(pool: &PgPool, start_block: u64, chain_type: &str) -> Result<u64, sqlx::Error> {
+pub async fn get_last_synced_block(pool: &PgPool, start_block: u64, chain_type: ChainType) -> Result<u64, sqlx::Error> {
     let record = sqlx::query!(
         "SELECT last_synced_block FROM sync_status WHERE chain_type = $1 ORDER BY id DESC LIMIT 1",
-        chain_type
+        chain_type.as_str()
     )
     .fetch_optional(pool)
     .await?;
@@ -20,7 +30,7 @@ pub async fn get_last_synced_block(pool: &PgPool, start_block: u64, chain_type:
             sqlx::query!(
                 "INSERT INTO sync_status (last_synced_block, chain_type) VALUES ($1, $2)",
                 start_block as i64,
-                chain_type
+                chain_type.as_str()
             )
             .execute(pool)
             .await?;
@@ -34,11 +44,11 @@ pub async fn get_last_synced_block(pool: &PgPool, start_block: u64, chain_type:
 pub async fn get_last_synced_block_with_metadata(
     pool: &PgPool, 
     start_block: u64, 
-    chain_type: &str
+    chain_type: ChainType
 ) -> Result<(u64, Option<String>), sqlx::Error> {
     let record = sqlx::query!(
         "SELECT last_synced_block, metadata FROM sync_status WHERE chain_type = $1 ORDER BY id DESC LIMIT 1",
-        chain_type
+        chain_type.as_str()
     )
     .fetch_optional(pool)
     .await?;
@@ -50,7 +60,7 @@ pub async fn get_last_synced_block_with_metadata(
             sqlx::query!(
                 "INSERT INTO sync_status (last_synced_block, chain_type) VALUES ($1, $2)",
                 start_block as i64,
-                chain_type
+                chain_type.as_str()
             )
             .execute(pool)
             .await?;
@@ -61,50 +71,196 @@ pub async fn get_last_synced_block_with_metadata(
 }
 
 // Update the last synchronized block number
-pub async fn update_last_synced_block(pool: &PgPool, block_number: u64, chain_type: &str) -> Result<(), sqlx::Error> {
+pub async fn update_last_synced_block(pool: &PgPool, block_number: u64, chain_type: ChainType) -> Result<(), sqlx::Error> {
     sqlx::query!(
         "UPDATE sync_status SET last_synced_block = $1 WHERE chain_type = $2 AND id = (SELECT id FROM sync_status WHERE chain_type = $2 ORDER BY id DESC LIMIT 1)",
         block_number as i64,
-        chain_type
+        chain_type.as_str()
     )
     .execute(pool)
     .await?;
     
+    metrics::record_sync_progress(chain_type, block_number);
     Ok(())
 }
 
-// Process buy trade
+// Save sync progress with the hash of the last synced block, keeping earlier rows as reorg history
+pub async fn record_synced_block(pool: &PgPool, block_number: u64, block_hash: &str, chain_type: ChainType) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO sync_status (last_synced_block, chain_type, block_hash) VALUES ($1, $2, $3)",
+        block_number as i64,
+        chain_type.as_str(),
+        block_hash
+    )
+    .execute(pool)
+    .await?;
+
+    metrics::record_sync_progress(chain_type, block_number);
+    Ok(())
+}
+
+// Latest sync progress of a chain without creating it, None before its first sync
+pub async fn get_sync_position(pool: &PgPool, chain_type: ChainType) -> Result<Option<SyncPosition>, sqlx::Error> {
+    sqlx::query_as!(
+        SyncPosition,
+        "SELECT last_synced_block, metadata, updated_at FROM sync_status WHERE chain_type = $1 ORDER BY id DESC LIMIT 1",
+        chain_type.as_str()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Saved cursor of a chain indexed by event cursor, None before its first page was synced
+pub async fn get_cursor(pool: &PgPool, chain_type: ChainType) -> Result<Option<SyncCursor>, sqlx::Error> {
+    sqlx::query_as!(
+        SyncCursor,
+        "SELECT cursor_json, updated_at FROM sync_cursors WHERE chain_type = $1",
+        chain_type.as_str()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Save the cursor a chain's sync resumes from
+pub async fn set_cursor(pool: &PgPool, chain_type: ChainType, cursor_json: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO sync_cursors (chain_type, cursor_json) VALUES ($1, $2)
+         ON CONFLICT (chain_type) DO UPDATE SET cursor_json = $2, updated_at = CURRENT_TIMESTAMP",
+        chain_type.as_str(),
+        cursor_json
+    )
+    .execute(pool)
+    .await?;
+
+    metrics::record_cursor_progress(chain_type);
+    Ok(())
+}
+
+// EVM networks configured in the database
+pub async fn get_evm_chains(pool: &PgPool) -> Result<Vec<EvmChain>, sqlx::Error> {
+    sqlx::query_as!(
+        EvmChain,
+        r#"SELECT chain_type as "chain_type: ChainType", rpc_url, ws_url, contract_address, start_block,
+               confirmations, share_decimals, price_id
+           FROM evm_chains
+           ORDER BY chain_type"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Most recent synced blocks with their hashes, newest first
+pub async fn get_synced_block_hashes(pool: &PgPool, chain_type: ChainType, limit: i64) -> Result<Vec<(u64, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT DISTINCT ON (last_synced_block) last_synced_block, block_hash as "block_hash!"
+           FROM sync_status
+           WHERE chain_type = $1 AND block_hash IS NOT NULL
+           ORDER BY last_synced_block DESC, id DESC
+           LIMIT $2"#,
+        chain_type.as_str(),
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.last_synced_block as u64, row.block_hash)).collect())
+}
+
+// Undo the trade events of blocks after `fork_block` that a reorg orphaned: their share and
+// fee effects are reverted and sync progress moves back to the fork, all or nothing.
+// Returns the new balance of every affected trader and subject
+pub async fn rollback_trade_events(
+    pool: &PgPool,
+    chain_type: ChainType,
+    fork_block: u64,
+    fork_hash: &str,
+) -> Result<Vec<(String, String, BigDecimal)>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let balances = sqlx::query!(
+        r#"WITH removed AS (
+               DELETE FROM trade_events WHERE chain_type = $1 AND block_number > $2
+               RETURNING trader, subject, is_buy, share_amount, protocol_fee, subject_fee,
+                         (COALESCE(block_time, created_at) AT TIME ZONE 'UTC')::date AS day
+           ),
+           fees AS (
+               UPDATE subject_fees f
+               SET protocol_fee = f.protocol_fee - r.protocol_fee,
+                   subject_fee = f.subject_fee - r.subject_fee,
+                   trade_count = f.trade_count - r.trades,
+                   updated_at = CURRENT_TIMESTAMP
+               FROM (SELECT subject, day, SUM(protocol_fee) AS protocol_fee,
+                            SUM(subject_fee) AS subject_fee, COUNT(*) AS trades
+                     FROM removed GROUP BY subject, day) r
+               WHERE f.subject = r.subject AND f.chain_type = $1 AND f.day = r.day
+           )
+           UPDATE trades t
+           SET share_amount = t.share_amount - r.delta
+           FROM (SELECT trader, subject, SUM(CASE WHEN is_buy THEN share_amount ELSE -share_amount END) AS delta
+                 FROM removed GROUP BY trader, subject) r
+           WHERE t.trader = r.trader AND t.subject = r.subject AND t.chain_type = $1
+           RETURNING t.trader as "trader!", t.subject as "subject!", t.share_amount as "share_amount!""#,
+        chain_type.as_str(),
+        fork_block as i64
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM sync_status WHERE chain_type = $1 AND last_synced_block > $2",
+        chain_type.as_str(),
+        fork_block as i64
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO sync_status (last_synced_block, chain_type, block_hash) VALUES ($1, $2, $3)",
+        fork_block as i64,
+        chain_type.as_str(),
+        fork_hash
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    metrics::record_sync_progress(chain_type, fork_block);
+    Ok(balances.into_iter().map(|row| (row.trader, row.subject, row.share_amount)).collect())
+}
+
+// Process buy trade, returns the trader's new balance
 pub async fn process_buy_trade(
-    pool: &PgPool, 
+    conn: &mut PgConnection, 
     trader: String, 
     subject: String, 
     share_amount: BigDecimal,
-    chain_type: &str
-) -> anyhow::Result<()> {
-    sqlx::query!(
+    chain_type: ChainType
+) -> anyhow::Result<BigDecimal> {
+    let record = sqlx::query!(
         "INSERT INTO trades (trader, subject, share_amount, chain_type) 
         VALUES ($1, $2, $3, $4) 
         ON CONFLICT (trader, subject, chain_type) 
-        DO UPDATE SET share_amount = trades.share_amount + $3",
+        DO UPDATE SET share_amount = trades.share_amount + $3
+        RETURNING share_amount",
         trader,
         subject,
         share_amount,
-        chain_type
+        chain_type.as_str()
     )
-    .execute(pool)
+    .fetch_one(conn)
     .await?;
     
-    Ok(())
+    Ok(record.share_amount)
 }
 
-// Process sell trade
+// Process sell trade, returns the trader's new balance if they held any
 pub async fn process_sell_trade(
-    pool: &PgPool, 
+    conn: &mut PgConnection, 
     trader: String, 
     subject: String, 
     share_amount: BigDecimal,
-    chain_type: &str
-) -> anyhow::Result<(bool, Option<String>)> {
+    chain_type: ChainType
+) -> anyhow::Result<Option<BigDecimal>> {
     let ret = sqlx::query!(
         "UPDATE trades SET share_amount = share_amount - $1 
         WHERE trader = $2 AND subject = $3 AND chain_type = $4
@@ -112,35 +268,15 @@ pub async fn process_sell_trade(
         share_amount,
         trader,
         subject,
-        chain_type
+        chain_type.as_str()
     )
-    .fetch_optional(pool)
+    .fetch_optional(conn)
     .await?;
     
-    match ret {
-        Some(record) => {
-            // Check if share_amount is 0
-            if record.share_amount == 0.into() {
-                // Get user's Telegram ID
-                let telegram_id = sqlx::query!(
-                    "SELECT telegram_id FROM user_mappings WHERE address = $1 AND chain_type = $2",
-                    trader,
-                    chain_type
-                )
-                .fetch_optional(pool)
-                .await?;
-                
-                if let Some(user_record) = telegram_id {
-                    return Ok((true, Some(user_record.telegram_id)));
-                }
-            }
-            Ok((false, None))
-        },
-        None => {
-            println!("Trade record not found: trader={}, subject={}, chain={}", trader, subject, chain_type);
-            Ok((false, None))
-        }
+    if ret.is_none() {
+        warn!("Trade record not found: trader={}, subject={}, chain={}", trader, subject, chain_type);
     }
+    Ok(ret.map(|record| record.share_amount))
 }
 
 // Get user's shares for a subject
@@ -148,13 +284,13 @@ pub async fn get_user_subject_shares(
     pool: &PgPool,
     trader: &str,
     subject: &str,
-    chain_type: &str
+    chain_type: ChainType
 ) -> Result<BigDecimal, sqlx::Error> {
     let record = sqlx::query!(
         "SELECT share_amount FROM trades WHERE trader = $1 AND subject = $2 AND chain_type = $3",
         trader,
         subject,
-        chain_type
+        chain_type.as_str()
     )
     .fetch_optional(pool)
     .await?;
@@ -168,13 +304,13 @@ pub async fn get_user_subject_shares(
 pub async fn get_user_shares(
     pool: &PgPool,
     trader: &str,
-    chain_type: &str
+    chain_type: ChainType
 ) -> Result<Vec<UserShares>, sqlx::Error> {
     let rows = sqlx::query_as!(
         UserShares,
-        "SELECT trader, subject, share_amount, chain_type FROM trades WHERE trader = $1 AND chain_type = $2",
+        "SELECT trader, subject, share_amount, chain_type as \"chain_type: ChainType\" FROM trades WHERE trader = $1 AND chain_type = $2",
         trader,
-        chain_type
+        chain_type.as_str()
     )
     .fetch_all(pool)
     .await?;
@@ -182,12 +318,33 @@ pub async fn get_user_shares(
     Ok(rows)
 }
 
+// Stored balances of (subject, trader) pairs on a chain, pairs without a trades row are left out
+pub async fn get_trade_balances(
+    pool: &PgPool,
+    chain_type: ChainType,
+    subjects: &[String],
+    traders: &[String],
+) -> Result<Vec<(String, String, BigDecimal)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT t.subject, t.trader, t.share_amount
+           FROM UNNEST($1::varchar[], $2::varchar[]) AS q(subject, trader)
+           JOIN trades t ON t.subject = q.subject AND t.trader = q.trader AND t.chain_type = $3"#,
+        subjects,
+        traders,
+        chain_type.as_str()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.subject, row.trader, row.share_amount)).collect())
+}
+
 // Update last synchronized block info with metadata
 pub async fn update_last_synced_block_with_metadata(
     pool: &PgPool, 
     block_number: u64, 
     metadata: String,
-    chain_type: &str
+    chain_type: ChainType
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         "UPDATE sync_status 
@@ -197,10 +354,2492 @@ pub async fn update_last_synced_block_with_metadata(
          )",
         block_number as i64,
         metadata,
-        chain_type
+        chain_type.as_str()
     )
     .execute(pool)
     .await?;
     
+    metrics::record_sync_progress(chain_type, block_number);
+    Ok(())
+}
+
+// Create a pending verification session that expires after ttl_secs
+pub async fn create_verification_session(
+    pool: &PgPool,
+    session_id: &str,
+    telegram_id: &str,
+    chat_id: &str,
+    chain_type: ChainType,
+    ttl_secs: i64,
+) -> Result<VerificationSession, sqlx::Error> {
+    sqlx::query_as!(
+        VerificationSession,
+        "INSERT INTO verification_sessions (id, telegram_id, chat_id, chain_type, expires_at)
+         VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5::float8))
+         RETURNING id, telegram_id, chat_id, chain_type as \"chain_type: ChainType\", status, created_at, expires_at, completed_at",
+        session_id,
+        telegram_id,
+        chat_id,
+        chain_type.as_str(),
+        ttl_secs as f64
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_verification_session(
+    pool: &PgPool,
+    session_id: &str,
+) -> Result<Option<VerificationSession>, sqlx::Error> {
+    sqlx::query_as!(
+        VerificationSession,
+        "SELECT id, telegram_id, chat_id, chain_type as \"chain_type: ChainType\", status, created_at, expires_at, completed_at
+         FROM verification_sessions WHERE id = $1",
+        session_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Move a pending session to its final status
+pub async fn finish_verification_session(
+    pool: &PgPool,
+    session_id: &str,
+    status: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE verification_sessions SET status = $2, completed_at = NOW()
+         WHERE id = $1 AND status = 'pending'",
+        session_id,
+        status
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Issue a one-time challenge nonce, returns its expiry
+pub async fn create_challenge(
+    pool: &PgPool,
+    nonce: &str,
+    telegram_id: &str,
+    chat_id: &str,
+    chain_type: ChainType,
+    ttl_secs: i64,
+) -> Result<OffsetDateTime, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO challenges (nonce, telegram_id, chat_id, chain_type, expires_at)
+         VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5::float8))
+         RETURNING expires_at",
+        nonce,
+        telegram_id,
+        chat_id,
+        chain_type.as_str(),
+        ttl_secs as f64
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Atomically consume an unexpired challenge issued to this user and chat, false if none matches
+pub async fn consume_challenge(
+    pool: &PgPool,
+    nonce: &str,
+    telegram_id: &str,
+    chat_id: &str,
+    chain_type: ChainType,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE challenges SET consumed_at = NOW()
+         WHERE nonce = $1 AND telegram_id = $2 AND chat_id = $3 AND chain_type = $4
+           AND consumed_at IS NULL AND expires_at > NOW()",
+        nonce,
+        telegram_id,
+        chat_id,
+        chain_type.as_str()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Retire a failed or expired session whose link was replaced, false if it cannot be renewed
+pub async fn mark_session_renewed(pool: &PgPool, session_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE verification_sessions SET status = 'renewed', completed_at = NOW()
+         WHERE id = $1 AND (status = 'failed' OR (status = 'pending' AND expires_at < NOW()))",
+        session_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Append an entry to the moderation log
+pub async fn record_moderation_event(
+    pool: &PgPool,
+    agent_name: &str,
+    chat_id: &str,
+    telegram_id: Option<&str>,
+    action: &str,
+    details: Option<String>,
+    trace_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO moderation_events (agent_name, chat_id, telegram_id, action, details, trace_id)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+        agent_name,
+        chat_id,
+        telegram_id,
+        action,
+        details,
+        trace_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+
+// Schedule a bot message for deletion once its TTL has passed
+pub async fn track_bot_message(
+    pool: &PgPool,
+    agent_name: &str,
+    chat_id: &str,
+    message_id: i32,
+    ttl_secs: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO bot_messages (agent_name, chat_id, message_id, delete_after)
+         VALUES ($1, $2, $3, NOW() + make_interval(secs => $4::float8))",
+        agent_name,
+        chat_id,
+        message_id,
+        ttl_secs as f64
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Tracked bot messages whose TTL has passed, with the token of the bot that sent them
+pub async fn get_due_bot_messages(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<DueBotMessage>, sqlx::Error> {
+    sqlx::query_as!(
+        DueBotMessage,
+        "SELECT m.id, m.agent_name, m.chat_id, m.message_id, b.bot_token
+         FROM bot_messages m
+         JOIN telegram_bots b ON b.agent_name = m.agent_name
+         WHERE m.delete_after <= NOW()
+         ORDER BY m.delete_after
+         LIMIT $1",
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Stop tracking a bot message
+pub async fn remove_bot_message(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM bot_messages WHERE id = $1", id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Add the fees of one trade to the subject's total for the current day
+pub async fn record_subject_fees(
+    conn: &mut PgConnection,
+    subject: &str,
+    protocol_fee: BigDecimal,
+    subject_fee: BigDecimal,
+    chain_type: ChainType,
+    day: Date,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO subject_fees (subject, chain_type, day, protocol_fee, subject_fee, trade_count)
+         VALUES ($1, $2, $5, $3, $4, 1)
+         ON CONFLICT (subject, chain_type, day)
+         DO UPDATE SET protocol_fee = subject_fees.protocol_fee + $3,
+                       subject_fee = subject_fees.subject_fee + $4,
+                       trade_count = subject_fees.trade_count + 1,
+                       updated_at = CURRENT_TIMESTAMP",
+        subject,
+        chain_type.as_str(),
+        protocol_fee,
+        subject_fee,
+        day
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Take a trade's fees back out of a subject's daily totals
+pub async fn revert_subject_fees(
+    conn: &mut PgConnection,
+    subject: &str,
+    protocol_fee: BigDecimal,
+    subject_fee: BigDecimal,
+    chain_type: ChainType,
+    day: Date,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE subject_fees
+         SET protocol_fee = protocol_fee - $3, subject_fee = subject_fee - $4, trade_count = trade_count - 1, updated_at = CURRENT_TIMESTAMP
+         WHERE subject = $1 AND chain_type = $2 AND day = $5",
+        subject,
+        chain_type.as_str(),
+        protocol_fee,
+        subject_fee,
+        day
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Daily fee totals for a subject between two dates (inclusive)
+pub async fn get_subject_fees(
+    pool: &PgPool,
+    subject: &str,
+    chain_type: ChainType,
+    from: Date,
+    to: Date,
+) -> Result<Vec<DailySubjectFees>, sqlx::Error> {
+    sqlx::query_as!(
+        DailySubjectFees,
+        "SELECT day, protocol_fee, subject_fee, trade_count FROM subject_fees
+         WHERE subject = $1 AND chain_type = $2 AND day BETWEEN $3 AND $4
+         ORDER BY day",
+        subject,
+        chain_type.as_str(),
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Fee totals of a subject per day rebuilt from its trade events, leaving out suspected wash trades.
+// Days are the UTC days of the events' blocks, as for subject_fees
+pub async fn get_subject_fees_without_wash_trades(
+    pool: &PgPool,
+    subject: &str,
+    chain_type: ChainType,
+    from: Date,
+    to: Date,
+) -> Result<Vec<DailySubjectFees>, sqlx::Error> {
+    sqlx::query_as!(
+        DailySubjectFees,
+        r#"SELECT (COALESCE(block_time, created_at) AT TIME ZONE 'UTC')::date as "day!",
+                  SUM(protocol_fee) as "protocol_fee!", SUM(subject_fee) as "subject_fee!", COUNT(*) as "trade_count!"
+           FROM trade_events
+           WHERE subject = $1 AND chain_type = $2 AND wash_reason IS NULL
+             AND (COALESCE(block_time, created_at) AT TIME ZONE 'UTC')::date BETWEEN $3 AND $4
+           GROUP BY 1
+           ORDER BY 1"#,
+        subject,
+        chain_type.as_str(),
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Groups whose subject a Telegram user's bound addresses hold or have held shares of
+pub async fn get_group_holdings(
+    pool: &PgPool,
+    telegram_id: &str,
+) -> Result<Vec<GroupHolding>, sqlx::Error> {
+    sqlx::query_as!(
+        GroupHolding,
+        r#"SELECT b.agent_name, b.chat_group_id, b.chain_type as "chain_type: ChainType",
+                  b.subject_address, m.address, t.share_amount,
+                  COALESCE((SELECT MAX(d.decimals) FROM share_decimals d WHERE d.chain_type = b.chain_type), 0) as "share_decimals!",
+                  b.min_shares
+           FROM user_mappings m
+           JOIN trades t ON t.trader = m.address AND t.chain_type = m.chain_type
+           JOIN telegram_bots b ON b.subject_address = t.subject AND b.chain_type = t.chain_type
+           WHERE m.telegram_id = $1
+           ORDER BY b.agent_name"#,
+        telegram_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Append a decoded trade event to the audit log with the raw payload it was decoded from, if
+// any, returns its id, or None if the event at this position of the transaction was already stored
+pub async fn record_trade_event(
+    conn: &mut PgConnection,
+    chain_type: ChainType,
+    location: &EventLocation,
+    event: &NewTradeEvent,
+    raw: Option<&RawEventPayload>,
+    trace_id: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO trade_events (chain_type, block_number, tx_hash, log_index, trader, subject, is_buy,
+                                   share_amount, eth_amount, protocol_fee, subject_fee, supply, block_time, trace_id,
+                                   raw_event_id, raw_bcs, raw_bcs_encoding)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+         ON CONFLICT (chain_type, tx_hash, log_index) DO NOTHING
+         RETURNING id",
+        chain_type.as_str(),
+        location.block_number,
+        location.tx_hash,
+        location.log_index,
+        event.trader,
+        event.subject,
+        event.is_buy,
+        event.share_amount,
+        event.eth_amount,
+        event.protocol_fee,
+        event.subject_fee,
+        event.supply,
+        location.block_time,
+        trace_id,
+        raw.map(|raw| raw.event_id.as_str()),
+        raw.map(|raw| raw.bcs.as_str()),
+        raw.map(|raw| raw.bcs_encoding.as_str())
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+// Keep the raw payload of a trade event the parser could not decode, for a replay
+pub async fn record_undecoded_trade_event(
+    pool: &PgPool,
+    chain_type: ChainType,
+    location: &EventLocation,
+    raw: &RawEventPayload,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO undecoded_trade_events (chain_type, tx_hash, log_index, block_time, raw_event_id, raw_bcs, raw_bcs_encoding, error)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         ON CONFLICT (chain_type, tx_hash, log_index) DO UPDATE SET error = EXCLUDED.error",
+        chain_type.as_str(),
+        location.tx_hash,
+        location.log_index,
+        location.block_time,
+        raw.event_id,
+        raw.bcs,
+        raw.bcs_encoding,
+        error
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Trade events of a chain kept undecoded, oldest first
+pub async fn get_undecoded_trade_events(pool: &PgPool, chain_type: ChainType) -> Result<Vec<UndecodedTradeEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        UndecodedTradeEvent,
+        "SELECT tx_hash, log_index, block_time, raw_event_id as event_id, raw_bcs as bcs, raw_bcs_encoding as bcs_encoding, error
+         FROM undecoded_trade_events
+         WHERE chain_type = $1
+         ORDER BY created_at, tx_hash, log_index",
+        chain_type.as_str()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Forget an undecoded trade event once it was stored in trade_events
+pub async fn delete_undecoded_trade_event(pool: &PgPool, chain_type: ChainType, tx_hash: &str, log_index: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM undecoded_trade_events WHERE chain_type = $1 AND tx_hash = $2 AND log_index = $3",
+        chain_type.as_str(),
+        tx_hash,
+        log_index
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Stored trade events of a chain with a raw payload, in id order after `after_id`
+pub async fn get_raw_trade_events(
+    pool: &PgPool,
+    chain_type: ChainType,
+    after_id: i64,
+    limit: i64,
+) -> Result<Vec<RawTradeEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        RawTradeEvent,
+        r#"SELECT id, raw_event_id as event_id, raw_bcs as "bcs!", raw_bcs_encoding as "bcs_encoding!"
+           FROM trade_events
+           WHERE chain_type = $1 AND id > $2 AND raw_bcs IS NOT NULL AND raw_bcs_encoding IS NOT NULL
+           ORDER BY id
+           LIMIT $3"#,
+        chain_type.as_str(),
+        after_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// A stored trade event, locked until the transaction ends
+pub async fn get_trade_event_for_update(conn: &mut PgConnection, id: i64) -> Result<Option<TradeEventRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        TradeEventRecord,
+        r#"SELECT id, chain_type as "chain_type: ChainType", block_number, tx_hash, log_index, trader, subject,
+                  is_buy, share_amount, eth_amount, protocol_fee, subject_fee, supply, block_time, wash_reason, trace_id,
+                  created_at
+           FROM trade_events
+           WHERE id = $1
+           FOR UPDATE"#,
+        id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+// Overwrite the decoded fields of a stored trade event
+pub async fn update_trade_event(conn: &mut PgConnection, id: i64, event: &NewTradeEvent) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE trade_events
+         SET trader = $2, subject = $3, is_buy = $4, share_amount = $5, eth_amount = $6, protocol_fee = $7, subject_fee = $8, supply = $9
+         WHERE id = $1",
+        id,
+        event.trader,
+        event.subject,
+        event.is_buy,
+        event.share_amount,
+        event.eth_amount,
+        event.protocol_fee,
+        event.subject_fee,
+        event.supply
+    )
+    .execute(conn)
+    .await?;
+
     Ok(())
-}
\ No newline at end of file
+}
+
+// Tag a newly recorded trade event and the trades it pairs with as suspected wash trades.
+// Trades of the same subject count as nearby within `block_window` blocks, or `window_secs`
+// seconds where either has no block number (Sui). Returns the number of events tagged
+pub async fn tag_wash_trades(
+    conn: &mut PgConnection,
+    id: i64,
+    block_window: i64,
+    window_secs: f64,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "WITH new AS (SELECT * FROM trade_events WHERE id = $1),
+         nearby AS (
+             SELECT t.* FROM trade_events t, new n
+             WHERE t.chain_type = n.chain_type AND t.subject = n.subject AND t.id <> n.id
+               AND CASE WHEN t.block_number IS NOT NULL AND n.block_number IS NOT NULL
+                        THEN ABS(t.block_number - n.block_number) <= $2
+                        ELSE ABS(EXTRACT(EPOCH FROM COALESCE(t.block_time, t.created_at) - COALESCE(n.block_time, n.created_at))) <= $3
+                   END
+         ),
+         -- The trader bought and sold the subject within the window
+         round_trips AS (
+             SELECT w.id FROM nearby w, new n WHERE w.trader = n.trader AND w.is_buy <> n.is_buy
+         ),
+         -- Another wallet took the other side of the same amount, and the two also traded it the other way round
+         mirrors AS (
+             SELECT w.id FROM nearby w, new n
+             WHERE w.trader <> n.trader AND w.is_buy <> n.is_buy AND w.share_amount = n.share_amount
+               AND EXISTS (
+                   SELECT 1 FROM nearby a JOIN nearby b ON b.share_amount = a.share_amount
+                   WHERE a.trader = n.trader AND a.is_buy = w.is_buy AND b.trader = w.trader AND b.is_buy = n.is_buy
+               )
+         ),
+         tagged AS (
+             SELECT id, 'self_round_trip' AS reason FROM round_trips
+             UNION ALL SELECT $1, 'self_round_trip' WHERE EXISTS (SELECT 1 FROM round_trips)
+             UNION ALL SELECT id, 'mirrored_pair' FROM mirrors
+             UNION ALL SELECT $1, 'mirrored_pair' WHERE EXISTS (SELECT 1 FROM mirrors)
+         )
+         UPDATE trade_events t SET wash_reason = tagged.reason
+         FROM (SELECT DISTINCT ON (id) id, reason FROM tagged ORDER BY id, reason DESC) tagged
+         WHERE t.id = tagged.id AND t.wash_reason IS NULL",
+        id,
+        block_window,
+        window_secs
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Trade events of a chain in chain order, optionally filtered by trader and/or subject
+pub async fn get_trade_events(
+    pool: &PgPool,
+    chain_type: ChainType,
+    trader: Option<&str>,
+    subject: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<TradeEventRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        TradeEventRecord,
+        r#"SELECT id, chain_type as "chain_type: ChainType", block_number, tx_hash, log_index, trader, subject,
+                  is_buy, share_amount, eth_amount, protocol_fee, subject_fee, supply, block_time, wash_reason, trace_id,
+                  created_at
+           FROM trade_events
+           WHERE chain_type = $1
+             AND ($2::text IS NULL OR trader = $2)
+             AND ($3::text IS NULL OR subject = $3)
+           ORDER BY COALESCE(block_time, created_at), block_number, log_index, id
+           LIMIT $4 OFFSET $5"#,
+        chain_type.as_str(),
+        trader,
+        subject,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Last recorded trade of each of the given subjects, subjects never traded are left out
+pub async fn get_latest_subject_prices(
+    pool: &PgPool,
+    chain_type: ChainType,
+    subjects: &[String],
+) -> Result<Vec<SubjectPrice>, sqlx::Error> {
+    sqlx::query_as!(
+        SubjectPrice,
+        r#"SELECT DISTINCT ON (subject) subject, supply, share_amount, eth_amount, protocol_fee, subject_fee,
+                  COALESCE(block_time, created_at) as "traded_at!"
+           FROM trade_events
+           WHERE chain_type = $1 AND subject = ANY($2)
+           ORDER BY subject, block_number DESC NULLS LAST, COALESCE(block_time, created_at) DESC, log_index DESC, id DESC"#,
+        chain_type.as_str(),
+        subjects
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Balance of a trader in a subject rebuilt from the recorded trade events
+pub async fn get_trade_events_balance(
+    pool: &PgPool,
+    trader: &str,
+    subject: &str,
+    chain_type: ChainType,
+) -> Result<BigDecimal, sqlx::Error> {
+    let record = sqlx::query!(
+        r#"SELECT COALESCE(SUM(CASE WHEN is_buy THEN share_amount ELSE -share_amount END), 0) AS "balance!"
+           FROM trade_events
+           WHERE trader = $1 AND subject = $2 AND chain_type = $3"#,
+        trader,
+        subject,
+        chain_type.as_str()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.balance)
+}
+
+// Page of current holders of a subject, largest (or smallest) balance first, and the total count
+pub async fn get_subject_holders(
+    pool: &PgPool,
+    subject: &str,
+    chain_type: ChainType,
+    ascending: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<SubjectHolder>, i64), sqlx::Error> {
+    let holders = sqlx::query_as!(
+        SubjectHolder,
+        r#"SELECT t.trader, t.share_amount, m.telegram_id as "telegram_id?"
+           FROM trades t
+           LEFT JOIN user_mappings m ON m.address = t.trader AND m.chain_type = t.chain_type
+           WHERE t.subject = $1 AND t.chain_type = $2 AND t.share_amount > 0
+           ORDER BY CASE WHEN $3 THEN t.share_amount END ASC,
+                    CASE WHEN NOT $3 THEN t.share_amount END DESC,
+                    t.trader
+           LIMIT $4 OFFSET $5"#,
+        subject,
+        chain_type.as_str(),
+        ascending,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM trades WHERE subject = $1 AND chain_type = $2 AND share_amount > 0"#,
+        subject,
+        chain_type.as_str()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((holders, total))
+}
+
+// Largest holders of a subject, holders with equal amounts share a rank. With `exclude_wash_traders`
+// holders with a suspected wash trade of the subject are left out
+// Trade totals of a subject since `since`, leaving out suspected wash trades if asked
+pub async fn get_subject_trade_stats(
+    pool: &PgPool,
+    subject: &str,
+    chain_type: ChainType,
+    since: OffsetDateTime,
+    exclude_wash_trades: bool,
+) -> Result<SubjectTradeStats, sqlx::Error> {
+    sqlx::query_as!(
+        SubjectTradeStats,
+        r#"SELECT $3::timestamptz as "start!",
+                  COUNT(*) FILTER (WHERE is_buy) as "buy_count!",
+                  COUNT(*) FILTER (WHERE NOT is_buy) as "sell_count!",
+                  COALESCE(SUM(eth_amount), 0) as "volume!",
+                  COUNT(DISTINCT trader) as "unique_traders!"
+           FROM trade_events
+           WHERE chain_type = $2 AND subject = $1 AND COALESCE(block_time, created_at) >= $3
+             AND NOT ($4 AND wash_reason IS NOT NULL)"#,
+        subject,
+        chain_type.as_str(),
+        since,
+        exclude_wash_trades
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Trade totals of a subject per `bucket` ("hour" or "day") since `since`, oldest first.
+// Buckets without trades are included so the series has no gaps
+pub async fn get_subject_trade_series(
+    pool: &PgPool,
+    subject: &str,
+    chain_type: ChainType,
+    since: OffsetDateTime,
+    bucket: &str,
+    exclude_wash_trades: bool,
+) -> Result<Vec<SubjectTradeStats>, sqlx::Error> {
+    sqlx::query_as!(
+        SubjectTradeStats,
+        r#"SELECT b.start as "start!",
+                  COUNT(t.id) FILTER (WHERE t.is_buy) as "buy_count!",
+                  COUNT(t.id) FILTER (WHERE NOT t.is_buy) as "sell_count!",
+                  COALESCE(SUM(t.eth_amount), 0) as "volume!",
+                  COUNT(DISTINCT t.trader) as "unique_traders!"
+           FROM generate_series(date_trunc($4, $3::timestamptz), date_trunc($4, NOW()), ('1 ' || $4)::interval) AS b(start)
+           LEFT JOIN trade_events t
+             ON t.chain_type = $2 AND t.subject = $1
+            AND COALESCE(t.block_time, t.created_at) >= $3
+            AND date_trunc($4, COALESCE(t.block_time, t.created_at)) = b.start
+            AND NOT ($5 AND t.wash_reason IS NOT NULL)
+           GROUP BY b.start
+           ORDER BY b.start"#,
+        subject,
+        chain_type.as_str(),
+        since,
+        bucket,
+        exclude_wash_trades
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_subject_leaderboard(
+    pool: &PgPool,
+    subject: &str,
+    chain_type: ChainType,
+    limit: i64,
+    exclude_wash_traders: bool,
+) -> Result<Vec<LeaderboardEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        LeaderboardEntry,
+        r#"SELECT RANK() OVER (ORDER BY t.share_amount DESC) as "rank!", t.trader, t.share_amount,
+                  (SELECT u.username FROM user_mappings m JOIN telegram_users u ON u.telegram_id = m.telegram_id
+                   WHERE m.address = t.trader AND m.chain_type = t.chain_type LIMIT 1) as "telegram_username?"
+           FROM trades t
+           WHERE t.chain_type = $2 AND t.subject = $1 AND t.share_amount > 0
+             AND NOT ($4 AND EXISTS (
+                 SELECT 1 FROM trade_events e
+                 WHERE e.chain_type = t.chain_type AND e.subject = t.subject AND e.trader = t.trader AND e.wash_reason IS NOT NULL
+             ))
+           ORDER BY t.share_amount DESC, t.trader
+           LIMIT $3"#,
+        subject,
+        chain_type.as_str(),
+        limit,
+        exclude_wash_traders
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Remember the username of a Telegram user the bot saw, None when they have none
+pub async fn record_telegram_username(pool: &PgPool, telegram_id: &str, username: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO telegram_users (telegram_id, username) VALUES ($1, $2)
+         ON CONFLICT (telegram_id) DO UPDATE SET username = $2, updated_at = CURRENT_TIMESTAMP
+         WHERE telegram_users.username IS DISTINCT FROM $2",
+        telegram_id,
+        username
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Open a rejoin token for a kicked member, keeps the existing one if already open
+pub async fn create_rejoin_token(
+    conn: &mut PgConnection,
+    token: &str,
+    telegram_id: &str,
+    chat_id: &str,
+    chain_type: ChainType,
+    address: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO rejoin_tokens (token, telegram_id, chat_id, chain_type, address)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (telegram_id, chat_id) WHERE used_at IS NULL DO NOTHING",
+        token,
+        telegram_id,
+        chat_id,
+        chain_type.as_str(),
+        address
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Open rejoin token of a member, if they were kicked from the group
+pub async fn get_open_rejoin_token(
+    pool: &PgPool,
+    telegram_id: &str,
+    chat_id: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT token FROM rejoin_tokens WHERE telegram_id = $1 AND chat_id = $2 AND used_at IS NULL",
+        telegram_id,
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Drop a rejoin token whose kick did not go through
+pub async fn delete_rejoin_token(pool: &PgPool, token: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM rejoin_tokens WHERE token = $1 AND sent_at IS NULL", token)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Remember the invite link sent for a rejoin token
+pub async fn mark_rejoin_link_sent(pool: &PgPool, token: &str, invite_link: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE rejoin_tokens SET invite_link = $2, sent_at = NOW() WHERE token = $1",
+        token,
+        invite_link
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Close the rejoin token of a member who came back through their invite, false if none was sent
+pub async fn consume_rejoin_token(pool: &PgPool, telegram_id: &str, chat_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE rejoin_tokens SET used_at = NOW()
+         WHERE telegram_id = $1 AND chat_id = $2 AND used_at IS NULL AND sent_at IS NOT NULL",
+        telegram_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Count a failed Telegram call in the agent's error summary
+pub async fn record_telegram_error_kind(
+    pool: &PgPool,
+    agent_name: &str,
+    kind: &str,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO telegram_errors (agent_name, kind, count, last_error)
+         VALUES ($1, $2, 1, $3)
+         ON CONFLICT (agent_name, kind)
+         DO UPDATE SET count = telegram_errors.count + 1, last_error = $3, last_seen_at = NOW()",
+        agent_name,
+        kind,
+        error
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Error summaries of every agent, most recent first
+pub async fn get_telegram_error_summaries(pool: &PgPool) -> Result<Vec<TelegramErrorSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        TelegramErrorSummary,
+        "SELECT agent_name, kind, count, last_error, last_seen_at FROM telegram_errors ORDER BY last_seen_at DESC"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Flag an active agent as misconfigured, false if it was not active
+pub async fn mark_agent_misconfigured(pool: &PgPool, agent_name: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE telegram_bots SET status = 'misconfigured' WHERE agent_name = $1 AND status = 'active'",
+        agent_name
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Local balances of verified users in subjects that have a bot
+pub async fn get_reconcile_targets(pool: &PgPool, chain_type: ChainType) -> Result<Vec<ReconcileTarget>, sqlx::Error> {
+    sqlx::query_as!(
+        ReconcileTarget,
+        "SELECT t.trader, t.subject, t.share_amount
+         FROM user_mappings m
+         JOIN trades t ON t.trader = m.address AND t.chain_type = m.chain_type
+         JOIN telegram_bots b ON b.subject_address = t.subject AND b.chain_type = t.chain_type
+         WHERE m.chain_type = $1",
+        chain_type.as_str()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Overwrite a local balance with the on-chain value
+pub async fn set_trade_balance(
+    pool: &PgPool,
+    trader: &str,
+    subject: &str,
+    chain_type: ChainType,
+    share_amount: &BigDecimal,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE trades SET share_amount = $4
+         WHERE trader = $1 AND subject = $2 AND chain_type = $3",
+        trader,
+        subject,
+        chain_type.as_str(),
+        share_amount
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record how long a trade took to be enforced in Telegram
+pub async fn record_enforcement_latency(
+    pool: &PgPool,
+    chain_type: ChainType,
+    agent_name: &str,
+    action: &str,
+    latency_ms: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO enforcement_latencies (chain_type, agent_name, action, latency_ms) VALUES ($1, $2, $3, $4)",
+        chain_type.as_str(),
+        agent_name,
+        action,
+        latency_ms
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Latency percentiles and histogram per chain and agent over the last `hours`
+pub async fn get_enforcement_latency_stats(pool: &PgPool, hours: i64) -> Result<Vec<EnforcementLatencyStats>, sqlx::Error> {
+    sqlx::query_as!(
+        EnforcementLatencyStats,
+        r#"SELECT chain_type as "chain_type: ChainType", agent_name,
+                  COUNT(*) as "count!",
+                  percentile_cont(0.5) WITHIN GROUP (ORDER BY latency_ms) as "p50_ms!",
+                  percentile_cont(0.9) WITHIN GROUP (ORDER BY latency_ms) as "p90_ms!",
+                  percentile_cont(0.99) WITHIN GROUP (ORDER BY latency_ms) as "p99_ms!",
+                  MAX(latency_ms) as "max_ms!",
+                  COUNT(*) FILTER (WHERE latency_ms <= 1000) as "le_1s!",
+                  COUNT(*) FILTER (WHERE latency_ms <= 5000) as "le_5s!",
+                  COUNT(*) FILTER (WHERE latency_ms <= 15000) as "le_15s!",
+                  COUNT(*) FILTER (WHERE latency_ms <= 60000) as "le_60s!",
+                  COUNT(*) FILTER (WHERE latency_ms <= 300000) as "le_300s!",
+                  COUNT(*) FILTER (WHERE latency_ms <= 900000) as "le_900s!"
+           FROM enforcement_latencies
+           WHERE recorded_at > NOW() - make_interval(hours => $1::int)
+           GROUP BY chain_type, agent_name
+           ORDER BY chain_type, agent_name"#,
+        hours as i32
+    )
+    .fetch_all(pool)
+    .await
+}
+// Register an indexer on its first batch and lock its row until the transaction ends, returns the high-water mark
+pub async fn lock_ingest_source(conn: &mut PgConnection, source_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO ingest_sources (source_id) VALUES ($1) ON CONFLICT (source_id) DO NOTHING",
+        source_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query_scalar!(
+        "SELECT high_water_mark FROM ingest_sources WHERE source_id = $1 FOR UPDATE",
+        source_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+}
+
+// Move an indexer's high-water mark to the batch just applied
+pub async fn advance_ingest_source(conn: &mut PgConnection, source_id: &str, sequence: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE ingest_sources SET high_water_mark = $2, updated_at = NOW() WHERE source_id = $1",
+        source_id,
+        sequence
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// High-water mark of an indexer, 0 if it never pushed a batch
+pub async fn get_ingest_high_water_mark(pool: &PgPool, source_id: &str) -> Result<i64, sqlx::Error> {
+    let mark = sqlx::query_scalar!(
+        "SELECT high_water_mark FROM ingest_sources WHERE source_id = $1",
+        source_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(mark.unwrap_or(0))
+}
+
+// Bring stored share amounts of a chain in line with its configured decimals, true if rows were rescaled
+pub async fn rescale_share_decimals(
+    pool: &PgPool,
+    chain_type: ChainType,
+    contract_address: &str,
+    decimals: u32,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT rescale_share_decimals($1, $2, $3) as "rescaled!""#,
+        chain_type.as_str(),
+        contract_address,
+        decimals as i32
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Telegram user an address is bound to on a chain
+pub async fn get_address_binding(pool: &PgPool, address: &str, chain_type: ChainType) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT telegram_id FROM user_mappings WHERE address = $1 AND chain_type = $2",
+        address,
+        chain_type.as_str()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Remove the binding of an address to a Telegram user, false if it was not bound to them
+pub async fn delete_user_mapping(pool: &PgPool, address: &str, chain_type: ChainType, telegram_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM user_mappings WHERE address = $1 AND chain_type = $2 AND telegram_id = $3",
+        address,
+        chain_type.as_str(),
+        telegram_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Move a Telegram user's binding to a new address, keeping its ban state, false if the old one was not bound to them
+pub async fn rebind_user_mapping(
+    pool: &PgPool,
+    old_address: &str,
+    new_address: &str,
+    chain_type: ChainType,
+    telegram_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE user_mappings SET address = $2 WHERE address = $1 AND chain_type = $3 AND telegram_id = $4",
+        old_address,
+        new_address,
+        chain_type.as_str(),
+        telegram_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Onboarding sequence of an agent in order, with how often each step was delivered
+pub async fn get_onboarding_steps(pool: &PgPool, agent_name: &str) -> Result<Vec<OnboardingStep>, sqlx::Error> {
+    sqlx::query_as!(
+        OnboardingStep,
+        r#"SELECT s.id, s.position, s.message, s.delay_secs, s.pin,
+                  COUNT(d.id) FILTER (WHERE d.status = 'sent') as "sent!",
+                  COUNT(d.id) FILTER (WHERE d.status = 'failed') as "failed!",
+                  COUNT(d.id) FILTER (WHERE d.status = 'pending') as "pending!"
+           FROM onboarding_steps s
+           LEFT JOIN onboarding_deliveries d ON d.step_id = s.id
+           WHERE s.agent_name = $1
+           GROUP BY s.id
+           ORDER BY s.position"#,
+        agent_name
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Replace an agent's onboarding sequence, dropping the delivery history of the old one
+pub async fn replace_onboarding_steps(
+    pool: &PgPool,
+    agent_name: &str,
+    steps: &[NewOnboardingStep],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("DELETE FROM onboarding_deliveries WHERE agent_name = $1", agent_name)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM onboarding_steps WHERE agent_name = $1", agent_name)
+        .execute(&mut *tx)
+        .await?;
+
+    for (position, step) in steps.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO onboarding_steps (agent_name, position, message, delay_secs, pin) VALUES ($1, $2, $3, $4, $5)",
+            agent_name,
+            position as i32 + 1,
+            step.message,
+            step.delay_secs,
+            step.pin
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+// Queue an agent's onboarding sequence for a verified member, steps already queued for them are kept
+pub async fn schedule_onboarding(pool: &PgPool, agent_name: &str, telegram_id: &str, chat_id: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO onboarding_deliveries (step_id, agent_name, telegram_id, chat_id, due_at)
+         SELECT id, agent_name, $2, $3,
+                NOW() + make_interval(secs => (SUM(delay_secs) OVER (ORDER BY position))::float8)
+         FROM onboarding_steps
+         WHERE agent_name = $1
+         ON CONFLICT (step_id, telegram_id) DO NOTHING",
+        agent_name,
+        telegram_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Pending onboarding messages whose time has come, of agents whose bot is enabled
+pub async fn get_due_onboarding_deliveries(pool: &PgPool, limit: i64) -> Result<Vec<DueOnboardingDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        DueOnboardingDelivery,
+        "SELECT d.id, d.agent_name, d.telegram_id, d.chat_id, s.message, s.pin, b.bot_token, b.quiet_hours
+         FROM onboarding_deliveries d
+         JOIN onboarding_steps s ON s.id = d.step_id
+         JOIN telegram_bots b ON b.agent_name = d.agent_name
+         WHERE d.status = 'pending' AND d.due_at <= NOW() AND b.enabled
+         ORDER BY d.due_at
+         LIMIT $1",
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Hold a due onboarding message back until `until`, e.g. the end of its agent's quiet hours
+pub async fn defer_onboarding_delivery(pool: &PgPool, id: i64, until: OffsetDateTime) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE onboarding_deliveries SET due_at = $2 WHERE id = $1 AND status = 'pending'",
+        id,
+        until
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record the outcome of an onboarding message
+pub async fn finish_onboarding_delivery(pool: &PgPool, id: i64, error: Option<String>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE onboarding_deliveries
+         SET status = CASE WHEN $2::text IS NULL THEN 'sent' ELSE 'failed' END,
+             sent_at = CASE WHEN $2::text IS NULL THEN NOW() END,
+             error = $2
+         WHERE id = $1",
+        id,
+        error
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Addresses bound to a Telegram user on any chain
+pub async fn get_user_bindings(pool: &PgPool, telegram_id: &str) -> Result<Vec<UserBinding>, sqlx::Error> {
+    sqlx::query_as!(
+        UserBinding,
+        r#"SELECT address, chain_type as "chain_type: ChainType", is_banned
+           FROM user_mappings WHERE telegram_id = $1
+           ORDER BY chain_type, address"#,
+        telegram_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// State of a service flag, None if it was never set
+pub async fn get_service_flag(pool: &PgPool, name: &str) -> Result<Option<(bool, Option<String>, OffsetDateTime)>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT enabled, reason, updated_at FROM service_flags WHERE name = $1",
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| (row.enabled, row.reason, row.updated_at)))
+}
+
+// Turn a service flag on or off
+pub async fn set_service_flag(pool: &PgPool, name: &str, enabled: bool, reason: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO service_flags (name, enabled, reason) VALUES ($1, $2, $3)
+         ON CONFLICT (name) DO UPDATE SET enabled = $2, reason = $3, updated_at = NOW()",
+        name,
+        enabled,
+        reason
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Remember an enforcement action the kill switch kept from reaching Telegram
+pub async fn record_held_action(conn: &mut PgConnection, held: &NewHeldAction) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO held_enforcement_actions (chain_type, agent_name, chat_id, telegram_id, address, subject, action, balance)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        held.chain_type.as_str(),
+        held.agent_name,
+        held.chat_id,
+        held.telegram_id,
+        held.address,
+        held.subject,
+        held.action,
+        held.balance
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Start walking a holder up the escalation ladder, false if one is already running for them
+pub async fn start_escalation(conn: &mut PgConnection, escalation: &NewEscalation, first_delay_secs: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO enforcement_escalations (agent_name, chat_id, telegram_id, chain_type, address, subject, trace_id, next_run_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, NOW() + make_interval(secs => $8::float8))
+         ON CONFLICT (chat_id, telegram_id) WHERE status = 'active' DO NOTHING",
+        escalation.agent_name,
+        escalation.chat_id,
+        escalation.telegram_id,
+        escalation.chain_type.as_str(),
+        escalation.address,
+        escalation.subject,
+        escalation.trace_id,
+        first_delay_secs as f64
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Cancel the active escalations in a group of a holder who bought back in, whichever of
+// the group's subjects they were started for
+pub async fn cancel_escalations(conn: &mut PgConnection, chain_type: ChainType, address: &str, chat_id: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE enforcement_escalations SET status = 'cancelled', updated_at = NOW()
+         WHERE chain_type = $1 AND address = $2 AND chat_id = $3 AND status = 'active'",
+        chain_type.as_str(),
+        address,
+        chat_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Active escalations whose next step is due, of enabled agents
+pub async fn get_due_escalations(pool: &PgPool, limit: i64) -> Result<Vec<DueEscalation>, sqlx::Error> {
+    sqlx::query_as!(
+        DueEscalation,
+        r#"SELECT e.id, e.agent_name, e.chat_id, e.telegram_id, e.chain_type as "chain_type: ChainType", e.address, e.subject,
+                  e.next_step, b.bot_token, b.escalation_ladder, b.min_shares, b.subject_rule as "subject_rule: SubjectRule",
+                  b.quiet_hours, b.supply_cap, e.trace_id, b.enforcement_mode as "enforcement_mode: EnforcementMode"
+           FROM enforcement_escalations e
+           JOIN telegram_bots b ON b.agent_name = e.agent_name
+           WHERE e.status = 'active' AND e.next_run_at <= NOW() AND b.enabled
+           ORDER BY e.next_run_at
+           LIMIT $1"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Move an escalation to its next step in `delay_secs`, or mark it completed when `delay_secs` is `None`
+pub async fn advance_escalation(conn: &mut PgConnection, id: i64, next_step: i32, delay_secs: Option<i64>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE enforcement_escalations SET
+            next_step = $2,
+            next_run_at = NOW() + make_interval(secs => COALESCE($3::float8, 0)),
+            status = CASE WHEN $3::float8 IS NULL THEN 'completed' ELSE status END,
+            updated_at = NOW()
+         WHERE id = $1 AND status = 'active'",
+        id,
+        next_step,
+        delay_secs.map(|secs| secs as f64)
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Postpone the due step of an escalation to `until` without advancing it
+pub async fn defer_escalation(conn: &mut PgConnection, id: i64, until: OffsetDateTime) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE enforcement_escalations SET next_run_at = $2, updated_at = NOW() WHERE id = $1 AND status = 'active'",
+        id,
+        until
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Stop a single escalation, its holder bought back in or is no longer verified
+pub async fn cancel_escalation(conn: &mut PgConnection, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE enforcement_escalations SET status = 'cancelled', updated_at = NOW() WHERE id = $1 AND status = 'active'",
+        id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Held enforcement actions, most recent first
+pub async fn get_held_actions(pool: &PgPool, limit: i64) -> Result<Vec<HeldAction>, sqlx::Error> {
+    sqlx::query_as!(
+        HeldAction,
+        r#"SELECT id, chain_type as "chain_type: ChainType", agent_name, chat_id, telegram_id, address, subject, action,
+                  balance::text as "balance!", created_at
+           FROM held_enforcement_actions
+           ORDER BY created_at DESC
+           LIMIT $1"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Start the verification timeout of a member who joined, restarting it if they join again
+pub async fn create_pending_verification(pool: &PgPool, telegram_id: &str, chat_id: &str, agent_name: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO pending_verifications (telegram_id, chat_id, agent_name) VALUES ($1, $2, $3)
+         ON CONFLICT (telegram_id, chat_id) DO UPDATE
+         SET agent_name = $3, status = 'pending', joined_at = NOW(), warned_at = NULL, resolved_at = NULL,
+             prompt_sent_at = NULL, prompt_session_id = NULL, prompt_message_id = NULL",
+        telegram_id,
+        chat_id,
+        agent_name
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record the sign-link prompt sent to a pending member
+pub async fn mark_verification_prompted(
+    pool: &PgPool,
+    telegram_id: &str,
+    chat_id: &str,
+    session_id: &str,
+    message_id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE pending_verifications SET prompt_sent_at = NOW(), prompt_session_id = $3, prompt_message_id = $4
+         WHERE telegram_id = $1 AND chat_id = $2 AND status = 'pending'",
+        telegram_id,
+        chat_id,
+        session_id,
+        message_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Pending members of enabled bots, oldest first, with whether their prompt went out
+pub async fn get_unsettled_verifications(pool: &PgPool, limit: i64) -> Result<Vec<UnsettledVerification>, sqlx::Error> {
+    sqlx::query_as!(
+        UnsettledVerification,
+        r#"SELECT p.telegram_id, p.chat_id, p.agent_name, b.chain_type as "chain_type: ChainType", b.bot_token,
+                  b.enforcement_mode as "enforcement_mode: EnforcementMode", b.delete_service_messages, p.prompt_sent_at, u.username as "username?"
+           FROM pending_verifications p
+           JOIN telegram_bots b ON b.agent_name = p.agent_name
+           LEFT JOIN telegram_users u ON u.telegram_id = p.telegram_id
+           WHERE p.status = 'pending' AND b.enabled
+           ORDER BY p.joined_at
+           LIMIT $1"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Close a member's pending verification as verified, left or removed
+pub async fn resolve_pending_verification(pool: &PgPool, telegram_id: &str, chat_id: &str, status: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE pending_verifications SET status = $3, resolved_at = NOW()
+         WHERE telegram_id = $1 AND chat_id = $2 AND status = 'pending'",
+        telegram_id,
+        chat_id,
+        status
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Pending members who joined more than `age_secs` ago, only those not warned yet when `unwarned` is set.
+// Members of view-only groups are welcome as readers and never overdue
+pub async fn get_overdue_verifications(
+    pool: &PgPool,
+    age_secs: i64,
+    unwarned: bool,
+    limit: i64,
+) -> Result<Vec<PendingVerification>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingVerification,
+        r#"SELECT p.telegram_id, p.chat_id, p.agent_name, b.chain_type as "chain_type: ChainType",
+                  b.enforcement_mode as "enforcement_mode: EnforcementMode", b.bot_token
+           FROM pending_verifications p
+           JOIN telegram_bots b ON b.agent_name = p.agent_name
+           WHERE p.status = 'pending'
+             AND p.joined_at <= NOW() - make_interval(secs => $1::float8)
+             AND (NOT $2 OR p.warned_at IS NULL)
+             AND b.enabled AND b.enforcement_mode <> 'view_only'
+           ORDER BY p.joined_at
+           LIMIT $3"#,
+        age_secs as f64,
+        unwarned,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Active groups on a chain whose share rule an address currently meets, from its stored balances
+pub async fn get_qualifying_groups(pool: &PgPool, address: &str, chain_type: ChainType) -> Result<Vec<QualifyingGroup>, sqlx::Error> {
+    sqlx::query_as!(
+        QualifyingGroup,
+        r#"SELECT b.agent_name, b.invite_url, b.chain_type as "chain_type: ChainType"
+           FROM telegram_bots b
+           CROSS JOIN LATERAL (
+               SELECT b.subject_address AS subject
+               UNION
+               SELECT g.subject_address FROM group_subjects g WHERE g.agent_name = b.agent_name
+           ) s
+           LEFT JOIN trades t ON t.trader = $1 AND t.subject = s.subject AND t.chain_type = b.chain_type
+           WHERE b.chain_type = $2 AND b.status = 'active' AND b.enabled
+           GROUP BY b.agent_name, b.invite_url, b.chain_type, b.subject_rule, b.min_shares
+           HAVING CASE WHEN b.subject_rule = 'all'
+                       THEN bool_and(COALESCE(t.share_amount, 0) >= b.min_shares)
+                       ELSE bool_or(COALESCE(t.share_amount, 0) >= b.min_shares)
+                  END
+           ORDER BY b.agent_name"#,
+        address,
+        chain_type.as_str()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Pending members of an agent's group with no wallet bound on its chain, longest waiting first
+pub async fn get_unverified_members(pool: &PgPool, agent_name: &str, limit: i64) -> Result<Vec<PendingVerification>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingVerification,
+        r#"SELECT p.telegram_id, p.chat_id, p.agent_name, b.chain_type as "chain_type: ChainType",
+                  b.enforcement_mode as "enforcement_mode: EnforcementMode", b.bot_token
+           FROM pending_verifications p
+           JOIN telegram_bots b ON b.agent_name = p.agent_name
+           WHERE p.agent_name = $1 AND p.status = 'pending'
+             AND NOT EXISTS (
+                 SELECT 1 FROM user_mappings m WHERE m.telegram_id = p.telegram_id AND m.chain_type = b.chain_type
+             )
+           ORDER BY p.joined_at
+           LIMIT $2"#,
+        agent_name,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Remember that a pending member was warned before removal
+pub async fn mark_verification_warned(pool: &PgPool, telegram_id: &str, chat_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE pending_verifications SET warned_at = NOW() WHERE telegram_id = $1 AND chat_id = $2",
+        telegram_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Member mutes, kicks and restores logged between two times, newest first, optionally for one agent
+pub async fn get_enforcement_events(
+    pool: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    agent_name: Option<&str>,
+) -> Result<Vec<EnforcementEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        EnforcementEvent,
+        r#"SELECT e.id, e.agent_name, e.chat_id, e.telegram_id as "telegram_id!", e.action, e.details, e.created_at, b.bot_token,
+                  b.enforcement_mode as "enforcement_mode: EnforcementMode"
+           FROM moderation_events e
+           JOIN telegram_bots b ON b.agent_name = e.agent_name
+           WHERE e.action IN ('mute', 'kick', 'view_only', 'restore', 'rejoin_link')
+             AND e.telegram_id IS NOT NULL
+             AND e.created_at >= $1 AND e.created_at < $2
+             AND ($3::varchar IS NULL OR e.agent_name = $3)
+           ORDER BY e.created_at DESC, e.id DESC"#,
+        from,
+        to,
+        agent_name
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Open a binding proven by a transaction carrying `memo`, returns its expiry
+pub async fn create_pending_binding(
+    pool: &PgPool,
+    memo: &str,
+    telegram_id: &str,
+    chat_id: &str,
+    chain_type: ChainType,
+    address: &str,
+    ttl_secs: i64,
+) -> Result<OffsetDateTime, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO pending_bindings (memo, telegram_id, chat_id, chain_type, address, expires_at)
+         VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(secs => $6::float8))
+         RETURNING expires_at",
+        memo,
+        telegram_id,
+        chat_id,
+        chain_type.as_str(),
+        address,
+        ttl_secs as f64
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Look up a transaction binding by its memo
+pub async fn get_pending_binding(pool: &PgPool, memo: &str) -> Result<Option<PendingBinding>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingBinding,
+        r#"SELECT memo, telegram_id, chat_id, chain_type as "chain_type: ChainType", address, status, tx_hash, expires_at
+           FROM pending_bindings WHERE memo = $1"#,
+        memo
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Unexpired bindings of a chain still waiting for their transaction
+pub async fn get_open_pending_bindings(pool: &PgPool, chain_type: ChainType) -> Result<Vec<PendingBinding>, sqlx::Error> {
+    sqlx::query_as!(
+        PendingBinding,
+        r#"SELECT memo, telegram_id, chat_id, chain_type as "chain_type: ChainType", address, status, tx_hash, expires_at
+           FROM pending_bindings
+           WHERE chain_type = $1 AND status = 'pending' AND expires_at > NOW()"#,
+        chain_type.as_str()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Mark a binding proven by `tx_hash`, false if it was already bound or expired
+pub async fn complete_pending_binding(pool: &PgPool, memo: &str, tx_hash: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE pending_bindings SET status = 'bound', tx_hash = $2, bound_at = NOW()
+         WHERE memo = $1 AND status = 'pending' AND expires_at > NOW()",
+        memo,
+        tx_hash
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Bot gating a group on a chain
+pub async fn get_group_bot(pool: &PgPool, chat_id: &str, chain_type: ChainType) -> Result<Option<GroupBot>, sqlx::Error> {
+    sqlx::query_as!(
+        GroupBot,
+        r#"SELECT agent_name, bot_token, chat_group_id, subject_address, min_shares, subject_rule as "subject_rule: SubjectRule",
+                  enforcement_mode as "enforcement_mode: EnforcementMode", optimistic_verify
+           FROM telegram_bots WHERE chat_group_id = $1 AND chain_type = $2"#,
+        chat_id,
+        chain_type.as_str()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Shares needed to chat in each group gated by a subject, lowest first and empty when no
+// agent gates it
+pub async fn get_subject_thresholds(conn: &mut PgConnection, subject: &str, chain_type: ChainType) -> Result<Vec<BigDecimal>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT DISTINCT min_shares as "min_shares!" FROM telegram_bots b
+           WHERE b.chain_type = $2 AND (b.subject_address = $1 OR EXISTS (
+               SELECT 1 FROM group_subjects g WHERE g.agent_name = b.agent_name AND g.subject_address = $1
+           ))
+           ORDER BY 1"#,
+        subject,
+        chain_type.as_str()
+    )
+    .fetch_all(conn)
+    .await
+}
+
+// Subjects gating any agent's group on a chain
+pub async fn get_chain_subjects(pool: &PgPool, chain_type: ChainType) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT subject_address as "subject!" FROM telegram_bots WHERE chain_type = $1
+           UNION
+           SELECT g.subject_address FROM group_subjects g
+           JOIN telegram_bots b ON b.agent_name = g.agent_name
+           WHERE b.chain_type = $1
+           ORDER BY 1"#,
+        chain_type.as_str()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Subjects gating an agent's group, its subject_address first
+pub async fn get_group_subjects(pool: &PgPool, agent_name: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT subject as "subject!" FROM (
+               SELECT subject_address AS subject, 0 AS position FROM telegram_bots WHERE agent_name = $1
+               UNION
+               SELECT subject_address, 1 FROM group_subjects WHERE agent_name = $1
+           ) s
+           ORDER BY position, subject"#,
+        agent_name
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Stored balances of a trader in every subject gating an agent's group, its subject_address first
+pub async fn get_group_subject_balances(
+    conn: &mut PgConnection,
+    agent_name: &str,
+    trader: &str,
+) -> Result<Vec<(String, BigDecimal)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT s.subject as "subject!", COALESCE(t.share_amount, 0) as "share_amount!"
+           FROM telegram_bots b
+           CROSS JOIN LATERAL (
+               SELECT b.subject_address AS subject, 0 AS position
+               UNION
+               SELECT g.subject_address, 1 FROM group_subjects g WHERE g.agent_name = b.agent_name
+           ) s
+           LEFT JOIN trades t ON t.trader = $2 AND t.subject = s.subject AND t.chain_type = b.chain_type
+           WHERE b.agent_name = $1
+           ORDER BY s.position, s.subject"#,
+        agent_name,
+        trader
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.subject, row.share_amount)).collect())
+}
+
+// Record whether a verified wallet satisfies its agent's gate, the trigger on gated_members
+// keeps the agent's active_members count in step
+pub async fn set_gated_member(
+    conn: &mut PgConnection,
+    agent_name: &str,
+    address: &str,
+    chain_type: ChainType,
+    telegram_id: &str,
+    active: bool,
+) -> Result<(), sqlx::Error> {
+    if active {
+        sqlx::query!(
+            "INSERT INTO gated_members (agent_name, address, chain_type, telegram_id)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (agent_name, address, chain_type) DO UPDATE SET telegram_id = $4
+             WHERE gated_members.telegram_id <> $4",
+            agent_name,
+            address,
+            chain_type.as_str(),
+            telegram_id
+        )
+        .execute(conn)
+        .await?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM gated_members WHERE agent_name = $1 AND address = $2 AND chain_type = $3",
+            agent_name,
+            address,
+            chain_type.as_str()
+        )
+        .execute(conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Whether a wallet lost access to an agent's group
+pub async fn is_group_banned(conn: &mut PgConnection, agent_name: &str, address: &str, chain_type: ChainType) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM group_bans WHERE agent_name = $1 AND address = $2 AND chain_type = $3) as "banned!""#,
+        agent_name,
+        address,
+        chain_type.as_str()
+    )
+    .fetch_one(conn)
+    .await
+}
+
+// Set whether a wallet lost access to an agent's group, the wallet stays banned in
+// user_mappings while it is banned from any group (kept in step by a trigger on group_bans)
+pub async fn set_group_banned(
+    conn: &mut PgConnection,
+    agent_name: &str,
+    address: &str,
+    chain_type: ChainType,
+    banned: bool,
+) -> Result<(), sqlx::Error> {
+    if banned {
+        sqlx::query!(
+            "INSERT INTO group_bans (agent_name, address, chain_type) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            agent_name,
+            address,
+            chain_type.as_str()
+        )
+        .execute(conn)
+        .await?;
+    } else {
+        sqlx::query!(
+            "DELETE FROM group_bans WHERE agent_name = $1 AND address = $2 AND chain_type = $3",
+            agent_name,
+            address,
+            chain_type.as_str()
+        )
+        .execute(conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Replace the subjects gating an agent's group besides its subject_address and set how they
+// combine, false when the agent does not exist
+pub async fn set_group_subjects(
+    pool: &PgPool,
+    agent_name: &str,
+    subjects: &[String],
+    rule: SubjectRule,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let chain_type = sqlx::query_scalar!(
+        "UPDATE telegram_bots SET subject_rule = $2 WHERE agent_name = $1 RETURNING chain_type",
+        agent_name,
+        rule.as_str()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(chain_type) = chain_type else {
+        return Ok(false);
+    };
+
+    sqlx::query!("DELETE FROM group_subjects WHERE agent_name = $1", agent_name)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO group_subjects (agent_name, chain_type, subject_address)
+         SELECT $1, $2, subject FROM UNNEST($3::varchar[]) AS subject
+         ON CONFLICT DO NOTHING",
+        agent_name,
+        chain_type,
+        subjects
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+// Store a new API key by its hash
+pub async fn create_api_key(
+    pool: &PgPool,
+    name: &str,
+    role: KeyRole,
+    subjects: Option<&[String]>,
+    key_hash: &str,
+    key_prefix: &str,
+) -> Result<ApiKeyInfo, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyInfo,
+        r#"INSERT INTO api_keys (name, role, subjects, key_hash, key_prefix) VALUES ($1, $2, $3, $4, $5)
+           RETURNING id, name, role as "role: KeyRole", subjects, key_prefix, created_at, last_used_at, revoked_at"#,
+        name,
+        role.as_str(),
+        subjects,
+        key_hash,
+        key_prefix
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// The unrevoked key with this hash, marking it used
+pub async fn authenticate_api_key(pool: &PgPool, key_hash: &str) -> Result<Option<AuthenticatedKey>, sqlx::Error> {
+    sqlx::query_as!(
+        AuthenticatedKey,
+        r#"UPDATE api_keys SET last_used_at = NOW() WHERE key_hash = $1 AND revoked_at IS NULL
+           RETURNING name, role as "role: KeyRole", subjects"#,
+        key_hash
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Every stored API key, newest first
+pub async fn list_api_keys(pool: &PgPool) -> Result<Vec<ApiKeyInfo>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyInfo,
+        r#"SELECT id, name, role as "role: KeyRole", subjects, key_prefix, created_at, last_used_at, revoked_at
+           FROM api_keys ORDER BY id DESC"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Revoke an API key, None if there is no such key
+pub async fn revoke_api_key(pool: &PgPool, id: i64) -> Result<Option<ApiKeyInfo>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyInfo,
+        r#"UPDATE api_keys SET revoked_at = COALESCE(revoked_at, NOW()) WHERE id = $1
+           RETURNING id, name, role as "role: KeyRole", subjects, key_prefix, created_at, last_used_at, revoked_at"#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Wallets bound on a chain to a Telegram user or address, with their balance of a subject
+pub async fn get_bound_holdings(
+    pool: &PgPool,
+    chain_type: ChainType,
+    subject: &str,
+    telegram_id: Option<&str>,
+    address: Option<&str>,
+) -> Result<Vec<BoundHolding>, sqlx::Error> {
+    sqlx::query_as!(
+        BoundHolding,
+        r#"SELECT m.address, m.telegram_id, m.is_banned, COALESCE(t.share_amount, 0) as "share_amount!"
+           FROM user_mappings m
+           LEFT JOIN trades t ON t.trader = m.address AND t.chain_type = m.chain_type AND t.subject = $2
+           WHERE m.chain_type = $1 AND (m.telegram_id = $3 OR m.address = $4)
+           ORDER BY m.address"#,
+        chain_type.as_str(),
+        subject,
+        telegram_id,
+        address
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Register a webhook of an agent
+pub async fn create_webhook(pool: &PgPool, agent_name: &str, url: &str, secret: &str, events: &[String]) -> Result<WebhookInfo, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookInfo,
+        r#"INSERT INTO webhooks (agent_name, url, secret, events) VALUES ($1, $2, $3, $4)
+           RETURNING id, url, events, created_at, 0::bigint as "delivered!", 0::bigint as "pending!", 0::bigint as "failed!""#,
+        agent_name,
+        url,
+        secret,
+        events
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Webhooks of an agent with their delivery counts, oldest first
+pub async fn list_webhooks(pool: &PgPool, agent_name: &str) -> Result<Vec<WebhookInfo>, sqlx::Error> {
+    sqlx::query_as!(
+        WebhookInfo,
+        r#"SELECT w.id, w.url, w.events, w.created_at,
+                  COUNT(d.id) FILTER (WHERE d.status = 'delivered') as "delivered!",
+                  COUNT(d.id) FILTER (WHERE d.status = 'pending') as "pending!",
+                  COUNT(d.id) FILTER (WHERE d.status = 'failed') as "failed!"
+           FROM webhooks w
+           LEFT JOIN webhook_deliveries d ON d.webhook_id = w.id
+           WHERE w.agent_name = $1
+           GROUP BY w.id
+           ORDER BY w.id"#,
+        agent_name
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Remove a webhook of an agent with its queued deliveries, false if there is no such webhook
+pub async fn delete_webhook(pool: &PgPool, agent_name: &str, id: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM webhooks WHERE id = $1 AND agent_name = $2", id, agent_name)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Queue an event for every webhook subscribed to it by the agent gating the subject
+pub async fn enqueue_webhook_deliveries(
+    conn: &mut PgConnection,
+    chain_type: ChainType,
+    subject: &str,
+    event_type: &str,
+    payload: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "INSERT INTO webhook_deliveries (webhook_id, event_type, payload)
+         SELECT w.id, $3, $4
+         FROM webhooks w
+         JOIN telegram_bots b ON b.agent_name = w.agent_name
+         WHERE b.chain_type = $2 AND $3 = ANY(w.events) AND (b.subject_address = $1 OR EXISTS (
+             SELECT 1 FROM group_subjects g WHERE g.agent_name = b.agent_name AND g.subject_address = $1
+         ))",
+        subject,
+        chain_type.as_str(),
+        event_type,
+        payload
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Pending webhook deliveries whose next attempt is due, in the order the events happened
+pub async fn get_due_webhook_deliveries(pool: &PgPool, limit: i64) -> Result<Vec<DueWebhookDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        DueWebhookDelivery,
+        "SELECT d.id, d.webhook_id, w.agent_name, w.url, w.secret, d.event_type, d.payload, d.attempts, d.created_at
+         FROM webhook_deliveries d
+         JOIN webhooks w ON w.id = d.webhook_id
+         WHERE d.status = 'pending' AND d.next_attempt_at <= NOW()
+         ORDER BY d.id
+         LIMIT $1",
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Record an attempt of a webhook delivery, a failed attempt is retried at `retry_at` or
+// given up on without one
+pub async fn finish_webhook_delivery(
+    pool: &PgPool,
+    id: i64,
+    error: Option<String>,
+    retry_at: Option<OffsetDateTime>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE webhook_deliveries
+         SET status = CASE WHEN $2::text IS NULL THEN 'delivered' WHEN $3::timestamptz IS NULL THEN 'failed' ELSE 'pending' END,
+             attempts = attempts + 1,
+             next_attempt_at = COALESCE($3, next_attempt_at),
+             delivered_at = CASE WHEN $2::text IS NULL THEN NOW() END,
+             last_error = $2
+         WHERE id = $1",
+        id,
+        error,
+        retry_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record the start of a job over positions `start..=end`, returning its id
+pub async fn start_job_run(pool: &PgPool, kind: &str, chain_type: ChainType, start: i64, end: i64) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO job_runs (kind, chain_type, start_position, end_position) VALUES ($1, $2, $3, $4) RETURNING id",
+        kind,
+        chain_type.as_str(),
+        start,
+        end
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Move a running job's position and event counts forward
+pub async fn update_job_run_progress(pool: &PgPool, id: i64, position: i64, stored: i64, skipped: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE job_runs
+         SET current_position = $2, events_stored = $3, events_skipped = $4, updated_at = NOW()
+         WHERE id = $1",
+        id,
+        position,
+        stored,
+        skipped
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Mark a job completed, or failed with `error`
+pub async fn finish_job_run(pool: &PgPool, id: i64, error: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE job_runs
+         SET status = CASE WHEN $2::text IS NULL THEN 'completed' ELSE 'failed' END,
+             error = $2, updated_at = NOW(), finished_at = NOW()
+         WHERE id = $1",
+        id,
+        error
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Most recently started jobs of a kind, running ones first
+pub async fn get_job_runs(pool: &PgPool, kind: &str, limit: i64) -> Result<Vec<JobRun>, sqlx::Error> {
+    sqlx::query_as!(
+        JobRun,
+        r#"SELECT id, kind, chain_type as "chain_type: ChainType", start_position, end_position, current_position,
+                  events_stored, events_skipped, status, error, started_at, updated_at, finished_at
+           FROM job_runs
+           WHERE kind = $1
+           ORDER BY status = 'running' DESC, started_at DESC
+           LIMIT $2"#,
+        kind,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Record that a user joined an agent's group, a rejoin clears the time they left
+pub async fn record_member_joined(pool: &PgPool, agent_name: &str, chat_id: &str, telegram_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO group_members (agent_name, telegram_id, chat_id, joined_at) VALUES ($1, $2, $3, NOW())
+         ON CONFLICT (agent_name, telegram_id) DO UPDATE SET chat_id = $3, joined_at = NOW(), left_at = NULL",
+        agent_name,
+        telegram_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record that a user left an agent's group
+pub async fn record_member_left(pool: &PgPool, agent_name: &str, telegram_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE group_members SET left_at = NOW() WHERE agent_name = $1 AND telegram_id = $2",
+        agent_name,
+        telegram_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Log a join or leave of a user seen by an agent's bot, `created_at` is the time Telegram reported
+pub async fn record_membership_event(
+    pool: &PgPool,
+    agent_name: &str,
+    chat_id: &str,
+    telegram_id: &str,
+    username: Option<&str>,
+    kind: &str,
+    created_at: OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO membership_events (agent_name, chat_id, telegram_id, username, kind, created_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        agent_name,
+        chat_id,
+        telegram_id,
+        username,
+        kind,
+        created_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Record that a user verified for an agent's group, members whose join was not seen are added
+pub async fn record_member_verified(pool: &PgPool, agent_name: &str, chat_id: &str, telegram_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO group_members (agent_name, telegram_id, chat_id, verified_at) VALUES ($1, $2, $3, NOW())
+         ON CONFLICT (agent_name, telegram_id) DO UPDATE SET verified_at = NOW()",
+        agent_name,
+        telegram_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Page of an agent's group roster, latest joins first, with the total number of members.
+// A member with several wallets on the agent's chain is shown with the largest holding
+pub async fn get_group_members(
+    pool: &PgPool,
+    agent_name: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<GroupMember>, i64), sqlx::Error> {
+    let members = sqlx::query_as!(
+        GroupMember,
+        r#"SELECT g.telegram_id, u.username as "username?", w.address as "address?", w.share_amount as "share_amount?",
+                  COALESCE(w.is_banned, false) as "is_banned!", g.joined_at, g.left_at, g.verified_at
+           FROM group_members g
+           JOIN telegram_bots b ON b.agent_name = g.agent_name
+           LEFT JOIN telegram_users u ON u.telegram_id = g.telegram_id
+           LEFT JOIN LATERAL (
+               SELECT m.address, COALESCE(t.share_amount, 0) AS share_amount,
+                      EXISTS (SELECT 1 FROM group_bans gb WHERE gb.agent_name = g.agent_name AND gb.address = m.address AND gb.chain_type = m.chain_type) AS is_banned
+               FROM user_mappings m
+               LEFT JOIN trades t ON t.trader = m.address AND t.subject = b.subject_address AND t.chain_type = m.chain_type
+               WHERE m.telegram_id = g.telegram_id AND m.chain_type = b.chain_type
+               ORDER BY share_amount DESC, m.address
+               LIMIT 1
+           ) w ON true
+           WHERE g.agent_name = $1
+           ORDER BY g.joined_at DESC NULLS LAST, g.telegram_id
+           LIMIT $2 OFFSET $3"#,
+        agent_name,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM group_members WHERE agent_name = $1"#,
+        agent_name
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((members, total))
+}
+
+// Moderation events of an agent, newest first, optionally of one member or one trace
+pub async fn get_moderation_events(
+    pool: &PgPool,
+    agent_name: &str,
+    telegram_id: Option<&str>,
+    trace_id: Option<&str>,
+    limit: i64,
+) -> Result<Vec<ModerationEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        ModerationEvent,
+        "SELECT id, chat_id, telegram_id, action, details, trace_id, created_at
+         FROM moderation_events
+         WHERE agent_name = $1
+           AND ($2::varchar IS NULL OR telegram_id = $2)
+           AND ($3::varchar IS NULL OR trace_id = $3)
+         ORDER BY created_at DESC, id DESC
+         LIMIT $4",
+        agent_name,
+        telegram_id,
+        trace_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Delete an agent, keeping an audit row that holds its export window open for `export_days`.
+// The bot row and what cascades from it go at once, the rest is purged when the window ends.
+// None if there is no such agent
+pub async fn delete_agent_with_export_window(
+    pool: &PgPool,
+    agent_name: &str,
+    requested_by: &str,
+    export_days: i32,
+) -> Result<Option<AgentDeletion>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let deletion = sqlx::query_as!(
+        AgentDeletion,
+        r#"INSERT INTO agent_deletions (agent_name, chain_type, chat_group_id, subjects, requested_by, export_until)
+           SELECT b.agent_name, b.chain_type, b.chat_group_id,
+                  b.subject_address || ARRAY(SELECT s.subject_address FROM group_subjects s
+                                             WHERE s.agent_name = b.agent_name AND s.subject_address <> b.subject_address
+                                             ORDER BY s.created_at),
+                  $2, NOW() + make_interval(days => $3)
+           FROM telegram_bots b
+           WHERE b.agent_name = $1
+           RETURNING id, agent_name, chain_type as "chain_type: ChainType", chat_group_id, subjects, requested_by, requested_at,
+                  export_until, export_count, last_exported_by, last_exported_at, purged_at, purged_rows"#,
+        agent_name,
+        requested_by,
+        export_days
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if deletion.is_some() {
+        sqlx::query!("DELETE FROM telegram_bots WHERE agent_name = $1", agent_name)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(deletion)
+}
+
+// Current name of the agent that went by `alias` before a rename
+pub async fn get_agent_by_alias(pool: &PgPool, alias: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT b.agent_name FROM agent_aliases a JOIN telegram_bots b ON b.agent_id = a.agent_id WHERE a.alias = $1",
+        alias
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Rename an agent, keeping its old name as an alias and moving its rows in every table keyed by
+// name, all or nothing. Returns the agent's id, None when there is no agent named `old_name`
+pub async fn rename_agent(pool: &PgPool, old_name: &str, new_name: &str) -> Result<Option<String>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let Some(agent_id) = sqlx::query_scalar!("SELECT agent_id FROM telegram_bots WHERE agent_name = $1 FOR UPDATE", old_name)
+        .fetch_optional(&mut *tx)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    // Taking back an earlier name of the same agent retires that alias
+    sqlx::query!("DELETE FROM agent_aliases WHERE alias = $1 AND agent_id = $2", new_name, agent_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("INSERT INTO agent_aliases (alias, agent_id) VALUES ($1, $2)", old_name, agent_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("UPDATE telegram_bots SET agent_name = $2 WHERE agent_id = $1", agent_id, new_name)
+        .execute(&mut *tx)
+        .await?;
+    for table in AGENT_TABLES {
+        sqlx::query(&format!("UPDATE {} SET agent_name = $2 WHERE agent_name = $1", table))
+            .bind(old_name)
+            .bind(new_name)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(Some(agent_id))
+}
+
+// Deletion of an agent whose data was not purged yet
+pub async fn get_open_agent_deletion(pool: &PgPool, agent_name: &str) -> Result<Option<AgentDeletion>, sqlx::Error> {
+    sqlx::query_as!(
+        AgentDeletion,
+        r#"SELECT id, agent_name, chain_type as "chain_type: ChainType", chat_group_id, subjects, requested_by, requested_at,
+                  export_until, export_count, last_exported_by, last_exported_at, purged_at, purged_rows
+           FROM agent_deletions
+           WHERE agent_name = $1 AND purged_at IS NULL"#,
+        agent_name
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Log an export of a deleted agent's data in its deletion row
+pub async fn record_agent_export(pool: &PgPool, id: i64, exported_by: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE agent_deletions
+         SET export_count = export_count + 1, last_exported_by = $2, last_exported_at = NOW()
+         WHERE id = $1",
+        id,
+        exported_by
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Deletions whose export window ended and whose data is still there
+pub async fn get_purgeable_agent_deletions(pool: &PgPool) -> Result<Vec<AgentDeletion>, sqlx::Error> {
+    sqlx::query_as!(
+        AgentDeletion,
+        r#"SELECT id, agent_name, chain_type as "chain_type: ChainType", chat_group_id, subjects, requested_by, requested_at,
+                  export_until, export_count, last_exported_by, last_exported_at, purged_at, purged_rows
+           FROM agent_deletions
+           WHERE purged_at IS NULL AND export_until <= NOW()
+           ORDER BY export_until"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Mark a deletion's data as purged, with the number of rows removed
+pub async fn mark_agent_purged(conn: &mut PgConnection, id: i64, rows: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE agent_deletions SET purged_at = NOW(), purged_rows = $2 WHERE id = $1",
+        id,
+        rows
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Attach a Discord server to an agent, replacing the one it had
+pub async fn set_discord_server(pool: &PgPool, server: &DiscordServer) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO discord_servers (agent_name, bot_token, guild_id, role_id, invite_channel_id)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (agent_name) DO UPDATE
+         SET bot_token = EXCLUDED.bot_token, guild_id = EXCLUDED.guild_id, role_id = EXCLUDED.role_id,
+             invite_channel_id = EXCLUDED.invite_channel_id",
+        server.agent_name,
+        server.bot_token,
+        server.guild_id,
+        server.role_id,
+        server.invite_channel_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_discord_server(pool: &PgPool, agent_name: &str) -> Result<Option<DiscordServer>, sqlx::Error> {
+    sqlx::query_as!(
+        DiscordServer,
+        "SELECT agent_name, bot_token, guild_id, role_id, invite_channel_id FROM discord_servers WHERE agent_name = $1",
+        agent_name
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Detach an agent's Discord server with its links, false if it had none
+pub async fn delete_discord_server(pool: &PgPool, agent_name: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM discord_servers WHERE agent_name = $1", agent_name)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Discord users besides `discord_id` a wallet is linked to in an agent's server, with whether
+// they were given the gated role
+pub async fn get_other_discord_links(
+    pool: &PgPool,
+    agent_name: &str,
+    address: &str,
+    chain_type: ChainType,
+    discord_id: &str,
+) -> Result<Vec<(String, bool)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT discord_id, has_role FROM discord_links
+         WHERE agent_name = $1 AND address = $2 AND chain_type = $3 AND discord_id <> $4",
+        agent_name,
+        address,
+        chain_type.as_str(),
+        discord_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.discord_id, row.has_role)).collect())
+}
+
+// Link a Discord user to a wallet on the agent's chain, moving the wallet over if it was linked
+// to another user. Linking another wallet keeps the stored role state, so the gate takes the
+// role away when the new wallet holds too little
+pub async fn link_discord_member(
+    pool: &PgPool,
+    agent_name: &str,
+    discord_id: &str,
+    address: &str,
+    chain_type: ChainType,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "DELETE FROM discord_links WHERE agent_name = $1 AND address = $2 AND chain_type = $3 AND discord_id <> $4",
+        agent_name,
+        address,
+        chain_type.as_str(),
+        discord_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO discord_links (agent_name, discord_id, address, chain_type)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (agent_name, discord_id) DO UPDATE
+         SET address = EXCLUDED.address, chain_type = EXCLUDED.chain_type, linked_at = NOW()",
+        agent_name,
+        discord_id,
+        address,
+        chain_type.as_str()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+// Gate of every Discord server whose agent is gated by `subject`, for the users linked to `address`
+pub async fn get_discord_gates(
+    conn: &mut PgConnection,
+    chain_type: ChainType,
+    address: &str,
+    subject: &str,
+) -> Result<Vec<DiscordGate>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT s.agent_name, s.bot_token, s.guild_id, s.role_id, s.invite_channel_id, l.discord_id, l.address, l.has_role,
+                  b.min_shares, b.subject_rule as "subject_rule: SubjectRule"
+           FROM discord_links l
+           JOIN discord_servers s ON s.agent_name = l.agent_name
+           JOIN telegram_bots b ON b.agent_name = l.agent_name AND b.chain_type = l.chain_type
+           WHERE l.address = $1 AND l.chain_type = $2 AND (b.subject_address = $3 OR EXISTS (
+               SELECT 1 FROM group_subjects g WHERE g.agent_name = b.agent_name AND g.subject_address = $3
+           ))"#,
+        address,
+        chain_type.as_str(),
+        subject
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DiscordGate {
+            server: DiscordServer {
+                agent_name: row.agent_name,
+                bot_token: row.bot_token,
+                guild_id: row.guild_id,
+                role_id: row.role_id,
+                invite_channel_id: row.invite_channel_id,
+            },
+            discord_id: row.discord_id,
+            address: row.address,
+            has_role: row.has_role,
+            min_shares: row.min_shares,
+            subject_rule: row.subject_rule,
+        })
+        .collect())
+}
+
+// Record whether a linked Discord user was last given the gated role
+pub async fn set_discord_role(conn: &mut PgConnection, agent_name: &str, discord_id: &str, has_role: bool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE discord_links SET has_role = $3 WHERE agent_name = $1 AND discord_id = $2",
+        agent_name,
+        discord_id,
+        has_role
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
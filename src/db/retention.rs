@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::db::operations::{get_purgeable_agent_deletions, mark_agent_purged};
+use crate::metrics::RETENTION_ROWS_DELETED;
+use crate::shutdown::sleep_or_shutdown;
+
+/// A single garbage collection rule: rows of `table` matching `condition`
+/// and older than `max_age_days` (by `age_column`) are deleted in batches.
+#[derive(Clone, Debug)]
+pub struct RetentionPolicy {
+    pub name: &'static str,
+    pub table: &'static str,
+    pub age_column: &'static str,
+    pub condition: &'static str,
+    pub max_age_days: i64,
+}
+
+// Per-table retention policies
+pub const RETENTION_POLICIES: &[RetentionPolicy] = &[
+    // Superseded sync progress rows, only the newest row per chain is ever read
+    RetentionPolicy {
+        name: "superseded_sync_status",
+        table: "sync_status",
+        age_column: "updated_at",
+        condition: "id NOT IN (SELECT MAX(id) FROM sync_status GROUP BY chain_type)",
+        max_age_days: 1,
+    },
+    // Balances that were fully sold and never touched again
+    RetentionPolicy {
+        name: "empty_trades",
+        table: "trades",
+        age_column: "updated_at",
+        condition: "share_amount = 0",
+        max_age_days: 30,
+    },
+    // Verification sessions that finished or expired
+    RetentionPolicy {
+        name: "stale_verification_sessions",
+        table: "verification_sessions",
+        age_column: "created_at",
+        condition: "(status <> 'pending' OR expires_at < NOW())",
+        max_age_days: 7,
+    },
+    // Challenge nonces that were used or expired
+    RetentionPolicy {
+        name: "stale_challenges",
+        table: "challenges",
+        age_column: "expires_at",
+        condition: "TRUE",
+        max_age_days: 1,
+    },
+    // Rejoin tokens of members who already came back
+    RetentionPolicy {
+        name: "used_rejoin_tokens",
+        table: "rejoin_tokens",
+        age_column: "used_at",
+        condition: "used_at IS NOT NULL",
+        max_age_days: 7,
+    },
+    // Enforcement latency samples, dashboards look at the last days only
+    RetentionPolicy {
+        name: "old_enforcement_latencies",
+        table: "enforcement_latencies",
+        age_column: "recorded_at",
+        condition: "TRUE",
+        max_age_days: 14,
+    },
+    // Transaction bindings that were proven or expired
+    RetentionPolicy {
+        name: "stale_pending_bindings",
+        table: "pending_bindings",
+        age_column: "expires_at",
+        condition: "TRUE",
+        max_age_days: 7,
+    },
+    // Pending verifications of members who verified, left or were removed
+    RetentionPolicy {
+        name: "resolved_pending_verifications",
+        table: "pending_verifications",
+        age_column: "resolved_at",
+        condition: "status <> 'pending'",
+        max_age_days: 30,
+    },
+    // Escalations that ran their ladder or were cancelled by a buy back
+    RetentionPolicy {
+        name: "finished_enforcement_escalations",
+        table: "enforcement_escalations",
+        age_column: "updated_at",
+        condition: "status <> 'active'",
+        max_age_days: 30,
+    },
+    // Webhook deliveries that were delivered or gave up
+    RetentionPolicy {
+        name: "finished_webhook_deliveries",
+        table: "webhook_deliveries",
+        age_column: "created_at",
+        condition: "status <> 'pending'",
+        max_age_days: 14,
+    },
+    // Tracked bot messages left behind by removed agents, Telegram refuses deletes after 48h anyway
+    RetentionPolicy {
+        name: "stale_bot_messages",
+        table: "bot_messages",
+        age_column: "delete_after",
+        condition: "TRUE",
+        max_age_days: 2,
+    },
+];
+
+// Tables keyed by agent name that outlive the agent's bot row, purged once a deleted
+// agent's export window ends and renamed with the agent. Tables referencing the bot row
+// (webhooks, group subjects, members, bans) cascade with it
+pub(crate) const AGENT_TABLES: &[&str] = &[
+    "moderation_events",
+    "bot_messages",
+    "telegram_errors",
+    "enforcement_latencies",
+    "onboarding_deliveries",
+    "onboarding_steps",
+    "held_enforcement_actions",
+    "pending_verifications",
+    "enforcement_escalations",
+    "membership_events",
+];
+
+/// Cumulative number of rows reclaimed per policy since startup
+#[derive(Clone, Default)]
+pub struct RetentionStats {
+    reclaimed: Arc<Mutex<HashMap<&'static str, u64>>>,
+}
+
+impl RetentionStats {
+    pub fn record(&self, policy: &'static str, rows: u64) {
+        let mut reclaimed = self.reclaimed.lock().unwrap();
+        *reclaimed.entry(policy).or_insert(0) += rows;
+    }
+
+    pub fn snapshot(&self) -> HashMap<&'static str, u64> {
+        self.reclaimed.lock().unwrap().clone()
+    }
+}
+
+// Delete rows matching a policy in batches so no single statement holds locks for long
+pub async fn apply_policy(pool: &PgPool, policy: &RetentionPolicy, batch_size: i64) -> Result<u64, sqlx::Error> {
+    let sql = format!(
+        "DELETE FROM {table} WHERE ctid IN (
+            SELECT ctid FROM {table}
+            WHERE {condition} AND {age_column} < NOW() - make_interval(days => $1::int)
+            LIMIT $2
+        )",
+        table = policy.table,
+        condition = policy.condition,
+        age_column = policy.age_column,
+    );
+
+    let mut total = 0u64;
+    loop {
+        let deleted = sqlx::query(&sql)
+            .bind(policy.max_age_days as i32)
+            .bind(batch_size)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        total += deleted;
+
+        if deleted < batch_size as u64 {
+            break;
+        }
+        // Give other transactions a chance between batches
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    Ok(total)
+}
+
+// Remove the remaining rows of deleted agents whose export window ended, each agent in one
+// transaction that also marks its deletion as purged. Returns the number of rows removed
+pub async fn purge_deleted_agents(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut total = 0u64;
+    for deletion in get_purgeable_agent_deletions(pool).await? {
+        let mut tx = pool.begin().await?;
+        let mut rows = 0u64;
+        for table in AGENT_TABLES {
+            rows += sqlx::query(&format!("DELETE FROM {} WHERE agent_name = $1", table))
+                .bind(&deletion.agent_name)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        mark_agent_purged(&mut tx, deletion.id, rows as i64).await?;
+        tx.commit().await?;
+
+        info!("Purged {} rows of agent {} deleted by {}", rows, deletion.agent_name, deletion.requested_by);
+        total += rows;
+    }
+    Ok(total)
+}
+
+// Count reclaimed rows in the stats and on /metrics
+fn record_reclaimed(stats: &RetentionStats, policy: &'static str, rows: u64) {
+    stats.record(policy, rows);
+    RETENTION_ROWS_DELETED.with_label_values(&[policy]).inc_by(rows);
+}
+
+// Run every retention policy once
+pub async fn run_retention(pool: &PgPool, batch_size: i64, stats: &RetentionStats) {
+    for policy in RETENTION_POLICIES {
+        match apply_policy(pool, policy, batch_size).await {
+            Ok(rows) => {
+                record_reclaimed(stats, policy.name, rows);
+                if rows > 0 {
+                    info!("Retention policy {} reclaimed {} rows from {}", policy.name, rows, policy.table);
+                }
+            },
+            Err(e) => {
+                error!("Retention policy {} failed: {:?}", policy.name, e);
+            }
+        }
+    }
+
+    match purge_deleted_agents(pool).await {
+        Ok(rows) => record_reclaimed(stats, "deleted_agents", rows),
+        Err(e) => error!("Purging deleted agents failed: {:?}", e),
+    }
+}
+
+// Periodic garbage collection loop
+pub async fn retention_loop(pool: PgPool, interval_secs: u64, batch_size: i64, stats: RetentionStats, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        run_retention(&pool, batch_size, &stats).await;
+        info!("Retention run complete, reclaimed so far: {:?}", stats.snapshot());
+        sleep_or_shutdown(&shutdown, Duration::from_secs(interval_secs)).await;
+    }
+}
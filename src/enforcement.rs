@@ -0,0 +1,859 @@
+//! Group access enforcement driven by share balance changes.
+//!
+//! Every chain implementation reports the trader's balance after applying a
+//! trade through [`handle_balance_change`] (or [`enforce_balance`] inside the
+//! trade's own transaction), which decides whether the linked Telegram user
+//! has to be restricted or restored in the subject's group, according to the
+//! group's [`EnforcementMode`]. A subject gating several groups is enforced in
+//! each of them, with a ban state of its own per group. The decision is stored
+//! with the transaction and carried out on Telegram and Discord once it
+//! committed, through their [`crate::platform::CommunityPlatform`], see
+//! [`PendingEnforcement`]. Groups gated by several subjects
+//! judge members on their balances of all of them, by the group's
+//! [`SubjectRule`].
+//! Restrictions are held back while the [`crate::kill_switch`] is engaged.
+//! Agents with an escalation ladder restrict step by step instead (see
+//! [`crate::bot::escalation`]), a ladder is cancelled as soon as the holder
+//! buys back in. Every action is logged as a moderation event so a window can
+//! be undone with [`rollback_enforcement`], and queued for the agent's
+//! [`crate::webhooks`].
+
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use sqlx::{PgConnection, PgPool};
+use teloxide::prelude::UserId;
+use teloxide::types::ChatPermissions;
+use teloxide::RequestError;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::block_chain::ChainType;
+use crate::bot::api::TelegramApi;
+use crate::db::models::{EnforcementEvent, NewEscalation, NewHeldAction};
+use crate::db::operations::{
+    cancel_escalations, create_rejoin_token, delete_rejoin_token, get_enforcement_events, get_group_subject_balances, get_open_rejoin_token,
+    is_group_banned, mark_rejoin_link_sent, record_enforcement_latency, record_held_action, record_moderation_event, set_gated_member,
+    set_group_banned, start_escalation,
+};
+use crate::kill_switch;
+use crate::logging::new_trace_id;
+use crate::platform::discord::enforce_discord_access;
+use crate::platform::telegram::TelegramCommunity;
+use crate::platform::CommunityPlatform;
+use crate::webhooks::{self, MemberEventData, WebhookEvent};
+
+// Lifetime of invite links sent to returning holders
+const REJOIN_LINK_TTL_SECS: i64 = 7 * 24 * 3600;
+
+/// Shares needed to chat in a group whose agent did not set `min_shares`
+pub const DEFAULT_MIN_SHARES: u64 = 1;
+
+/// What to do with a holder's group access after a balance change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enforcement {
+    /// Holder sold out, take away chat permissions
+    Restrict,
+    /// Previously restricted holder bought back in
+    Restore,
+    /// Access already matches the balance
+    Unchanged,
+}
+
+/// Whether a balance is enough to chat in a group requiring `min_shares`
+pub fn holds_shares(balance: &BigDecimal, min_shares: &BigDecimal) -> bool {
+    balance >= min_shares
+}
+
+/// Whether a trade moving a balance from `previous` to `new` can change group access.
+/// A holder who stays on the same side of the threshold was already enforced by the
+/// trade that took them there, so the trade needs no ban-state lookup or Telegram call
+pub fn crosses_threshold(previous: &BigDecimal, new: &BigDecimal, min_shares: &BigDecimal) -> bool {
+    holds_shares(previous, min_shares) != holds_shares(new, min_shares)
+}
+
+/// Whether a trade crosses the threshold of any of the groups a subject gates, see [`crosses_threshold`]
+pub fn crosses_any_threshold(previous: &BigDecimal, new: &BigDecimal, thresholds: &[BigDecimal]) -> bool {
+    thresholds.iter().any(|min_shares| crosses_threshold(previous, new, min_shares))
+}
+
+/// Decide the enforcement action for a member who now `holds` enough shares for their group or not
+pub fn decide(holds: bool, is_banned: bool) -> Enforcement {
+    if !holds {
+        Enforcement::Restrict
+    } else if is_banned {
+        Enforcement::Restore
+    } else {
+        Enforcement::Unchanged
+    }
+}
+
+/// Permissions granted to a holder in the group
+pub fn member_permissions() -> ChatPermissions {
+    ChatPermissions::empty()
+        | ChatPermissions::SEND_MESSAGES
+        | ChatPermissions::SEND_MEDIA_MESSAGES
+        | ChatPermissions::SEND_OTHER_MESSAGES
+        | ChatPermissions::SEND_POLLS
+        | ChatPermissions::ADD_WEB_PAGE_PREVIEWS
+}
+
+/// Permissions a member whose restrictions are lifted gets: the ones of [`member_permissions`]
+/// the chat's default permissions allow, so no member is given more than the group grants
+/// everyone. Chats whose defaults are unknown get [`member_permissions`]
+pub fn restored_permissions(chat_defaults: Option<ChatPermissions>) -> ChatPermissions {
+    member_permissions() & chat_defaults.unwrap_or_else(member_permissions)
+}
+
+/// Permissions of non-holders in view-only groups: they read the group and may invite others, but not post
+pub fn viewer_permissions() -> ChatPermissions {
+    ChatPermissions::INVITE_USERS
+}
+
+/// How a subject's group treats holders who sold out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum EnforcementMode {
+    /// Keep them in the group without chat permissions
+    #[default]
+    Mute,
+    /// Remove them and DM a single-use invite once they buy back in
+    Kick,
+    /// Keep them as readers with [`viewer_permissions`], for groups open to non-holders
+    /// as a funnel. Unverified members are never warned or removed either
+    #[serde(rename = "view_only")]
+    #[sqlx(rename = "view_only")]
+    ViewOnly,
+}
+
+impl EnforcementMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnforcementMode::Mute => "mute",
+            EnforcementMode::Kick => "kick",
+            EnforcementMode::ViewOnly => "view_only",
+        }
+    }
+
+    /// Permissions of members without enough shares who are still in the group
+    pub fn restricted_permissions(&self) -> ChatPermissions {
+        match self {
+            EnforcementMode::ViewOnly => viewer_permissions(),
+            EnforcementMode::Mute | EnforcementMode::Kick => ChatPermissions::empty(),
+        }
+    }
+}
+
+/// Which of the subjects gating a group a member must hold `min_shares` of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum SubjectRule {
+    /// Shares of at least one subject
+    #[default]
+    Any,
+    /// Shares of every subject
+    All,
+}
+
+impl SubjectRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubjectRule::Any => "any",
+            SubjectRule::All => "all",
+        }
+    }
+
+    /// Whether a member with these balances of a group's subjects may chat in it
+    pub fn admits<'a>(&self, balances: impl IntoIterator<Item = &'a BigDecimal>, min_shares: &BigDecimal) -> bool {
+        let mut holdings = balances.into_iter().map(|balance| holds_shares(balance, min_shares));
+        match self {
+            SubjectRule::Any => holdings.any(|holds| holds),
+            SubjectRule::All => holdings.all(|holds| holds),
+        }
+    }
+}
+
+/// Action taken at one step of an escalation ladder, in increasing severity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EscalationAction {
+    /// DM the holder that they will lose access unless they buy back in
+    Warn,
+    /// Keep them in the group without chat permissions
+    ReadOnly,
+    /// Remove them, they get a single-use invite once they buy back in
+    Kick,
+}
+
+impl EscalationAction {
+    /// Action name logged as moderation event, matching the immediate enforcement modes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EscalationAction::Warn => "warn",
+            EscalationAction::ReadOnly => "mute",
+            EscalationAction::Kick => "kick",
+        }
+    }
+}
+
+/// One step of an agent's escalation ladder, applied `delay_secs` after the previous
+/// step (the first one after the holder sold out)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EscalationStep {
+    pub action: EscalationAction,
+    #[serde(default)]
+    pub delay_secs: i64,
+}
+
+// Upper bounds keeping a ladder readable and its schedule within a sane horizon
+const MAX_ESCALATION_STEPS: usize = 10;
+const MAX_ESCALATION_DELAY_SECS: i64 = 30 * 24 * 3600;
+
+/// Check a ladder submitted for an agent: steps never get milder and nothing follows a kick
+pub fn validate_ladder(steps: &[EscalationStep]) -> Result<(), String> {
+    if steps.len() > MAX_ESCALATION_STEPS {
+        return Err(format!("Escalation ladder has more than {} steps", MAX_ESCALATION_STEPS));
+    }
+    for (i, step) in steps.iter().enumerate() {
+        if !(0..=MAX_ESCALATION_DELAY_SECS).contains(&step.delay_secs) {
+            return Err(format!("Step {} delay must be between 0 and {} seconds", i + 1, MAX_ESCALATION_DELAY_SECS));
+        }
+        if let Some(previous) = i.checked_sub(1).map(|p| steps[p]) {
+            if previous.action == EscalationAction::Kick {
+                return Err("No step can follow a kick".to_string());
+            }
+            if step.action < previous.action {
+                return Err(format!("Step {} is milder than the step before it", i + 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ladder stored for an agent, `None` when it enforces immediately
+pub fn parse_ladder(stored: Option<&str>) -> Option<Vec<EscalationStep>> {
+    let steps: Vec<EscalationStep> = serde_json::from_str(stored?).ok()?;
+    (!steps.is_empty()).then_some(steps)
+}
+
+/// What a member enforcement moderation event was based on, stored as its details
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EnforcementDetails {
+    pub chain_type: ChainType,
+    pub address: String,
+    pub subject: String,
+    pub balance: String,
+}
+
+/// Apply group access for `trader` after their balance of `subject` changed to `new_balance`,
+/// against the `min_shares` of every group the subject gates, returns the number of groups
+/// whose access changed. `event_time` is the on-chain time of the trade, used to measure enforcement latency.
+/// Balance changes not caused by a stored trade (reorgs, reconciliation, rebinding) get a
+/// trace id of their own.
+pub async fn handle_balance_change(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    chain: ChainType,
+    trader: &str,
+    subject: &str,
+    new_balance: &BigDecimal,
+    event_time: Option<OffsetDateTime>,
+) -> Result<usize> {
+    let trace_id = new_trace_id();
+    let span = info_span!("balance_change", trace_id = %trace_id);
+    let mut tx = pool.begin().await?;
+    let pending = enforce_balance(&mut tx, pool, chain, trader, subject, new_balance, event_time, &trace_id)
+        .instrument(span.clone())
+        .await?;
+    tx.commit().await?;
+    Ok(pending.apply(pool, telegram).instrument(span).await)
+}
+
+/// [`handle_balance_change`] within the caller's transaction, without calling Telegram or Discord.
+/// The ban state, rejoin token, held action and escalation are written through `conn`, so they
+/// commit or roll back with the trade that caused them; the returned [`PendingEnforcement`]
+/// carries out the decision once that transaction committed. Moderation events and escalations
+/// are stored with the `trace_id` of the cause.
+#[allow(clippy::too_many_arguments)]
+pub async fn enforce_balance(
+    conn: &mut PgConnection,
+    pool: &PgPool,
+    chain: ChainType,
+    trader: &str,
+    subject: &str,
+    new_balance: &BigDecimal,
+    event_time: Option<OffsetDateTime>,
+    trace_id: &str,
+) -> Result<PendingEnforcement> {
+    let mut pending = PendingEnforcement {
+        chain,
+        trader: trader.to_string(),
+        subject: subject.to_string(),
+        new_balance: new_balance.clone(),
+        event_time,
+        trace_id: trace_id.to_string(),
+        members: Vec::new(),
+    };
+
+    // Only traders who verified through the bot have a Telegram user to act on,
+    // the row stays locked so concurrent balance changes are enforced one at a time
+    let user = sqlx::query!(
+        "SELECT telegram_id FROM user_mappings WHERE address = $1 AND chain_type = $2 FOR UPDATE",
+        trader,
+        chain.as_str()
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(user) = user else {
+        return Ok(pending);
+    };
+
+    let groups = sqlx::query!(
+        r#"SELECT agent_name, bot_token, chat_group_id, enforcement_mode as "enforcement_mode: EnforcementMode", escalation_ladder, min_shares,
+                  subject_rule as "subject_rule: SubjectRule"
+           FROM telegram_bots b
+           WHERE b.chain_type = $2 AND (b.subject_address = $1 OR EXISTS (
+               SELECT 1 FROM group_subjects g WHERE g.agent_name = b.agent_name AND g.subject_address = $1
+           ))
+           ORDER BY agent_name"#,
+        subject,
+        chain.as_str()
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if groups.is_empty() {
+        warn!("No telegram bot info found for subject {}", subject);
+    }
+
+    // Every group the subject gates judges the member by its own rule, threshold and ban state
+    for bot_info in groups {
+        let agent = bot_info.agent_name.as_str();
+        let chat = bot_info.chat_group_id.as_str();
+        let banned = is_group_banned(&mut *conn, agent, trader, chain).await?;
+
+        // The other subjects of the group count with their stored balances, `subject` with the new one
+        let balances = get_group_subject_balances(&mut *conn, agent, trader).await?;
+        let holds = bot_info.subject_rule.admits(
+            balances.iter().map(|(group_subject, balance)| if group_subject == subject { new_balance } else { balance }),
+            &bot_info.min_shares,
+        );
+        set_gated_member(&mut *conn, agent, trader, chain, &user.telegram_id, holds).await?;
+
+        // Buying back in stops any escalation before its next step
+        if holds {
+            let cancelled = cancel_escalations(&mut *conn, chain, trader, chat).await?;
+            if cancelled > 0 {
+                info!("User {} holds shares of {} again, cancelled escalation in chat {}", trader, subject, chat);
+            }
+        }
+
+        let action = decide(holds, banned);
+        if action == Enforcement::Unchanged {
+            continue;
+        }
+
+        // Agents with a ladder restrict step by step, already restricted members are left
+        // to the running or finished escalation
+        if let (Enforcement::Restrict, Some(steps)) = (action, parse_ladder(bot_info.escalation_ladder.as_deref())) {
+            if !banned {
+                let started = start_escalation(&mut *conn, &NewEscalation {
+                    agent_name: agent.to_string(),
+                    chat_id: chat.to_string(),
+                    telegram_id: user.telegram_id.clone(),
+                    chain_type: chain,
+                    address: trader.to_string(),
+                    subject: subject.to_string(),
+                    trace_id: trace_id.to_string(),
+                }, steps[0].delay_secs).await?;
+                if started {
+                    info!("User {} holds {} shares of {}, below {}, starting escalation in chat {}", trader, new_balance, subject, bot_info.min_shares, chat);
+                }
+            }
+            continue;
+        }
+
+        let (applied, rejoin_token) = match action {
+            Enforcement::Restrict if kill_switch::is_engaged(pool).await? => {
+                warn!("Kill switch engaged, holding {} of user {} in chat {}", bot_info.enforcement_mode.as_str(), user.telegram_id, chat);
+                record_held_action(&mut *conn, &NewHeldAction {
+                    chain_type: chain,
+                    agent_name: agent.to_string(),
+                    chat_id: chat.to_string(),
+                    telegram_id: user.telegram_id.clone(),
+                    address: trader.to_string(),
+                    subject: subject.to_string(),
+                    action: bot_info.enforcement_mode.as_str().to_string(),
+                    balance: new_balance.clone(),
+                }).await?;
+                continue;
+            }
+            Enforcement::Restrict => {
+                info!("User {} holds {} shares of {}, below {}, banning user in chat {}", trader, new_balance, subject, bot_info.min_shares, chat);
+                // Kicked members can rejoin by link once they buy back in
+                let token = if bot_info.enforcement_mode == EnforcementMode::Kick {
+                    let token = Uuid::new_v4().simple().to_string();
+                    create_rejoin_token(&mut *conn, &token, &user.telegram_id, chat, chain, trader).await?;
+                    Some(token)
+                } else {
+                    None
+                };
+                (bot_info.enforcement_mode.as_str(), token)
+            }
+            Enforcement::Restore => {
+                info!("User {} holds {} shares of {} again, restoring access to chat {}", trader, new_balance, subject, chat);
+                match get_open_rejoin_token(pool, &user.telegram_id, chat).await? {
+                    Some(token) => ("rejoin_link", Some(token)),
+                    // Muted members are still in the group
+                    None => ("restore", None),
+                }
+            }
+            Enforcement::Unchanged => continue,
+        };
+        set_group_banned(&mut *conn, agent, trader, chain, action == Enforcement::Restrict).await?;
+
+        pending.members.push(MemberAction {
+            action,
+            applied,
+            agent_name: bot_info.agent_name,
+            bot_token: bot_info.bot_token,
+            chat_id: bot_info.chat_group_id,
+            telegram_id: user.telegram_id.clone(),
+            enforcement_mode: bot_info.enforcement_mode,
+            rejoin_token,
+        });
+    }
+    Ok(pending)
+}
+
+// Telegram action decided for the linked member in one group
+#[derive(Debug)]
+struct MemberAction {
+    action: Enforcement,
+    // Action logged as moderation event: the enforcement mode, restore or rejoin_link
+    applied: &'static str,
+    agent_name: String,
+    bot_token: String,
+    chat_id: String,
+    telegram_id: String,
+    enforcement_mode: EnforcementMode,
+    // Token opened for a kick, or the open one whose link a restore sends
+    rejoin_token: Option<String>,
+}
+
+/// Discord and Telegram side of a balance change decided by [`enforce_balance`], to be applied
+/// once the transaction that decided it committed
+#[must_use]
+#[derive(Debug)]
+pub struct PendingEnforcement {
+    chain: ChainType,
+    trader: String,
+    subject: String,
+    new_balance: BigDecimal,
+    event_time: Option<OffsetDateTime>,
+    trace_id: String,
+    members: Vec<MemberAction>,
+}
+
+impl PendingEnforcement {
+    /// Update the Discord roles of the trader and act on their Telegram user in each group,
+    /// returns the number of groups whose access changed. Failures are logged and the group's
+    /// ban state is put back so a later balance change tries again, they never undo the trade
+    /// that caused them
+    pub async fn apply(self, pool: &PgPool, telegram: &dyn TelegramApi) -> usize {
+        // Discord users linked to the trader are gated whether or not they verified through Telegram
+        let discord = match pool.acquire().await {
+            Ok(mut conn) => enforce_discord_access(&mut conn, self.chain, &self.trader, &self.subject, &self.new_balance).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = discord {
+            warn!("Failed to enforce Discord access of {} on {}: {:?}", self.trader, self.subject, e);
+        }
+
+        let mut changed = 0;
+        for member in &self.members {
+            if let Err(e) = self.act(pool, telegram, member).await {
+                error!("Failed to {} user {} in chat {}: {:?}", member.applied, member.telegram_id, member.chat_id, e);
+                self.revert(pool, member).await;
+                continue;
+            }
+            self.record(pool, member).await;
+            changed += 1;
+        }
+        changed
+    }
+
+    async fn act(&self, pool: &PgPool, telegram: &dyn TelegramApi, member: &MemberAction) -> Result<()> {
+        let group = TelegramCommunity::new(telegram, &member.bot_token, &member.chat_id, member.enforcement_mode)
+            .tracked(pool, &member.agent_name);
+
+        match (member.action, &member.rejoin_token) {
+            (Enforcement::Restrict, _) => group.ban(&member.telegram_id).await?,
+            (Enforcement::Restore, Some(token)) => send_rejoin_link(&group, pool, token, &member.telegram_id).await?,
+            (Enforcement::Restore, None) => group.unban(&member.telegram_id).await?,
+            (Enforcement::Unchanged, _) => {}
+        }
+        Ok(())
+    }
+
+    // Undo the ban state committed for an action Telegram refused
+    async fn revert(&self, pool: &PgPool, member: &MemberAction) {
+        let restricted = member.action == Enforcement::Restrict;
+        let reset = match pool.acquire().await {
+            Ok(mut conn) => set_group_banned(&mut conn, &member.agent_name, &self.trader, self.chain, !restricted).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = reset {
+            error!("Failed to reset ban state of {} after a failed {}: {:?}", self.trader, member.applied, e);
+        }
+        if let (true, Some(token)) = (restricted, &member.rejoin_token) {
+            if let Err(e) = delete_rejoin_token(pool, token).await {
+                warn!("Failed to drop rejoin token of user {} in chat {}: {:?}", member.telegram_id, member.chat_id, e);
+            }
+        }
+    }
+
+    // Notify webhooks and log the applied action
+    async fn record(&self, pool: &PgPool, member: &MemberAction) {
+        let agent = member.agent_name.as_str();
+        let event = if member.action == Enforcement::Restrict { WebhookEvent::MemberBanned } else { WebhookEvent::MemberUnbanned };
+        let data = MemberEventData {
+            agent_name: agent.to_string(),
+            chat_id: member.chat_id.clone(),
+            telegram_id: member.telegram_id.clone(),
+            chain_type: self.chain,
+            address: self.trader.clone(),
+            subject: self.subject.clone(),
+            action: member.applied.to_string(),
+            balance: self.new_balance.to_string(),
+        };
+        let queued = match pool.acquire().await {
+            Ok(mut conn) => webhooks::enqueue(&mut conn, self.chain, &self.subject, event, &data).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = queued {
+            warn!("Failed to queue {} webhook of user {} for agent {}: {:?}", member.applied, member.telegram_id, agent, e);
+        }
+
+        // Logged per member so an erroneous window can be rolled back
+        let details = EnforcementDetails {
+            chain_type: self.chain,
+            address: self.trader.clone(),
+            subject: self.subject.clone(),
+            balance: self.new_balance.to_string(),
+        };
+        if let Err(e) = record_moderation_event(
+            pool,
+            agent,
+            &member.chat_id,
+            Some(&member.telegram_id),
+            member.applied,
+            serde_json::to_string(&details).ok(),
+            Some(&self.trace_id),
+        ).await {
+            warn!("Failed to log {} of user {} for agent {}: {:?}", member.applied, member.telegram_id, agent, e);
+        }
+
+        if let Some(event_time) = self.event_time {
+            let latency_ms = (OffsetDateTime::now_utc() - event_time).whole_milliseconds() as i64;
+            if let Err(e) = record_enforcement_latency(pool, self.chain, agent, member.applied, latency_ms).await {
+                warn!("Failed to record enforcement latency for agent {}: {:?}", agent, e);
+            }
+        }
+    }
+}
+
+/// Take a member's access to a group away the way its `mode` says: mute them, leave them
+/// read-only, or kick them out while letting them rejoin by link
+pub async fn restrict_member(
+    telegram: &dyn TelegramApi,
+    bot_token: &str,
+    chat_id: &str,
+    user_id: UserId,
+    mode: EnforcementMode,
+) -> Result<(), RequestError> {
+    match mode {
+        EnforcementMode::Mute | EnforcementMode::ViewOnly => {
+            telegram.restrict_chat_member(bot_token, chat_id, user_id, mode.restricted_permissions()).await
+        }
+        EnforcementMode::Kick => telegram.kick_chat_member(bot_token, chat_id, user_id).await,
+    }
+}
+
+// DM a kicked member a single-use invite back into the group
+async fn send_rejoin_link(group: &dyn CommunityPlatform, pool: &PgPool, token: &str, member_id: &str) -> Result<()> {
+    let expire_date = Utc::now() + chrono::Duration::seconds(REJOIN_LINK_TTL_SECS);
+    let link = group.invite(token, expire_date).await?;
+    group
+        .send_message(member_id, format!("You hold shares again, welcome back! This link lets you rejoin the group once: {}", link))
+        .await?;
+
+    mark_rejoin_link_sent(pool, token, &link).await?;
+    Ok(())
+}
+
+/// Outcome of undoing the enforcement of one member
+#[derive(Clone, Debug, Serialize)]
+pub struct RollbackItem {
+    pub event_id: i64,
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    /// Logged action being undone
+    pub action: String,
+    /// unmute, readmit (DM the rejoin link of a kicked member) or mute
+    pub undo: &'static str,
+    /// planned (dry run), applied, held (kill switch engaged) or failed
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Action restoring the state a member was in before a logged action
+fn undo_of(action: &str) -> Option<&'static str> {
+    match action {
+        "mute" | "view_only" => Some("unmute"),
+        "kick" => Some("readmit"),
+        "restore" | "rejoin_link" => Some("mute"),
+        _ => None,
+    }
+}
+
+async fn apply_undo(pool: &PgPool, telegram: &dyn TelegramApi, event: &EnforcementEvent, undo: &str) -> Result<()> {
+    let details: EnforcementDetails = serde_json::from_str(event.details.as_deref().unwrap_or_default())
+        .map_err(|_| anyhow!("Event has no enforcement details"))?;
+    let agent = event.agent_name.as_str();
+    let chat = event.chat_id.as_str();
+    // A restore is undone by muting, also in groups that kick members who sell out
+    let mode = match event.enforcement_mode {
+        EnforcementMode::Kick => EnforcementMode::Mute,
+        mode => mode,
+    };
+    let group = TelegramCommunity::new(telegram, &event.bot_token, chat, mode).tracked(pool, agent);
+
+    match undo {
+        "unmute" => {
+            group.unban(&event.telegram_id).await?;
+            set_group_banned(&mut *pool.acquire().await?, agent, &details.address, details.chain_type, false).await?;
+        }
+        "readmit" => {
+            let token = get_open_rejoin_token(pool, &event.telegram_id, chat)
+                .await?
+                .ok_or_else(|| anyhow!("No open rejoin token for the kicked member"))?;
+            send_rejoin_link(&group, pool, &token, &event.telegram_id).await?;
+            set_group_banned(&mut *pool.acquire().await?, agent, &details.address, details.chain_type, false).await?;
+        }
+        _ => {
+            group.ban(&event.telegram_id).await?;
+            set_group_banned(&mut *pool.acquire().await?, agent, &details.address, details.chain_type, true).await?;
+        }
+    }
+
+    let note = format!("Rollback of moderation event {}", event.id);
+    if let Err(e) = record_moderation_event(pool, agent, chat, Some(&event.telegram_id), &format!("rollback_{}", undo), Some(note), None).await {
+        warn!("Failed to log rollback of event {}: {:?}", event.id, e);
+    }
+    Ok(())
+}
+
+/// Undo the member mutes, kicks and restores logged between `from` and `to`.
+/// The log is replayed newest first, so each member ends up as they were before
+/// the window: only their oldest action in it is undone. With `dry_run` nothing
+/// is sent and the report lists what would change.
+pub async fn rollback_enforcement(
+    pool: &PgPool,
+    telegram: &dyn TelegramApi,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    agent_name: Option<&str>,
+    dry_run: bool,
+) -> Result<Vec<RollbackItem>> {
+    let events = get_enforcement_events(pool, from, to, agent_name).await?;
+
+    // Replaying in reverse, the last event seen for a member is the oldest one
+    let mut oldest: HashMap<(String, String), EnforcementEvent> = HashMap::new();
+    for event in events {
+        oldest.insert((event.chat_id.clone(), event.telegram_id.clone()), event);
+    }
+    let mut events: Vec<EnforcementEvent> = oldest.into_values().collect();
+    events.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+
+    let held = !dry_run && kill_switch::is_engaged(pool).await?;
+    let mut items = Vec::with_capacity(events.len());
+    for event in events {
+        let Some(undo) = undo_of(&event.action) else {
+            continue;
+        };
+
+        let (status, error) = if dry_run {
+            ("planned", None)
+        } else if held && undo == "mute" {
+            ("held", None)
+        } else {
+            match apply_undo(pool, telegram, &event, undo).await {
+                Ok(()) => ("applied", None),
+                Err(e) => {
+                    error!("Failed to roll back moderation event {}: {:?}", event.id, e);
+                    ("failed", Some(e.to_string()))
+                }
+            }
+        };
+
+        items.push(RollbackItem {
+            event_id: event.id,
+            agent_name: event.agent_name,
+            chat_id: event.chat_id,
+            telegram_id: event.telegram_id,
+            action: event.action,
+            undo,
+            status,
+            error,
+        });
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::api::{MockTelegramApi, Outage, TelegramCall};
+    use crate::bot::errors::{classify, TelegramErrorKind};
+
+    fn one() -> BigDecimal {
+        BigDecimal::from(DEFAULT_MIN_SHARES)
+    }
+
+    fn single(balance: u64, is_banned: bool, min_shares: &BigDecimal) -> Enforcement {
+        decide(SubjectRule::Any.admits([&BigDecimal::from(balance)], min_shares), is_banned)
+    }
+
+    #[test]
+    fn test_sold_out_is_restricted() {
+        assert_eq!(single(0, false, &one()), Enforcement::Restrict);
+        assert_eq!(single(0, true, &one()), Enforcement::Restrict);
+    }
+
+    #[test]
+    fn test_banned_holder_is_restored() {
+        assert_eq!(single(1, true, &one()), Enforcement::Restore);
+    }
+
+    #[test]
+    fn test_holder_is_unchanged() {
+        assert_eq!(single(3, false, &one()), Enforcement::Unchanged);
+    }
+
+    #[test]
+    fn test_subject_rules() {
+        let (none, some) = (BigDecimal::from(0), BigDecimal::from(2));
+        assert!(SubjectRule::Any.admits([&none, &some], &one()));
+        assert!(!SubjectRule::All.admits([&none, &some], &one()));
+        assert!(SubjectRule::All.admits([&some, &some], &one()));
+        assert!(!SubjectRule::Any.admits([&none, &none], &one()));
+    }
+
+    #[test]
+    fn test_min_shares_threshold() {
+        let five = BigDecimal::from(5);
+        assert_eq!(single(4, false, &five), Enforcement::Restrict);
+        assert_eq!(single(5, true, &five), Enforcement::Restore);
+        assert!(crosses_threshold(&BigDecimal::from(6), &BigDecimal::from(4), &five));
+        assert!(!crosses_threshold(&BigDecimal::from(1), &BigDecimal::from(4), &five));
+    }
+
+    #[test]
+    fn test_threshold_crossing() {
+        assert!(crosses_threshold(&BigDecimal::from(0), &BigDecimal::from(1), &one()));
+        assert!(crosses_threshold(&BigDecimal::from(2), &BigDecimal::from(0), &one()));
+        assert!(!crosses_threshold(&BigDecimal::from(2), &BigDecimal::from(5), &one()));
+        assert!(!crosses_threshold(&BigDecimal::from(5), &BigDecimal::from(1), &one()));
+        assert!(!crosses_threshold(&BigDecimal::from(0), &BigDecimal::from(0), &one()));
+    }
+
+    #[test]
+    fn test_threshold_crossing_of_several_groups() {
+        let thresholds = [one(), BigDecimal::from(10)];
+        // Only the second group's threshold is crossed
+        assert!(crosses_any_threshold(&BigDecimal::from(5), &BigDecimal::from(12), &thresholds));
+        assert!(crosses_any_threshold(&BigDecimal::from(12), &BigDecimal::from(5), &thresholds));
+        assert!(crosses_any_threshold(&BigDecimal::from(0), &BigDecimal::from(5), &thresholds));
+        assert!(!crosses_any_threshold(&BigDecimal::from(2), &BigDecimal::from(9), &thresholds));
+        assert!(!crosses_any_threshold(&BigDecimal::from(11), &BigDecimal::from(20), &thresholds));
+        assert!(!crosses_any_threshold(&BigDecimal::from(5), &BigDecimal::from(12), &[]));
+    }
+
+    #[test]
+    fn test_restored_permissions_stay_within_chat_defaults() {
+        assert_eq!(restored_permissions(None), member_permissions());
+        assert_eq!(restored_permissions(Some(ChatPermissions::all())), member_permissions());
+
+        // A group without polls or link previews keeps them off for restored members
+        let defaults = ChatPermissions::SEND_MESSAGES | ChatPermissions::SEND_MEDIA_MESSAGES | ChatPermissions::INVITE_USERS;
+        let restored = restored_permissions(Some(defaults));
+        assert_eq!(restored, ChatPermissions::SEND_MESSAGES | ChatPermissions::SEND_MEDIA_MESSAGES);
+        assert!(!restored.contains(ChatPermissions::SEND_POLLS));
+    }
+
+    #[tokio::test]
+    async fn test_restrict_member_follows_mode() {
+        let telegram = MockTelegramApi::new();
+        restrict_member(&telegram, "token", "-100", UserId(42), EnforcementMode::Mute).await.unwrap();
+        restrict_member(&telegram, "token", "-100", UserId(43), EnforcementMode::Kick).await.unwrap();
+        restrict_member(&telegram, "token", "-100", UserId(44), EnforcementMode::ViewOnly).await.unwrap();
+        assert_eq!(telegram.calls(), vec![
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 42, permissions: ChatPermissions::empty() },
+            TelegramCall::Kick { chat_id: "-100".to_string(), user_id: 43 },
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 44, permissions: viewer_permissions() },
+        ]);
+        assert!(!viewer_permissions().contains(ChatPermissions::SEND_MESSAGES));
+    }
+
+    #[tokio::test]
+    async fn test_restrict_fails_during_outage() {
+        let telegram = MockTelegramApi::new();
+        let outages = [
+            (Outage::Timeout, TelegramErrorKind::Network),
+            (Outage::FloodWait(30), TelegramErrorKind::FloodWait),
+            (Outage::Forbidden, TelegramErrorKind::Forbidden),
+        ];
+        for (outage, kind) in outages {
+            telegram.set_outage(Some(outage));
+            let error = restrict_member(&telegram, "token", "-100", UserId(42), EnforcementMode::Mute).await.unwrap_err();
+            assert_eq!(classify(&error), kind);
+        }
+        assert!(telegram.calls().is_empty());
+        assert_eq!(telegram.failed_calls().len(), 3);
+
+        telegram.set_outage(None);
+        restrict_member(&telegram, "token", "-100", UserId(42), EnforcementMode::Mute).await.unwrap();
+        assert_eq!(telegram.calls(), vec![
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 42, permissions: ChatPermissions::empty() },
+        ]);
+    }
+
+    #[test]
+    fn test_escalation_ladder_validation() {
+        let step = |action, delay_secs| EscalationStep { action, delay_secs };
+        let ladder = [
+            step(EscalationAction::Warn, 0),
+            step(EscalationAction::ReadOnly, 3600),
+            step(EscalationAction::Kick, 86400),
+        ];
+        assert!(validate_ladder(&ladder).is_ok());
+        assert!(validate_ladder(&[step(EscalationAction::Kick, 0), step(EscalationAction::Kick, 60)]).is_err());
+        assert!(validate_ladder(&[step(EscalationAction::ReadOnly, 0), step(EscalationAction::Warn, 60)]).is_err());
+        assert!(validate_ladder(&[step(EscalationAction::Warn, -1)]).is_err());
+
+        let stored = r#"[{"action":"warn","delay_secs":0},{"action":"read_only","delay_secs":3600}]"#;
+        assert_eq!(parse_ladder(Some(stored)).map(|steps| steps.len()), Some(2));
+        assert_eq!(parse_ladder(Some("[]")), None);
+        assert_eq!(parse_ladder(None), None);
+    }
+}
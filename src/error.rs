@@ -1,28 +1,118 @@
-use actix_web::{HttpResponse, ResponseError, http::StatusCode};
-use derive_more::Display;
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use thiserror::Error;
 
-#[derive(Debug, Display)]
+/// Errors surfaced by the HTTP API and the chain implementations
+#[derive(Debug, Error)]
 pub enum AppError {
-    #[display(fmt = "Internal Server Error")]
-    InternalError,
-    #[display(fmt = "Resource not found: {}", _0)]
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
     NotFound(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("Invalid Telegram user id: {0}")]
+    InvalidTelegramId(String),
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid bot token: {0}")]
+    InvalidBotToken(String),
+    #[error("Bot cannot moderate the group: {0}")]
+    BotNotAdmin(String),
+    #[error("{0}")]
+    AgentExists(String),
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Telegram request failed: {0}")]
+    Telegram(#[from] teloxide::RequestError),
+    #[error("Chain request failed: {0}")]
+    Chain(#[from] anyhow::Error),
+    #[error("Batch {sequence} was already applied, high-water mark is {high_water_mark}")]
+    DuplicateBatch { sequence: i64, high_water_mark: i64 },
+    #[error("Batch {sequence} is out of order, expected {expected}")]
+    OutOfOrderBatch { sequence: i64, expected: i64 },
+    #[error("Too many requests, retry in {retry_after_secs} seconds")]
+    RateLimited { retry_after_secs: u64 },
+    #[error("Server is busy, retry in {retry_after_secs} seconds")]
+    Overloaded { retry_after_secs: u64 },
 }
 
-impl ResponseError for AppError {
-    fn error_response(&self) -> HttpResponse {
+impl AppError {
+    /// Machine-readable code returned alongside the message
+    pub fn code(&self) -> &'static str {
         match self {
-            AppError::InternalError => HttpResponse::InternalServerError()
-                .json(json!({"error": "Internal Server Error"})),
-            AppError::NotFound(ref message) => HttpResponse::NotFound()
-                .json(json!({"error": format!("Resource not found: {}", message)})),
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::InvalidSignature(_) => "invalid_signature",
+            AppError::InvalidTelegramId(_) => "invalid_telegram_id",
+            AppError::InvalidAddress(_) => "invalid_address",
+            AppError::InvalidBotToken(_) => "invalid_bot_token",
+            AppError::BotNotAdmin(_) => "bot_not_admin",
+            AppError::AgentExists(_) => "agent_exists",
+            AppError::Config(_) => "config_error",
+            AppError::Database(_) => "database_error",
+            AppError::Telegram(_) => "telegram_error",
+            AppError::Chain(_) => "chain_error",
+            AppError::DuplicateBatch { .. } => "duplicate_batch",
+            AppError::OutOfOrderBatch { .. } => "out_of_order_batch",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::Overloaded { .. } => "overloaded",
         }
     }
+}
 
+#[derive(Serialize)]
+struct ErrorBody {
+    success: bool,
+    error: String,
+    code: &'static str,
+}
+
+impl ResponseError for AppError {
     fn status_code(&self) -> StatusCode {
         match self {
-            AppError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::BadRequest(_)
+            | AppError::InvalidSignature(_)
+            | AppError::InvalidTelegramId(_)
+            | AppError::InvalidAddress(_)
+            | AppError::InvalidBotToken(_)
+            | AppError::BotNotAdmin(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::DuplicateBatch { .. } | AppError::OutOfOrderBatch { .. } | AppError::AgentExists(_) => StatusCode::CONFLICT,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Telegram(_) | AppError::Chain(_) => StatusCode::BAD_GATEWAY,
+            AppError::Config(_) | AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
-} 
\ No newline at end of file
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let AppError::RateLimited { retry_after_secs } | AppError::Overloaded { retry_after_secs } = self {
+            response.insert_header((header::RETRY_AFTER, retry_after_secs.to_string()));
+        }
+        response.json(ErrorBody {
+            success: false,
+            error: self.to_string(),
+            code: self.code(),
+        })
+    }
+}
+
+/// Parse a Telegram user id as stored in user_mappings and sent as the challenge
+pub fn parse_telegram_id(telegram_id: &str) -> Result<u64, AppError> {
+    telegram_id
+        .parse()
+        .map_err(|_| AppError::InvalidTelegramId(telegram_id.to_string()))
+}
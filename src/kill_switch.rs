@@ -0,0 +1,72 @@
+//! Emergency stop for outbound restrict/kick actions.
+//!
+//! The switch is engaged either for the whole process with the `KILL_SWITCH`
+//! environment variable, or at runtime for every instance through the
+//! `kill_switch` service flag set by the admin API. While engaged,
+//! [`crate::enforcement::handle_balance_change`] records the actions it would
+//! have taken instead of sending them to Telegram.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::Serialize;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use crate::db::operations::{get_service_flag, set_service_flag};
+
+// Name of the service flag holding the runtime switch
+const KILL_SWITCH_FLAG: &str = "kill_switch";
+
+static ENV_ENGAGED: AtomicBool = AtomicBool::new(false);
+
+/// Where the kill switch stands and who engaged it
+#[derive(Clone, Debug, Serialize)]
+pub struct KillSwitchState {
+    pub engaged: bool,
+    /// Engaged by the KILL_SWITCH environment variable, cannot be released through the API
+    pub env_engaged: bool,
+    pub flag_engaged: bool,
+    pub reason: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+/// Apply the KILL_SWITCH environment setting, called once at startup
+pub fn set_env_engaged(engaged: bool) {
+    ENV_ENGAGED.store(engaged, Ordering::Relaxed);
+    if engaged {
+        warn!("Kill switch engaged by KILL_SWITCH, restrict and kick actions are held");
+    }
+}
+
+/// Current state of the switch
+pub async fn state(pool: &PgPool) -> Result<KillSwitchState, sqlx::Error> {
+    let env_engaged = ENV_ENGAGED.load(Ordering::Relaxed);
+    let (flag_engaged, reason, updated_at) = match get_service_flag(pool, KILL_SWITCH_FLAG).await? {
+        Some((enabled, reason, updated_at)) => (enabled, reason, Some(updated_at)),
+        None => (false, None, None),
+    };
+
+    Ok(KillSwitchState {
+        engaged: env_engaged || flag_engaged,
+        env_engaged,
+        flag_engaged,
+        reason,
+        updated_at,
+    })
+}
+
+/// Whether restrict/kick actions must be held back
+pub async fn is_engaged(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    if ENV_ENGAGED.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    Ok(state(pool).await?.flag_engaged)
+}
+
+/// Engage or release the runtime switch for every instance
+pub async fn set_engaged(pool: &PgPool, engaged: bool, reason: Option<&str>) -> Result<KillSwitchState, sqlx::Error> {
+    set_service_flag(pool, KILL_SWITCH_FLAG, engaged, reason).await?;
+    info!("Kill switch {} ({})", if engaged { "engaged" } else { "released" }, reason.unwrap_or("no reason given"));
+    state(pool).await
+}
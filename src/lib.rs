@@ -0,0 +1,48 @@
+//! Token-gated Telegram groups backed by on-chain share ownership.
+//!
+//! The crate can be embedded as a library, the `alice_ai_server` binary only
+//! wires these modules together:
+//!
+//! - [`config`]: settings read from the environment, see [`AppConfig`]
+//! - [`error`]: API errors, see [`AppError`]
+//! - [`block_chain`]: chain implementations, with
+//!   [`block_chain::create_blockchain`] as the registry of supported chains
+//! - [`db`]: persistence
+//! - [`enforcement`]: group access rules
+//! - [`kill_switch`]: emergency stop of enforcement
+//! - [`bot`]: bot supervision
+//! - [`platform`]: Discord servers gated like Telegram groups
+//! - [`routes`]: the HTTP API
+//! - [`tls`]: HTTPS certificates of the API
+//! - [`services`]: member verification
+//! - [`pricing`]: share valuation
+//! - [`oracle`]: cached native token prices and gas
+//! - [`metrics`]: Prometheus metrics
+//! - [`logging`]: log output
+//! - [`webhooks`]: signed event callbacks
+//! - [`shutdown`]: stopping long running loops
+//! - [`backfill`]: replaying chain history
+//! - [`replay`]: decoding stored Sui events again
+
+pub mod backfill;
+pub mod block_chain;
+pub mod bot;
+pub mod config;
+pub mod db;
+pub mod enforcement;
+pub mod error;
+pub mod kill_switch;
+pub mod logging;
+pub mod metrics;
+pub mod oracle;
+pub mod platform;
+pub mod pricing;
+pub mod replay;
+pub mod routes;
+pub mod services;
+pub mod shutdown;
+pub mod tls;
+pub mod webhooks;
+
+pub use config::AppConfig;
+pub use error::AppError;
@@ -0,0 +1,129 @@
+//! Log output setup.
+//!
+//! Everything logs through `tracing`. Levels are filtered with `RUST_LOG`
+//! (default `info`) and `LOG_FORMAT=json` switches to one JSON object per line.
+//! Bot tokens and EVM signatures are masked on the way out, so an error that
+//! embeds a Telegram API URL never leaks the token whichever module logged it.
+//! Work caused by a chain event runs in a span carrying its [`new_trace_id`], so
+//! the lines of one trade and the Telegram actions it led to can be found together.
+
+use std::io::{self, Write};
+
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::bot::mask_token;
+
+// Shortest digit run taken for a bot id and secret taken for a bot token
+const MIN_BOT_ID_LEN: usize = 5;
+const MIN_TOKEN_SECRET_LEN: usize = 30;
+// A 65 byte ECDSA signature is 130 hex chars
+const MIN_SIGNATURE_HEX_LEN: usize = 128;
+
+/// Install the global subscriber, call once at startup
+pub fn init(json: bool) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(|| RedactingWriter(io::stdout()));
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+/// Id tying a chain event to the escalations and moderation events it causes
+pub fn new_trace_id() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+// Length of the bot token `<bot id>:<secret>` at the start of `bytes`
+fn token_len(bytes: &[u8]) -> Option<usize> {
+    let id_len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    if id_len < MIN_BOT_ID_LEN || bytes.get(id_len) != Some(&b':') {
+        return None;
+    }
+    let secret_len = bytes[id_len + 1..]
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_' || **b == b'-')
+        .count();
+    (secret_len >= MIN_TOKEN_SECRET_LEN).then_some(id_len + 1 + secret_len)
+}
+
+// Length of the 0x prefixed signature at the start of `bytes`
+fn signature_len(bytes: &[u8]) -> Option<usize> {
+    if !bytes.starts_with(b"0x") {
+        return None;
+    }
+    let hex_len = bytes[2..].iter().take_while(|b| b.is_ascii_hexdigit()).count();
+    (hex_len >= MIN_SIGNATURE_HEX_LEN).then_some(2 + hex_len)
+}
+
+/// Mask bot tokens and hex signatures in a log line
+pub fn redact(line: &str) -> String {
+    let bytes = line.as_bytes();
+    let mut redacted = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < line.len() {
+        // Never start a match halfway through a number
+        if i == 0 || !bytes[i - 1].is_ascii_digit() {
+            if let Some(len) = token_len(&bytes[i..]) {
+                redacted.push_str(&mask_token(&line[i..i + len]));
+                i += len;
+                continue;
+            }
+            if let Some(len) = signature_len(&bytes[i..]) {
+                redacted.push_str("0x****");
+                i += len;
+                continue;
+            }
+        }
+        let ch = line[i..].chars().next().unwrap_or_default();
+        redacted.push(ch);
+        i += ch.len_utf8();
+    }
+    redacted
+}
+
+// Writes every formatted event through `redact`
+struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "123456789:AAHdqTcvCH1vGWJxfSeofSAs0K5PALDsawx";
+
+    #[test]
+    fn test_redacts_bot_token_in_url() {
+        let line = format!("error sending request for url (https://api.telegram.org/bot{}/sendMessage)", TOKEN);
+        let redacted = redact(&line);
+        assert!(!redacted.contains(TOKEN));
+        assert!(redacted.contains("/bot123456789:****sawx/sendMessage"));
+    }
+
+    #[test]
+    fn test_redacts_signature() {
+        let signature = format!("0x{}", "ab".repeat(65));
+        let redacted = redact(&format!("signature={} user=0x{}", signature, "12".repeat(20)));
+        assert_eq!(redacted, format!("signature=0x**** user=0x{}", "12".repeat(20)));
+    }
+
+    #[test]
+    fn test_keeps_ordinary_text() {
+        let line = "Syncing blocks 1200000 to 1201000 for monad, tx 0xdeadbeef at 12:30:45";
+        assert_eq!(redact(line), line);
+    }
+}
@@ -1,138 +1,241 @@
-mod block_chain;
-mod db;
-mod routes;
-
-use std::env;
 use actix_cors::Cors;
-use actix_web::{App, HttpServer, web};
+use std::time::Instant;
+use actix_web::dev::Service;
+use actix_web::http::header::HeaderName;
+use actix_web::{middleware, App, HttpServer, web};
 use dotenv::dotenv;
-use std::sync::Arc;
-use std::time::Duration;
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use crate::routes::signature::handle_verify;
-use crate::routes::agent::{handle_add_tg_bot,get_agents,get_agent_by_name,get_agent_detail};
-use crate::routes::user::get_user_shares_handler;
-const ABI: &str = r#"[	{
-		"inputs": [
-			{
-				"internalType": "address",
-				"name": "",
-				"type": "address"
-			},
-			{
-				"internalType": "address",
-				"name": "",
-				"type": "address"
-			}
-		],
-		"name": "sharesBalance",
-		"outputs": [
-			{
-				"internalType": "uint256",
-				"name": "",
-				"type": "uint256"
-			}
-		],
-		"stateMutability": "view",
-		"type": "function"
-	}]"#;
-
-#[derive(Clone)]
-struct AppConfig {
-    telegram_bot_token: String,
-    telegram_group_id: String,
-    shares_contract: String,
-    chain_rpc: String,
-    database_url: String,
-    start_block: u64,
-    // Sui chain configuration
-    sui_rpc: Option<String>,
-    sui_contract: Option<String>,
-    sui_shares_trading_object_id: Option<String>,
-}
-
-use crate::block_chain::monad::sync_trade_events;
+use sqlx::postgres::PgPoolOptions;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use alice_ai_server::AppConfig;
+use alice_ai_server::backfill::{run_backfill, BackfillArgs, USAGE};
+use alice_ai_server::replay::{run_replay, ReplayArgs, USAGE as REPLAY_USAGE};
+use alice_ai_server::block_chain::sync_trade_events;
+use alice_ai_server::block_chain::reconcile::reconcile_loop;
+use alice_ai_server::block_chain::trade::sync_share_decimals;
+use alice_ai_server::bot::BotManager;
+use alice_ai_server::bot::cleanup::message_cleanup_loop;
+use alice_ai_server::bot::escalation::escalation_loop;
+use alice_ai_server::bot::onboarding::onboarding_loop;
+use alice_ai_server::bot::recovery::recover_verifications_on_startup;
+use alice_ai_server::bot::unverified::verification_timeout_loop;
+use alice_ai_server::db::run_migrations;
+use alice_ai_server::db::retention::{retention_loop, RetentionStats};
+use alice_ai_server::kill_switch;
+use alice_ai_server::logging;
+use alice_ai_server::metrics;
+use alice_ai_server::oracle::{oracle_loop, PriceOracle};
+use alice_ai_server::routes;
+use alice_ai_server::routes::concurrency::ConcurrencyLimiter;
+use alice_ai_server::routes::rate_limit::RateLimiter;
+use alice_ai_server::routes::response::{request_id, REQUEST_ID_HEADER};
+use alice_ai_server::tls::load_tls_config;
+use alice_ai_server::webhooks::webhook_delivery_loop;
+use tracing::{error, info, info_span, warn, Instrument};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    let config = AppConfig {
-        telegram_bot_token: env::var("TELEGRAM_BOT_TOKEN")
-            .expect("TELEGRAM_BOT_TOKEN not set"),
-        telegram_group_id: env::var("TELEGRAM_GROUP_ID")
-            .expect("TELEGRAM_GROUP_ID not set"),
-        shares_contract: env::var("SHARES_CONTRACT_ADDRESS")
-            .expect("SHARES_CONTRACT_ADDRESS not set"),
-        chain_rpc: env::var("CHAIN_RPC")
-            .expect("CHAIN_RPC not set"),
-        database_url: env::var("DATABASE_URL")
-            .expect("DATABASE_URL not set"),
-        start_block: env::var("START_BLOCK")
-            .expect("START_BLOCK not set")
-            .parse()
-            .expect("START_BLOCK must be a number"),
-        sui_rpc: env::var("SUI_RPC").ok().map(|s| s),
-        sui_contract: env::var("SUI_CONTRACT").ok().map(|s| s),
-        sui_shares_trading_object_id: env::var("SUI_SHARES_TRADING_OBJECT_ID").ok().map(|s| s),
-    };
-    
+    let mut config = AppConfig::from_env();
+    logging::init(config.log_json);
+
     // Initialize database connection pool
     let pool = PgPoolOptions::new()
         .max_connections(5)
         .connect(&config.database_url)
         .await
         .expect("Failed to connect to database");
-    
-    // Initialize database tables
-    //init_db(&pool).await.expect("Failed to initialize database");
-    
-    
-    
-    // Set up signal handler for graceful shutdown
-    let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel::<()>(1);
-    
+
+    // Bring the schema up to date before anything queries it
+    run_migrations(&pool).await.expect("Failed to run database migrations");
+    config.load_evm_chains(&pool).await.expect("Failed to load EVM chain configuration");
+
+    // `backfill` replays chain history and `replay-sui` stored Sui events, both exit without starting the server
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("backfill") {
+        let backfill_args = match BackfillArgs::parse(&args[1..]) {
+            Ok(backfill_args) => backfill_args,
+            Err(e) => {
+                eprintln!("{}\n{}", e, USAGE);
+                std::process::exit(2);
+            }
+        };
+        if let Err(e) = run_backfill(config, &pool, &backfill_args).await {
+            error!("Backfill failed: {:?}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("replay-sui") {
+        let replay_args = match ReplayArgs::parse(&args[1..]) {
+            Ok(replay_args) => replay_args,
+            Err(e) => {
+                eprintln!("{}\n{}", e, REPLAY_USAGE);
+                std::process::exit(2);
+            }
+        };
+        if let Err(e) = run_replay(&config, &pool, &replay_args).await {
+            error!("Replay failed: {:?}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    kill_switch::set_env_engaged(config.kill_switch);
+
+    if config.admin_api_key.is_none() {
+        warn!("ADMIN_API_KEY is not set, administrative routes only accept stored API keys");
+    }
+
+    // Convert stored share amounts before any new trade is applied
+    sync_share_decimals(&pool, &config).await.expect("Failed to rescale share decimals");
+
+    // Cancelled on Ctrl+C, every background loop stops at its next checkpoint
+    let shutdown = CancellationToken::new();
+    let tasks = TaskTracker::new();
+
+    // Start garbage collection of stale rows
+    let retention_stats = RetentionStats::default();
+    tasks.spawn(retention_loop(
+        pool.clone(),
+        config.gc_interval_secs,
+        config.gc_batch_size,
+        retention_stats.clone(),
+        shutdown.clone(),
+    ));
+
+    // Start refreshing native price and gas data
+    let price_oracle = PriceOracle::default();
+    tasks.spawn(oracle_loop(price_oracle.clone(), config.clone(), shutdown.clone()));
+
+    // Start correcting local balances that drifted from the chain
+    tasks.spawn(reconcile_loop(pool.clone(), config.clone(), shutdown.clone()));
+
+    // Start a bot for every registered agent
+    let bot_manager = BotManager::new(
+        pool.clone(),
+        config.sign_page_url.clone(),
+        config.prompt_ttl_secs,
+        config.verify_session_ttl_secs,
+        config.branding.clone(),
+        config.bot_lanes,
+        config.bot_lane_capacity,
+        shutdown.clone(),
+    );
+    if let Err(e) = bot_manager.start_all().await {
+        error!("Failed to start Telegram bots: {:?}", e);
+    }
+
+    // Admit or re-prompt members whose verification was in flight when the server last stopped
+    tasks.spawn(recover_verifications_on_startup(pool.clone(), bot_manager.lanes().clone(), config.clone()));
+
+    // Start deleting expired bot prompts
+    tasks.spawn(message_cleanup_loop(pool.clone(), config.message_cleanup_interval_secs, shutdown.clone()));
+
+    // Start removing members who never verified
+    tasks.spawn(verification_timeout_loop(pool.clone(), config.clone(), shutdown.clone()));
+
+    // Start applying escalation ladders to holders who sold out
+    tasks.spawn(escalation_loop(pool.clone(), config.clone(), shutdown.clone()));
+
+    // Start sending onboarding messages to verified members
+    tasks.spawn(onboarding_loop(pool.clone(), config.onboarding_interval_secs, shutdown.clone()));
+
+    // Start posting queued events to agents' webhooks
+    tasks.spawn(webhook_delivery_loop(pool.clone(), config.webhook_interval_secs, shutdown.clone()));
+
     // Handle Ctrl+C signal
-    let shutdown_tx_clone = shutdown_tx.clone();
+    let signal_shutdown = shutdown.clone();
     tokio::spawn(async move {
         match tokio::signal::ctrl_c().await {
             Ok(()) => {
-                println!("Received Ctrl+C signal, shutting down gracefully...");
-                let _ = shutdown_tx_clone.send(()).await;
+                info!("Received Ctrl+C signal, shutting down gracefully...");
+                signal_shutdown.cancel();
             }
             Err(err) => {
-                eprintln!("Error setting up Ctrl+C handler: {}", err);
+                error!("Error setting up Ctrl+C handler: {}", err);
             }
         }
     });
-    
+
+    let bind_addr = (config.http_bind_addr.clone(), config.http_port);
+    let tls_config = config.tls_paths()
+        .map(|(cert, key)| load_tls_config(cert, key).expect("Failed to load TLS configuration"));
+
+    let rate_limiter = RateLimiter::from_config(&config).await;
+    let concurrency_limiter = ConcurrencyLimiter::from_config(&config);
     let config_clone = config.clone();
     let pool_clone = pool.clone();
+    let server_bot_manager = bot_manager.clone();
     let http_server = HttpServer::new(move || {
         let cors = Cors::permissive();
         App::new()
+            // Inside the rate limiter, so refused clients never take a slot
+            .wrap(middleware::from_fn(routes::concurrency::limit_concurrency))
+            .wrap(middleware::from_fn(routes::rate_limit::rate_limit))
             .wrap(cors)
+            .wrap_fn(|req, srv| {
+                let started_at = Instant::now();
+                let method = req.method().to_string();
+                let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+                let request_id = request_id(&req);
+                let span = info_span!("http_request", %method, %route, request_id = %request_id.0);
+                let response = span.in_scope(|| srv.call(req));
+                async move {
+                    let mut response = response.await?;
+                    routes::deprecation::apply_headers(&route, response.headers_mut());
+                    if let Some(value) = request_id.header_value() {
+                        response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                    }
+                    let status = response.status().as_u16();
+                    let elapsed = started_at.elapsed();
+                    metrics::observe_request(&method, &route, status, elapsed);
+                    info!(status, elapsed_ms = elapsed.as_millis() as u64, "Request completed");
+                    Ok(response)
+                }
+                .instrument(span)
+            })
             .app_data(web::Data::new(config_clone.clone()))
             .app_data(web::Data::new(pool_clone.clone()))
-            .service(handle_verify)
-            .service(handle_add_tg_bot)
-            .service(get_agents)
-            .service(get_agent_by_name)
-            .service(get_agent_detail)
-            .service(get_user_shares_handler)
+            .app_data(web::Data::new(server_bot_manager.clone()))
+            .app_data(web::Data::new(price_oracle.clone()))
+            .app_data(web::Data::new(rate_limiter.clone()))
+            .app_data(web::Data::new(concurrency_limiter.clone()))
+            .configure(routes::configure)
     })
-        .bind("0.0.0.0:8088").unwrap()
+        // Ctrl+C is handled below so the server stops together with the other tasks
+        .disable_signals();
+    let http_server = match tls_config {
+        Some(tls_config) => {
+            info!("Serving HTTPS on {}:{}", bind_addr.0, bind_addr.1);
+            http_server.bind_rustls_0_22(bind_addr, tls_config)
+        },
+        None => {
+            info!("Serving HTTP on {}:{}", bind_addr.0, bind_addr.1);
+            http_server.bind(bind_addr)
+        },
+    }
+        .expect("Failed to bind HTTP server")
         .run();
-    
+
     // Create futures for all main tasks
+    let server_handle = http_server.handle();
     let server_future = http_server;
-    let sync_future = sync_trade_events(config, pool);
-    
+    let mut sync_task = tasks.spawn(sync_trade_events(config, pool, bot_manager.lanes().clone(), shutdown.clone()));
+
     // Run all tasks concurrently and terminate when either completes or shutdown signal received
     tokio::select! {
-        _ = server_future => println!("HTTP server terminated"),
-        _ = sync_future => println!("Blockchain sync process terminated"),
-        _ = shutdown_rx.recv() => println!("Shutdown signal received, terminating all tasks"),
+        _ = server_future => info!("HTTP server terminated"),
+        _ = &mut sync_task => info!("Blockchain sync process terminated"),
+        _ = shutdown.cancelled() => info!("Shutdown signal received, stopping all tasks"),
     }
-    
-    println!("Application shutdown complete");
-}
\ No newline at end of file
+
+    // Let in-flight requests finish, then wait for every loop to checkpoint and exit
+    shutdown.cancel();
+    server_handle.stop(true).await;
+    tasks.close();
+    tasks.wait().await;
+    bot_manager.wait_stopped().await;
+
+    info!("Application shutdown complete");
+}
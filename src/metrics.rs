@@ -0,0 +1,156 @@
+//! Prometheus metrics exposed on `/metrics`.
+//!
+//! Metrics live in the default registry and are updated where the work
+//! happens: sync progress in the sync status operations, trade events in
+//! [`crate::block_chain::trade`], verifications in the verify route,
+//! Telegram failures in [`crate::bot::errors`], reclaimed rows in
+//! [`crate::db::retention`] and RPC endpoint health in
+//! [`crate::block_chain::rpc`]. Request latency is recorded by
+//! [`observe_request`], installed as a middleware in `main`.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec, Encoder, HistogramVec,
+    IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use sqlx::PgPool;
+
+use crate::block_chain::ChainType;
+
+pub static SYNCED_BLOCK: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_sync_last_block", "Last block, slot or checkpoint synced per chain", &["chain"]).unwrap()
+});
+
+pub static SYNC_PROGRESS_TIME: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_sync_last_progress_timestamp_seconds", "Unix time sync progress was last saved per chain", &["chain"]).unwrap()
+});
+
+pub static CHAIN_HEAD_BLOCK: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_chain_head_block", "Latest block reported by the chain, where the chain exposes one", &["chain"]).unwrap()
+});
+
+pub static RPC_ENDPOINT_HEALTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_rpc_endpoint_health", "Health score of each RPC endpoint of a chain, 100 when healthy", &["chain", "endpoint"]).unwrap()
+});
+
+pub static RPC_FAILOVERS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_rpc_failovers_total", "RPC calls retried on another endpoint after one failed per chain", &["chain"]).unwrap()
+});
+
+pub static RETENTION_ROWS_DELETED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_retention_rows_deleted_total", "Rows reclaimed by the retention job per policy", &["policy"]).unwrap()
+});
+
+pub static TRADE_EVENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_trade_events_total", "Trade events applied per chain and result", &["chain", "result"]).unwrap()
+});
+
+pub static ENFORCEMENT_SKIPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_enforcement_skipped_total", "Trades not enforced because the balance stayed on the same side of the threshold", &["chain"]).unwrap()
+});
+
+pub static WEBHOOK_DELIVERIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_webhook_deliveries_total", "Webhook delivery attempts per event type and result", &["event", "result"]).unwrap()
+});
+
+pub static VERIFICATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_verifications_total", "Signature verification attempts per chain and result", &["chain", "result"]).unwrap()
+});
+
+pub static OPTIMISTIC_VERIFICATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "alice_optimistic_verifications_total",
+        "Members unmuted before their balance was confirmed per chain, and how the check ended",
+        &["chain", "result"]
+    )
+    .unwrap()
+});
+
+pub static BOT_LANE_QUEUE_DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_bot_lane_queue_depth", "Bot updates and verification grants queued per worker lane", &["lane"]).unwrap()
+});
+
+pub static BOT_LANE_WAIT: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "alice_bot_lane_wait_seconds",
+        "Time bot updates and verification grants waited in their lane before running",
+        &["lane"],
+        vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0]
+    )
+    .unwrap()
+});
+
+pub static BOT_LANE_BACKPRESSURE: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_bot_lane_backpressure_total", "Submissions that waited because their lane was full", &["lane"]).unwrap()
+});
+
+pub static TELEGRAM_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_telegram_errors_total", "Failed Telegram API calls per error class", &["kind"]).unwrap()
+});
+
+pub static RATE_LIMITED_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_rate_limited_requests_total", "Requests refused by the rate limiter per route and bucket", &["route", "scope"]).unwrap()
+});
+
+pub static HTTP_CONCURRENCY_LIMIT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_http_concurrency_limit", "Requests a concurrency limited route runs at once", &["route"]).unwrap()
+});
+
+pub static HTTP_IN_FLIGHT_REQUESTS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("alice_http_in_flight_requests", "Requests running on a concurrency limited route", &["route"]).unwrap()
+});
+
+pub static HTTP_QUEUED_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_http_queued_requests_total", "Requests that waited for a slot of a concurrency limited route", &["route"]).unwrap()
+});
+
+pub static HTTP_SHED_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!("alice_http_shed_requests_total", "Requests refused because a concurrency limited route stayed saturated", &["route"]).unwrap()
+});
+
+pub static DB_POOL_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("alice_db_pool_connections", "Open database connections").unwrap()
+});
+
+pub static DB_POOL_IDLE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("alice_db_pool_idle_connections", "Idle database connections").unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "alice_http_request_duration_seconds",
+        "HTTP request latency per route",
+        &["method", "route", "status"],
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]
+    )
+    .unwrap()
+});
+
+/// Record saved sync progress of a chain
+pub fn record_sync_progress(chain_type: ChainType, block: u64) {
+    SYNCED_BLOCK.with_label_values(&[chain_type.as_str()]).set(block as i64);
+    SYNC_PROGRESS_TIME.with_label_values(&[chain_type.as_str()]).set(time::OffsetDateTime::now_utc().unix_timestamp());
+}
+
+/// Record that a chain indexed by cursor (Sui) saved its position, it has no block to report
+pub fn record_cursor_progress(chain_type: ChainType) {
+    SYNC_PROGRESS_TIME.with_label_values(&[chain_type.as_str()]).set(time::OffsetDateTime::now_utc().unix_timestamp());
+}
+
+/// Record the latency of an HTTP request under its route pattern, so path parameters do not explode the labels
+pub fn observe_request(method: &str, route: &str, status: u16, elapsed: Duration) {
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method, route, &status.to_string()])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Render every metric in the Prometheus text format, sampling the pool first
+pub fn render(pool: &PgPool) -> Result<String, prometheus::Error> {
+    DB_POOL_CONNECTIONS.set(pool.size() as i64);
+    DB_POOL_IDLE.set(pool.num_idle() as i64);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+
+use crate::block_chain::{create_blockchain, ChainType};
+use crate::shutdown::sleep_or_shutdown;
+use crate::AppConfig;
+
+/// Latest cached price and gas data for one chain
+#[derive(Clone, Debug, Serialize)]
+pub struct OracleSnapshot {
+    pub chain_type: ChainType,
+    pub native_symbol: String,
+    pub native_price_usd: Option<f64>,
+    /// Gas price in the chain's smallest native unit (wei, MIST)
+    pub gas_price: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// In-memory cache of oracle snapshots, refreshed by `oracle_loop`
+#[derive(Clone, Default)]
+pub struct PriceOracle {
+    snapshots: Arc<RwLock<HashMap<ChainType, OracleSnapshot>>>,
+}
+
+impl PriceOracle {
+    pub fn get(&self, chain_type: ChainType) -> Option<OracleSnapshot> {
+        self.snapshots.read().unwrap().get(&chain_type).cloned()
+    }
+
+    fn set(&self, snapshot: OracleSnapshot) {
+        self.snapshots.write().unwrap().insert(snapshot.chain_type, snapshot);
+    }
+}
+
+// Coin id used to look up the native token price for a chain
+fn price_id(config: &AppConfig, chain_type: ChainType) -> Option<String> {
+    match chain_type {
+        ChainType::Monad | ChainType::Base | ChainType::Arbitrum => config.evm_chain(chain_type).and_then(|chain| chain.price_id.clone()),
+        ChainType::Sui => config.sui_price_id.clone(),
+        ChainType::Solana => config.solana_price_id.clone(),
+        ChainType::Aptos => config.aptos_price_id.clone(),
+    }
+}
+
+// Fetch a USD price from a CoinGecko compatible simple price API
+async fn fetch_native_price(client: &Client, price_api_url: &str, coin_id: &str) -> anyhow::Result<f64> {
+    let response: Value = client.get(price_api_url)
+        .query(&[("ids", coin_id), ("vs_currencies", "usd")])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    response.get(coin_id)
+        .and_then(|price| price.get("usd"))
+        .and_then(|usd| usd.as_f64())
+        .ok_or_else(|| anyhow::anyhow!("No USD price for {} in response", coin_id))
+}
+
+async fn refresh_chain(oracle: &PriceOracle, client: &Client, config: Arc<AppConfig>, chain_type: ChainType) {
+    let blockchain = match create_blockchain(chain_type, config.clone()) {
+        Ok(blockchain) => blockchain,
+        Err(e) => {
+            warn!("Cannot refresh oracle for {}: {}", chain_type, e);
+            return;
+        }
+    };
+
+    let gas_price = match blockchain.get_gas_price().await {
+        Ok(gas_price) => Some(gas_price.to_string()),
+        Err(e) => {
+            error!("Failed to refresh gas price for {}: {:?}", chain_type, e);
+            // Keep serving the last known value
+            oracle.get(chain_type).and_then(|s| s.gas_price)
+        }
+    };
+
+    let native_price_usd = match price_id(&config, chain_type) {
+        Some(coin_id) => match fetch_native_price(client, &config.price_api_url, &coin_id).await {
+            Ok(price) => Some(price),
+            Err(e) => {
+                error!("Failed to refresh native price for {}: {:?}", chain_type, e);
+                oracle.get(chain_type).and_then(|s| s.native_price_usd)
+            }
+        },
+        None => None,
+    };
+
+    oracle.set(OracleSnapshot {
+        chain_type,
+        native_symbol: blockchain.native_symbol().to_string(),
+        native_price_usd,
+        gas_price,
+        updated_at: Utc::now(),
+    });
+}
+
+// Periodically refresh price and gas data for every enabled chain
+pub async fn oracle_loop(oracle: PriceOracle, config: AppConfig, shutdown: CancellationToken) {
+    let config = Arc::new(config);
+    let client = Client::new();
+
+    while !shutdown.is_cancelled() {
+        for chain_type in &config.enabled_chains {
+            refresh_chain(&oracle, &client, config.clone(), *chain_type).await;
+        }
+        sleep_or_shutdown(&shutdown, Duration::from_secs(config.oracle_refresh_secs)).await;
+    }
+}
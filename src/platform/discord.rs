@@ -0,0 +1,228 @@
+//! [`CommunityPlatform`] of a Discord server, through the Discord REST API.
+//!
+//! Discord servers are gated with a role instead of per-member permissions: the role
+//! opens the holders' channels, [`DiscordCommunity::ban`] takes it away and
+//! [`DiscordCommunity::unban`] gives it back, members stay in the server either way.
+//! Users link a wallet with a signed challenge and prove their Discord account with an
+//! OAuth2 token checked by [`oauth_user`], [`enforce_discord_access`] then keeps the role
+//! in step with their balance like the Telegram enforcement does for groups.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
+use tracing::{info, warn};
+
+use super::{CommunityPlatform, Platform};
+use crate::block_chain::ChainType;
+use crate::db::models::{DiscordGate, DiscordServer};
+use crate::db::operations::{get_discord_gates, get_group_subject_balances, set_discord_role};
+
+const API_URL: &str = "https://discord.com/api/v10";
+// Longest an invite can stay valid, 7 days
+const MAX_INVITE_AGE_SECS: i64 = 604_800;
+
+/// An agent's Discord server, gated by `role_id`
+pub struct DiscordCommunity {
+    client: Client,
+    bot_token: String,
+    guild_id: String,
+    role_id: String,
+    invite_channel_id: String,
+}
+
+#[derive(Deserialize)]
+struct Invite {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct Channel {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Role {
+    id: String,
+}
+
+// Answer of GET /oauth2/@me, the user is only there with the identify scope
+#[derive(Deserialize)]
+struct Authorization {
+    application: Application,
+    user: Option<User>,
+}
+
+#[derive(Deserialize)]
+struct Application {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+}
+
+impl DiscordCommunity {
+    pub fn new(server: &DiscordServer) -> Self {
+        Self {
+            client: Client::new(),
+            bot_token: server.bot_token.clone(),
+            guild_id: server.guild_id.clone(),
+            role_id: server.role_id.clone(),
+            invite_channel_id: server.invite_channel_id.clone(),
+        }
+    }
+
+    // Authenticated call, failing on any non-2xx answer with Discord's error body
+    async fn request(&self, method: Method, path: &str, body: Option<Value>) -> Result<Response> {
+        let mut request = self
+            .client
+            .request(method.clone(), format!("{}{}", API_URL, path))
+            .header("Authorization", format!("Bot {}", self.bot_token));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Discord {} {} failed: {} {}", method, path, status, error));
+        }
+        Ok(response)
+    }
+
+    /// Whether the bot can see the server and its gated role, false when the role does not exist.
+    /// Fails when the token is rejected or the bot is not in the server
+    pub async fn has_role(&self) -> Result<bool> {
+        let roles: Vec<Role> = self.request(Method::GET, &format!("/guilds/{}/roles", self.guild_id), None).await?.json().await?;
+        Ok(roles.iter().any(|role| role.id == self.role_id))
+    }
+
+    fn role_path(&self, member_id: &str) -> String {
+        format!("/guilds/{}/members/{}/roles/{}", self.guild_id, member_id, self.role_id)
+    }
+}
+
+#[async_trait]
+impl CommunityPlatform for DiscordCommunity {
+    fn platform(&self) -> Platform {
+        Platform::Discord
+    }
+
+    async fn ban(&self, member_id: &str) -> Result<()> {
+        self.request(Method::DELETE, &self.role_path(member_id), None).await?;
+        Ok(())
+    }
+
+    async fn unban(&self, member_id: &str) -> Result<()> {
+        self.request(Method::PUT, &self.role_path(member_id), None).await?;
+        Ok(())
+    }
+
+    // Discord invites expire after a number of seconds, capped at 7 days; the name is not kept
+    async fn invite(&self, _name: &str, expire_date: DateTime<Utc>) -> Result<String> {
+        let max_age = (expire_date - Utc::now()).num_seconds().clamp(1, MAX_INVITE_AGE_SECS);
+        let invite: Invite = self
+            .request(
+                Method::POST,
+                &format!("/channels/{}/invites", self.invite_channel_id),
+                Some(json!({ "max_age": max_age, "max_uses": 1, "unique": true })),
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(format!("https://discord.gg/{}", invite.code))
+    }
+
+    async fn send_message(&self, member_id: &str, text: String) -> Result<()> {
+        let channel: Channel = self
+            .request(Method::POST, "/users/@me/channels", Some(json!({ "recipient_id": member_id })))
+            .await?
+            .json()
+            .await?;
+        self.request(Method::POST, &format!("/channels/{}/messages", channel.id), Some(json!({ "content": text })))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Discord user an OAuth2 access token with the `identify` scope was issued to by the application
+/// `client_id`. None when Discord rejects the token or it belongs to another application
+pub async fn oauth_user(access_token: &str, client_id: &str) -> Result<Option<String>> {
+    let response = Client::new().get(format!("{}/oauth2/@me", API_URL)).bearer_auth(access_token).send().await?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        let status = response.status();
+        let error = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Discord GET /oauth2/@me failed: {} {}", status, error));
+    }
+    let authorization: Authorization = response.json().await?;
+    if authorization.application.id != client_id {
+        return Ok(None);
+    }
+    Ok(authorization.user.map(|user| user.id))
+}
+
+/// Give or take the gated role of every Discord user linked to `trader` in the servers of agents
+/// gated by `subject`, after its balance changed to `new_balance`. Only crossings call Discord;
+/// users it cannot be reached for keep their stored role state and are tried again on the next
+/// change. Returns the number of users whose role changed
+pub async fn enforce_discord_access(
+    conn: &mut PgConnection,
+    chain: ChainType,
+    trader: &str,
+    subject: &str,
+    new_balance: &BigDecimal,
+) -> Result<usize> {
+    let mut changed = 0;
+    for gate in get_discord_gates(&mut *conn, chain, trader, subject).await? {
+        let balances = get_group_subject_balances(&mut *conn, &gate.server.agent_name, trader).await?;
+        let holds = gate.subject_rule.admits(
+            balances.iter().map(|(group_subject, balance)| if group_subject == subject { new_balance } else { balance }),
+            &gate.min_shares,
+        );
+        if holds == gate.has_role {
+            continue;
+        }
+        match apply_discord_gate(&gate, holds).await {
+            Ok(()) => {
+                set_discord_role(&mut *conn, &gate.server.agent_name, &gate.discord_id, holds).await?;
+                changed += 1;
+            }
+            Err(e) => warn!(
+                "Failed to update the Discord role of user {} for agent {}: {:?}",
+                gate.discord_id, gate.server.agent_name, e
+            ),
+        }
+    }
+    Ok(changed)
+}
+
+// Grant or revoke the role, telling members who lost it why
+async fn apply_discord_gate(gate: &DiscordGate, holds: bool) -> Result<()> {
+    let discord = DiscordCommunity::new(&gate.server);
+    if holds {
+        discord.unban(&gate.discord_id).await?;
+        info!("Granted Discord role of agent {} to user {} holding shares with {}", gate.server.agent_name, gate.discord_id, gate.address);
+        return Ok(());
+    }
+
+    discord.ban(&gate.discord_id).await?;
+    info!("Revoked Discord role of agent {} from user {}", gate.server.agent_name, gate.discord_id);
+    let text = format!(
+        "Wallet {} no longer holds enough shares for the holders' channels of {}. Buy back in to get them back.",
+        gate.address, gate.server.agent_name
+    );
+    // Users can close their DMs, the role is gone either way
+    if let Err(e) = discord.send_message(&gate.discord_id, text).await {
+        warn!("Failed to DM Discord user {}: {:?}", gate.discord_id, e);
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! Community platforms an agent gates by share ownership, behind [`CommunityPlatform`]
+//! so access is taken away and given back the same way whatever the platform:
+//! [`telegram::TelegramCommunity`] gates a Telegram group through the bot's permissions,
+//! [`discord::DiscordCommunity`] a Discord server through a role given to holders.
+
+pub mod discord;
+pub mod telegram;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Platform a gated community lives on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Telegram,
+    Discord,
+}
+
+impl Platform {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Telegram => "telegram",
+            Platform::Discord => "discord",
+        }
+    }
+}
+
+/// One gated community, acting with its agent's bot. Members are identified by their
+/// platform's user id
+#[async_trait]
+pub trait CommunityPlatform: Send + Sync {
+    fn platform(&self) -> Platform;
+
+    /// Take a member's access away, after they no longer hold enough shares
+    async fn ban(&self, member_id: &str) -> Result<()>;
+
+    /// Give a member their access back, after they hold enough shares again
+    async fn unban(&self, member_id: &str) -> Result<()>;
+
+    /// Create a single-use invite into the community valid until `expire_date`, returns its URL
+    async fn invite(&self, name: &str, expire_date: DateTime<Utc>) -> Result<String>;
+
+    /// DM a member
+    async fn send_message(&self, member_id: &str, text: String) -> Result<()>;
+}
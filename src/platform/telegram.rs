@@ -0,0 +1,109 @@
+//! [`CommunityPlatform`] of a Telegram group, through the [`TelegramApi`] calls
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use teloxide::types::{ChatId, UserId};
+use teloxide::RequestError;
+
+use super::{CommunityPlatform, Platform};
+use crate::bot::api::TelegramApi;
+use crate::bot::errors::track;
+use crate::enforcement::{restrict_member, EnforcementMode};
+
+/// An agent's Telegram group. Access is taken away the way the agent's enforcement mode says:
+/// kicked members are removed, the others keep reading with the mode's restricted permissions
+pub struct TelegramCommunity<'a> {
+    telegram: &'a dyn TelegramApi,
+    bot_token: String,
+    chat_id: String,
+    mode: EnforcementMode,
+    // Agent failed calls are recorded for, see [`TelegramCommunity::tracked`]
+    tracked: Option<(&'a PgPool, String)>,
+}
+
+impl<'a> TelegramCommunity<'a> {
+    pub fn new(telegram: &'a dyn TelegramApi, bot_token: &str, chat_id: &str, mode: EnforcementMode) -> Self {
+        Self { telegram, bot_token: bot_token.to_string(), chat_id: chat_id.to_string(), mode, tracked: None }
+    }
+
+    /// Record the errors of the group's calls against `agent_name`, classified like
+    /// [`crate::bot::errors::track`] does
+    pub fn tracked(mut self, pool: &'a PgPool, agent_name: &str) -> Self {
+        self.tracked = Some((pool, agent_name.to_string()));
+        self
+    }
+
+    async fn track<T>(&self, result: Result<T, RequestError>) -> Result<T> {
+        match &self.tracked {
+            Some((pool, agent_name)) => Ok(track(pool, agent_name, &self.chat_id, result).await?),
+            None => Ok(result?),
+        }
+    }
+}
+
+fn user_id(member_id: &str) -> Result<UserId> {
+    Ok(UserId(member_id.parse().with_context(|| format!("Invalid Telegram user id {}", member_id))?))
+}
+
+#[async_trait]
+impl CommunityPlatform for TelegramCommunity<'_> {
+    fn platform(&self) -> Platform {
+        Platform::Telegram
+    }
+
+    async fn ban(&self, member_id: &str) -> Result<()> {
+        let user_id = user_id(member_id)?;
+        self.track(restrict_member(self.telegram, &self.bot_token, &self.chat_id, user_id, self.mode).await).await
+    }
+
+    async fn unban(&self, member_id: &str) -> Result<()> {
+        let user_id = user_id(member_id)?;
+        self.track(self.telegram.restore_chat_member(&self.bot_token, &self.chat_id, user_id).await).await
+    }
+
+    async fn invite(&self, name: &str, expire_date: DateTime<Utc>) -> Result<String> {
+        self.track(self.telegram.create_invite_link(&self.bot_token, &self.chat_id, name.to_string(), 1, expire_date).await).await
+    }
+
+    async fn send_message(&self, member_id: &str, text: String) -> Result<()> {
+        let user_id = user_id(member_id)?;
+        self.track(self.telegram.send_message(&self.bot_token, ChatId(user_id.0 as i64), text).await).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::api::{MockTelegramApi, TelegramCall};
+    use crate::enforcement::member_permissions;
+
+    #[tokio::test]
+    async fn test_ban_follows_enforcement_mode() {
+        let telegram = MockTelegramApi::new();
+        TelegramCommunity::new(&telegram, "token", "-100", EnforcementMode::Mute).ban("42").await.unwrap();
+        TelegramCommunity::new(&telegram, "token", "-100", EnforcementMode::Kick).ban("42").await.unwrap();
+        TelegramCommunity::new(&telegram, "token", "-100", EnforcementMode::ViewOnly).ban("42").await.unwrap();
+        TelegramCommunity::new(&telegram, "token", "-100", EnforcementMode::Mute).unban("42").await.unwrap();
+        assert_eq!(telegram.calls(), vec![
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 42, permissions: EnforcementMode::Mute.restricted_permissions() },
+            TelegramCall::Kick { chat_id: "-100".to_string(), user_id: 42 },
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 42, permissions: EnforcementMode::ViewOnly.restricted_permissions() },
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 42, permissions: member_permissions() },
+        ]);
+        assert!(TelegramCommunity::new(&telegram, "token", "-100", EnforcementMode::Mute).ban("alice").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invite_and_dm() {
+        let telegram = MockTelegramApi::new();
+        let group = TelegramCommunity::new(&telegram, "token", "-100", EnforcementMode::Kick);
+        group.invite("rejoin", Utc::now()).await.unwrap();
+        group.send_message("42", "welcome back".to_string()).await.unwrap();
+        assert_eq!(telegram.calls(), vec![
+            TelegramCall::CreateInviteLink { chat_id: "-100".to_string(), name: "rejoin".to_string() },
+            TelegramCall::SendMessage { chat_id: ChatId(42), text: "welcome back".to_string() },
+        ]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Share prices and sell quotes of subjects.
+//!
+//! Shares trade on a quadratic bonding curve: the share bought at supply `n`
+//! costs `n² / SHARE_PRICE_DIVISOR` native tokens. The latest price of a subject
+//! is tracked from its last recorded trade event, whose supply is where quotes
+//! start on the curve and whose fees give the rate a seller pays. Subjects
+//! whose contract caps the supply run out of shares to buy once it is reached.
+
+use sqlx::types::BigDecimal;
+
+use crate::db::models::SubjectPrice;
+
+/// Sum of `i²` for `i` in `1..=n`, 0 for `n <= 0`
+fn sum_of_squares(n: &BigDecimal) -> BigDecimal {
+    let n = n.with_scale(0);
+    if n <= BigDecimal::from(0) {
+        return BigDecimal::from(0);
+    }
+    &n * (&n + BigDecimal::from(1)) * (BigDecimal::from(2) * &n + BigDecimal::from(1)) / BigDecimal::from(6)
+}
+
+/// Native tokens returned by the curve for selling `amount` shares at `supply`, before fees
+pub fn curve_sell_price(supply: &BigDecimal, amount: &BigDecimal, divisor: u64) -> BigDecimal {
+    let amount = amount.min(supply);
+    let one = BigDecimal::from(1);
+    let sold = sum_of_squares(&(supply - &one)) - sum_of_squares(&(supply - amount - &one));
+    sold / BigDecimal::from(divisor)
+}
+
+/// Share of the trade value the last trade paid in protocol and subject fees
+pub fn fee_rate(price: &SubjectPrice) -> BigDecimal {
+    if price.eth_amount <= BigDecimal::from(0) {
+        return BigDecimal::from(0);
+    }
+    (&price.protocol_fee + &price.subject_fee) / &price.eth_amount
+}
+
+/// Per-share price of the last trade in native tokens, `None` for a zero-share trade
+pub fn last_price(price: &SubjectPrice, native_decimals: u32) -> Option<BigDecimal> {
+    if price.share_amount <= BigDecimal::from(0) {
+        return None;
+    }
+    Some((&price.eth_amount / &price.share_amount * BigDecimal::new(1.into(), native_decimals as i64)).normalized())
+}
+
+/// Native tokens a holder would receive for selling `amount` shares now, net of fees
+pub fn sell_quote(price: &SubjectPrice, amount: &BigDecimal, divisor: u64) -> BigDecimal {
+    let gross = curve_sell_price(&price.supply, amount, divisor);
+    (&gross * (BigDecimal::from(1) - fee_rate(price))).normalized()
+}
+
+/// Shares of a subject still available to buy under its supply cap, `None` when uncapped
+pub fn available_supply(supply: &BigDecimal, supply_cap: Option<&BigDecimal>) -> Option<BigDecimal> {
+    supply_cap.map(|cap| (cap - supply).max(BigDecimal::from(0)))
+}
+
+/// Whether `amount` more shares can still be bought, always for an uncapped subject
+pub fn can_buy(supply: &BigDecimal, supply_cap: Option<&BigDecimal>, amount: &BigDecimal) -> bool {
+    available_supply(supply, supply_cap).is_none_or(|available| &available >= amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn price(supply: i64, share_amount: i64, eth_amount: &str, protocol_fee: &str, subject_fee: &str) -> SubjectPrice {
+        SubjectPrice {
+            subject: "subject".to_string(),
+            supply: BigDecimal::from(supply),
+            share_amount: BigDecimal::from(share_amount),
+            eth_amount: BigDecimal::from_str(eth_amount).unwrap(),
+            protocol_fee: BigDecimal::from_str(protocol_fee).unwrap(),
+            subject_fee: BigDecimal::from_str(subject_fee).unwrap(),
+            traded_at: time::OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_curve_sell_price() {
+        // Selling the 10th and 9th share: (9² + 8²) / 16000
+        let gross = curve_sell_price(&BigDecimal::from(10), &BigDecimal::from(2), 16000);
+        assert_eq!(gross, BigDecimal::from(145) / BigDecimal::from(16000));
+        // Never sells below zero supply
+        assert_eq!(
+            curve_sell_price(&BigDecimal::from(3), &BigDecimal::from(5), 1),
+            BigDecimal::from(5)
+        );
+    }
+
+    #[test]
+    fn test_sell_quote_net_of_fees() {
+        let last = price(101, 1, "1000", "50", "50");
+        let quote = sell_quote(&last, &BigDecimal::from(1), 1);
+        assert_eq!(quote, BigDecimal::from(9000));
+    }
+
+    #[test]
+    fn test_last_price_in_native_units() {
+        let last = price(10, 2, "3000000000000000000", "0", "0");
+        assert_eq!(last_price(&last, 18), Some(BigDecimal::from_str("1.5").unwrap()));
+        assert_eq!(last_price(&price(10, 0, "0", "0", "0"), 18), None);
+    }
+
+    #[test]
+    fn test_capped_supply() {
+        let cap = BigDecimal::from(100);
+        assert_eq!(available_supply(&BigDecimal::from(97), Some(&cap)), Some(BigDecimal::from(3)));
+        // Supply reported past the cap leaves nothing, not a negative amount
+        assert_eq!(available_supply(&BigDecimal::from(101), Some(&cap)), Some(BigDecimal::from(0)));
+        assert_eq!(available_supply(&BigDecimal::from(97), None), None);
+
+        assert!(can_buy(&BigDecimal::from(97), Some(&cap), &BigDecimal::from(3)));
+        assert!(!can_buy(&BigDecimal::from(98), Some(&cap), &BigDecimal::from(3)));
+        assert!(can_buy(&BigDecimal::from(1_000_000), None, &BigDecimal::from(3)));
+    }
+}
@@ -0,0 +1,175 @@
+//! `alice_ai_server replay-sui` subcommand.
+//!
+//! Decodes the raw bcs kept with stored Sui trade events again, with the
+//! current parser, and compares the result with the fields stored when the
+//! event was synced. Without `--apply` differences are only reported; with it
+//! each differing event is corrected and re-applied, reverting the balance and
+//! fees of the stored decoding and enforcing group access on the corrected
+//! balances, e.g. `alice_ai_server replay-sui --apply`. Events synced before
+//! raw payloads were kept cannot be replayed. Events the parser could not decode
+//! when they were synced are decoded again too; with `--apply` those that decode
+//! now are applied like a live sync would.
+
+use anyhow::Result;
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use crate::block_chain::sui::decode_raw_trade_event;
+use crate::block_chain::trade::{apply_trade_event, correct_trade_event, matches_stored};
+use crate::block_chain::ChainType;
+use crate::bot::api::TelegramBotApi;
+use crate::db::models::{EventLocation, RawEventPayload};
+use crate::db::operations::{delete_undecoded_trade_event, get_raw_trade_events, get_trade_event_for_update, get_undecoded_trade_events};
+use crate::AppConfig;
+
+pub const USAGE: &str = "Usage: alice_ai_server replay-sui [--apply] [--after-id <trade event id>]";
+
+// Stored events decoded per page
+const PAGE_SIZE: i64 = 500;
+
+/// Arguments of the replay-sui subcommand
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayArgs {
+    /// Correct differing events instead of only reporting them
+    pub apply: bool,
+    /// Only events stored after this trade event id
+    pub after_id: i64,
+}
+
+impl ReplayArgs {
+    /// Parse the arguments following `replay-sui`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--apply" => parsed.apply = true,
+                "--after-id" => {
+                    let value = args.next().ok_or("--after-id needs a value")?;
+                    parsed.after_id = value.parse().map_err(|_| format!("Invalid --after-id value {}", value))?;
+                }
+                _ => return Err(format!("Unknown argument {}", flag)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Outcome of a replay
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReplayStats {
+    pub checked: u64,
+    /// Events whose new decoding differs from the stored fields
+    pub differing: u64,
+    pub corrected: u64,
+    /// Raw payloads the current parser cannot decode
+    pub undecodable: u64,
+    /// Events kept undecoded by sync that the current parser decodes
+    pub decodable: u64,
+    /// Of those, events now applied
+    pub recovered: u64,
+}
+
+/// Replay every stored Sui event with a raw payload
+pub async fn run_replay(config: &AppConfig, pool: &PgPool, args: &ReplayArgs) -> Result<ReplayStats> {
+    let share_decimals = config.share_decimals(ChainType::Sui);
+    let mut stats = ReplayStats::default();
+    let mut after_id = args.after_id;
+
+    loop {
+        let events = get_raw_trade_events(pool, ChainType::Sui, after_id, PAGE_SIZE).await?;
+        let Some(last) = events.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        for raw in &events {
+            stats.checked += 1;
+            let event = match decode_raw_trade_event(&raw.bcs, &raw.bcs_encoding) {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("Cannot decode raw Sui event {} (trade event {}): {:?}", raw.event_id.as_deref().unwrap_or("-"), raw.id, e);
+                    stats.undecodable += 1;
+                    continue;
+                }
+            };
+
+            // A dry run only compares with the stored fields
+            if !args.apply {
+                let stored = get_trade_event_for_update(&mut *pool.acquire().await?, raw.id).await?;
+                if stored.is_some_and(|stored| !matches_stored(&stored, &event, share_decimals)) {
+                    info!("Trade event {} differs from its raw Sui event {}", raw.id, raw.event_id.as_deref().unwrap_or("-"));
+                    stats.differing += 1;
+                }
+                continue;
+            }
+            if correct_trade_event(pool, &TelegramBotApi, raw.id, &event, share_decimals).await? {
+                stats.differing += 1;
+                stats.corrected += 1;
+            }
+        }
+    }
+
+    replay_undecoded(pool, args, share_decimals, &mut stats).await?;
+
+    info!(
+        "Replayed {} Sui events: {} differ, {} corrected, {} undecodable, {} recovered of {} decodable",
+        stats.checked, stats.differing, stats.corrected, stats.undecodable, stats.recovered, stats.decodable
+    );
+    Ok(stats)
+}
+
+// Decode the events sync kept undecoded, applying those that decode now with --apply
+async fn replay_undecoded(pool: &PgPool, args: &ReplayArgs, share_decimals: u32, stats: &mut ReplayStats) -> Result<()> {
+    for undecoded in get_undecoded_trade_events(pool, ChainType::Sui).await? {
+        stats.checked += 1;
+        let event = match decode_raw_trade_event(&undecoded.bcs, &undecoded.bcs_encoding) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Still cannot decode Sui event {}:{}: {:?}", undecoded.tx_hash, undecoded.log_index, e);
+                stats.undecodable += 1;
+                continue;
+            }
+        };
+        stats.decodable += 1;
+        if !args.apply {
+            info!("Undecoded Sui event {}:{} decodes now, failed with: {}", undecoded.tx_hash, undecoded.log_index, undecoded.error);
+            continue;
+        }
+
+        let location = EventLocation {
+            block_number: None,
+            tx_hash: undecoded.tx_hash.clone(),
+            log_index: undecoded.log_index,
+            block_time: undecoded.block_time,
+        };
+        let raw = RawEventPayload {
+            event_id: undecoded.event_id.clone().unwrap_or_default(),
+            bcs: undecoded.bcs.clone(),
+            bcs_encoding: undecoded.bcs_encoding.clone(),
+        };
+        // Already stored events are skipped, so a replay interrupted here can run again
+        apply_trade_event(pool, &TelegramBotApi, ChainType::Sui, &location, &event, Some(&raw), share_decimals).await?;
+        delete_undecoded_trade_event(pool, ChainType::Sui, &undecoded.tx_hash, undecoded.log_index).await?;
+        stats.recovered += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_replay_args() {
+        assert_eq!(ReplayArgs::parse(&[]).unwrap(), ReplayArgs { apply: false, after_id: 0 });
+        assert_eq!(ReplayArgs::parse(&args("--apply --after-id 42")).unwrap(), ReplayArgs { apply: true, after_id: 42 });
+        assert!(ReplayArgs::parse(&args("--after-id")).is_err());
+        assert!(ReplayArgs::parse(&args("--after-id x")).is_err());
+        assert!(ReplayArgs::parse(&args("--chain sui")).is_err());
+    }
+}
@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::backfill::{self, BackfillEstimate};
+use crate::block_chain::ChainType;
+use crate::bot::api::TelegramBotApi;
+use crate::bot::{mask_token, BotManager, BotStatus};
+use crate::db::models::{ApiKeyInfo, HeldAction, JobRun, TelegramErrorSummary};
+use crate::db::operations::{
+    create_api_key, get_enforcement_latency_stats, get_held_actions, get_job_runs, get_telegram_error_summaries, list_api_keys, revoke_api_key,
+};
+use crate::enforcement::{rollback_enforcement, RollbackItem};
+use crate::error::AppError;
+use crate::kill_switch::{self, KillSwitchState};
+use crate::routes::auth::{generate_key, hash_key, key_prefix, ApiKey, KeyRole};
+
+#[derive(Debug, Serialize)]
+pub struct BotInfo {
+    pub agent_name: String,
+    pub bot_token: String,
+    pub chat_group_id: String,
+    pub chain_type: ChainType,
+    pub status: BotStatus,
+    pub last_update_at: Option<DateTime<Utc>>,
+    pub error_count: u64,
+    pub restart_count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BotListResponse {
+    pub bots: Vec<BotInfo>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestartBotResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/admin/bots")]
+async fn get_bots(
+    _api_key: ApiKey,
+    pool: web::Data<PgPool>,
+    bot_manager: web::Data<BotManager>,
+) -> impl Responder {
+    let rows = match sqlx::query!(
+        r#"SELECT agent_name, bot_token, chat_group_id, chain_type as "chain_type: ChainType" FROM telegram_bots ORDER BY agent_name"#
+    )
+        .fetch_all(pool.get_ref())
+        .await {
+        Ok(rows) => rows,
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(BotListResponse {
+                bots: Vec::new(),
+                success: false,
+                error: Some(format!("Database error: {}", e)),
+            });
+        }
+    };
+
+    let bots = rows.into_iter()
+        .map(|row| {
+            let state = bot_manager.status(&row.agent_name).unwrap_or_default();
+            BotInfo {
+                bot_token: mask_token(&row.bot_token),
+                agent_name: row.agent_name,
+                chat_group_id: row.chat_group_id,
+                chain_type: row.chain_type,
+                status: state.status,
+                last_update_at: state.last_update_at,
+                error_count: state.error_count,
+                restart_count: state.restart_count,
+            }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(BotListResponse {
+        bots,
+        success: true,
+        error: None,
+    })
+}
+
+#[post("/admin/bots/{agent_name}/restart")]
+async fn restart_bot(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+    bot_manager: web::Data<BotManager>,
+) -> Result<HttpResponse, AppError> {
+    let agent_name = path.into_inner();
+
+    let bot_info = sqlx::query!(
+        "SELECT bot_token, chat_group_id, delete_service_messages, enabled FROM telegram_bots WHERE agent_name = $1",
+        agent_name
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    if !bot_info.enabled {
+        return Err(AppError::BadRequest("Bot is disabled".to_string()));
+    }
+
+    // Always restart from the stored token so the bot picks up database changes
+    bot_manager.start(&agent_name, &bot_info.bot_token, &bot_info.chat_group_id, bot_info.delete_service_messages);
+    info!("Bot restarted for agent {}", agent_name);
+
+    Ok(HttpResponse::Ok().json(RestartBotResponse {
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TelegramErrorsResponse {
+    /// Failures per error class across all agents
+    pub totals: HashMap<String, i64>,
+    pub agents: Vec<TelegramErrorSummary>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/admin/telegram-errors")]
+async fn get_telegram_errors(
+    _api_key: ApiKey,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let agents = get_telegram_error_summaries(pool.get_ref()).await?;
+
+    let mut totals = HashMap::new();
+    for summary in &agents {
+        *totals.entry(summary.kind.clone()).or_insert(0) += summary.count;
+    }
+
+    Ok(HttpResponse::Ok().json(TelegramErrorsResponse {
+        totals,
+        agents,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LatencyQuery {
+    /// Window to aggregate, in hours (default 24, max 336)
+    pub hours: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyBucket {
+    /// Upper bound of the bucket in milliseconds, samples are counted cumulatively
+    pub le_ms: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentLatency {
+    pub chain_type: ChainType,
+    pub agent_name: String,
+    pub count: i64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: i64,
+    pub buckets: Vec<LatencyBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnforcementLatencyResponse {
+    pub hours: i64,
+    pub agents: Vec<AgentLatency>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/admin/enforcement-latency")]
+async fn get_enforcement_latency(
+    _api_key: ApiKey,
+    query: web::Query<LatencyQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let hours = query.hours.unwrap_or(24);
+    if !(1..=336).contains(&hours) {
+        return Err(AppError::BadRequest("hours must be between 1 and 336".to_string()));
+    }
+
+    let agents = get_enforcement_latency_stats(pool.get_ref(), hours).await?
+        .into_iter()
+        .map(|stats| AgentLatency {
+            buckets: vec![
+                LatencyBucket { le_ms: 1_000, count: stats.le_1s },
+                LatencyBucket { le_ms: 5_000, count: stats.le_5s },
+                LatencyBucket { le_ms: 15_000, count: stats.le_15s },
+                LatencyBucket { le_ms: 60_000, count: stats.le_60s },
+                LatencyBucket { le_ms: 300_000, count: stats.le_300s },
+                LatencyBucket { le_ms: 900_000, count: stats.le_900s },
+            ],
+            chain_type: stats.chain_type,
+            agent_name: stats.agent_name,
+            count: stats.count,
+            p50_ms: stats.p50_ms,
+            p90_ms: stats.p90_ms,
+            p99_ms: stats.p99_ms,
+            max_ms: stats.max_ms,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(EnforcementLatencyResponse {
+        hours,
+        agents,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KillSwitchRequest {
+    pub engaged: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KillSwitchResponse {
+    pub kill_switch: KillSwitchState,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/admin/kill-switch")]
+async fn get_kill_switch(
+    _api_key: ApiKey,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(KillSwitchResponse {
+        kill_switch: kill_switch::state(pool.get_ref()).await?,
+        success: true,
+        error: None,
+    }))
+}
+
+#[post("/admin/kill-switch")]
+async fn set_kill_switch(
+    _api_key: ApiKey,
+    data: web::Json<KillSwitchRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let state = kill_switch::set_engaged(pool.get_ref(), data.engaged, data.reason.as_deref()).await?;
+    Ok(HttpResponse::Ok().json(KillSwitchResponse {
+        kill_switch: state,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HeldActionsQuery {
+    /// Most recent actions to return (default 100, max 1000)
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HeldActionsResponse {
+    pub actions: Vec<HeldAction>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/admin/held-actions")]
+async fn get_held_actions_handler(
+    _api_key: ApiKey,
+    query: web::Query<HeldActionsQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let limit = query.limit.unwrap_or(100);
+    if !(1..=1000).contains(&limit) {
+        return Err(AppError::BadRequest("limit must be between 1 and 1000".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(HeldActionsResponse {
+        actions: get_held_actions(pool.get_ref(), limit).await?,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackfillsQuery {
+    /// Most recently started backfills to return (default 20, max 100)
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackfillProgress {
+    #[serde(flatten)]
+    pub run: JobRun,
+    #[serde(flatten)]
+    pub estimate: BackfillEstimate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackfillsResponse {
+    pub backfills: Vec<BackfillProgress>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/admin/backfills")]
+async fn get_backfills(
+    _api_key: ApiKey,
+    query: web::Query<BackfillsQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let limit = query.limit.unwrap_or(20);
+    if !(1..=100).contains(&limit) {
+        return Err(AppError::BadRequest("limit must be between 1 and 100".to_string()));
+    }
+
+    // Estimated at request time so running backfills get a live ETA
+    let now = OffsetDateTime::now_utc();
+    let backfills = get_job_runs(pool.get_ref(), backfill::JOB_KIND, limit).await?
+        .into_iter()
+        .map(|run| BackfillProgress { estimate: backfill::estimate(&run, now), run })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(BackfillsResponse {
+        backfills,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollbackQuery {
+    #[serde(with = "time::serde::rfc3339")]
+    pub from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to: OffsetDateTime,
+    pub agent: Option<String>,
+    /// Only report what would change, on unless explicitly turned off
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RollbackResponse {
+    pub dry_run: bool,
+    pub items: Vec<RollbackItem>,
+    pub applied: usize,
+    pub failed: usize,
+    pub held: usize,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[post("/admin/enforcement/rollback")]
+async fn rollback_enforcement_handler(
+    _api_key: ApiKey,
+    query: web::Query<RollbackQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    if query.from >= query.to {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+    let dry_run = query.dry_run.unwrap_or(true);
+
+    let items = rollback_enforcement(pool.get_ref(), &TelegramBotApi, query.from, query.to, query.agent.as_deref(), dry_run).await?;
+    let count = |status: &str| items.iter().filter(|item| item.status == status).count();
+    info!(
+        "Enforcement rollback {} to {} (agent {:?}, dry run {}): {} members",
+        query.from, query.to, query.agent, dry_run, items.len()
+    );
+
+    Ok(HttpResponse::Ok().json(RollbackResponse {
+        dry_run,
+        applied: count("applied"),
+        failed: count("failed"),
+        held: count("held"),
+        items,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub role: KeyRole,
+    /// Subjects a partner key may look up, every subject when omitted
+    pub subjects: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateApiKeyResponse {
+    /// Full key, only ever returned here
+    pub key: String,
+    pub api_key: ApiKeyInfo,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyListResponse {
+    pub api_keys: Vec<ApiKeyInfo>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokeApiKeyResponse {
+    pub api_key: ApiKeyInfo,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[post("/admin/api-keys")]
+async fn create_api_key_handler(
+    api_key: ApiKey,
+    data: web::Json<CreateApiKeyRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let name = data.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest("name must be 1 to 100 characters".to_string()));
+    }
+
+    let subjects = data.subjects.as_ref().map(|subjects| {
+        subjects.iter().map(|subject| subject.trim().to_string()).filter(|subject| !subject.is_empty()).collect::<Vec<_>>()
+    });
+    if data.role == KeyRole::Admin && subjects.is_some() {
+        return Err(AppError::BadRequest("Only partner keys can be limited to subjects".to_string()));
+    }
+
+    let key = generate_key();
+    let info = create_api_key(pool.get_ref(), name, data.role, subjects.as_deref(), &hash_key(&key), key_prefix(&key)).await?;
+    info!("API key {} ({}, {}) created with key {}", info.id, info.name, info.role.as_str(), api_key.name);
+    Ok(HttpResponse::Ok().json(CreateApiKeyResponse {
+        key,
+        api_key: info,
+        success: true,
+        error: None,
+    }))
+}
+
+#[get("/admin/api-keys")]
+async fn list_api_keys_handler(
+    _api_key: ApiKey,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(ApiKeyListResponse {
+        api_keys: list_api_keys(pool.get_ref()).await?,
+        success: true,
+        error: None,
+    }))
+}
+
+#[delete("/admin/api-keys/{id}")]
+async fn revoke_api_key_handler(
+    api_key: ApiKey,
+    path: web::Path<i64>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let info = revoke_api_key(pool.get_ref(), id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("API key {} not found", id)))?;
+    info!("API key {} ({}) revoked with key {}", info.id, info.name, api_key.name);
+    Ok(HttpResponse::Ok().json(RevokeApiKeyResponse {
+        api_key: info,
+        success: true,
+        error: None,
+    }))
+}
@@ -1,8 +1,34 @@
 use std::collections::HashMap;
-use actix_web::{get, HttpResponse, post, Responder, web};
+use actix_web::web::Redirect;
+use actix_web::{delete, get, post, put, web, Either};
 use serde::{Deserialize, Serialize, Serializer};
+use sqlx::types::BigDecimal;
 use sqlx::PgPool;
-use time::PrimitiveDateTime;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+use teloxide::Bot;
+use teloxide::prelude::Requester;
+use teloxide::types::{ChatId, ChatPermissions};
+use tracing::{error, info, warn};
+use crate::block_chain::ChainType;
+use crate::bot::api::TelegramBotApi;
+use crate::bot::quiet_hours::QuietHours;
+use crate::bot::unverified::{reprompt_unverified, RepromptStats, MAX_REPROMPT_MEMBERS};
+use crate::bot::BotManager;
+use crate::db::models::{AgentDeletion, ModerationEvent, SubjectTradeStats};
+use crate::db::operations::{
+    delete_agent_with_export_window, get_agent_by_alias, get_group_members, get_group_subjects, get_open_agent_deletion, get_latest_subject_prices, get_moderation_events, get_subject_leaderboard, get_subject_trade_series, get_subject_trade_stats,
+    record_moderation_event, rename_agent, set_group_subjects,
+};
+use crate::enforcement::{validate_ladder, EnforcementMode, EscalationStep, SubjectRule, DEFAULT_MIN_SHARES};
+use crate::error::AppError;
+use crate::pricing::{available_supply, can_buy};
+use crate::routes::auth::ApiKey;
+use crate::routes::response::ApiResponse;
+use crate::routes::subject::to_native_units;
+use crate::AppConfig;
+
+/// Days a deleted agent's data can still be exported before it is purged
+pub const EXPORT_WINDOW_DAYS: i32 = 14;
 
 // Custom datetime serialization function
 fn serialize_datetime<S>(
@@ -18,10 +44,14 @@ where
 
 #[derive(Debug, Serialize)]
 pub struct Agent {
+    /// Immutable id, stays the same when the agent is renamed
+    pub agent_id: String,
     pub agent_name: String,
     pub subject_address: String,
     #[serde(serialize_with = "serialize_datetime")]
     pub created_at: PrimitiveDateTime,
+    /// Verified members whose balances currently satisfy the group's gate
+    pub active_members: i32,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,9 +65,6 @@ pub struct AgentListResponse {
 #[derive(Debug, Serialize)]
 pub struct AgentResponse {
     pub agent: Option<Agent>,
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -46,9 +73,17 @@ pub struct AgentDetailResponse {
     pub subject_address: String,
     pub invite_url: String,
     pub bio: Option<String>,
-    pub success: bool,
+    /// Shares a member must hold to chat in the group
+    pub min_shares: String,
+    /// Shares in circulation as of the subject's last trade
+    pub supply: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supply_cap: Option<String>,
+    /// Shares left to buy under the cap
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+    pub available_supply: Option<String>,
+    /// Too few shares are left under the cap for a new member to reach `min_shares`
+    pub sold_out: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,214 +94,1221 @@ pub struct AddTelegramBotRequest {
     pub agent_name: String,
     pub invite_url: String,
     pub bio: Option<String>,
+    pub delete_service_messages: Option<bool>,
+    pub chain_type: Option<ChainType>,
+    pub enforcement_mode: Option<EnforcementMode>,
+    /// Restrict holders who sold out step by step instead of right away
+    pub escalation_ladder: Option<Vec<EscalationStep>>,
+    /// Shares a member must hold to chat, default 1
+    pub min_shares: Option<u64>,
+    /// Daily window in which non-urgent DMs wait
+    pub quiet_hours: Option<QuietHours>,
+    /// Most shares the subject's contract lets exist, uncapped when omitted
+    pub supply_cap: Option<u64>,
+    /// Unmute members as soon as their signature checks out, confirming their balance afterwards
+    pub optimistic_verify: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct AddTelegramBotResponse {
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+// Validate a submitted ladder and serialize it for the escalation_ladder column
+fn ladder_column(ladder: &Option<Vec<EscalationStep>>) -> Result<Option<String>, AppError> {
+    let Some(steps) = ladder else {
+        return Ok(None);
+    };
+    validate_ladder(steps)
+        .and_then(|_| serde_json::to_string(steps).map_err(|e| e.to_string()))
+        .map(Some)
+        .map_err(|e| AppError::BadRequest(format!("Invalid escalation ladder: {}", e)))
+}
+
+// Validate submitted quiet hours and serialize them for the quiet_hours column, an empty
+// window becomes an empty string the queries store as NULL
+fn quiet_hours_column(quiet_hours: &Option<QuietHours>) -> Result<Option<String>, AppError> {
+    let Some(hours) = quiet_hours else {
+        return Ok(None);
+    };
+    if hours.is_empty() {
+        return Ok(Some(String::new()));
+    }
+    hours.validate()
+        .and_then(|_| serde_json::to_string(hours).map_err(|e| e.to_string()))
+        .map(Some)
+        .map_err(|e| AppError::BadRequest(format!("Invalid quiet hours: {}", e)))
+}
+
+// Validate a submitted share threshold for the min_shares column
+fn min_shares_column(min_shares: Option<u64>) -> Result<Option<BigDecimal>, AppError> {
+    match min_shares {
+        Some(0) => Err(AppError::BadRequest("min_shares must be at least 1".to_string())),
+        min_shares => Ok(min_shares.map(BigDecimal::from)),
+    }
 }
 
 #[post("/add_tg_bot")]
 async fn handle_add_tg_bot(
+    _api_key: ApiKey,
     data: web::Json<AddTelegramBotRequest>,
     pool: web::Data<PgPool>,
-) -> impl Responder {
-    let subject_address = data.subject_address.to_lowercase().trim_start_matches("0x").to_owned();
+    bot_manager: web::Data<BotManager>,
+) -> Result<ApiResponse<()>, AppError> {
+    let chain_type = data.chain_type.unwrap_or_default();
+    let subject_address = chain_type.normalize_address(&data.subject_address);
+    if !chain_type.is_valid_address(&subject_address) {
+        return Err(AppError::InvalidAddress(format!("{} is not a {} address", data.subject_address, chain_type.as_str())));
+    }
+    let escalation_ladder = ladder_column(&data.escalation_ladder)?;
+    let min_shares = min_shares_column(data.min_shares)?.unwrap_or_else(|| BigDecimal::from(DEFAULT_MIN_SHARES));
+    let quiet_hours = quiet_hours_column(&data.quiet_hours)?;
+
+    // Registering the same bot again is a no-op, so retried requests succeed
+    let existing = sqlx::query!(
+        r#"SELECT bot_token, chat_group_id, subject_address, chain_type as "chain_type: ChainType"
+           FROM telegram_bots WHERE agent_name = $1"#,
+        data.agent_name
+    )
+        .fetch_optional(pool.get_ref())
+        .await?;
+    if let Some(existing) = existing {
+        let same = existing.bot_token == data.bot_token
+            && existing.chat_group_id == data.chat_group_id
+            && existing.subject_address == subject_address
+            && existing.chain_type == chain_type;
+        if same {
+            info!("Telegram bot of agent {} already registered", data.agent_name);
+            return Ok(ApiResponse::done());
+        }
+        return Err(AppError::AgentExists(format!("Agent {} is already registered with another bot, group or subject", data.agent_name)));
+    }
+    // The name stays taken until the data of a deleted agent with it is purged
+    if let Some(deletion) = get_open_agent_deletion(pool.get_ref(), &data.agent_name).await? {
+        return Err(AppError::BadRequest(format!(
+            "Agent name {} is held by a deleted agent until its data is purged after {}",
+            data.agent_name, deletion.export_until
+        )));
+    }
+    if let Some(current) = get_agent_by_alias(pool.get_ref(), &data.agent_name).await? {
+        return Err(AppError::BadRequest(format!("Agent name {} is an old name of agent {}", data.agent_name, current)));
+    }
+    check_bot_can_moderate(&data.bot_token, &data.chat_group_id).await?;
+
     // Store bot information in database
     let result = sqlx::query!(
-        "INSERT INTO telegram_bots (agent_name, bot_token, chat_group_id, subject_address, invite_url, bio) VALUES ($1, $2, $3, $4, $5, $6)",
+        "INSERT INTO telegram_bots (agent_name, bot_token, chat_group_id, subject_address, invite_url, bio, delete_service_messages, chain_type, enforcement_mode, escalation_ladder, min_shares, quiet_hours, supply_cap, optimistic_verify)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NULLIF($10, '[]'), $11, NULLIF($12, ''), NULLIF($13, 0), $14)",
         data.agent_name,
         data.bot_token,
         data.chat_group_id,
         subject_address.clone(),
         data.invite_url,
-        data.bio
+        data.bio,
+        data.delete_service_messages.unwrap_or(false),
+        chain_type.as_str(),
+        data.enforcement_mode.unwrap_or_default().as_str(),
+        escalation_ladder,
+        min_shares,
+        quiet_hours,
+        data.supply_cap.map(BigDecimal::from),
+        data.optimistic_verify.unwrap_or(false)
     )
         .execute(pool.get_ref())
         .await;
 
-    match result {
-        Ok(_) => {
-            println!("New Telegram bot added, Agent: {}", data.agent_name);
-            HttpResponse::Ok().json(AddTelegramBotResponse {
-                success: true,
-                error: None,
-            })
-        },
-        Err(e) => {
-            println!("Failed to add Telegram bot: {:?}", e);
-            HttpResponse::InternalServerError().json(AddTelegramBotResponse {
-                success: false,
-                error: Some(format!("Failed to add bot: {}", e)),
-            })
+    if let Err(e) = result {
+        // Lost a race with a concurrent registration of the same name
+        if e.as_database_error().is_some_and(|db| db.is_unique_violation()) {
+            return Err(AppError::AgentExists(format!("Agent {} already exists", data.agent_name)));
         }
+        error!("Failed to add Telegram bot: {:?}", e);
+        return Err(e.into());
+    }
+
+    info!("New Telegram bot added, Agent: {}", data.agent_name);
+    bot_manager.start(
+        &data.agent_name,
+        &data.bot_token,
+        &data.chat_group_id,
+        data.delete_service_messages.unwrap_or(false),
+    );
+    Ok(ApiResponse::done())
+}
+
+// The token must belong to a bot that is an admin of the group allowed to restrict members,
+// otherwise the agent could never gate it
+async fn check_bot_can_moderate(bot_token: &str, chat_group_id: &str) -> Result<(), AppError> {
+    let chat_id = chat_group_id
+        .parse()
+        .map(ChatId)
+        .map_err(|_| AppError::BadRequest(format!("Invalid chat group id {}", chat_group_id)))?;
+    let bot = Bot::new(bot_token);
+    let me = bot.get_me().await.map_err(|e| AppError::InvalidBotToken(e.to_string()))?;
+    let member = bot
+        .get_chat_member(chat_id, me.id)
+        .await
+        .map_err(|e| AppError::BotNotAdmin(format!("Bot @{} cannot access group {}: {}", me.username(), chat_group_id, e)))?;
+    if !member.can_restrict_members() {
+        return Err(AppError::BotNotAdmin(format!(
+            "Bot @{} must be an admin of group {} allowed to restrict members",
+            me.username(),
+            chat_group_id
+        )));
     }
+    Ok(())
 }
 
 #[get("/agents")]
 async fn get_agents(
     query: web::Query<HashMap<String, String>>,
     pool: web::Data<PgPool>,
-) -> impl Responder {
+) -> Result<ApiResponse<AgentListResponse>, AppError> {
     // Parse pagination parameters
     let page = query.get("page").and_then(|p| p.parse::<i64>().ok()).unwrap_or(1);
     let page_size = query.get("page_size").and_then(|ps| ps.parse::<i64>().ok()).unwrap_or(10);
 
     if page < 1 || page_size < 1 {
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "success": false,
-            "error": "Invalid pagination parameters"
-        }));
+        return Err(AppError::BadRequest("Invalid pagination parameters".to_string()));
     }
 
     let offset = (page - 1) * page_size;
 
     // Get total count
-    let total_result = sqlx::query!(
+    let total = sqlx::query!(
         "SELECT COUNT(*) as count FROM telegram_bots"
     )
         .fetch_one(pool.get_ref())
-        .await;
-
-    let total = match total_result {
-        Ok(record) => record.count.unwrap_or(0),
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "success": false,
-                "error": format!("Database error: {}", e)
-            }));
-        }
-    };
+        .await?
+        .count
+        .unwrap_or(0);
 
     // Get paginated agents
-    let agents_result = sqlx::query!(
-        "SELECT agent_name, subject_address, created_at FROM telegram_bots ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+    let rows = sqlx::query!(
+        "SELECT agent_id, agent_name, subject_address, created_at, active_members FROM telegram_bots ORDER BY created_at DESC LIMIT $1 OFFSET $2",
         page_size,
         offset
     )
         .fetch_all(pool.get_ref())
-        .await;
+        .await?;
 
-    match agents_result {
-        Ok(rows) => {
-            // Manually convert query results to Agent struct
-            let agents: Vec<Agent> = rows.into_iter()
-                .map(|row| Agent {
-                    agent_name: row.agent_name,
-                    subject_address: row.subject_address,
-                    created_at: row.created_at,
-                })
-                .collect();
-
-            HttpResponse::Ok().json(AgentListResponse {
-                agents,
-                total,
-                page,
-                page_size,
-            })
-        },
-        Err(e) => {
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "success": false,
-                "error": format!("Database error: {}", e)
-            }))
-        }
+    // Manually convert query results to Agent struct
+    let agents: Vec<Agent> = rows.into_iter()
+        .map(|row| Agent {
+            agent_id: row.agent_id,
+            agent_name: row.agent_name,
+            subject_address: row.subject_address,
+            created_at: row.created_at,
+            active_members: row.active_members,
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(AgentListResponse {
+        agents,
+        total,
+        page,
+        page_size,
+    }))
+}
+
+// Default and maximum number of search results
+const DEFAULT_SEARCH_LIMIT: i64 = 10;
+const MAX_SEARCH_LIMIT: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct AgentSearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentSearchResult {
+    pub agent_name: String,
+    pub subject_address: String,
+    pub bio: Option<String>,
+    #[serde(serialize_with = "serialize_datetime")]
+    pub created_at: PrimitiveDateTime,
+    /// Trigram similarity between 0 and 1
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentSearchResponse {
+    pub agents: Vec<AgentSearchResult>,
+}
+
+// Must be registered before /agents/{agent_name} so "search" is not taken as a name
+#[get("/agents/search")]
+async fn search_agents(
+    query: web::Query<AgentSearchQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<AgentSearchResponse>, AppError> {
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Err(AppError::BadRequest("Query must not be empty".to_string()));
     }
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+
+    // Names are matched as a whole, bios by their best matching words
+    let rows = sqlx::query!(
+        r#"SELECT agent_name, subject_address, bio, created_at,
+                  GREATEST(similarity(agent_name, $1), word_similarity($1, COALESCE(bio, ''))) AS "score!"
+           FROM telegram_bots
+           WHERE agent_name % $1 OR agent_name ILIKE '%' || $1 || '%' OR $1 <% COALESCE(bio, '')
+           ORDER BY 5 DESC, agent_name
+           LIMIT $2"#,
+        q,
+        limit
+    )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let agents = rows.into_iter()
+        .map(|row| AgentSearchResult {
+            agent_name: row.agent_name,
+            subject_address: row.subject_address,
+            bio: row.bio,
+            created_at: row.created_at,
+            score: row.score,
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(AgentSearchResponse { agents }))
 }
 
 #[get("/agents/{agent_name}")]
 async fn get_agent_by_name(
     path: web::Path<String>,
     pool: web::Data<PgPool>,
-) -> impl Responder {
+) -> Result<Either<Redirect, ApiResponse<AgentResponse>>, AppError> {
     let agent_name = path.into_inner();
 
-    let agent_result = sqlx::query!(
-        "SELECT agent_name, subject_address, created_at FROM telegram_bots WHERE agent_name = $1",
+    let row = sqlx::query!(
+        "SELECT agent_id, agent_name, subject_address, created_at, active_members FROM telegram_bots WHERE agent_name = $1",
         agent_name
     )
         .fetch_optional(pool.get_ref())
-        .await;
+        .await?;
 
-    match agent_result {
-        Ok(Some(row)) => {
-            // Manually create Agent struct
-            let agent = Agent {
-                agent_name: row.agent_name,
-                subject_address: row.subject_address,
-                created_at: row.created_at,
-            };
-            
-            HttpResponse::Ok().json(AgentResponse {
-                agent: Some(agent),
-                success: true,
-                error: None,
-            })
-        },
-        Ok(None) => {
-            HttpResponse::Ok().json(AgentResponse {
-                agent: None,
-                success: true,
-                error: None,
-            })
-        },
-        Err(e) => {
-            HttpResponse::InternalServerError().json(AgentResponse {
-                agent: None,
-                success: false,
-                error: Some(format!("Database error: {}", e)),
-            })
+    if row.is_none() {
+        if let Some(current) = get_agent_by_alias(pool.get_ref(), &agent_name).await? {
+            return Ok(Either::Left(Redirect::to(format!("/agents/{}", current)).permanent()));
         }
     }
+
+    // An unknown agent is not an error here, the response just has no agent
+    let agent = row.map(|row| Agent {
+        agent_id: row.agent_id,
+        agent_name: row.agent_name,
+        subject_address: row.subject_address,
+        created_at: row.created_at,
+        active_members: row.active_members,
+    });
+    Ok(Either::Right(ApiResponse::ok(AgentResponse { agent })))
+}
+
+#[get("/agents/by-id/{agent_id}")]
+async fn get_agent_by_id(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<AgentResponse>, AppError> {
+    let agent_id = path.into_inner();
+
+    let row = sqlx::query!(
+        "SELECT agent_id, agent_name, subject_address, created_at, active_members FROM telegram_bots WHERE agent_id = $1",
+        agent_id
+    )
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let agent = row.map(|row| Agent {
+        agent_id: row.agent_id,
+        agent_name: row.agent_name,
+        subject_address: row.subject_address,
+        created_at: row.created_at,
+        active_members: row.active_members,
+    });
+    Ok(ApiResponse::ok(AgentResponse { agent }))
 }
 
 #[get("/agent/detail/{agent_name}")]
 async fn get_agent_detail(
     path: web::Path<String>,
     pool: web::Data<PgPool>,
-) -> impl Responder {
+) -> Result<Either<Redirect, ApiResponse<AgentDetailResponse>>, AppError> {
     let agent_name = path.into_inner();
 
     // Query agent details from database
-    let agent_result = sqlx::query!(
-        "SELECT agent_name, subject_address, invite_url, bio FROM telegram_bots WHERE agent_name = $1",
+    let agent = sqlx::query!(
+        r#"SELECT agent_name, subject_address, invite_url, bio, min_shares, supply_cap, chain_type as "chain_type: ChainType"
+           FROM telegram_bots WHERE agent_name = $1"#,
         agent_name
     )
         .fetch_optional(pool.get_ref())
-        .await;
+        .await?;
+    let Some(agent) = agent else {
+        return match get_agent_by_alias(pool.get_ref(), &agent_name).await? {
+            Some(current) => Ok(Either::Left(Redirect::to(format!("/agent/detail/{}", current)).permanent())),
+            None => Err(AppError::NotFound("Agent not found".to_string())),
+        };
+    };
+
+    // A subject nobody traded yet has no supply
+    let supply = get_latest_subject_prices(pool.get_ref(), agent.chain_type, std::slice::from_ref(&agent.subject_address))
+        .await?
+        .into_iter()
+        .next()
+        .map(|price| price.supply)
+        .unwrap_or_default();
+    let available = available_supply(&supply, agent.supply_cap.as_ref());
+
+    Ok(Either::Right(ApiResponse::ok(AgentDetailResponse {
+        agent_name: agent.agent_name,
+        subject_address: agent.subject_address,
+        invite_url: agent.invite_url,
+        bio: agent.bio,
+        sold_out: !can_buy(&supply, agent.supply_cap.as_ref(), &agent.min_shares),
+        min_shares: agent.min_shares.to_string(),
+        supply: supply.to_string(),
+        supply_cap: agent.supply_cap.map(|cap| cap.to_string()),
+        available_supply: available.map(|available| available.to_string()),
+    })))
+}
+
+// Default and maximum number of leaderboard entries
+const DEFAULT_LEADERBOARD_LIMIT: i64 = 10;
+const MAX_LEADERBOARD_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub limit: Option<i64>,
+    /// Leave out holders with a trade tagged as a suspected wash trade
+    #[serde(default)]
+    pub exclude_wash_trades: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardHolder {
+    pub rank: i64,
+    pub address: String,
+    pub share_amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_username: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardResponse {
+    pub agent_name: String,
+    pub subject_address: String,
+    pub chain_type: ChainType,
+    pub holders: Vec<LeaderboardHolder>,
+}
+
+#[get("/agents/{agent_name}/leaderboard")]
+async fn get_agent_leaderboard(
+    path: web::Path<String>,
+    query: web::Query<LeaderboardQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<LeaderboardResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_LEADERBOARD_LIMIT);
+    if !(1..=MAX_LEADERBOARD_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {}", MAX_LEADERBOARD_LIMIT)));
+    }
+
+    let agent = sqlx::query!(
+        r#"SELECT subject_address, chain_type as "chain_type: ChainType" FROM telegram_bots WHERE agent_name = $1"#,
+        agent_name
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Agent {} not found", agent_name)))?;
+
+    let subject = agent.chain_type.normalize_address(&agent.subject_address);
+    let holders = get_subject_leaderboard(pool.get_ref(), &subject, agent.chain_type, limit, query.exclude_wash_trades)
+        .await?
+        .into_iter()
+        .map(|entry| LeaderboardHolder {
+            rank: entry.rank,
+            address: entry.trader,
+            share_amount: entry.share_amount.to_string(),
+            telegram_username: entry.telegram_username,
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(LeaderboardResponse {
+        agent_name,
+        subject_address: agent.subject_address,
+        chain_type: agent.chain_type,
+        holders,
+    }))
+}
+
+/// Bucket size of an agent's trade series: hourly over the last day or daily over the last week
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsInterval {
+    Hour,
+    Day,
+}
+
+impl StatsInterval {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsInterval::Hour => "hour",
+            StatsInterval::Day => "day",
+        }
+    }
+
+    /// Time covered by the series
+    pub fn window(&self) -> Duration {
+        match self {
+            StatsInterval::Hour => Duration::hours(24),
+            StatsInterval::Day => Duration::days(7),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgentStatsQuery {
+    /// Also return a time-bucketed series, for charts
+    pub series: Option<StatsInterval>,
+    /// Leave out trades tagged as suspected wash trades
+    #[serde(default)]
+    pub exclude_wash_trades: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TradeStats {
+    pub buy_count: i64,
+    pub sell_count: i64,
+    /// Native tokens traded, in whole tokens
+    pub volume: String,
+    pub unique_traders: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TradeStatsPoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub start: OffsetDateTime,
+    #[serde(flatten)]
+    pub stats: TradeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentStatsResponse {
+    pub agent_name: String,
+    pub subject_address: String,
+    pub chain_type: ChainType,
+    /// Current supply of the subject, in whole shares
+    pub supply: String,
+    pub last_24h: TradeStats,
+    pub last_7d: TradeStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<Vec<TradeStatsPoint>>,
+}
+
+pub fn trade_stats(stats: &SubjectTradeStats, decimals: u32) -> TradeStats {
+    TradeStats {
+        buy_count: stats.buy_count,
+        sell_count: stats.sell_count,
+        volume: to_native_units(&stats.volume, decimals),
+        unique_traders: stats.unique_traders,
+    }
+}
+
+#[get("/agents/{agent_name}/stats")]
+async fn get_agent_stats(
+    path: web::Path<String>,
+    query: web::Query<AgentStatsQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<AgentStatsResponse>, AppError> {
+    let agent_name = path.into_inner();
+
+    let agent = sqlx::query!(
+        r#"SELECT subject_address, chain_type as "chain_type: ChainType" FROM telegram_bots WHERE agent_name = $1"#,
+        agent_name
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Agent {} not found", agent_name)))?;
+
+    let chain_type = agent.chain_type;
+    let subject = chain_type.normalize_address(&agent.subject_address);
+    let decimals = chain_type.native_decimals();
+    let now = OffsetDateTime::now_utc();
+
+    let last_24h = get_subject_trade_stats(pool.get_ref(), &subject, chain_type, now - StatsInterval::Hour.window(), query.exclude_wash_trades).await?;
+    let last_7d = get_subject_trade_stats(pool.get_ref(), &subject, chain_type, now - StatsInterval::Day.window(), query.exclude_wash_trades).await?;
+
+    let series = match query.series {
+        Some(interval) => {
+            let buckets = get_subject_trade_series(
+                pool.get_ref(),
+                &subject,
+                chain_type,
+                now - interval.window(),
+                interval.as_str(),
+                query.exclude_wash_trades,
+            ).await?;
+            Some(buckets.iter().map(|bucket| TradeStatsPoint { start: bucket.start, stats: trade_stats(bucket, decimals) }).collect())
+        }
+        None => None,
+    };
+
+    // A subject nobody traded yet has no supply
+    let supply = get_latest_subject_prices(pool.get_ref(), chain_type, std::slice::from_ref(&subject))
+        .await?
+        .into_iter()
+        .next()
+        .map(|price| price.supply)
+        .unwrap_or_default();
+
+    Ok(ApiResponse::ok(AgentStatsResponse {
+        agent_name,
+        subject_address: agent.subject_address,
+        chain_type,
+        supply: supply.to_string(),
+        last_24h: trade_stats(&last_24h, decimals),
+        last_7d: trade_stats(&last_7d, decimals),
+        series,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuspendAgentRequest {
+    /// "suspended" (default) or "archived"
+    pub status: Option<String>,
+    /// Also remove posting rights for everyone in the group
+    #[serde(default)]
+    pub close_group: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentStatusResponse {
+    pub agent_name: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_url: Option<String>,
+}
+
+// Log a moderation action without failing the request when logging fails
+async fn log_moderation(pool: &PgPool, agent_name: &str, chat_id: &str, action: &str, details: Option<String>) {
+    if let Err(e) = record_moderation_event(pool, agent_name, chat_id, None, action, details, None).await {
+        warn!("Failed to record moderation event {} for {}: {:?}", action, agent_name, e);
+    }
+}
 
-    match agent_result {
-        Ok(Some(agent)) => {
-            HttpResponse::Ok().json(AgentDetailResponse {
-                agent_name: agent.agent_name,
-                subject_address: agent.subject_address,
-                invite_url: agent.invite_url,
-                bio: agent.bio,
-                success: true,
-                error: None,
-            })
+#[post("/agents/{agent_name}/suspend")]
+async fn suspend_agent(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<SuspendAgentRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<AgentStatusResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let status = data.status.clone().unwrap_or_else(|| "suspended".to_string());
+    if status != "suspended" && status != "archived" {
+        return Err(AppError::BadRequest("Status must be suspended or archived".to_string()));
+    }
+
+    let agent = sqlx::query!(
+        "SELECT bot_token, chat_group_id, invite_url FROM telegram_bots WHERE agent_name = $1",
+        agent_name
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    let bot = Bot::new(agent.bot_token);
+
+    // Stop the stored invite link from admitting anyone new
+    match bot.revoke_chat_invite_link(agent.chat_group_id.clone(), agent.invite_url.clone()).await {
+        Ok(_) => {
+            log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "invite_revoked", Some(agent.invite_url.clone())).await;
         },
-        Ok(None) => {
-            HttpResponse::NotFound().json(AgentDetailResponse {
-                agent_name: String::new(),
-                subject_address: String::new(),
-                invite_url: String::new(),
-                bio: None,
-                success: false,
-                error: Some("Agent not found".to_string()),
-            })
+        Err(e) => {
+            error!("Failed to revoke invite link for agent {}: {:?}", agent_name, e);
+            log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "invite_revoke_failed", Some(e.to_string())).await;
+        }
+    }
+
+    let mut group_closed = false;
+    if data.close_group {
+        match bot.set_chat_permissions(agent.chat_group_id.clone(), ChatPermissions::empty()).await {
+            Ok(_) => {
+                group_closed = true;
+                log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "group_closed", None).await;
+            },
+            Err(e) => {
+                error!("Failed to close group for agent {}: {:?}", agent_name, e);
+                log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "group_close_failed", Some(e.to_string())).await;
+            }
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE telegram_bots SET status = $2, group_closed = group_closed OR $3 WHERE agent_name = $1",
+        agent_name,
+        status,
+        group_closed
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, &status, None).await;
+    info!("Agent {} is now {}", agent_name, status);
+    Ok(ApiResponse::ok(AgentStatusResponse {
+        agent_name,
+        status,
+        invite_url: None,
+    }))
+}
+
+#[post("/agents/{agent_name}/reactivate")]
+async fn reactivate_agent(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<AgentStatusResponse>, AppError> {
+    let agent_name = path.into_inner();
+
+    let agent = sqlx::query!(
+        "SELECT bot_token, chat_group_id, invite_url, group_closed FROM telegram_bots WHERE agent_name = $1",
+        agent_name
+    )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    let bot = Bot::new(agent.bot_token);
+
+    // The old link was revoked on suspension, issue a fresh one
+    let invite_url = match bot.create_chat_invite_link(agent.chat_group_id.clone()).await {
+        Ok(link) => {
+            log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "invite_created", Some(link.invite_link.clone())).await;
+            link.invite_link
         },
         Err(e) => {
-            HttpResponse::InternalServerError().json(AgentDetailResponse {
-                agent_name: String::new(),
-                subject_address: String::new(),
-                invite_url: String::new(),
-                bio: None,
-                success: false,
-                error: Some(format!("Database error: {}", e)),
-            })
+            error!("Failed to create invite link for agent {}: {:?}", agent_name, e);
+            return Err(e.into());
+        }
+    };
+
+    if agent.group_closed {
+        let permissions = ChatPermissions::empty()
+            | ChatPermissions::SEND_MESSAGES
+            | ChatPermissions::SEND_MEDIA_MESSAGES
+            | ChatPermissions::SEND_OTHER_MESSAGES
+            | ChatPermissions::SEND_POLLS
+            | ChatPermissions::ADD_WEB_PAGE_PREVIEWS;
+
+        match bot.set_chat_permissions(agent.chat_group_id.clone(), permissions).await {
+            Ok(_) => {
+                log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "group_reopened", None).await;
+            },
+            Err(e) => {
+                error!("Failed to reopen group for agent {}: {:?}", agent_name, e);
+                log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "group_reopen_failed", Some(e.to_string())).await;
+            }
+        }
+    }
+
+    sqlx::query!(
+        "UPDATE telegram_bots SET status = 'active', group_closed = FALSE, invite_url = $2 WHERE agent_name = $1",
+        agent_name,
+        invite_url
+    )
+        .execute(pool.get_ref())
+        .await?;
+
+    log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "reactivated", None).await;
+    info!("Agent {} reactivated", agent_name);
+    Ok(ApiResponse::ok(AgentStatusResponse {
+        agent_name,
+        status: "active".to_string(),
+        invite_url: Some(invite_url),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAgentRequest {
+    pub bot_token: Option<String>,
+    pub chat_group_id: Option<String>,
+    pub subject_address: Option<String>,
+    pub invite_url: Option<String>,
+    pub bio: Option<String>,
+    pub delete_service_messages: Option<bool>,
+    pub enforcement_mode: Option<EnforcementMode>,
+    /// An empty ladder goes back to immediate enforcement
+    pub escalation_ladder: Option<Vec<EscalationStep>>,
+    /// Members are held to a new threshold from their next trade or verification
+    pub min_shares: Option<u64>,
+    /// A window starting at the hour it ends turns quiet hours off
+    pub quiet_hours: Option<QuietHours>,
+    /// 0 removes the cap
+    pub supply_cap: Option<u64>,
+    pub optimistic_verify: Option<bool>,
+    /// Disabled agents keep their settings but their bot is stopped
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateTokenRequest {
+    pub bot_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentUpdateResponse {
+    pub agent_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+// Restart or stop the bot task so it matches the stored row
+fn apply_bot_state(
+    bot_manager: &BotManager,
+    agent_name: &str,
+    bot_token: &str,
+    chat_group_id: &str,
+    delete_service_messages: bool,
+    enabled: bool,
+) {
+    if enabled {
+        bot_manager.start(agent_name, bot_token, chat_group_id, delete_service_messages);
+    } else {
+        bot_manager.stop(agent_name);
+    }
+}
+
+#[put("/agents/{agent_name}")]
+async fn update_agent(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<UpdateAgentRequest>,
+    pool: web::Data<PgPool>,
+    bot_manager: web::Data<BotManager>,
+) -> Result<ApiResponse<AgentUpdateResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let escalation_ladder = ladder_column(&data.escalation_ladder)?;
+    let min_shares = min_shares_column(data.min_shares)?;
+    let quiet_hours = quiet_hours_column(&data.quiet_hours)?;
+    // Subjects are normalized for the chain the agent is registered on
+    let subject_address = match &data.subject_address {
+        Some(address) => {
+            let chain_type = sqlx::query_scalar!(
+                r#"SELECT chain_type as "chain_type: ChainType" FROM telegram_bots WHERE agent_name = $1"#,
+                agent_name
+            )
+                .fetch_optional(pool.get_ref())
+                .await?
+                .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+            Some(chain_type.normalize_address(address))
+        },
+        None => None,
+    };
+
+    let agent = sqlx::query!(
+        "UPDATE telegram_bots SET
+            bot_token = COALESCE($2, bot_token),
+            chat_group_id = COALESCE($3, chat_group_id),
+            subject_address = COALESCE($4, subject_address),
+            invite_url = COALESCE($5, invite_url),
+            bio = COALESCE($6, bio),
+            delete_service_messages = COALESCE($7, delete_service_messages),
+            enabled = COALESCE($8, enabled),
+            enforcement_mode = COALESCE($9, enforcement_mode),
+            escalation_ladder = CASE WHEN $10::text IS NULL THEN escalation_ladder ELSE NULLIF($10, '[]') END,
+            min_shares = COALESCE($11, min_shares),
+            quiet_hours = CASE WHEN $12::text IS NULL THEN quiet_hours ELSE NULLIF($12, '') END,
+            supply_cap = CASE WHEN $13::numeric IS NULL THEN supply_cap ELSE NULLIF($13, 0) END,
+            optimistic_verify = COALESCE($14, optimistic_verify)
+         WHERE agent_name = $1
+         RETURNING bot_token, chat_group_id, delete_service_messages, enabled",
+        agent_name,
+        data.bot_token,
+        data.chat_group_id,
+        subject_address,
+        data.invite_url,
+        data.bio,
+        data.delete_service_messages,
+        data.enabled,
+        data.enforcement_mode.map(|mode| mode.as_str()),
+        escalation_ladder,
+        min_shares,
+        quiet_hours,
+        data.supply_cap.map(BigDecimal::from),
+        data.optimistic_verify
+    )
+        .fetch_optional(pool.get_ref())
+        .await
+        .inspect_err(|e| error!("Failed to update agent {}: {:?}", agent_name, e))?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    apply_bot_state(
+        &bot_manager,
+        &agent_name,
+        &agent.bot_token,
+        &agent.chat_group_id,
+        agent.delete_service_messages,
+        agent.enabled,
+    );
+    info!("Agent {} updated", agent_name);
+    Ok(ApiResponse::ok(AgentUpdateResponse { agent_name, enabled: Some(agent.enabled) }))
+}
+
+#[delete("/agents/{agent_name}")]
+async fn delete_agent(
+    api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+    bot_manager: web::Data<BotManager>,
+) -> Result<ApiResponse<AgentDeletion>, AppError> {
+    let agent_name = path.into_inner();
+
+    let deletion = delete_agent_with_export_window(pool.get_ref(), &agent_name, &api_key.name, EXPORT_WINDOW_DAYS)
+        .await
+        .inspect_err(|e| error!("Failed to delete agent {}: {:?}", agent_name, e))?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    bot_manager.stop(&agent_name);
+    info!("Agent {} deleted by {}, data can be exported until {}", agent_name, api_key.name, deletion.export_until);
+    Ok(ApiResponse::ok(deletion))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameAgentRequest {
+    pub agent_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentRenameResponse {
+    pub agent_id: String,
+    pub agent_name: String,
+    /// Kept as an alias, lookups by it are redirected to `agent_name`
+    pub previous_name: String,
+}
+
+#[post("/agents/{agent_name}/rename")]
+async fn rename_agent_handler(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<RenameAgentRequest>,
+    pool: web::Data<PgPool>,
+    bot_manager: web::Data<BotManager>,
+) -> Result<ApiResponse<AgentRenameResponse>, AppError> {
+    let previous_name = path.into_inner();
+    let agent_name = data.agent_name.trim().to_string();
+    if agent_name.is_empty() {
+        return Err(AppError::BadRequest("agent_name must not be empty".to_string()));
+    }
+    if agent_name == previous_name {
+        return Err(AppError::BadRequest(format!("Agent is already named {}", agent_name)));
+    }
+
+    // The new name must be free: no agent, no other agent's old name, no deleted agent's data
+    let taken = sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM telegram_bots WHERE agent_name = $1) as "taken!""#, agent_name)
+        .fetch_one(pool.get_ref())
+        .await?;
+    if taken {
+        return Err(AppError::BadRequest(format!("Agent name {} is already taken", agent_name)));
+    }
+    if let Some(current) = get_agent_by_alias(pool.get_ref(), &agent_name).await? {
+        if current != previous_name {
+            return Err(AppError::BadRequest(format!("Agent name {} is an old name of agent {}", agent_name, current)));
         }
     }
-}
\ No newline at end of file
+    if let Some(deletion) = get_open_agent_deletion(pool.get_ref(), &agent_name).await? {
+        return Err(AppError::BadRequest(format!(
+            "Agent name {} is held by a deleted agent until its data is purged after {}",
+            agent_name, deletion.export_until
+        )));
+    }
+
+    let agent_id = rename_agent(pool.get_ref(), &previous_name, &agent_name)
+        .await
+        .inspect_err(|e| error!("Failed to rename agent {} to {}: {:?}", previous_name, agent_name, e))?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    // The running bot carries the name in its context, restart it under the new one
+    let agent = sqlx::query!(
+        "SELECT bot_token, chat_group_id, delete_service_messages, enabled FROM telegram_bots WHERE agent_id = $1",
+        agent_id
+    )
+        .fetch_one(pool.get_ref())
+        .await?;
+    bot_manager.stop(&previous_name);
+    apply_bot_state(&bot_manager, &agent_name, &agent.bot_token, &agent.chat_group_id, agent.delete_service_messages, agent.enabled);
+
+    log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "renamed", Some(previous_name.clone())).await;
+    info!("Agent {} renamed to {}", previous_name, agent_name);
+    Ok(ApiResponse::ok(AgentRenameResponse { agent_id, agent_name, previous_name }))
+}
+
+#[post("/agents/{agent_name}/rotate-token")]
+async fn rotate_agent_token(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<RotateTokenRequest>,
+    pool: web::Data<PgPool>,
+    bot_manager: web::Data<BotManager>,
+) -> Result<ApiResponse<AgentUpdateResponse>, AppError> {
+    let agent_name = path.into_inner();
+
+    // Refuse tokens Telegram does not accept before replacing the working one
+    if let Err(e) = Bot::new(&data.bot_token).get_me().await {
+        return Err(AppError::InvalidBotToken(e.to_string()));
+    }
+
+    let agent = sqlx::query!(
+        "UPDATE telegram_bots SET bot_token = $2 WHERE agent_name = $1
+         RETURNING chat_group_id, delete_service_messages, enabled",
+        agent_name,
+        data.bot_token
+    )
+        .fetch_optional(pool.get_ref())
+        .await
+        .inspect_err(|e| error!("Failed to rotate token for agent {}: {:?}", agent_name, e))?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    apply_bot_state(
+        &bot_manager,
+        &agent_name,
+        &data.bot_token,
+        &agent.chat_group_id,
+        agent.delete_service_messages,
+        agent.enabled,
+    );
+    info!("Bot token rotated for agent {}", agent_name);
+    Ok(ApiResponse::ok(AgentUpdateResponse { agent_name, enabled: Some(agent.enabled) }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepromptResponse {
+    pub agent_name: String,
+    #[serde(flatten)]
+    pub stats: RepromptStats,
+    /// More unverified members are waiting, send the request again for the next batch
+    pub has_more: bool,
+}
+
+#[post("/agents/{agent_name}/reprompt-unverified")]
+async fn reprompt_unverified_members(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+    config: web::Data<AppConfig>,
+) -> Result<ApiResponse<RepromptResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let agent = sqlx::query!("SELECT chat_group_id, enabled FROM telegram_bots WHERE agent_name = $1", agent_name)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+    if !agent.enabled {
+        return Err(AppError::BadRequest("Agent is disabled, enable it before re-prompting its members".to_string()));
+    }
+
+    let stats = reprompt_unverified(pool.get_ref(), &TelegramBotApi, &config, &agent_name).await?;
+    let details = serde_json::to_string(&stats).ok();
+    log_moderation(pool.get_ref(), &agent_name, &agent.chat_group_id, "reprompted", details).await;
+    info!("Re-prompted unverified members of agent {}: {:?}", agent_name, stats);
+
+    let has_more = stats.members as i64 >= MAX_REPROMPT_MEMBERS;
+    Ok(ApiResponse::ok(RepromptResponse { agent_name, stats, has_more }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationLogQuery {
+    /// Most recent events to return (default 100, max 1000)
+    pub limit: Option<i64>,
+    pub telegram_id: Option<String>,
+    /// Only the events caused by one chain event
+    pub trace_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModerationLogResponse {
+    pub agent_name: String,
+    pub events: Vec<ModerationEvent>,
+}
+
+#[get("/agents/{agent_name}/moderation-log")]
+async fn get_moderation_log(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    query: web::Query<ModerationLogQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<ModerationLogResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let limit = query.limit.unwrap_or(100);
+    if !(1..=1000).contains(&limit) {
+        return Err(AppError::BadRequest("limit must be between 1 and 1000".to_string()));
+    }
+
+    let events = get_moderation_events(pool.get_ref(), &agent_name, query.telegram_id.as_deref(), query.trace_id.as_deref(), limit).await?;
+    Ok(ApiResponse::ok(ModerationLogResponse { agent_name, events }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GroupMembersQuery {
+    pub page: Option<i64>,
+    /// Members per page (default 50, max 500)
+    pub page_size: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupMemberEntry {
+    pub telegram_id: String,
+    pub username: Option<String>,
+    /// Wallet the member verified on the agent's chain
+    pub address: Option<String>,
+    pub share_amount: Option<String>,
+    pub is_banned: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub joined_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub left_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub verified_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupMembersResponse {
+    pub agent_name: String,
+    pub members: Vec<GroupMemberEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub page_size: i64,
+}
+
+#[get("/agents/{agent_name}/members")]
+async fn get_agent_members(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    query: web::Query<GroupMembersQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<GroupMembersResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(50);
+    if page < 1 || !(1..=500).contains(&page_size) {
+        return Err(AppError::BadRequest("Invalid pagination parameters".to_string()));
+    }
+
+    sqlx::query!("SELECT agent_name FROM telegram_bots WHERE agent_name = $1", agent_name)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    let (members, total) = get_group_members(pool.get_ref(), &agent_name, page_size, (page - 1) * page_size).await?;
+    let members = members
+        .into_iter()
+        .map(|member| GroupMemberEntry {
+            telegram_id: member.telegram_id,
+            username: member.username,
+            address: member.address,
+            share_amount: member.share_amount.map(|amount| amount.to_string()),
+            is_banned: member.is_banned,
+            joined_at: member.joined_at,
+            left_at: member.left_at,
+            verified_at: member.verified_at,
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(GroupMembersResponse { agent_name, members, total, page, page_size }))
+}
+
+// Subjects a group can be gated by besides the agent's own
+const MAX_EXTRA_SUBJECTS: usize = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateGroupSubjectsRequest {
+    /// Subjects gating the group besides `subject_address`, replacing the stored ones
+    pub subjects: Vec<String>,
+    pub rule: Option<SubjectRule>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupSubjectsResponse {
+    pub agent_name: String,
+    pub chain_type: ChainType,
+    pub rule: SubjectRule,
+    /// Every subject gating the group, `subject_address` first
+    pub subjects: Vec<String>,
+}
+
+// Normalized extra subjects without duplicates or the agent's own subject
+fn extra_subjects(chain_type: ChainType, primary: &str, subjects: &[String]) -> Result<Vec<String>, AppError> {
+    let mut extra = Vec::new();
+    for subject in subjects {
+        if subject.trim().is_empty() {
+            return Err(AppError::BadRequest("subjects must not be empty".to_string()));
+        }
+        let subject = chain_type.normalize_address(subject);
+        if subject != primary && !extra.contains(&subject) {
+            extra.push(subject);
+        }
+    }
+    if extra.len() > MAX_EXTRA_SUBJECTS {
+        return Err(AppError::BadRequest(format!("At most {} subjects besides subject_address are allowed", MAX_EXTRA_SUBJECTS)));
+    }
+    Ok(extra)
+}
+
+async fn group_subjects_response(pool: &PgPool, agent_name: String) -> Result<GroupSubjectsResponse, AppError> {
+    let agent = sqlx::query!(
+        r#"SELECT chain_type as "chain_type: ChainType", subject_rule as "subject_rule: SubjectRule" FROM telegram_bots WHERE agent_name = $1"#,
+        agent_name
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    let subjects = get_group_subjects(pool, &agent_name).await?;
+    Ok(GroupSubjectsResponse { agent_name, chain_type: agent.chain_type, rule: agent.subject_rule, subjects })
+}
+
+#[get("/agents/{agent_name}/subjects")]
+async fn get_agent_subjects(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<GroupSubjectsResponse>, AppError> {
+    Ok(ApiResponse::ok(group_subjects_response(pool.get_ref(), path.into_inner()).await?))
+}
+
+#[put("/agents/{agent_name}/subjects")]
+async fn update_agent_subjects(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<UpdateGroupSubjectsRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<GroupSubjectsResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let agent = sqlx::query!(
+        r#"SELECT chain_type as "chain_type: ChainType", subject_address, subject_rule as "subject_rule: SubjectRule"
+           FROM telegram_bots WHERE agent_name = $1"#,
+        agent_name
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+    let subjects = extra_subjects(agent.chain_type, &agent.subject_address, &data.subjects)?;
+    let rule = data.rule.unwrap_or(agent.subject_rule);
+    if !set_group_subjects(pool.get_ref(), &agent_name, &subjects, rule).await? {
+        return Err(AppError::NotFound("Agent not found".to_string()));
+    }
+
+    // Members are held to the new rule from their next trade or verification
+    info!("Agent {} gated by {} extra subjects with rule {}", agent_name, subjects.len(), rule.as_str());
+    Ok(ApiResponse::ok(group_subjects_response(pool.get_ref(), agent_name).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_subjects() {
+        let subjects = vec!["0xABC".to_string(), "0xabc".to_string(), "0xdef".to_string()];
+        assert_eq!(extra_subjects(ChainType::Monad, "def", &subjects).unwrap(), vec!["abc".to_string()]);
+        assert!(extra_subjects(ChainType::Monad, "def", &[" ".to_string()]).is_err());
+        let many: Vec<String> = (0..=MAX_EXTRA_SUBJECTS).map(|i| format!("0x{:x}", i + 100)).collect();
+        assert!(extra_subjects(ChainType::Monad, "def", &many).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! API key authentication of the administrative and partner routes.
+//!
+//! Handlers that change agents, push trades or operate the service take an
+//! [`ApiKey`] argument, which only extracts when the request carries a valid
+//! admin `X-Api-Key`: either `ADMIN_API_KEY` from the environment, used to
+//! create the first keys, or an unrevoked admin key from the `api_keys` table.
+//! Partner keys only extract as [`PartnerKey`], scoped to a list of subjects.
+//! Keys are stored as their SHA-256 and shown in full only when created.
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::block_chain::ChainType;
+use crate::db::models::AuthenticatedKey;
+use crate::db::operations::authenticate_api_key;
+use crate::error::AppError;
+use crate::AppConfig;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+// Characters of a key kept in listings
+const KEY_PREFIX_LEN: usize = 11;
+
+/// What an API key may call
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum KeyRole {
+    /// Every administrative route
+    #[default]
+    Admin,
+    /// Holder introspection of its subjects only
+    Partner,
+}
+
+impl KeyRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyRole::Admin => "admin",
+            KeyRole::Partner => "partner",
+        }
+    }
+}
+
+/// Caller authenticated with an admin `X-Api-Key`
+#[derive(Clone, Debug)]
+pub struct ApiKey {
+    /// Name of the key, `admin` for `ADMIN_API_KEY`
+    pub name: String,
+}
+
+/// Caller authenticated with any `X-Api-Key`, admin keys are not scoped
+#[derive(Clone, Debug)]
+pub struct PartnerKey {
+    pub name: String,
+    /// Subjects the key may look up, `None` for every subject
+    pub subjects: Option<Vec<String>>,
+}
+
+impl PartnerKey {
+    /// Whether the key may look up `subject`, given normalized for `chain_type`
+    pub fn allows(&self, chain_type: ChainType, subject: &str) -> bool {
+        match &self.subjects {
+            Some(subjects) => subjects.iter().any(|allowed| chain_type.normalize_address(allowed) == subject),
+            None => true,
+        }
+    }
+}
+
+/// A new random key, returned once to its creator
+pub fn generate_key() -> String {
+    format!("ak_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Stored form of a key
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Leading characters identifying a key in listings
+pub fn key_prefix(key: &str) -> &str {
+    &key[..key.len().min(KEY_PREFIX_LEN)]
+}
+
+// Look up the key sent with a request
+fn authenticate(req: &HttpRequest) -> LocalBoxFuture<'static, Result<AuthenticatedKey, AppError>> {
+    let key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let config = req.app_data::<web::Data<AppConfig>>().cloned();
+    let pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+    Box::pin(async move {
+        let key = key.ok_or_else(|| AppError::Unauthorized(format!("Missing {} header", API_KEY_HEADER)))?;
+        let hash = hash_key(&key);
+        // Compared by hash so the comparison time says nothing about the key
+        if let Some(admin_key) = config.as_ref().and_then(|config| config.admin_api_key.as_deref()) {
+            if hash_key(admin_key) == hash {
+                return Ok(AuthenticatedKey { name: "admin".to_string(), role: KeyRole::Admin, subjects: None });
+            }
+        }
+        let pool = pool.ok_or_else(|| AppError::Config("Database pool is not configured".to_string()))?;
+        authenticate_api_key(pool.get_ref(), &hash)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized("Invalid or revoked API key".to_string()))
+    })
+}
+
+impl FromRequest for ApiKey {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let key = authenticate(req);
+        Box::pin(async move {
+            let key = key.await?;
+            if key.role != KeyRole::Admin {
+                return Err(AppError::Forbidden(format!("API key {} is not an admin key", key.name)));
+            }
+            Ok(ApiKey { name: key.name })
+        })
+    }
+}
+
+impl FromRequest for PartnerKey {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let key = authenticate(req);
+        Box::pin(async move {
+            let key = key.await?;
+            let subjects = match key.role {
+                KeyRole::Admin => None,
+                KeyRole::Partner => key.subjects,
+            };
+            Ok(PartnerKey { name: key.name, subjects })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_keys() {
+        let key = generate_key();
+        assert!(key.starts_with("ak_"));
+        assert_eq!(key.len(), 67);
+        assert_ne!(key, generate_key());
+        assert_eq!(key_prefix(&key).len(), KEY_PREFIX_LEN);
+        assert_eq!(hash_key(&key).len(), 64);
+        assert_eq!(hash_key(&key), hash_key(&key));
+    }
+
+    #[test]
+    fn test_partner_key_scope() {
+        let key = PartnerKey { name: "partner".to_string(), subjects: Some(vec!["0xABCDEF".to_string()]) };
+        assert!(key.allows(ChainType::Monad, "abcdef"));
+        assert!(!key.allows(ChainType::Monad, "123456"));
+        assert!(PartnerKey { name: "admin".to_string(), subjects: None }.allows(ChainType::Monad, "123456"));
+    }
+}
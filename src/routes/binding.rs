@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use actix_web::{get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use ethers::utils::hex;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use super::challenge::{binding_message, ChallengePurpose};
+use crate::block_chain::tx_binding::new_binding_memo;
+use crate::block_chain::{create_blockchain, ChainType};
+use crate::bot::api::TelegramBotApi;
+use crate::db::models::GroupHolding;
+use crate::db::operations::{
+    consume_challenge, create_pending_binding, delete_user_mapping, get_address_binding, get_group_holdings, get_pending_binding, rebind_user_mapping,
+};
+use crate::enforcement::handle_balance_change;
+use crate::error::{parse_telegram_id, AppError};
+use crate::services::verification::get_verified_group_bot;
+use crate::AppConfig;
+
+/// Signed challenge common to unbind and rebind requests
+#[derive(Debug, Deserialize)]
+pub struct BindingProof {
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub nonce: String, // Issued by POST /challenge with purpose "unbind" or "rebind"
+    pub signature: String,
+    pub chain_type: Option<ChainType>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnbindRequest {
+    #[serde(flatten)]
+    pub proof: BindingProof, // Signed by the bound address
+    pub address: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RebindRequest {
+    #[serde(flatten)]
+    pub proof: BindingProof, // Signed by the new address
+    pub old_address: String,
+    pub new_address: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BindingResponse {
+    pub address: String,
+    /// Gated groups whose access was re-evaluated
+    pub groups_updated: usize,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Burn the nonce and check that `address` signed the binding message for it
+pub(crate) async fn verify_binding_signature(
+    pool: &PgPool,
+    config: &AppConfig,
+    purpose: ChallengePurpose,
+    proof: &BindingProof,
+    address: &str,
+) -> Result<(), AppError> {
+    let chain_type = proof.chain_type.unwrap_or_default();
+    parse_telegram_id(&proof.telegram_id)?;
+    if !consume_challenge(pool, &proof.nonce, &proof.telegram_id, &proof.chat_id, chain_type).await? {
+        return Err(AppError::InvalidSignature("Challenge nonce is invalid, expired or already used".to_string()));
+    }
+
+    let blockchain = create_blockchain(chain_type, Arc::new(config.clone()))?;
+    let message = binding_message(purpose, &proof.telegram_id, &proof.chat_id, address, &proof.nonce);
+    let verified_address = blockchain.verify_signature(&message, &proof.signature, address)?;
+    if verified_address != address {
+        return Err(AppError::InvalidSignature(format!("Signed by {}, expected {}", verified_address, address)));
+    }
+    Ok(())
+}
+
+// Subjects on `chain_type` in which `address` holds shares
+fn held_subjects(holdings: &[GroupHolding], chain_type: ChainType, address: &str) -> BTreeSet<String> {
+    holdings
+        .iter()
+        .filter(|h| h.chain_type == chain_type && h.address == address && h.share_amount > BigDecimal::from(0))
+        .map(|h| h.subject_address.clone())
+        .collect()
+}
+
+// Apply group access of each subject from the user's combined balance over the addresses still bound.
+// `address` must be bound when this runs, it is the row enforcement acts on.
+async fn reevaluate_groups(
+    pool: &PgPool,
+    chain_type: ChainType,
+    telegram_id: &str,
+    address: &str,
+    subjects: &BTreeSet<String>,
+    excluded_address: Option<&str>,
+) -> Result<usize, AppError> {
+    let holdings = get_group_holdings(pool, telegram_id).await?;
+    for subject in subjects {
+        let balance: BigDecimal = holdings
+            .iter()
+            .filter(|h| h.chain_type == chain_type && &h.subject_address == subject)
+            .filter(|h| Some(h.address.as_str()) != excluded_address)
+            .map(|h| h.share_amount.clone())
+            .sum();
+        if let Err(e) = handle_balance_change(pool, &TelegramBotApi, chain_type, address, subject, &balance, None).await {
+            error!("Failed to re-evaluate access of {} to {}: {:?}", telegram_id, subject, e);
+        }
+    }
+    Ok(subjects.len())
+}
+
+#[post("/unbind")]
+async fn unbind_wallet(
+    data: web::Json<UnbindRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = data.proof.chain_type.unwrap_or_default();
+    let telegram_id = data.proof.telegram_id.as_str();
+    let address = chain_type.normalize_address(&data.address);
+
+    verify_binding_signature(pool.get_ref(), config.get_ref(), ChallengePurpose::Unbind, &data.proof, &address).await?;
+
+    if get_address_binding(pool.get_ref(), &address, chain_type).await?.as_deref() != Some(telegram_id) {
+        return Err(AppError::NotFound(format!("{} is not bound to this Telegram user", address)));
+    }
+
+    // Enforce while the binding still exists, counting only the user's other addresses
+    let holdings = get_group_holdings(pool.get_ref(), telegram_id).await?;
+    let subjects = held_subjects(&holdings, chain_type, &address);
+    let groups_updated = reevaluate_groups(pool.get_ref(), chain_type, telegram_id, &address, &subjects, Some(&address)).await?;
+
+    delete_user_mapping(pool.get_ref(), &address, chain_type, telegram_id).await?;
+    info!("Unbound {} from Telegram user {} on {}", address, telegram_id, chain_type);
+
+    Ok(HttpResponse::Ok().json(BindingResponse {
+        address,
+        groups_updated,
+        success: true,
+        error: None,
+    }))
+}
+
+#[post("/rebind")]
+async fn rebind_wallet(
+    data: web::Json<RebindRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = data.proof.chain_type.unwrap_or_default();
+    let telegram_id = data.proof.telegram_id.as_str();
+    let old_address = chain_type.normalize_address(&data.old_address);
+    let new_address = chain_type.normalize_address(&data.new_address);
+    if old_address == new_address {
+        return Err(AppError::BadRequest("new_address is already the bound address".to_string()));
+    }
+
+    verify_binding_signature(pool.get_ref(), config.get_ref(), ChallengePurpose::Rebind, &data.proof, &new_address).await?;
+
+    if get_address_binding(pool.get_ref(), &new_address, chain_type).await?.is_some() {
+        return Err(AppError::BadRequest(format!("{} is already bound to a Telegram user", new_address)));
+    }
+
+    let holdings = get_group_holdings(pool.get_ref(), telegram_id).await?;
+    let mut subjects = held_subjects(&holdings, chain_type, &old_address);
+
+    if !rebind_user_mapping(pool.get_ref(), &old_address, &new_address, chain_type, telegram_id).await? {
+        return Err(AppError::NotFound(format!("{} is not bound to this Telegram user", old_address)));
+    }
+    info!("Rebound Telegram user {} from {} to {} on {}", telegram_id, old_address, new_address, chain_type);
+
+    // Groups of the old wallet may be lost, groups of the new one gained
+    let holdings = get_group_holdings(pool.get_ref(), telegram_id).await?;
+    subjects.extend(held_subjects(&holdings, chain_type, &new_address));
+    let groups_updated = reevaluate_groups(pool.get_ref(), chain_type, telegram_id, &new_address, &subjects, None).await?;
+
+    Ok(HttpResponse::Ok().json(BindingResponse {
+        address: new_address,
+        groups_updated,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionBindingRequest {
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub chain_type: Option<ChainType>,
+    /// Wallet that will send the proof transaction
+    pub address: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionBindingResponse {
+    pub memo: String,
+    /// Memo as transaction data, send any amount from `address` with it
+    pub calldata: String,
+    pub address: String,
+    /// pending, bound or expired
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// API endpoint for wallets that cannot sign messages, binding completes once sync sees the memo transaction
+#[post("/bind-by-transaction")]
+async fn bind_by_transaction(
+    data: web::Json<TransactionBindingRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = data.chain_type.unwrap_or_default();
+    parse_telegram_id(&data.telegram_id)?;
+    let blockchain = create_blockchain(chain_type, Arc::new(config.get_ref().clone()))?;
+    if !blockchain.supports_tx_binding() {
+        return Err(AppError::BadRequest(format!("{} does not support binding by transaction", chain_type)));
+    }
+    get_verified_group_bot(pool.get_ref(), &data.chat_id, chain_type).await?;
+
+    let address = chain_type.normalize_address(&data.address);
+    let memo = new_binding_memo();
+    let expires_at = create_pending_binding(
+        pool.get_ref(),
+        &memo,
+        &data.telegram_id,
+        &data.chat_id,
+        chain_type,
+        &address,
+        config.tx_binding_ttl_secs,
+    ).await?;
+    info!("Waiting for a transaction from {} to bind Telegram user {} on {}", address, data.telegram_id, chain_type);
+
+    Ok(HttpResponse::Ok().json(TransactionBindingResponse {
+        calldata: format!("0x{}", hex::encode(memo.as_bytes())),
+        memo,
+        address,
+        status: "pending".to_string(),
+        tx_hash: None,
+        expires_at,
+        success: true,
+        error: None,
+    }))
+}
+
+// API endpoint to poll a transaction binding
+#[get("/bind-by-transaction/{memo}")]
+async fn get_transaction_binding(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let memo = path.into_inner();
+    let binding = get_pending_binding(pool.get_ref(), &memo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Binding {} not found", memo)))?;
+
+    let status = if binding.status == "pending" && binding.expires_at <= OffsetDateTime::now_utc() {
+        "expired".to_string()
+    } else {
+        binding.status
+    };
+
+    Ok(HttpResponse::Ok().json(TransactionBindingResponse {
+        calldata: format!("0x{}", hex::encode(binding.memo.as_bytes())),
+        memo: binding.memo,
+        address: binding.address,
+        status,
+        tx_hash: binding.tx_hash,
+        expires_at: binding.expires_at,
+        success: true,
+        error: None,
+    }))
+}
@@ -0,0 +1,12 @@
+use actix_web::{get, web};
+
+use crate::config::Branding;
+use crate::error::AppError;
+use crate::routes::response::ApiResponse;
+use crate::AppConfig;
+
+/// Product name, logo, colors and support link frontends should present this deployment with
+#[get("/branding")]
+async fn get_branding(config: web::Data<AppConfig>) -> Result<ApiResponse<Branding>, AppError> {
+    Ok(ApiResponse::ok(config.branding.clone()))
+}
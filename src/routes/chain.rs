@@ -0,0 +1,114 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Serialize;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::block_chain::head::{get_head, ChainHead};
+use crate::block_chain::ChainType;
+use crate::db::operations::{get_cursor, get_sync_position};
+use crate::error::AppError;
+use crate::oracle::{OracleSnapshot, PriceOracle};
+use crate::AppConfig;
+
+#[derive(Debug, Serialize)]
+pub struct NativeCurrency {
+    pub symbol: &'static str,
+    pub decimals: u32,
+}
+
+/// Indexing progress of one enabled chain
+#[derive(Debug, Serialize)]
+pub struct ChainStatus {
+    pub chain_type: ChainType,
+    pub native_currency: NativeCurrency,
+    /// Latest head seen by the sync loop, absent until it polled one
+    pub head: Option<ChainHead>,
+    /// Last indexed block or slot, absent for Sui and Aptos which index by event cursor
+    pub indexed_height: Option<u64>,
+    pub indexed_cursor: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub indexed_at: Option<OffsetDateTime>,
+    pub blocks_behind: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainsResponse {
+    pub chains: Vec<ChainStatus>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/chains")]
+async fn get_chains(
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let mut chains = Vec::new();
+    for chain_type in &config.enabled_chains {
+        let head = get_head(*chain_type);
+        let (indexed_height, indexed_cursor, indexed_at) = match chain_type {
+            ChainType::Sui | ChainType::Aptos => {
+                let cursor = get_cursor(pool.get_ref(), *chain_type).await?;
+                (None, cursor.as_ref().map(|c| c.cursor_json.clone()), cursor.map(|c| c.updated_at))
+            }
+            _ => {
+                let position = get_sync_position(pool.get_ref(), *chain_type).await?;
+                (
+                    position.as_ref().map(|p| p.last_synced_block as u64),
+                    position.as_ref().and_then(|p| p.metadata.clone()),
+                    position.and_then(|p| p.updated_at),
+                )
+            }
+        };
+
+        chains.push(ChainStatus {
+            chain_type: *chain_type,
+            native_currency: NativeCurrency {
+                symbol: chain_type.native_symbol(),
+                decimals: chain_type.native_decimals(),
+            },
+            head,
+            indexed_height,
+            indexed_cursor,
+            indexed_at,
+            blocks_behind: head.zip(indexed_height).map(|(head, indexed)| head.height.saturating_sub(indexed)),
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(ChainsResponse {
+        chains,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct OracleResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oracle: Option<OracleSnapshot>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[get("/chains/{chain_type}/oracle")]
+async fn get_chain_oracle(
+    path: web::Path<ChainType>,
+    oracle: web::Data<PriceOracle>,
+) -> impl Responder {
+    let chain_type = path.into_inner();
+
+    match oracle.get(chain_type) {
+        Some(snapshot) => HttpResponse::Ok().json(OracleResponse {
+            oracle: Some(snapshot),
+            success: true,
+            error: None,
+        }),
+        None => HttpResponse::NotFound().json(OracleResponse {
+            oracle: None,
+            success: false,
+            error: Some(format!("No oracle data for chain {}", chain_type)),
+        }),
+    }
+}
@@ -0,0 +1,109 @@
+use actix_web::{post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::block_chain::ChainType;
+use crate::db::operations::create_challenge;
+use crate::error::{parse_telegram_id, AppError};
+use crate::AppConfig;
+
+/// Text the wallet signs for a challenge, binding the nonce to the Telegram user and group
+pub fn challenge_message(telegram_id: &str, chat_id: &str, nonce: &str) -> String {
+    format!(
+        "Verify Telegram user {} for group {}\nNonce: {}",
+        telegram_id, chat_id, nonce
+    )
+}
+
+/// What a signed challenge authorizes, each purpose signs a different message
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChallengePurpose {
+    /// Join a gated group through /verify-signature
+    #[default]
+    Verify,
+    /// Remove a bound wallet through /unbind, signed by that wallet
+    Unbind,
+    /// Replace a bound wallet through /rebind, signed by the new wallet
+    Rebind,
+    /// Link a wallet to a Discord user through /discord/link, signed by that wallet. The
+    /// challenge is issued to the Discord user id and server id instead of Telegram's
+    Discord,
+}
+
+/// Text the wallet signs to unbind or rebind `address`
+pub fn binding_message(purpose: ChallengePurpose, telegram_id: &str, chat_id: &str, address: &str, nonce: &str) -> String {
+    match purpose {
+        ChallengePurpose::Verify => challenge_message(telegram_id, chat_id, nonce),
+        ChallengePurpose::Unbind => format!(
+            "Unbind wallet {} from Telegram user {}\nNonce: {}",
+            address, telegram_id, nonce
+        ),
+        ChallengePurpose::Rebind => format!(
+            "Bind wallet {} to Telegram user {}\nNonce: {}",
+            address, telegram_id, nonce
+        ),
+        ChallengePurpose::Discord => format!(
+            "Link wallet {} to Discord user {} in server {}\nNonce: {}",
+            address, telegram_id, chat_id, nonce
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateChallengeRequest {
+    pub telegram_id: String,
+    pub chat_id: String,
+    pub chain_type: Option<ChainType>,
+    #[serde(default)]
+    pub purpose: ChallengePurpose,
+    /// Wallet that will sign, required to unbind, rebind or link Discord
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateChallengeResponse {
+    pub nonce: String,
+    /// Exact message to sign and send back to /verify-signature, /unbind or /rebind
+    pub message: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[post("/challenge")]
+async fn create_challenge_handler(
+    data: web::Json<CreateChallengeRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = data.chain_type.unwrap_or_default();
+    parse_telegram_id(&data.telegram_id)?;
+    let address = match (data.purpose, &data.address) {
+        (ChallengePurpose::Verify, _) => String::new(),
+        (_, Some(address)) => chain_type.normalize_address(address),
+        (_, None) => return Err(AppError::BadRequest("address is required to unbind, rebind or link Discord".to_string())),
+    };
+    let nonce = Uuid::new_v4().simple().to_string();
+
+    let expires_at = create_challenge(
+        pool.get_ref(),
+        &nonce,
+        &data.telegram_id,
+        &data.chat_id,
+        chain_type,
+        config.challenge_ttl_secs,
+    ).await?;
+
+    Ok(HttpResponse::Ok().json(CreateChallengeResponse {
+        message: binding_message(data.purpose, &data.telegram_id, &data.chat_id, &address, &nonce),
+        nonce,
+        expires_at,
+        success: true,
+        error: None,
+    }))
+}
@@ -0,0 +1,147 @@
+//! Per-route concurrency limits protecting the shared database pool.
+//!
+//! The pool's few connections are shared by the HTTP workers, the sync loops
+//! and the background jobs, so a burst on one heavy route could starve the
+//! indexer. Routes listed in `CONCURRENCY_LIMITS` run at most that many
+//! requests at once; further requests queue for up to `CONCURRENCY_QUEUE_MS`
+//! and are then shed with `503` and code `overloaded`. Requests in flight,
+//! queued and shed are exported per route as metrics.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::AppError;
+use crate::metrics;
+use crate::AppConfig;
+
+/// Limits applied when `CONCURRENCY_LIMITS` is not set, leaving pool connections for the indexer
+pub const DEFAULT_CONCURRENCY_LIMITS: &str =
+    "/verify-signature=3,/verify-sessions=3,/bind-by-transaction=2,/users/{address}/portfolio=2,/subjects/{subject}/holders=2,/partner/introspect=2";
+
+// Seconds a shed client is told to wait before retrying
+const SHED_RETRY_AFTER_SECS: u64 = 1;
+
+/// Parse `route=limit` pairs separated by commas, a limit of 0 leaves the route unlimited
+pub fn parse_limits(value: &str) -> Result<Vec<(String, usize)>, String> {
+    let mut limits = Vec::new();
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (route, limit) = pair.split_once('=').ok_or_else(|| format!("Expected route=limit, got {}", pair))?;
+        let route = route.trim();
+        if !route.starts_with('/') {
+            return Err(format!("Route {} must start with /", route));
+        }
+        let limit = limit.trim().parse().map_err(|_| format!("Invalid limit of route {}: {}", route, limit.trim()))?;
+        if limit > 0 {
+            limits.push((route.to_string(), limit));
+        }
+    }
+    Ok(limits)
+}
+
+/// Slots of the limited routes, shared by the HTTP workers
+#[derive(Clone)]
+pub struct ConcurrencyLimiter {
+    routes: Arc<HashMap<String, Arc<Semaphore>>>,
+    queue_timeout: Duration,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(limits: &[(String, usize)], queue_timeout: Duration) -> Self {
+        let routes = limits
+            .iter()
+            .map(|(route, limit)| {
+                metrics::HTTP_CONCURRENCY_LIMIT.with_label_values(&[route]).set(*limit as i64);
+                (route.clone(), Arc::new(Semaphore::new(*limit)))
+            })
+            .collect();
+        Self { routes: Arc::new(routes), queue_timeout }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::new(&config.concurrency_limits, Duration::from_millis(config.concurrency_queue_ms))
+    }
+
+    /// Wait for a slot of `semaphore`'s route, `None` once the queue timeout passed
+    async fn acquire(&self, route: &str, semaphore: Arc<Semaphore>) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+        metrics::HTTP_QUEUED_REQUESTS.with_label_values(&[route]).inc();
+        match tokio::time::timeout(self.queue_timeout, semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => Some(permit),
+            _ => None,
+        }
+    }
+}
+
+/// Middleware holding a slot of the route's limit while the handler runs, answering 503
+/// when none frees up in time
+pub async fn limit_concurrency<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let limiter = req.app_data::<web::Data<ConcurrencyLimiter>>().cloned();
+    let route = req.match_pattern();
+    let limited = route.zip(limiter).and_then(|(route, limiter)| {
+        let semaphore = limiter.routes.get(&route)?.clone();
+        Some((route, limiter, semaphore))
+    });
+    let Some((route, limiter, semaphore)) = limited else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let Some(permit) = limiter.acquire(&route, semaphore).await else {
+        metrics::HTTP_SHED_REQUESTS.with_label_values(&[&route]).inc();
+        let error = AppError::Overloaded { retry_after_secs: SHED_RETRY_AFTER_SECS };
+        return Ok(req.into_response(error.error_response()).map_into_right_body());
+    };
+
+    let in_flight = metrics::HTTP_IN_FLIGHT_REQUESTS.with_label_values(&[&route]);
+    in_flight.inc();
+    let response = next.call(req).await;
+    in_flight.dec();
+    drop(permit);
+    response.map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(
+            parse_limits(" /verify-signature=3, /users/{address}/portfolio = 2 ,/challenge=0").unwrap(),
+            vec![("/verify-signature".to_string(), 3), ("/users/{address}/portfolio".to_string(), 2)]
+        );
+        assert!(parse_limits("").unwrap().is_empty());
+        assert!(parse_limits("/verify-signature").is_err());
+        assert!(parse_limits("verify-signature=3").is_err());
+        assert!(parse_limits("/verify-signature=many").is_err());
+        assert!(parse_limits(DEFAULT_CONCURRENCY_LIMITS).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_requests_queue_then_shed() {
+        let limiter = ConcurrencyLimiter::new(&[("/verify-signature".to_string(), 1)], Duration::from_millis(20));
+        let semaphore = limiter.routes["/verify-signature"].clone();
+
+        let held = limiter.acquire("/verify-signature", semaphore.clone()).await.unwrap();
+        assert!(limiter.acquire("/verify-signature", semaphore.clone()).await.is_none());
+
+        // A slot freed while queued is handed to the waiting request
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            let semaphore = semaphore.clone();
+            async move { limiter.acquire("/verify-signature", semaphore).await.is_some() }
+        });
+        drop(held);
+        assert!(waiting.await.unwrap());
+    }
+}
@@ -0,0 +1,88 @@
+//! Deprecation notices for public endpoints and fields.
+//!
+//! Nothing is removed from a public response without first being listed in
+//! [`DEPRECATIONS`]. Every response of a listed route then carries
+//! `Deprecation: true`, a `Sunset` date and, when there is one, a `Link` to the
+//! replacement (RFC 8594). Deprecated fields are named in `X-Deprecated-Fields`
+//! so clients can find them before the schema snapshot drops them.
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// A route, or a single field of its response, that is going away
+pub struct Deprecation {
+    /// Route pattern as registered, e.g. `/agents/{agent_name}`
+    pub route: &'static str,
+    /// Response field, or `None` when the whole route is deprecated
+    pub field: Option<&'static str>,
+    /// HTTP date after which it may be removed
+    pub sunset: &'static str,
+    pub replacement: Option<&'static str>,
+}
+
+pub const DEPRECATIONS: &[Deprecation] = &[];
+
+/// Add the deprecation headers for `route` to a response
+pub fn apply_headers(route: &str, headers: &mut HeaderMap) {
+    apply(DEPRECATIONS, route, headers);
+}
+
+fn apply(deprecations: &[Deprecation], route: &str, headers: &mut HeaderMap) {
+    let matching: Vec<&Deprecation> = deprecations.iter().filter(|d| d.route == route).collect();
+    if matching.is_empty() {
+        return;
+    }
+
+    headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    // The earliest sunset wins, it is when clients may first break
+    if let Some(sunset) = matching.iter().min_by_key(|d| d.sunset).map(|d| d.sunset) {
+        headers.insert(HeaderName::from_static("sunset"), HeaderValue::from_static(sunset));
+    }
+    if let Some(replacement) = matching.iter().filter(|d| d.field.is_none()).find_map(|d| d.replacement) {
+        if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", replacement)) {
+            headers.insert(HeaderName::from_static("link"), link);
+        }
+    }
+    let fields: Vec<&str> = matching.iter().filter_map(|d| d.field).collect();
+    if !fields.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&fields.join(", ")) {
+            headers.insert(HeaderName::from_static("x-deprecated-fields"), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[Deprecation] = &[
+        Deprecation {
+            route: "/agent/detail/{agent_name}",
+            field: None,
+            sunset: "Wed, 01 Jul 2026 00:00:00 GMT",
+            replacement: Some("/agents/{agent_name}"),
+        },
+        Deprecation {
+            route: "/agent/detail/{agent_name}",
+            field: Some("invite_url"),
+            sunset: "Wed, 01 Jul 2026 00:00:00 GMT",
+            replacement: None,
+        },
+    ];
+
+    #[test]
+    fn test_deprecated_route_headers() {
+        let mut headers = HeaderMap::new();
+        apply(SAMPLE, "/agent/detail/{agent_name}", &mut headers);
+        assert_eq!(headers.get("deprecation").unwrap(), "true");
+        assert_eq!(headers.get("sunset").unwrap(), "Wed, 01 Jul 2026 00:00:00 GMT");
+        assert_eq!(headers.get("link").unwrap(), "</agents/{agent_name}>; rel=\"successor-version\"");
+        assert_eq!(headers.get("x-deprecated-fields").unwrap(), "invite_url");
+    }
+
+    #[test]
+    fn test_other_routes_untouched() {
+        let mut headers = HeaderMap::new();
+        apply(SAMPLE, "/agents", &mut headers);
+        assert!(headers.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use actix_web::{delete, post, put, web};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use super::binding::{verify_binding_signature, BindingProof};
+use super::challenge::ChallengePurpose;
+use crate::block_chain::ChainType;
+use crate::db::models::DiscordServer;
+use crate::db::operations::{
+    delete_discord_server, get_discord_gates, get_discord_server, get_other_discord_links, get_user_subject_shares, link_discord_member,
+    set_discord_server,
+};
+use crate::error::AppError;
+use crate::platform::discord::{enforce_discord_access, oauth_user, DiscordCommunity};
+use crate::platform::CommunityPlatform;
+use crate::routes::auth::ApiKey;
+use crate::routes::response::ApiResponse;
+use crate::AppConfig;
+
+// Days the invite handed to a newly linked holder stays valid
+const INVITE_DAYS: i64 = 7;
+
+#[derive(Debug, Deserialize)]
+pub struct SetDiscordServerRequest {
+    pub bot_token: String,
+    pub guild_id: String,
+    /// Role opening the holders' channels, the bot's own role must be above it
+    pub role_id: String,
+    /// Channel invites for linked holders are created in
+    pub invite_channel_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordServerResponse {
+    pub agent_name: String,
+    pub guild_id: String,
+    pub role_id: String,
+    pub invite_channel_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkDiscordRequest {
+    pub agent_name: String,
+    pub discord_id: String,
+    /// OAuth2 access token of `discord_id` with the identify scope, issued to DISCORD_CLIENT_ID
+    pub access_token: String,
+    pub address: String,
+    /// Issued by POST /challenge with purpose "discord", the Discord user id as telegram_id
+    /// and the server id as chat_id
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordLinkResponse {
+    pub agent_name: String,
+    pub discord_id: String,
+    pub address: String,
+    /// Whether the user holds the gated role after linking
+    pub has_role: bool,
+    /// Single-use invite into the server for holders who are not in it yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_url: Option<String>,
+}
+
+// Discord ids are snowflakes, 64-bit integers sent as strings
+fn validate_snowflake(field: &str, value: &str) -> Result<(), AppError> {
+    if value.parse::<u64>().is_err() {
+        return Err(AppError::BadRequest(format!("{} must be a Discord id, got {}", field, value)));
+    }
+    Ok(())
+}
+
+#[put("/agents/{agent_name}/discord")]
+async fn set_discord_server_handler(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<SetDiscordServerRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<DiscordServerResponse>, AppError> {
+    let agent_name = path.into_inner();
+    validate_snowflake("guild_id", &data.guild_id)?;
+    validate_snowflake("role_id", &data.role_id)?;
+    validate_snowflake("invite_channel_id", &data.invite_channel_id)?;
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM telegram_bots WHERE agent_name = $1) as "exists!""#,
+        agent_name
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+    if !exists {
+        return Err(AppError::NotFound("Agent not found".to_string()));
+    }
+
+    let server = DiscordServer {
+        agent_name,
+        bot_token: data.bot_token.clone(),
+        guild_id: data.guild_id.clone(),
+        role_id: data.role_id.clone(),
+        invite_channel_id: data.invite_channel_id.clone(),
+    };
+    // Refuse servers the bot cannot gate before members link to them
+    match DiscordCommunity::new(&server).has_role().await {
+        Ok(true) => {}
+        Ok(false) => return Err(AppError::BadRequest(format!("Role {} does not exist in server {}", server.role_id, server.guild_id))),
+        Err(e) => return Err(AppError::InvalidBotToken(e.to_string())),
+    }
+
+    set_discord_server(pool.get_ref(), &server).await?;
+    info!("Discord server {} attached to agent {}", server.guild_id, server.agent_name);
+    Ok(ApiResponse::ok(DiscordServerResponse {
+        agent_name: server.agent_name,
+        guild_id: server.guild_id,
+        role_id: server.role_id,
+        invite_channel_id: server.invite_channel_id,
+    }))
+}
+
+#[delete("/agents/{agent_name}/discord")]
+async fn delete_discord_server_handler(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<()>, AppError> {
+    let agent_name = path.into_inner();
+    if !delete_discord_server(pool.get_ref(), &agent_name).await? {
+        return Err(AppError::NotFound("Agent has no Discord server".to_string()));
+    }
+    info!("Discord server detached from agent {}", agent_name);
+    Ok(ApiResponse::done())
+}
+
+#[post("/discord/link")]
+async fn link_discord_handler(
+    data: web::Json<LinkDiscordRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<DiscordLinkResponse>, AppError> {
+    validate_snowflake("discord_id", &data.discord_id)?;
+    let client_id = config
+        .discord_client_id
+        .as_deref()
+        .ok_or_else(|| AppError::Config("DISCORD_CLIENT_ID is not set".to_string()))?;
+    // The wallet's signature only covers the claimed id, the token proves the account is the caller's
+    match oauth_user(&data.access_token, client_id).await {
+        Ok(Some(user_id)) if user_id == data.discord_id => {}
+        Ok(_) => return Err(AppError::Unauthorized(format!("Access token does not belong to Discord user {}", data.discord_id))),
+        Err(e) => return Err(AppError::Unauthorized(format!("Failed to check the Discord access token: {}", e))),
+    }
+    let server = get_discord_server(pool.get_ref(), &data.agent_name)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent has no Discord server".to_string()))?;
+    let agent = sqlx::query!(
+        r#"SELECT subject_address, chain_type as "chain_type: ChainType" FROM telegram_bots WHERE agent_name = $1"#,
+        data.agent_name
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+    let address = agent.chain_type.normalize_address(&data.address);
+
+    let proof = BindingProof {
+        telegram_id: data.discord_id.clone(),
+        chat_id: server.guild_id.clone(),
+        nonce: data.nonce.clone(),
+        signature: data.signature.clone(),
+        chain_type: Some(agent.chain_type),
+    };
+    verify_binding_signature(pool.get_ref(), config.get_ref(), ChallengePurpose::Discord, &proof, &address).await?;
+
+    // The wallet moves over from users it was linked to before, who lose the role it gave them
+    let discord = DiscordCommunity::new(&server);
+    for (discord_id, has_role) in get_other_discord_links(pool.get_ref(), &data.agent_name, &address, agent.chain_type, &data.discord_id).await? {
+        if has_role {
+            discord
+                .ban(&discord_id)
+                .await
+                .map_err(|e| AppError::InvalidBotToken(format!("Failed to revoke the role of Discord user {}: {}", discord_id, e)))?;
+            info!("Revoked Discord role of agent {} from user {}, {} moved to user {}", data.agent_name, discord_id, address, data.discord_id);
+        }
+    }
+    link_discord_member(pool.get_ref(), &data.agent_name, &data.discord_id, &address, agent.chain_type).await?;
+    info!("Linked Discord user {} of agent {} to {}", data.discord_id, data.agent_name, address);
+
+    // Gate the new link right away with the stored balances, a role kept from a previous wallet
+    // is taken away when this one holds too little
+    let balance = get_user_subject_shares(pool.get_ref(), &address, &agent.subject_address, agent.chain_type).await?;
+    let mut conn = pool.acquire().await?;
+    enforce_discord_access(&mut conn, agent.chain_type, &address, &agent.subject_address, &balance).await?;
+    let has_role = get_discord_gates(&mut conn, agent.chain_type, &address, &agent.subject_address)
+        .await?
+        .iter()
+        .any(|gate| gate.server.agent_name == data.agent_name && gate.discord_id == data.discord_id && gate.has_role);
+
+    let invite_url = if has_role {
+        discord
+            .invite(&data.discord_id, Utc::now() + Duration::days(INVITE_DAYS))
+            .await
+            .inspect_err(|e| warn!("Failed to create a Discord invite for user {}: {:?}", data.discord_id, e))
+            .ok()
+    } else {
+        None
+    };
+
+    Ok(ApiResponse::ok(DiscordLinkResponse {
+        agent_name: data.agent_name.clone(),
+        discord_id: data.discord_id.clone(),
+        address,
+        has_role,
+        invite_url,
+    }))
+}
@@ -0,0 +1,159 @@
+//! Data export of deleted agents.
+//!
+//! Deleting an agent stops its bot at once but keeps what it gathered for
+//! [`EXPORT_WINDOW_DAYS`](crate::routes::agent::EXPORT_WINDOW_DAYS):
+//! `GET /agents/{agent_name}/export` hands the creator the group's members,
+//! the trades of its subjects and their analytics until the retention job
+//! purges the agent. It takes a partner [`PartnerKey`] allowed for the agent's
+//! subject, admin keys may export any agent. Every export is counted on the
+//! deletion's audit row.
+
+use actix_web::{get, web};
+use serde::Serialize;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use crate::block_chain::ChainType;
+use crate::db::operations::{get_open_agent_deletion, get_subject_holders, get_subject_trade_stats, get_trade_events, record_agent_export};
+use crate::error::AppError;
+use crate::routes::agent::{trade_stats, TradeStats};
+use crate::routes::auth::PartnerKey;
+use crate::routes::response::ApiResponse;
+use crate::routes::subject::to_native_units;
+
+// Most members and trades exported per subject
+const EXPORT_ROW_LIMIT: i64 = 50_000;
+
+#[derive(Debug, Serialize)]
+pub struct ExportMember {
+    pub subject: String,
+    pub address: String,
+    /// Telegram user bound to the address, if any
+    pub telegram_id: Option<String>,
+    /// Balance in whole shares
+    pub share_amount: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportTrade {
+    pub subject: String,
+    pub trader: String,
+    pub is_buy: bool,
+    pub share_amount: String,
+    /// Amounts in whole native tokens
+    pub native_amount: String,
+    pub protocol_fee: String,
+    pub subject_fee: String,
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_number: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub block_time: Option<OffsetDateTime>,
+    pub wash_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubjectAnalytics {
+    pub subject: String,
+    /// Current holders
+    pub holders: i64,
+    /// Trade totals over the subject's whole history
+    pub all_time: TradeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentExport {
+    pub agent_name: String,
+    pub chain_type: ChainType,
+    pub chat_group_id: String,
+    pub subjects: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted_at: OffsetDateTime,
+    /// The data is purged after this time
+    #[serde(with = "time::serde::rfc3339")]
+    pub export_until: OffsetDateTime,
+    pub members: Vec<ExportMember>,
+    pub trades: Vec<ExportTrade>,
+    pub analytics: Vec<SubjectAnalytics>,
+    /// Some subject had more than the exported members or trades
+    pub truncated: bool,
+}
+
+#[get("/agents/{agent_name}/export")]
+async fn export_deleted_agent(
+    key: PartnerKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<AgentExport>, AppError> {
+    let agent_name = path.into_inner();
+
+    let deletion = get_open_agent_deletion(pool.get_ref(), &agent_name)
+        .await?
+        .filter(|deletion| deletion.export_until > OffsetDateTime::now_utc())
+        .ok_or_else(|| AppError::NotFound(format!("No export of deleted agent {} is available", agent_name)))?;
+
+    let chain_type = deletion.chain_type;
+    let allowed = deletion.subjects.first().is_some_and(|subject| key.allows(chain_type, &chain_type.normalize_address(subject)));
+    if !allowed {
+        return Err(AppError::Forbidden(format!("API key {} may not export agent {}", key.name, agent_name)));
+    }
+
+    let decimals = chain_type.native_decimals();
+    let mut members = Vec::new();
+    let mut trades = Vec::new();
+    let mut analytics = Vec::new();
+    let mut truncated = false;
+
+    for subject in &deletion.subjects {
+        let subject = chain_type.normalize_address(subject);
+
+        let (holders, total) = get_subject_holders(pool.get_ref(), &subject, chain_type, false, EXPORT_ROW_LIMIT, 0).await?;
+        truncated |= total > EXPORT_ROW_LIMIT;
+        members.extend(holders.into_iter().map(|holder| ExportMember {
+            subject: subject.clone(),
+            address: holder.trader,
+            telegram_id: holder.telegram_id,
+            share_amount: holder.share_amount.to_string(),
+        }));
+
+        let events = get_trade_events(pool.get_ref(), chain_type, None, Some(&subject), EXPORT_ROW_LIMIT, 0).await?;
+        truncated |= events.len() as i64 == EXPORT_ROW_LIMIT;
+        trades.extend(events.into_iter().map(|event| ExportTrade {
+            subject: event.subject,
+            trader: event.trader,
+            is_buy: event.is_buy,
+            share_amount: event.share_amount.to_string(),
+            native_amount: to_native_units(&event.eth_amount, decimals),
+            protocol_fee: to_native_units(&event.protocol_fee, decimals),
+            subject_fee: to_native_units(&event.subject_fee, decimals),
+            tx_hash: event.tx_hash,
+            log_index: event.log_index,
+            block_number: event.block_number,
+            block_time: event.block_time,
+            wash_reason: event.wash_reason,
+        }));
+
+        let all_time = get_subject_trade_stats(pool.get_ref(), &subject, chain_type, OffsetDateTime::UNIX_EPOCH, false).await?;
+        analytics.push(SubjectAnalytics { subject, holders: total, all_time: trade_stats(&all_time, decimals) });
+    }
+
+    // The export was served either way, a failed audit update is only logged
+    if let Err(e) = record_agent_export(pool.get_ref(), deletion.id, &key.name).await {
+        warn!("Failed to record export of deleted agent {}: {:?}", agent_name, e);
+    }
+    info!("Deleted agent {} exported by {}", agent_name, key.name);
+
+    Ok(ApiResponse::ok(AgentExport {
+        agent_name,
+        chain_type,
+        chat_group_id: deletion.chat_group_id,
+        subjects: deletion.subjects,
+        deleted_at: deletion.requested_at,
+        export_until: deletion.export_until,
+        members,
+        trades,
+        analytics,
+        truncated,
+    }))
+}
@@ -0,0 +1,158 @@
+//! Liveness and readiness probes for load balancers and Kubernetes.
+//!
+//! `GET /health/live` answers as long as the process serves requests.
+//! `GET /health/ready` checks the database, the RPC of every enabled chain and
+//! how far indexing lags behind the head its sync loop last saw, and answers
+//! `503` with the failing components when any of them is unhealthy.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use actix_web::{get, web, HttpResponse};
+use futures::future::join_all;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::block_chain::head::{get_head, ChainHead};
+use crate::block_chain::{create_blockchain, ChainType};
+use crate::db::operations::get_sync_position;
+use crate::AppConfig;
+
+// Slow dependencies fail the probe instead of hanging it
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Fail,
+}
+
+impl HealthStatus {
+    fn of(healthy: bool) -> Self {
+        if healthy { HealthStatus::Ok } else { HealthStatus::Fail }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComponentHealth {
+    pub status: HealthStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainHealth {
+    pub chain_type: ChainType,
+    pub status: HealthStatus,
+    pub rpc: ComponentHealth,
+    /// Blocks or slots between the last indexed one and the head, absent for Sui and before the first poll
+    pub blocks_behind: Option<u64>,
+    /// `blocks_behind` in seconds at the observed block time
+    pub sync_lag_secs: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub database: ComponentHealth,
+    pub chains: Vec<ChainHealth>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LivenessResponse {
+    pub status: HealthStatus,
+}
+
+// Time a check, failing it when it errors or outlasts the timeout
+async fn check<F, T, E>(future: F) -> ComponentHealth
+where
+    F: std::future::Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let started = Instant::now();
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, future).await {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    ComponentHealth {
+        status: HealthStatus::of(error.is_none()),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error,
+    }
+}
+
+/// Seconds indexing is behind the head, from the observed average block time
+pub fn sync_lag_secs(head: Option<ChainHead>, indexed_height: Option<u64>) -> Option<f64> {
+    let head = head?;
+    let behind = head.height.saturating_sub(indexed_height?);
+    Some(behind as f64 * head.avg_block_time_ms? / 1000.0)
+}
+
+async fn chain_health(pool: &PgPool, config: Arc<AppConfig>, chain_type: ChainType) -> ChainHealth {
+    let max_lag_secs = config.health_max_sync_lag_secs as f64;
+    let rpc = check(async {
+        let blockchain = create_blockchain(chain_type, config).map_err(|e| e.to_string())?;
+        blockchain.get_gas_price().await.map_err(|e| e.to_string())
+    })
+    .await;
+
+    let head = get_head(chain_type);
+    let indexed_height = match chain_type {
+        ChainType::Sui | ChainType::Aptos => None,
+        _ => get_sync_position(pool, chain_type).await.ok().flatten().map(|p| p.last_synced_block as u64),
+    };
+    let sync_lag_secs = sync_lag_secs(head, indexed_height);
+
+    ChainHealth {
+        chain_type,
+        status: HealthStatus::of(rpc.status == HealthStatus::Ok && sync_lag_secs.is_none_or(|lag| lag <= max_lag_secs)),
+        rpc,
+        blocks_behind: head.zip(indexed_height).map(|(head, indexed)| head.height.saturating_sub(indexed)),
+        sync_lag_secs,
+    }
+}
+
+#[get("/health/live")]
+async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(LivenessResponse { status: HealthStatus::Ok })
+}
+
+#[get("/health/ready")]
+async fn readiness(
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let database = check(sqlx::query_scalar!(r#"SELECT 1 as "one!""#).fetch_one(pool.get_ref())).await;
+    let chains = join_all(
+        config
+            .enabled_chains
+            .iter()
+            .map(|chain_type| chain_health(pool.get_ref(), config.clone().into_inner(), *chain_type)),
+    )
+    .await;
+
+    let ready = database.status == HealthStatus::Ok && chains.iter().all(|chain| chain.status == HealthStatus::Ok);
+    let response = ReadinessResponse { status: HealthStatus::of(ready), database, chains };
+    if ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_sync_lag() {
+        let head = ChainHead { height: 120, observed_at: OffsetDateTime::UNIX_EPOCH, avg_block_time_ms: Some(500.0) };
+        assert_eq!(sync_lag_secs(Some(head), Some(100)), Some(10.0));
+        assert_eq!(sync_lag_secs(Some(head), Some(130)), Some(0.0));
+        assert_eq!(sync_lag_secs(Some(head), None), None);
+        assert_eq!(sync_lag_secs(Some(ChainHead { avg_block_time_ms: None, ..head }), Some(100)), None);
+    }
+}
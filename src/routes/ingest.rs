@@ -0,0 +1,169 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use time::OffsetDateTime;
+use tracing::error;
+
+use crate::block_chain::trade::apply_trade_event;
+use crate::block_chain::ChainType;
+use crate::bot::api::TelegramBotApi;
+use crate::db::models::{EventLocation, NewTradeEvent};
+use crate::db::operations::{advance_ingest_source, get_ingest_high_water_mark, lock_ingest_source};
+use crate::error::AppError;
+use crate::routes::auth::ApiKey;
+use crate::AppConfig;
+
+// Most events accepted in one batch
+const MAX_BATCH_EVENTS: usize = 1000;
+
+/// A trade decoded by an external indexer, amounts as emitted by the contract
+#[derive(Debug, Deserialize)]
+pub struct IngestTradeEvent {
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_number: Option<i64>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub block_time: Option<OffsetDateTime>,
+    pub trader: String,
+    pub subject: String,
+    pub is_buy: bool,
+    pub share_amount: String,
+    pub eth_amount: String,
+    pub protocol_fee: String,
+    pub subject_fee: String,
+    pub supply: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IngestBatchRequest {
+    /// Must be exactly one above the source's high-water mark
+    pub sequence: i64,
+    pub chain_type: ChainType,
+    pub events: Vec<IngestTradeEvent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IngestBatchResponse {
+    pub source: String,
+    pub sequence: i64,
+    pub accepted: usize,
+    pub failed: usize,
+    pub high_water_mark: i64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IngestResyncResponse {
+    pub source: String,
+    pub high_water_mark: i64,
+    /// Sequence the pusher must send next
+    pub next_sequence: i64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn parse_amount(field: &str, value: &str) -> Result<BigDecimal, AppError> {
+    BigDecimal::from_str(value).map_err(|_| AppError::BadRequest(format!("Invalid {}: {}", field, value)))
+}
+
+// Check and convert every event before anything is applied, a bad event rejects the whole batch
+fn parse_events(chain_type: ChainType, events: &[IngestTradeEvent]) -> Result<Vec<(EventLocation, NewTradeEvent)>, AppError> {
+    events
+        .iter()
+        .map(|event| {
+            if event.tx_hash.trim().is_empty() || event.trader.trim().is_empty() || event.subject.trim().is_empty() {
+                return Err(AppError::BadRequest("tx_hash, trader and subject are required".to_string()));
+            }
+            let location = EventLocation {
+                block_number: event.block_number,
+                tx_hash: event.tx_hash.trim().to_string(),
+                log_index: event.log_index,
+                block_time: event.block_time,
+            };
+            let record = NewTradeEvent {
+                trader: chain_type.normalize_address(&event.trader),
+                subject: chain_type.normalize_address(&event.subject),
+                is_buy: event.is_buy,
+                share_amount: parse_amount("share_amount", &event.share_amount)?,
+                eth_amount: parse_amount("eth_amount", &event.eth_amount)?,
+                protocol_fee: parse_amount("protocol_fee", &event.protocol_fee)?,
+                subject_fee: parse_amount("subject_fee", &event.subject_fee)?,
+                supply: parse_amount("supply", &event.supply)?,
+            };
+            Ok((location, record))
+        })
+        .collect()
+}
+
+#[post("/ingest/{source}/batches")]
+async fn ingest_batch(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<IngestBatchRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let source = path.into_inner();
+    if data.sequence < 1 {
+        return Err(AppError::BadRequest("sequence must start at 1".to_string()));
+    }
+    if data.events.len() > MAX_BATCH_EVENTS {
+        return Err(AppError::BadRequest(format!("A batch holds at most {} events", MAX_BATCH_EVENTS)));
+    }
+    let events = parse_events(data.chain_type, &data.events)?;
+
+    // The source row stays locked until commit, so concurrent pushes of one source are serialized
+    let mut tx = pool.begin().await?;
+    let high_water_mark = lock_ingest_source(&mut tx, &source).await?;
+    if data.sequence <= high_water_mark {
+        return Err(AppError::DuplicateBatch { sequence: data.sequence, high_water_mark });
+    }
+    if data.sequence != high_water_mark + 1 {
+        return Err(AppError::OutOfOrderBatch { sequence: data.sequence, expected: high_water_mark + 1 });
+    }
+
+    let share_decimals = config.share_decimals(data.chain_type);
+    let mut failed = 0;
+    for (location, record) in &events {
+        if let Err(e) = apply_trade_event(pool.get_ref(), &TelegramBotApi, data.chain_type, location, record, None, share_decimals).await {
+            error!("Error applying ingested trade {} from {}: {:?}", location.tx_hash, source, e);
+            failed += 1;
+        }
+    }
+
+    advance_ingest_source(&mut tx, &source, data.sequence).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(IngestBatchResponse {
+        source,
+        sequence: data.sequence,
+        accepted: events.len() - failed,
+        failed,
+        high_water_mark: data.sequence,
+        success: true,
+        error: None,
+    }))
+}
+
+#[get("/ingest/{source}/resync")]
+async fn resync_source(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let source = path.into_inner();
+    let high_water_mark = get_ingest_high_water_mark(pool.get_ref(), &source).await?;
+
+    Ok(HttpResponse::Ok().json(IngestResyncResponse {
+        source,
+        high_water_mark,
+        next_sequence: high_water_mark + 1,
+        success: true,
+        error: None,
+    }))
+}
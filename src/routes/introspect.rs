@@ -0,0 +1,122 @@
+//! Holder introspection for partner apps.
+//!
+//! `POST /partner/introspect` answers server-to-server whether a Telegram user
+//! (or wallet) is a verified holder of a subject, with their balance and tier.
+//! It takes a partner [`PartnerKey`] limited to its subjects, admin keys may
+//! look up any subject.
+
+use actix_web::{post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+
+use crate::block_chain::ChainType;
+use crate::db::operations::{get_bound_holdings, get_subject_thresholds, get_user_subject_shares};
+use crate::enforcement::{holds_shares, DEFAULT_MIN_SHARES};
+use crate::error::{parse_telegram_id, AppError};
+use crate::routes::auth::PartnerKey;
+
+// Holder tiers by whole shares held, highest first
+const TIERS: &[(u64, &str)] = &[(100, "whale"), (10, "supporter"), (1, "holder")];
+
+/// Tier of a balance, `none` below a whole share
+pub fn tier(balance: &BigDecimal) -> &'static str {
+    TIERS
+        .iter()
+        .find(|(min, _)| *balance >= BigDecimal::from(*min))
+        .map(|(_, name)| *name)
+        .unwrap_or("none")
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntrospectRequest {
+    pub subject: String,
+    pub chain_type: Option<ChainType>,
+    /// Look up every wallet bound to this Telegram user, or
+    pub telegram_id: Option<String>,
+    /// look up a single wallet
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IntrospectResponse {
+    pub subject: String,
+    pub chain_type: ChainType,
+    pub telegram_id: Option<String>,
+    /// Bound wallets the balance was summed over
+    pub addresses: Vec<String>,
+    /// The user signed in with a wallet on this chain
+    pub verified: bool,
+    pub balance: String,
+    pub tier: &'static str,
+    /// Verified and holding the `min_shares` of the subject's group
+    pub has_access: bool,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[post("/partner/introspect")]
+async fn introspect(
+    key: PartnerKey,
+    data: web::Json<IntrospectRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = data.chain_type.unwrap_or_default();
+    let subject = chain_type.normalize_address(&data.subject);
+    if !key.allows(chain_type, &subject) {
+        return Err(AppError::Forbidden(format!("API key {} may not look up subject {}", key.name, data.subject)));
+    }
+
+    let (telegram_id, address) = match (&data.telegram_id, &data.address) {
+        (Some(telegram_id), None) => {
+            parse_telegram_id(telegram_id)?;
+            (Some(telegram_id.trim()), None)
+        }
+        (None, Some(address)) => (None, Some(chain_type.normalize_address(address))),
+        _ => return Err(AppError::BadRequest("Exactly one of telegram_id and address is required".to_string())),
+    };
+
+    let holdings = get_bound_holdings(pool.get_ref(), chain_type, &subject, telegram_id, address.as_deref()).await?;
+    let verified = !holdings.is_empty();
+    let balance = match &address {
+        // Unbound wallets still have a balance, they just cannot be tied to a user
+        Some(address) if !verified => get_user_subject_shares(pool.get_ref(), address, &subject, chain_type).await?,
+        _ => holdings.iter().map(|holding| &holding.share_amount).sum(),
+    };
+    // Access to at least one of the groups the subject gates
+    let min_shares = get_subject_thresholds(&mut *pool.acquire().await?, &subject, chain_type)
+        .await?
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| BigDecimal::from(DEFAULT_MIN_SHARES));
+
+    Ok(HttpResponse::Ok().json(IntrospectResponse {
+        subject,
+        chain_type,
+        telegram_id: holdings.first().map(|holding| holding.telegram_id.clone()).or(telegram_id.map(str::to_string)),
+        addresses: match address {
+            Some(address) if !verified => vec![address],
+            _ => holdings.into_iter().map(|holding| holding.address).collect(),
+        },
+        verified,
+        tier: tier(&balance),
+        has_access: verified && holds_shares(&balance, &min_shares),
+        balance: balance.to_string(),
+        success: true,
+        error: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiers() {
+        assert_eq!(tier(&BigDecimal::from(0)), "none");
+        assert_eq!(tier(&BigDecimal::from(1)), "holder");
+        assert_eq!(tier(&BigDecimal::from(10)), "supporter");
+        assert_eq!(tier(&BigDecimal::from(250)), "whale");
+    }
+}
@@ -0,0 +1,14 @@
+use actix_web::{get, web, HttpResponse};
+use prometheus::TEXT_FORMAT;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+use crate::metrics;
+
+#[get("/metrics")]
+async fn get_metrics(
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let body = metrics::render(pool.get_ref()).map_err(|e| AppError::Config(format!("Failed to encode metrics: {}", e)))?;
+    Ok(HttpResponse::Ok().content_type(TEXT_FORMAT).body(body))
+}
@@ -1,3 +1,98 @@
 pub mod user;
 pub mod agent;
-pub mod signature;
\ No newline at end of file
+pub mod signature;
+pub mod admin;
+pub mod session;
+pub mod chain;
+pub mod subject;
+pub mod challenge;
+pub mod ingest;
+pub mod binding;
+pub mod onboarding;
+pub mod metrics;
+pub mod schema;
+pub mod deprecation;
+pub mod sign_page;
+pub mod rate_limit;
+pub mod auth;
+pub mod concurrency;
+pub mod introspect;
+pub mod export;
+pub mod response;
+pub mod webhook;
+pub mod branding;
+pub mod health;
+pub mod discord;
+
+use actix_web::web;
+
+/// Register every HTTP route of the service
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(challenge::create_challenge_handler)
+        .service(signature::handle_verify)
+        .service(binding::unbind_wallet)
+        .service(binding::rebind_wallet)
+        .service(binding::bind_by_transaction)
+        .service(binding::get_transaction_binding)
+        .service(agent::handle_add_tg_bot)
+        .service(agent::get_agents)
+        .service(agent::search_agents)
+        .service(agent::get_agent_by_id)
+        .service(agent::get_agent_by_name)
+        .service(agent::get_agent_detail)
+        .service(agent::get_agent_leaderboard)
+        .service(agent::get_agent_stats)
+        .service(agent::suspend_agent)
+        .service(agent::reactivate_agent)
+        .service(agent::update_agent)
+        .service(agent::delete_agent)
+        .service(export::export_deleted_agent)
+        .service(agent::rotate_agent_token)
+        .service(agent::reprompt_unverified_members)
+        .service(agent::get_moderation_log)
+        .service(agent::get_agent_members)
+        .service(agent::rename_agent_handler)
+        .service(agent::get_agent_subjects)
+        .service(agent::update_agent_subjects)
+        .service(onboarding::get_onboarding)
+        .service(onboarding::update_onboarding)
+        .service(webhook::create_webhook_handler)
+        .service(webhook::list_webhooks_handler)
+        .service(webhook::delete_webhook_handler)
+        .service(discord::set_discord_server_handler)
+        .service(discord::delete_discord_server_handler)
+        .service(discord::link_discord_handler)
+        .service(user::get_user_shares_handler)
+        .service(user::get_batch_shares_handler)
+        .service(user::get_user_access_handler)
+        .service(user::get_user_portfolio_handler)
+        .service(user::get_user_groups_handler)
+        .service(introspect::introspect)
+        .service(subject::get_subject_fees_handler)
+        .service(subject::get_subject_holders_handler)
+        .service(admin::get_bots)
+        .service(admin::restart_bot)
+        .service(admin::get_telegram_errors)
+        .service(admin::get_enforcement_latency)
+        .service(admin::get_kill_switch)
+        .service(admin::set_kill_switch)
+        .service(admin::get_held_actions_handler)
+        .service(admin::get_backfills)
+        .service(admin::rollback_enforcement_handler)
+        .service(admin::create_api_key_handler)
+        .service(admin::list_api_keys_handler)
+        .service(admin::revoke_api_key_handler)
+        .service(sign_page::sign_page)
+        .service(session::create_session)
+        .service(session::get_session_status)
+        .service(session::renew_session)
+        .service(session::get_verify_qr)
+        .service(chain::get_chains)
+        .service(chain::get_chain_oracle)
+        .service(branding::get_branding)
+        .service(health::liveness)
+        .service(health::readiness)
+        .service(metrics::get_metrics)
+        .service(ingest::ingest_batch)
+        .service(ingest::resync_source);
+}
@@ -0,0 +1,114 @@
+use actix_web::{get, put, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::db::models::NewOnboardingStep;
+use crate::db::operations::{get_onboarding_steps, replace_onboarding_steps};
+use crate::error::AppError;
+use crate::routes::auth::ApiKey;
+
+// Limits of a sequence, Telegram rejects messages over 4096 characters
+const MAX_ONBOARDING_STEPS: usize = 10;
+const MAX_MESSAGE_LENGTH: usize = 4096;
+const MAX_STEP_DELAY_SECS: i32 = 7 * 24 * 3600;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOnboardingRequest {
+    pub steps: Vec<NewOnboardingStep>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OnboardingStepInfo {
+    pub position: i32,
+    pub message: String,
+    pub delay_secs: i32,
+    pub pin: bool,
+    /// Members the step was delivered to, could not be delivered to, or is still queued for
+    pub sent: i64,
+    pub failed: i64,
+    pub pending: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OnboardingResponse {
+    pub agent_name: String,
+    pub steps: Vec<OnboardingStepInfo>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+async fn ensure_agent_exists(pool: &PgPool, agent_name: &str) -> Result<(), AppError> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM telegram_bots WHERE agent_name = $1) as "exists!""#,
+        agent_name
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !exists {
+        return Err(AppError::NotFound("Agent not found".to_string()));
+    }
+    Ok(())
+}
+
+async fn onboarding_response(pool: &PgPool, agent_name: String) -> Result<HttpResponse, AppError> {
+    let steps = get_onboarding_steps(pool, &agent_name)
+        .await?
+        .into_iter()
+        .map(|step| OnboardingStepInfo {
+            position: step.position,
+            message: step.message,
+            delay_secs: step.delay_secs,
+            pin: step.pin,
+            sent: step.sent,
+            failed: step.failed,
+            pending: step.pending,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(OnboardingResponse {
+        agent_name,
+        steps,
+        success: true,
+        error: None,
+    }))
+}
+
+#[get("/agents/{agent_name}/onboarding")]
+async fn get_onboarding(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let agent_name = path.into_inner();
+    ensure_agent_exists(pool.get_ref(), &agent_name).await?;
+    onboarding_response(pool.get_ref(), agent_name).await
+}
+
+#[put("/agents/{agent_name}/onboarding")]
+async fn update_onboarding(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<UpdateOnboardingRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let agent_name = path.into_inner();
+    if data.steps.len() > MAX_ONBOARDING_STEPS {
+        return Err(AppError::BadRequest(format!("At most {} onboarding steps are allowed", MAX_ONBOARDING_STEPS)));
+    }
+    for step in &data.steps {
+        if step.message.trim().is_empty() || step.message.chars().count() > MAX_MESSAGE_LENGTH {
+            return Err(AppError::BadRequest(format!("Step messages must be 1 to {} characters", MAX_MESSAGE_LENGTH)));
+        }
+        if !(0..=MAX_STEP_DELAY_SECS).contains(&step.delay_secs) {
+            return Err(AppError::BadRequest(format!("delay_secs must be between 0 and {}", MAX_STEP_DELAY_SECS)));
+        }
+    }
+
+    ensure_agent_exists(pool.get_ref(), &agent_name).await?;
+    replace_onboarding_steps(pool.get_ref(), &agent_name, &data.steps).await?;
+    info!("Onboarding of agent {} set to {} steps", agent_name, data.steps.len());
+
+    onboarding_response(pool.get_ref(), agent_name).await
+}
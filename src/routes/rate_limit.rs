@@ -0,0 +1,305 @@
+//! Rate limiting of the verification and agent registration routes.
+//!
+//! Requests to the routes in [`LIMITED_ROUTES`] take a token from a bucket
+//! per client IP and, where the body names a Telegram user, one per Telegram
+//! id, so signatures cannot be brute forced and bots cannot be registered in
+//! bulk. Buckets live in memory, or in Redis when `REDIS_URL` is set and the
+//! `redis` feature is enabled, so several instances share them. A failing
+//! store lets requests through rather than blocking verification.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::error::AppError;
+use crate::metrics;
+use crate::AppConfig;
+
+/// A route taking tokens from the rate limit buckets
+pub struct LimitedRoute {
+    /// Route pattern as registered
+    pub route: &'static str,
+    /// JSON body field holding the Telegram id of the member, if any
+    pub telegram_id_field: Option<&'static str>,
+}
+
+pub const LIMITED_ROUTES: &[LimitedRoute] = &[
+    LimitedRoute { route: "/verify-signature", telegram_id_field: Some("challenge") },
+    LimitedRoute { route: "/verify-sessions", telegram_id_field: Some("challenge") },
+    LimitedRoute { route: "/challenge", telegram_id_field: Some("telegram_id") },
+    LimitedRoute { route: "/unbind", telegram_id_field: Some("telegram_id") },
+    LimitedRoute { route: "/rebind", telegram_id_field: Some("telegram_id") },
+    LimitedRoute { route: "/add_tg_bot", telegram_id_field: None },
+];
+
+// Memory buckets are pruned of idle entries once there are this many
+const MAX_MEMORY_BUCKETS: usize = 10_000;
+
+/// Size and refill rate of a token bucket, disabled when either is zero
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl Limit {
+    fn enabled(&self) -> bool {
+        self.burst > 0 && self.per_minute > 0
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// Storage of token buckets
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Take a token from the bucket `key`, returns how long until one is available when it is empty
+    async fn take(&self, key: &str, limit: Limit) -> anyhow::Result<Option<Duration>>;
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+// Refill a bucket up to `now` and take a token from it
+fn take_token(bucket: &mut Bucket, limit: Limit, now: Instant) -> Option<Duration> {
+    let rate = limit.refill_per_sec();
+    let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * rate).min(limit.burst as f64);
+    bucket.updated_at = now;
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        None
+    } else {
+        Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    }
+}
+
+/// Buckets of a single instance
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, (Bucket, Limit)>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn take(&self, key: &str, limit: Limit) -> anyhow::Result<Option<Duration>> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_MEMORY_BUCKETS {
+            // A bucket refilled to its burst is the same as no bucket
+            buckets.retain(|_, (bucket, limit)| {
+                bucket.tokens + now.saturating_duration_since(bucket.updated_at).as_secs_f64() * limit.refill_per_sec() < limit.burst as f64
+            });
+        }
+        let (bucket, _) = buckets
+            .entry(key.to_string())
+            .or_insert((Bucket { tokens: limit.burst as f64, updated_at: now }, limit));
+        Ok(take_token(bucket, limit, now))
+    }
+}
+
+/// Buckets shared by every instance through Redis
+#[cfg(feature = "redis")]
+pub struct RedisStore {
+    conn: redis::aio::ConnectionManager,
+    script: redis::Script,
+}
+
+// Same refill as take_token, returns 0 when a token was taken or the milliseconds until one is available
+#[cfg(feature = "redis")]
+const TAKE_TOKEN_SCRIPT: &str = r#"
+local burst = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or burst
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - ts) * rate)
+local wait = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+else
+  wait = math.ceil((1 - tokens) / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(burst / rate))
+return wait
+"#;
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(Self {
+            conn: redis::aio::ConnectionManager::new(client).await?,
+            script: redis::Script::new(TAKE_TOKEN_SCRIPT),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl RateLimitStore for RedisStore {
+    async fn take(&self, key: &str, limit: Limit) -> anyhow::Result<Option<Duration>> {
+        let now_ms = time::OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+        let mut conn = self.conn.clone();
+        let wait_ms: u64 = self
+            .script
+            .key(format!("rate_limit:{}", key))
+            .arg(limit.burst)
+            .arg(limit.refill_per_sec() / 1000.0)
+            .arg(now_ms as i64)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok((wait_ms > 0).then(|| Duration::from_millis(wait_ms)))
+    }
+}
+
+/// Rate limits of the limited routes, shared by the HTTP workers
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    per_ip: Limit,
+    per_telegram_id: Limit,
+    trust_proxy_headers: bool,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, per_ip: Limit, per_telegram_id: Limit, trust_proxy_headers: bool) -> Self {
+        Self { store, per_ip, per_telegram_id, trust_proxy_headers }
+    }
+
+    /// Limiter configured from the environment, in Redis when `REDIS_URL` is set and reachable
+    pub async fn from_config(config: &AppConfig) -> Self {
+        let per_ip = Limit { burst: config.rate_limit_ip_burst, per_minute: config.rate_limit_ip_per_minute };
+        let per_telegram_id = Limit { burst: config.rate_limit_telegram_burst, per_minute: config.rate_limit_telegram_per_minute };
+        Self::new(Self::store(config).await, per_ip, per_telegram_id, config.trust_proxy_headers)
+    }
+
+    #[cfg(feature = "redis")]
+    async fn store(config: &AppConfig) -> Arc<dyn RateLimitStore> {
+        if let Some(url) = &config.redis_url {
+            match RedisStore::connect(url).await {
+                Ok(store) => return Arc::new(store),
+                Err(e) => warn!("Cannot connect to Redis, rate limiting per instance: {:?}", e),
+            }
+        }
+        Arc::new(MemoryStore::default())
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn store(config: &AppConfig) -> Arc<dyn RateLimitStore> {
+        if config.redis_url.is_some() {
+            warn!("REDIS_URL is set but the server was built without the redis feature, rate limiting per instance");
+        }
+        Arc::new(MemoryStore::default())
+    }
+
+    // Take a token, letting the request through when the store fails
+    async fn check(&self, route: &str, scope: &str, id: &str, limit: Limit) -> Option<Duration> {
+        if !limit.enabled() {
+            return None;
+        }
+        match self.store.take(&format!("{}:{}:{}", scope, route, id), limit).await {
+            Ok(Some(wait)) => {
+                metrics::RATE_LIMITED_REQUESTS.with_label_values(&[route, scope]).inc();
+                Some(wait)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Rate limit store failed, allowing request: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+// Telegram id named by a JSON body, put back for the handler to read
+async fn peek_telegram_id(req: &mut ServiceRequest, field: &str) -> Result<Option<String>, Error> {
+    let body = req.extract::<web::Bytes>().await?;
+    let telegram_id = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|value| value.get(field)?.as_str().map(str::to_string));
+    req.set_payload(Payload::from(body));
+    Ok(telegram_id)
+}
+
+/// Middleware answering 429 once a client or Telegram user runs out of tokens on a limited route
+pub async fn rate_limit<B: MessageBody>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let route = req.match_pattern();
+    let rule = route.as_deref().and_then(|route| LIMITED_ROUTES.iter().find(|rule| rule.route == route));
+    let (Some(rule), Some(limiter)) = (rule, req.app_data::<web::Data<RateLimiter>>().cloned()) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let ip = if limiter.trust_proxy_headers {
+        req.connection_info().realip_remote_addr().map(str::to_string)
+    } else {
+        req.peer_addr().map(|addr| addr.ip().to_string())
+    };
+    let mut wait = match &ip {
+        Some(ip) => limiter.check(rule.route, "ip", ip, limiter.per_ip).await,
+        None => None,
+    };
+    if let (None, Some(field)) = (wait, rule.telegram_id_field) {
+        let telegram_id = match peek_telegram_id(&mut req, field).await {
+            Ok(telegram_id) => telegram_id,
+            Err(e) => return Ok(req.error_response(e).map_into_right_body()),
+        };
+        if let Some(telegram_id) = telegram_id {
+            wait = limiter.check(rule.route, "telegram_id", &telegram_id, limiter.per_telegram_id).await;
+        }
+    }
+
+    match wait {
+        Some(wait) => {
+            let error = AppError::RateLimited { retry_after_secs: wait.as_secs_f64().ceil() as u64 };
+            Ok(req.into_response(error.error_response()).map_into_right_body())
+        }
+        None => next.call(req).await.map(ServiceResponse::map_into_left_body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limit = Limit { burst: 2, per_minute: 60 };
+        let start = Instant::now();
+        let mut bucket = Bucket { tokens: 2.0, updated_at: start };
+
+        assert_eq!(take_token(&mut bucket, limit, start), None);
+        assert_eq!(take_token(&mut bucket, limit, start), None);
+        let wait = take_token(&mut bucket, limit, start).unwrap();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 1e-9);
+
+        // One token per second, never more than the burst
+        assert_eq!(take_token(&mut bucket, limit, start + Duration::from_secs(1)), None);
+        let later = start + Duration::from_secs(60);
+        assert_eq!(take_token(&mut bucket, limit, later), None);
+        assert_eq!(take_token(&mut bucket, limit, later), None);
+        assert!(take_token(&mut bucket, limit, later).is_some());
+    }
+
+    #[test]
+    fn test_zero_limit_is_disabled() {
+        assert!(!Limit { burst: 0, per_minute: 10 }.enabled());
+        assert!(!Limit { burst: 10, per_minute: 0 }.enabled());
+    }
+}
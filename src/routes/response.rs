@@ -0,0 +1,149 @@
+//! Shared envelope of JSON responses.
+//!
+//! Handlers return their payload wrapped in [`ApiResponse`], which adds
+//! `success`, `error` and the `request_id` of the request. The payload's fields
+//! are flattened into the envelope, so a response keeps the shape it had before
+//! it was wrapped and the schema snapshot only gains fields. Every request is
+//! given an id by [`request_id`], taken from an incoming `X-Request-Id` when it
+//! looks sane, and echoed back in the same header.
+
+use actix_web::body::BoxBody;
+use actix_web::dev::ServiceRequest;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Longest client-supplied request id that is kept instead of replaced
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Id of the request being served, stored in the request extensions
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        HeaderValue::from_str(&self.0).ok()
+    }
+}
+
+// Ids are logged and echoed, so only short plain tokens are taken from clients
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Assign the request its id, reusing the client's `X-Request-Id` when valid
+pub fn request_id(req: &ServiceRequest) -> RequestId {
+    let incoming = req
+        .headers()
+        .get(HeaderName::from_static(REQUEST_ID_HEADER))
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| valid_request_id(id));
+    let id = RequestId(incoming.map(str::to_string).unwrap_or_else(|| Uuid::new_v4().to_string()));
+    req.extensions_mut().insert(id.clone());
+    id
+}
+
+/// JSON envelope of a response, `data` is flattened into the top level
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    #[serde(flatten)]
+    pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip)]
+    status: StatusCode,
+}
+
+impl<T> ApiResponse<T> {
+    /// Successful response carrying `data`
+    pub fn ok(data: T) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+            request_id: None,
+            status: StatusCode::OK,
+        }
+    }
+
+    /// Failed response without data
+    pub fn error(status: StatusCode, error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(error.into()),
+            request_id: None,
+            status,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl ApiResponse<()> {
+    /// Successful response without data
+    pub fn done() -> Self {
+        Self::ok(())
+    }
+}
+
+impl<T: Serialize> Responder for ApiResponse<T> {
+    type Body = BoxBody;
+
+    fn respond_to(mut self, req: &HttpRequest) -> HttpResponse {
+        if self.request_id.is_none() {
+            self.request_id = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+        }
+        HttpResponse::build(self.status).json(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        name: &'static str,
+    }
+
+    #[test]
+    fn test_data_is_flattened() {
+        let mut response = ApiResponse::ok(Sample { name: "alice" });
+        response.request_id = Some("abc".to_string());
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({"success": true, "name": "alice", "request_id": "abc"})
+        );
+    }
+
+    #[test]
+    fn test_error_has_no_data() {
+        let response: ApiResponse<Sample> = ApiResponse::error(StatusCode::NOT_FOUND, "Agent not found");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({"success": false, "error": "Agent not found"})
+        );
+        assert_eq!(serde_json::to_value(ApiResponse::done()).unwrap(), serde_json::json!({"success": true}));
+    }
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(valid_request_id("3f2a-bc_01"));
+        assert!(!valid_request_id(""));
+        assert!(!valid_request_id("id with spaces"));
+        assert!(!valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
@@ -0,0 +1,281 @@
+//! JSON shape of public responses, for contract tests.
+//!
+//! [`json_schema`] reduces a response to its field names and JSON types, and
+//! [`breaking_changes`] compares it with the snapshot frontends were built
+//! against. Adding fields is allowed; removing or retyping one fails the tests
+//! below. Fields on their way out are announced through [`super::deprecation`]
+//! first, and only removed (with `UPDATE_SCHEMA_SNAPSHOTS=1 cargo test schema`)
+//! after their sunset.
+
+use serde_json::{Map, Value};
+
+/// Field names and JSON types of a value: objects map their fields, arrays
+/// hold the schema of their first item, scalars become their type name
+pub fn json_schema(value: &Value) -> Value {
+    match value {
+        Value::Null => Value::from("null"),
+        Value::Bool(_) => Value::from("boolean"),
+        Value::Number(_) => Value::from("number"),
+        Value::String(_) => Value::from("string"),
+        Value::Array(items) => Value::Array(items.first().map(json_schema).into_iter().collect()),
+        Value::Object(fields) => Value::Object(
+            fields.iter().map(|(name, field)| (name.clone(), json_schema(field))).collect::<Map<String, Value>>(),
+        ),
+    }
+}
+
+/// Fields of `expected` that `actual` removed or retyped, as JSON paths.
+/// A null on either side matches any type, it only means the sample had no value
+pub fn breaking_changes(expected: &Value, actual: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    compare(expected, actual, "$", &mut changes);
+    changes
+}
+
+fn compare(expected: &Value, actual: &Value, path: &str, changes: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::String(null), _) | (_, Value::String(null)) if null == "null" => {},
+        (Value::Object(expected_fields), Value::Object(actual_fields)) => {
+            for (name, expected_field) in expected_fields {
+                let field_path = format!("{}.{}", path, name);
+                match actual_fields.get(name) {
+                    Some(actual_field) => compare(expected_field, actual_field, &field_path, changes),
+                    None => changes.push(format!("{} was removed", field_path)),
+                }
+            }
+        },
+        (Value::Array(expected_items), Value::Array(actual_items)) => {
+            if let (Some(expected_item), Some(actual_item)) = (expected_items.first(), actual_items.first()) {
+                compare(expected_item, actual_item, &format!("{}[]", path), changes);
+            }
+        },
+        _ if expected != actual => changes.push(format!("{} changed from {} to {}", path, type_name(expected), type_name(actual))),
+        _ => {},
+    }
+}
+
+fn type_name(schema: &Value) -> String {
+    match schema {
+        Value::Object(_) => "object".to_string(),
+        Value::Array(_) => "array".to_string(),
+        other => other.as_str().unwrap_or("unknown").to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::Serialize;
+    use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+    use crate::block_chain::ChainType;
+    use crate::config::Branding;
+    use crate::db::models::QualifyingGroup;
+    use crate::routes::agent::{
+        Agent, AgentDetailResponse, AgentListResponse, AgentResponse, AgentSearchResponse, AgentSearchResult, AgentStatsResponse, TradeStats,
+        TradeStatsPoint,
+    };
+    use crate::routes::challenge::CreateChallengeResponse;
+    use crate::routes::discord::DiscordLinkResponse;
+    use crate::routes::response::ApiResponse;
+    use crate::routes::session::SessionStatusResponse;
+    use crate::routes::subject::{DailyFees, Holder, SubjectFeesResponse, SubjectHoldersResponse};
+    use crate::routes::user::{
+        BatchSharesResponse, GroupAccess, ShareBalance, SharesSource, SubjectShare, UserAccessResponse, UserGroupsResponse, UserSharesResponse,
+    };
+
+    const SNAPSHOT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/routes/schemas/public_api.json");
+
+    fn text() -> String {
+        "text".to_string()
+    }
+
+    fn agent() -> Agent {
+        Agent {
+            agent_id: text(),
+            agent_name: text(),
+            subject_address: text(),
+            created_at: PrimitiveDateTime::new(Date::from_calendar_date(2025, Month::January, 1).unwrap(), Time::MIDNIGHT),
+            active_members: 1,
+        }
+    }
+
+    fn schema_of<T: Serialize>(response: T) -> Value {
+        json_schema(&serde_json::to_value(response).unwrap())
+    }
+
+    // Enveloped response with its optional error and request id set
+    fn enveloped<T: Serialize>(data: T) -> Value {
+        let mut response = ApiResponse::ok(data);
+        response.error = Some(text());
+        response.request_id = Some(text());
+        schema_of(response)
+    }
+
+    // One sample per public endpoint, with every optional field set
+    fn public_schemas() -> BTreeMap<&'static str, Value> {
+        let mut schemas = BTreeMap::new();
+        schemas.insert("GET /agents", enveloped(AgentListResponse { agents: vec![agent()], total: 1, page: 1, page_size: 10 }));
+        schemas.insert("GET /agents/{agent_name}", enveloped(AgentResponse { agent: Some(agent()) }));
+        schemas.insert("GET /agents/by-id/{agent_id}", enveloped(AgentResponse { agent: Some(agent()) }));
+        schemas.insert("GET /agent/detail/{agent_name}", enveloped(AgentDetailResponse {
+            agent_name: text(),
+            subject_address: text(),
+            invite_url: text(),
+            bio: Some(text()),
+            min_shares: text(),
+            supply: text(),
+            supply_cap: Some(text()),
+            available_supply: Some(text()),
+            sold_out: true,
+        }));
+        let stats = || TradeStats { buy_count: 1, sell_count: 1, volume: text(), unique_traders: 1 };
+        schemas.insert("GET /agents/{agent_name}/stats", enveloped(AgentStatsResponse {
+            agent_name: text(),
+            subject_address: text(),
+            chain_type: ChainType::Monad,
+            supply: text(),
+            last_24h: stats(),
+            last_7d: stats(),
+            series: Some(vec![TradeStatsPoint { start: OffsetDateTime::UNIX_EPOCH, stats: stats() }]),
+        }));
+        schemas.insert("GET /agents/search", enveloped(AgentSearchResponse {
+            agents: vec![AgentSearchResult {
+                agent_name: text(),
+                subject_address: text(),
+                bio: Some(text()),
+                created_at: agent().created_at,
+                score: 1.0,
+            }],
+        }));
+        schemas.insert("GET /branding", enveloped(Branding {
+            logo_url: Some(text()),
+            support_url: Some(text()),
+            ..Branding::default()
+        }));
+        schemas.insert("GET /users/{address}/groups", enveloped(UserGroupsResponse {
+            address: text(),
+            chain_type: ChainType::Monad,
+            groups: vec![QualifyingGroup { agent_name: text(), invite_url: text(), chain_type: ChainType::Monad }],
+        }));
+        schemas.insert("GET /users/{user_address}/shares/{chain_type}", enveloped(UserSharesResponse {
+            user_address: text(),
+            shares: vec![SubjectShare { subject_address: text(), shares_amount: text(), source: SharesSource::Db }],
+            chain_type: ChainType::Monad,
+        }));
+        schemas.insert("GET /users/{telegram_id}/access", enveloped(UserAccessResponse {
+            telegram_id: text(),
+            groups: vec![GroupAccess {
+                agent_name: text(),
+                chat_group_id: text(),
+                chain_type: ChainType::Monad,
+                subject_address: text(),
+                address: text(),
+                balance: text(),
+                required: text(),
+                shortfall: text(),
+                has_access: true,
+                buy_url: Some(text()),
+            }],
+        }));
+        schemas.insert("GET /subjects/{subject}/fees", schema_of(SubjectFeesResponse {
+            subject: text(),
+            chain_type: ChainType::Monad,
+            from: text(),
+            to: text(),
+            days: vec![DailyFees { day: text(), protocol_fee: text(), subject_fee: text(), trade_count: 1 }],
+            total_protocol_fee: text(),
+            total_subject_fee: text(),
+            total_trades: 1,
+            success: true,
+            error: Some(text()),
+        }));
+        schemas.insert("GET /subjects/{subject}/holders", schema_of(SubjectHoldersResponse {
+            subject: text(),
+            chain_type: ChainType::Monad,
+            holders: vec![Holder { address: text(), share_amount: text(), telegram_id: Some(text()) }],
+            total: 1,
+            page: 1,
+            page_size: 10,
+            success: true,
+        }));
+        schemas.insert("POST /challenge", schema_of(CreateChallengeResponse {
+            nonce: text(),
+            message: text(),
+            expires_at: OffsetDateTime::UNIX_EPOCH,
+            success: true,
+            error: Some(text()),
+        }));
+        schemas.insert("POST /verify-signature", enveloped(()));
+        schemas.insert("POST /shares/batch", enveloped(BatchSharesResponse {
+            balances: vec![ShareBalance {
+                subject_address: text(),
+                user_address: text(),
+                chain_type: ChainType::Monad,
+                shares_amount: Some(text()),
+                source: Some(SharesSource::Db),
+            }],
+        }));
+        schemas.insert("POST /discord/link", enveloped(DiscordLinkResponse {
+            agent_name: text(),
+            discord_id: text(),
+            address: text(),
+            has_role: true,
+            invite_url: Some(text()),
+        }));
+        schemas.insert("GET /verify-status/{session_id}", schema_of(SessionStatusResponse {
+            session_id: text(),
+            status: text(),
+            challenge: text(),
+            chat_id: text(),
+            chain_type: ChainType::Monad,
+            expires_at: OffsetDateTime::UNIX_EPOCH,
+            success: true,
+            error: Some(text()),
+        }));
+        schemas
+    }
+
+    #[test]
+    fn test_public_schemas_match_snapshot() {
+        let current = public_schemas();
+        if std::env::var("UPDATE_SCHEMA_SNAPSHOTS").is_ok() {
+            let snapshot = serde_json::to_string_pretty(&current).unwrap();
+            std::fs::write(SNAPSHOT_PATH, snapshot + "\n").unwrap();
+            return;
+        }
+
+        let snapshot: BTreeMap<String, Value> = serde_json::from_str(include_str!("schemas/public_api.json")).unwrap();
+        let mut problems = Vec::new();
+        for (endpoint, expected) in &snapshot {
+            match current.get(endpoint.as_str()) {
+                Some(actual) => problems.extend(breaking_changes(expected, actual).into_iter().map(|change| format!("{}: {}", endpoint, change))),
+                None => problems.push(format!("{} has no sample any more", endpoint)),
+            }
+        }
+        for endpoint in current.keys() {
+            if !snapshot.contains_key(*endpoint) {
+                problems.push(format!("{} is missing from the snapshot, run with UPDATE_SCHEMA_SNAPSHOTS=1", endpoint));
+            }
+        }
+        assert!(problems.is_empty(), "Public API schema changed:\n{}", problems.join("\n"));
+    }
+
+    #[test]
+    fn test_breaking_changes() {
+        let expected = json_schema(&serde_json::json!({"a": 1, "b": "x", "c": [{"d": true}], "e": null}));
+        let added = json_schema(&serde_json::json!({"a": 2, "b": "y", "c": [{"d": false, "f": 1}], "e": "z", "g": 1}));
+        assert!(breaking_changes(&expected, &added).is_empty());
+
+        let broken = json_schema(&serde_json::json!({"a": "1", "c": [{"d": 1}], "e": 1}));
+        assert_eq!(
+            breaking_changes(&expected, &broken),
+            vec![
+                "$.a changed from number to string".to_string(),
+                "$.b was removed".to_string(),
+                "$.c[].d changed from boolean to number".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::io::Cursor;
+
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{get, post, web, HttpResponse};
+use image::{ImageFormat, Luma};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use teloxide::prelude::{Requester, UserId};
+use teloxide::Bot;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::block_chain::ChainType;
+use crate::bot::handler::issue_sign_link;
+use crate::db::operations::{get_verification_session, mark_session_renewed, track_bot_message};
+use crate::error::{parse_telegram_id, AppError};
+use crate::routes::auth::PartnerKey;
+use crate::AppConfig;
+
+// Side of rendered QR codes in pixels, by default and at most
+const DEFAULT_QR_SIZE: u32 = 256;
+const MAX_QR_SIZE: u32 = 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    pub challenge: String,
+    pub chat_id: String,
+    pub chain_type: Option<ChainType>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateSessionResponse {
+    pub session_id: String,
+    /// Link the desktop page encodes as a QR code for the mobile wallet
+    pub sign_url: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionStatusResponse {
+    pub session_id: String,
+    pub status: String,
+    pub challenge: String,
+    pub chat_id: String,
+    pub chain_type: ChainType,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[post("/verify-sessions")]
+async fn create_session(
+    data: web::Json<CreateSessionRequest>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = data.chain_type.unwrap_or_default();
+    parse_telegram_id(&data.challenge)?;
+
+    let (session, sign_url) = issue_sign_link(
+        pool.get_ref(),
+        &config.sign_page_url,
+        &data.challenge,
+        &data.chat_id,
+        chain_type,
+        config.verify_session_ttl_secs,
+    ).await?;
+
+    Ok(HttpResponse::Ok().json(CreateSessionResponse {
+        session_id: session.id,
+        sign_url,
+        expires_at: session.expires_at,
+        success: true,
+        error: None,
+    }))
+}
+
+#[get("/verify-status/{session_id}")]
+async fn get_session_status(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let session_id = path.into_inner();
+
+    let session = get_verification_session(pool.get_ref(), &session_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(SessionStatusResponse {
+        status: session.effective_status().to_string(),
+        session_id: session.id,
+        challenge: session.telegram_id,
+        chat_id: session.chat_id,
+        chain_type: session.chain_type,
+        expires_at: session.expires_at,
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenewSessionResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Called from the "request a new link" page of an expired or failed session.
+// The new link is delivered by the group's bot, never returned to the caller.
+#[post("/verify-sessions/{session_id}/renew")]
+async fn renew_session(
+    path: web::Path<String>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let session_id = path.into_inner();
+
+    let session = get_verification_session(pool.get_ref(), &session_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+    let bot_info = sqlx::query!(
+        "SELECT agent_name, bot_token, delete_service_messages FROM telegram_bots WHERE chat_group_id = $1 AND chain_type = $2",
+        session.chat_id,
+        session.chain_type.as_str()
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Bot not found for this session".to_string()))?;
+
+    // Each stale link can be renewed once, pending and completed sessions not at all
+    if !mark_session_renewed(pool.get_ref(), &session.id).await? {
+        return Err(AppError::BadRequest("Session is still valid or was already renewed".to_string()));
+    }
+
+    let (_, sign_url) = issue_sign_link(
+        pool.get_ref(),
+        &config.sign_page_url,
+        &session.telegram_id,
+        &session.chat_id,
+        session.chain_type,
+        config.verify_session_ttl_secs,
+    ).await?;
+    let text = format!(
+        "Here is your new verification link, it expires in {} minutes: {}",
+        config.verify_session_ttl_secs / 60, sign_url
+    );
+
+    // Prefer a private message, members who never started the bot get it in the group
+    let bot = Bot::new(bot_info.bot_token);
+    let user_id = UserId(parse_telegram_id(&session.telegram_id)?);
+    if let Err(e) = bot.send_message(user_id, text.clone()).await {
+        warn!("Cannot DM new sign link to user {} ({}), posting in group", session.telegram_id, e);
+        let message = bot.send_message(session.chat_id.clone(), text).await?;
+        if bot_info.delete_service_messages {
+            if let Err(e) = track_bot_message(pool.get_ref(), &bot_info.agent_name, &session.chat_id, message.id.0, config.prompt_ttl_secs).await {
+                warn!("Failed to track prompt message for agent {}: {:?}", bot_info.agent_name, e);
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(RenewSessionResponse {
+        success: true,
+        error: None,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyQrQuery {
+    pub telegram_id: String,
+    pub format: Option<QrFormat>,
+    /// Minimum side in pixels (default 256, max 1024)
+    pub size: Option<u32>,
+}
+
+/// Render `link` as a QR code image of at least `size` pixels, returns its content type and bytes
+pub fn render_qr(link: &str, format: QrFormat, size: u32) -> Result<(&'static str, Vec<u8>), AppError> {
+    let code = QrCode::new(link.as_bytes()).map_err(|e| AppError::BadRequest(format!("Link cannot be encoded as a QR code: {}", e)))?;
+    match format {
+        QrFormat::Svg => {
+            let image = code.render::<svg::Color>().min_dimensions(size, size).build();
+            Ok(("image/svg+xml", image.into_bytes()))
+        }
+        QrFormat::Png => {
+            let image = code.render::<Luma<u8>>().min_dimensions(size, size).build();
+            let mut bytes = Vec::new();
+            image
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .expect("Encoding a PNG into memory cannot fail");
+            Ok(("image/png", bytes))
+        }
+    }
+}
+
+// QR code of a fresh sign link for one member, for onboarding at events where the
+// link is printed or shown on a screen. Every request opens a new verification
+// session, the image is cached by the client until that session expires
+#[get("/agents/{agent_name}/verify-qr")]
+async fn get_verify_qr(
+    key: PartnerKey,
+    path: web::Path<String>,
+    query: web::Query<VerifyQrQuery>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let agent_name = path.into_inner();
+    parse_telegram_id(&query.telegram_id)?;
+
+    let agent = sqlx::query!(
+        r#"SELECT chat_group_id, subject_address, chain_type as "chain_type: ChainType", enabled FROM telegram_bots WHERE agent_name = $1"#,
+        agent_name
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+    if !key.allows(agent.chain_type, &agent.chain_type.normalize_address(&agent.subject_address)) {
+        return Err(AppError::Forbidden(format!("API key {} may not onboard members of agent {}", key.name, agent_name)));
+    }
+    if !agent.enabled {
+        return Err(AppError::BadRequest("Agent is disabled".to_string()));
+    }
+
+    let (session, sign_url) = issue_sign_link(
+        pool.get_ref(),
+        &config.sign_page_url,
+        &query.telegram_id,
+        &agent.chat_group_id,
+        agent.chain_type,
+        config.verify_session_ttl_secs,
+    ).await?;
+
+    let size = query.size.unwrap_or(DEFAULT_QR_SIZE).clamp(64, MAX_QR_SIZE);
+    let (content_type, image) = render_qr(&sign_url, query.format.unwrap_or_default(), size)?;
+    let max_age = (session.expires_at - OffsetDateTime::now_utc()).whole_seconds().max(0) as u32;
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(CacheControl(vec![CacheDirective::Private, CacheDirective::MaxAge(max_age)]))
+        .insert_header(("X-Session-Id", session.id))
+        .body(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_qr_formats() {
+        let link = "https://alice.example/sign?session=0123456789abcdef";
+        let (content_type, png) = render_qr(link, QrFormat::Png, 256).unwrap();
+        assert_eq!(content_type, "image/png");
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let (content_type, svg) = render_qr(link, QrFormat::Svg, 256).unwrap();
+        assert_eq!(content_type, "image/svg+xml");
+        assert!(String::from_utf8(svg).unwrap().contains("<svg"));
+    }
+}
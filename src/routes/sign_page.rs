@@ -0,0 +1,146 @@
+//! Built-in wallet signing page.
+//!
+//! `GET /sign?session=<id>` renders the page the bot links members to, with the
+//! session's challenge, group and subject baked in and a fresh nonce already
+//! issued, so verification needs nothing hosted elsewhere. The page signs with
+//! an EVM or Solana wallet and posts the result to `/verify-signature`. The
+//! deployment's [`Branding`] sets the page's title, logo, colors and support
+//! link. `SIGN_PAGE_URL` still points the bot at an external page instead.
+
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::block_chain::ChainType;
+use crate::config::Branding;
+use crate::db::operations::{create_challenge, get_group_bot, get_verification_session};
+use crate::error::AppError;
+use crate::routes::challenge::challenge_message;
+use crate::AppConfig;
+
+const TEMPLATE: &str = include_str!("sign_page.html");
+
+#[derive(Debug, Deserialize)]
+pub struct SignPageQuery {
+    pub session: String,
+}
+
+// Everything the page script needs, rendered into the template as JSON
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PageData {
+    session_id: String,
+    telegram_id: String,
+    chat_id: String,
+    chain_type: ChainType,
+    subject: String,
+    nonce: String,
+    message: String,
+    #[serde(with = "time::serde::rfc3339")]
+    expires_at: OffsetDateTime,
+    verify_url: &'static str,
+    /// Keeps the field names of `GET /branding`
+    branding: Branding,
+}
+
+// The page data as a JavaScript literal, safe to place inside a <script> element
+fn render(data: &PageData) -> Result<String, serde_json::Error> {
+    let json = serde_json::to_string(data)?
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026");
+    Ok(TEMPLATE.replace("{{PAGE_DATA}}", &json))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn message_page(branding: &Branding, status: StatusCode, text: &str) -> HttpResponse {
+    HttpResponse::build(status).content_type("text/html; charset=utf-8").body(format!(
+        "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"utf-8\"><title>Verify your wallet · {}</title></head>\
+         <body style=\"font-family: system-ui, sans-serif; max-width: 32rem; margin: 3rem auto; color: {}\"><p>{}</p></body></html>",
+        escape_html(&branding.name),
+        branding.primary_color,
+        escape_html(text)
+    ))
+}
+
+#[get("/sign")]
+async fn sign_page(
+    query: web::Query<SignPageQuery>,
+    config: web::Data<AppConfig>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let Some(session) = get_verification_session(pool.get_ref(), &query.session).await? else {
+        return Ok(message_page(&config.branding, StatusCode::NOT_FOUND, "This verification link is invalid."));
+    };
+    if session.effective_status() != "pending" {
+        return Ok(message_page(
+            &config.branding,
+            StatusCode::GONE,
+            "This verification link has expired or was already used. Send /verify to the bot in a private chat for a new one.",
+        ));
+    }
+    let Some(bot) = get_group_bot(pool.get_ref(), &session.chat_id, session.chain_type).await? else {
+        return Ok(message_page(&config.branding, StatusCode::NOT_FOUND, "This group is not gated by any agent."));
+    };
+
+    let nonce = Uuid::new_v4().simple().to_string();
+    let nonce_expires_at = create_challenge(
+        pool.get_ref(),
+        &nonce,
+        &session.telegram_id,
+        &session.chat_id,
+        session.chain_type,
+        config.challenge_ttl_secs,
+    ).await?;
+
+    let html = render(&PageData {
+        message: challenge_message(&session.telegram_id, &session.chat_id, &nonce),
+        session_id: session.id,
+        telegram_id: session.telegram_id,
+        chat_id: session.chat_id,
+        chain_type: session.chain_type,
+        subject: bot.subject_address,
+        nonce,
+        expires_at: nonce_expires_at.min(session.expires_at),
+        verify_url: "/verify-signature",
+        branding: config.branding.clone(),
+    })
+    .map_err(|e| AppError::Config(format!("Cannot render sign page: {}", e)))?;
+
+    Ok(HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_escapes_script_data() {
+        let html = render(&PageData {
+            session_id: "s".to_string(),
+            telegram_id: "1".to_string(),
+            chat_id: "</script><script>alert(1)</script>".to_string(),
+            chain_type: ChainType::Monad,
+            subject: "subject".to_string(),
+            nonce: "n".to_string(),
+            message: "m".to_string(),
+            expires_at: OffsetDateTime::UNIX_EPOCH,
+            verify_url: "/verify-signature",
+            branding: Branding {
+                name: "</title><script>".to_string(),
+                ..Branding::default()
+            },
+        })
+        .unwrap();
+        assert!(!html.contains("{{PAGE_DATA}}"));
+        assert!(!html.contains("</script><script>"));
+        assert!(!html.contains("</title><script>"));
+        assert!(html.contains("\\u003c/script\\u003e"));
+    }
+}
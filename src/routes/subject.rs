@@ -0,0 +1,219 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use time::{Date, Month};
+use tracing::error;
+
+use crate::block_chain::ChainType;
+use crate::db::operations::{get_subject_fees, get_subject_fees_without_wash_trades, get_subject_holders};
+use crate::error::AppError;
+
+// Days covered when no range is given
+const DEFAULT_FEE_RANGE_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct SubjectFeesQuery {
+    pub chain_type: Option<ChainType>,
+    /// First day, YYYY-MM-DD (UTC)
+    pub from: Option<NaiveDate>,
+    /// Last day, YYYY-MM-DD (UTC)
+    pub to: Option<NaiveDate>,
+    /// Leave out trades tagged as suspected wash trades
+    #[serde(default)]
+    pub exclude_wash_trades: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyFees {
+    pub day: String,
+    pub protocol_fee: String,
+    pub subject_fee: String,
+    pub trade_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubjectFeesResponse {
+    pub subject: String,
+    pub chain_type: ChainType,
+    pub from: String,
+    pub to: String,
+    pub days: Vec<DailyFees>,
+    pub total_protocol_fee: String,
+    pub total_subject_fee: String,
+    pub total_trades: i64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn to_time_date(date: NaiveDate) -> Option<Date> {
+    let month = Month::try_from(date.month() as u8).ok()?;
+    Date::from_calendar_date(date.year(), month, date.day() as u8).ok()
+}
+
+/// Convert an amount in the smallest unit (wei, MIST, lamports) to whole native tokens
+pub fn to_native_units(amount: &BigDecimal, decimals: u32) -> String {
+    (amount / BigDecimal::from(10u64.pow(decimals))).normalized().to_string()
+}
+
+fn error_response(subject: String, chain_type: ChainType, error: String) -> SubjectFeesResponse {
+    SubjectFeesResponse {
+        subject,
+        chain_type,
+        from: String::new(),
+        to: String::new(),
+        days: Vec::new(),
+        total_protocol_fee: "0".to_string(),
+        total_subject_fee: "0".to_string(),
+        total_trades: 0,
+        success: false,
+        error: Some(error),
+    }
+}
+
+#[get("/subjects/{subject}/fees")]
+async fn get_subject_fees_handler(
+    path: web::Path<String>,
+    query: web::Query<SubjectFeesQuery>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let chain_type = query.chain_type.unwrap_or_default();
+    let subject = chain_type.normalize_address(&path.into_inner());
+
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_FEE_RANGE_DAYS - 1));
+    if from > to {
+        return HttpResponse::BadRequest().json(error_response(
+            subject,
+            chain_type,
+            "from must not be after to".to_string(),
+        ));
+    }
+
+    let (Some(from_date), Some(to_date)) = (to_time_date(from), to_time_date(to)) else {
+        return HttpResponse::BadRequest().json(error_response(subject, chain_type, "Invalid date range".to_string()));
+    };
+
+    // Daily totals don't know which trades were wash trades, so those are rebuilt from the trade log
+    let rows = if query.exclude_wash_trades {
+        get_subject_fees_without_wash_trades(pool.get_ref(), &subject, chain_type, from_date, to_date).await
+    } else {
+        get_subject_fees(pool.get_ref(), &subject, chain_type, from_date, to_date).await
+    };
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!("Failed to query subject fees: {:?}", e);
+            return HttpResponse::InternalServerError().json(error_response(
+                subject,
+                chain_type,
+                format!("Database error: {}", e),
+            ));
+        }
+    };
+
+    let decimals = chain_type.native_decimals();
+    let total_protocol_fee: BigDecimal = rows.iter().map(|row| &row.protocol_fee).sum();
+    let total_subject_fee: BigDecimal = rows.iter().map(|row| &row.subject_fee).sum();
+    let total_trades = rows.iter().map(|row| row.trade_count).sum();
+
+    let days = rows.iter()
+        .map(|row| DailyFees {
+            day: row.day.to_string(),
+            protocol_fee: to_native_units(&row.protocol_fee, decimals),
+            subject_fee: to_native_units(&row.subject_fee, decimals),
+            trade_count: row.trade_count,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(SubjectFeesResponse {
+        subject,
+        chain_type,
+        from: from.to_string(),
+        to: to.to_string(),
+        days,
+        total_protocol_fee: to_native_units(&total_protocol_fee, decimals),
+        total_subject_fee: to_native_units(&total_subject_fee, decimals),
+        total_trades,
+        success: true,
+        error: None,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubjectHoldersQuery {
+    pub chain_type: Option<ChainType>,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+    /// "desc" (default, largest holders first) or "asc"
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Holder {
+    pub address: String,
+    pub share_amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubjectHoldersResponse {
+    pub subject: String,
+    pub chain_type: ChainType,
+    pub holders: Vec<Holder>,
+    pub total: i64,
+    pub page: i64,
+    pub page_size: i64,
+    pub success: bool,
+}
+
+#[get("/subjects/{subject}/holders")]
+async fn get_subject_holders_handler(
+    path: web::Path<String>,
+    query: web::Query<SubjectHoldersQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let chain_type = query.chain_type.unwrap_or_default();
+    let subject = chain_type.normalize_address(&path.into_inner());
+
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(50);
+    if page < 1 || !(1..=500).contains(&page_size) {
+        return Err(AppError::BadRequest("Invalid pagination parameters".to_string()));
+    }
+    let ascending = match query.sort.as_deref() {
+        None | Some("desc") => false,
+        Some("asc") => true,
+        Some(other) => return Err(AppError::BadRequest(format!("Invalid sort order: {}", other))),
+    };
+
+    let (holders, total) = get_subject_holders(
+        pool.get_ref(),
+        &subject,
+        chain_type,
+        ascending,
+        page_size,
+        (page - 1) * page_size,
+    ).await?;
+
+    let holders = holders.into_iter()
+        .map(|holder| Holder {
+            address: holder.trader,
+            share_amount: holder.share_amount.to_string(),
+            telegram_id: holder.telegram_id,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(SubjectHoldersResponse {
+        subject,
+        chain_type,
+        holders,
+        total,
+        page,
+        page_size,
+        success: true,
+    }))
+}
@@ -1,54 +1,418 @@
-use crate::db::operations::get_user_shares;
-use actix_web::{web, get};
+use crate::block_chain::{create_blockchain, Blockchain, ChainType};
+use crate::db::models::QualifyingGroup;
+use crate::db::operations::{get_chain_subjects, get_group_holdings, get_latest_subject_prices, get_qualifying_groups, get_trade_balances, get_user_shares};
+use crate::enforcement::holds_shares;
+use crate::error::AppError;
+use crate::oracle::PriceOracle;
+use crate::pricing::{last_price, sell_quote};
+use crate::routes::response::ApiResponse;
+use crate::AppConfig;
+use actix_web::{web, get, post};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
 use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tracing::{debug, warn};
+
+// Balance calls in flight at once when reading shares from the chain
+const CHAIN_READ_CONCURRENCY: usize = 8;
+// Most entries of a POST /shares/batch request
+const MAX_BATCH_ENTRIES: usize = 200;
 
 #[derive(Serialize)]
 pub struct UserSharesResponse {
-    user_address: String,
-    shares: Vec<SubjectShare>,
-    chain_type: String,
+    pub user_address: String,
+    pub shares: Vec<SubjectShare>,
+    pub chain_type: ChainType,
 }
 
 #[derive(Serialize)]
 pub struct SubjectShare {
-    subject_address: String,
-    shares_amount: String,
+    pub subject_address: String,
+    pub shares_amount: String,
+    pub source: SharesSource,
+}
+
+/// Where a share balance was read from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SharesSource {
+    /// Trades synced into the database
+    #[default]
+    Db,
+    /// The shares contract, for balances the sync never saw
+    Chain,
 }
 
 #[derive(Deserialize)]
 pub struct PathParams {
     user_address: String,
-    chain_type: String,
+    chain_type: ChainType,
+}
+
+#[derive(Deserialize)]
+pub struct SharesQuery {
+    source: Option<SharesSource>,
+}
+
+// Contract balances of (subject, user) pairs, pairs whose balance cannot be read are left out
+async fn read_chain_shares(blockchain: &dyn Blockchain, pairs: Vec<(String, String)>) -> Vec<((String, String), BigDecimal)> {
+    futures::stream::iter(pairs)
+        .map(|(subject, user)| async move {
+            match blockchain.get_shares_balance(&subject, &user).await {
+                Ok(balance) => Some(((subject, user), balance)),
+                Err(e) => {
+                    warn!("Failed to read {} shares of {} from {}: {:?}", subject, user, blockchain.get_name(), e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(CHAIN_READ_CONCURRENCY)
+        .filter_map(|balance| async move { balance })
+        .collect()
+        .await
 }
 
 // API endpoint to get all shares for a user
 #[get("/users/{user_address}/shares/{chain_type}")]
 pub async fn get_user_shares_handler(
     pool: web::Data<PgPool>,
+    config: web::Data<AppConfig>,
     path: web::Path<PathParams>,
-) -> Result<web::Json<UserSharesResponse>, actix_web::Error> {
+    query: web::Query<SharesQuery>,
+) -> Result<ApiResponse<UserSharesResponse>, AppError> {
     let path_params = path.into_inner();
-    let user_address = path_params.user_address.to_lowercase().trim_start_matches("0x").to_owned();
     let chain_type = path_params.chain_type;
+    let user_address = chain_type.normalize_address(&path_params.user_address);
     
-    println!("user_address: {:?}", user_address);
-    println!("chain_type: {:?}", chain_type);
-    let shares = get_user_shares(&pool, &user_address, &chain_type)
-        .await
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Database operation failed"))?;
+    debug!(%user_address, %chain_type, "Loading user shares");
+    let mut shares: BTreeMap<String, (BigDecimal, SharesSource)> = get_user_shares(&pool, &user_address, chain_type)
+        .await?
+        .into_iter()
+        .map(|share| (share.subject, (share.share_amount, SharesSource::Db)))
+        .collect();
+
+    // Users whose trades predate the synced range have no rows, their balances are read from the contract.
+    // Only an explicit request fails when the chain cannot be reached
+    let explicit = query.source == Some(SharesSource::Chain);
+    if explicit || (query.source.is_none() && shares.is_empty()) {
+        match create_blockchain(chain_type, Arc::new(config.get_ref().clone())) {
+            Ok(blockchain) => {
+                let pairs = get_chain_subjects(&pool, chain_type)
+                    .await?
+                    .into_iter()
+                    .map(|subject| (subject, user_address.clone()))
+                    .collect();
+                for ((subject, _), balance) in read_chain_shares(blockchain.as_ref(), pairs).await {
+                    // Subjects the user never held stay out, like subjects without trades
+                    if balance > BigDecimal::from(0) || shares.contains_key(&subject) {
+                        shares.insert(subject, (balance, SharesSource::Chain));
+                    }
+                }
+            }
+            Err(e) if explicit => return Err(e),
+            Err(e) => warn!("Cannot fall back to {} for shares of {}: {}", chain_type, user_address, e),
+        }
+    }
     
     let subject_shares = shares
         .into_iter()
-        .map(|share| SubjectShare {
-            subject_address: share.subject,
-            shares_amount: share.share_amount.to_string(),
+        .map(|(subject_address, (amount, source))| SubjectShare {
+            subject_address,
+            shares_amount: amount.to_string(),
+            source,
         })
         .collect();
     
-    Ok(web::Json(UserSharesResponse {
+    Ok(ApiResponse::ok(UserSharesResponse {
         user_address,
         shares: subject_shares,
         chain_type,
     }))
-} 
\ No newline at end of file
+} 
+
+#[derive(Deserialize)]
+pub struct ShareBalanceQuery {
+    pub subject: String,
+    pub user: String,
+    pub chain_type: Option<ChainType>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchSharesRequest {
+    pub entries: Vec<ShareBalanceQuery>,
+}
+
+#[derive(Serialize)]
+pub struct ShareBalance {
+    pub subject_address: String,
+    pub user_address: String,
+    pub chain_type: ChainType,
+    /// None when neither the database nor the chain could tell
+    pub shares_amount: Option<String>,
+    pub source: Option<SharesSource>,
+}
+
+#[derive(Serialize)]
+pub struct BatchSharesResponse {
+    /// One balance per entry, in the order of the request
+    pub balances: Vec<ShareBalance>,
+}
+
+// API endpoint resolving many balances at once, from synced trades or else from the contract
+#[post("/shares/batch")]
+pub async fn get_batch_shares_handler(
+    pool: web::Data<PgPool>,
+    config: web::Data<AppConfig>,
+    data: web::Json<BatchSharesRequest>,
+) -> Result<ApiResponse<BatchSharesResponse>, AppError> {
+    if data.entries.is_empty() || data.entries.len() > MAX_BATCH_ENTRIES {
+        return Err(AppError::BadRequest(format!("entries must hold 1 to {} balances", MAX_BATCH_ENTRIES)));
+    }
+
+    let mut balances: Vec<ShareBalance> = data
+        .entries
+        .iter()
+        .map(|entry| {
+            let chain_type = entry.chain_type.unwrap_or_default();
+            ShareBalance {
+                subject_address: chain_type.normalize_address(&entry.subject),
+                user_address: chain_type.normalize_address(&entry.user),
+                chain_type,
+                shares_amount: None,
+                source: None,
+            }
+        })
+        .collect();
+
+    let mut by_chain: HashMap<ChainType, Vec<usize>> = HashMap::new();
+    for (index, balance) in balances.iter().enumerate() {
+        by_chain.entry(balance.chain_type).or_default().push(index);
+    }
+
+    for (chain_type, indexes) in by_chain {
+        let (subjects, users): (Vec<String>, Vec<String>) = indexes
+            .iter()
+            .map(|&index| (balances[index].subject_address.clone(), balances[index].user_address.clone()))
+            .unzip();
+        let stored: HashMap<(String, String), BigDecimal> = get_trade_balances(&pool, chain_type, &subjects, &users)
+            .await?
+            .into_iter()
+            .map(|(subject, user, amount)| ((subject, user), amount))
+            .collect();
+
+        let mut missing = Vec::new();
+        for &index in &indexes {
+            let balance = &mut balances[index];
+            match stored.get(&(balance.subject_address.clone(), balance.user_address.clone())) {
+                Some(amount) => {
+                    balance.shares_amount = Some(amount.to_string());
+                    balance.source = Some(SharesSource::Db);
+                }
+                None => missing.push((balance.subject_address.clone(), balance.user_address.clone())),
+            }
+        }
+        if missing.is_empty() {
+            continue;
+        }
+
+        // Pairs the sync never saw a trade of are read from the contract, once each
+        missing.sort();
+        missing.dedup();
+        let blockchain = match create_blockchain(chain_type, Arc::new(config.get_ref().clone())) {
+            Ok(blockchain) => blockchain,
+            Err(e) => {
+                warn!("Cannot read {} balances from the chain: {}", chain_type, e);
+                continue;
+            }
+        };
+        let read: HashMap<(String, String), BigDecimal> = read_chain_shares(blockchain.as_ref(), missing).await.into_iter().collect();
+        for &index in &indexes {
+            let balance = &mut balances[index];
+            if balance.source.is_some() {
+                continue;
+            }
+            if let Some(amount) = read.get(&(balance.subject_address.clone(), balance.user_address.clone())) {
+                balance.shares_amount = Some(amount.to_string());
+                balance.source = Some(SharesSource::Chain);
+            }
+        }
+    }
+
+    Ok(ApiResponse::ok(BatchSharesResponse { balances }))
+}
+
+#[derive(Serialize)]
+pub struct GroupAccess {
+    pub agent_name: String,
+    pub chat_group_id: String,
+    pub chain_type: ChainType,
+    pub subject_address: String,
+    pub address: String,
+    pub balance: String,
+    pub required: String,
+    pub shortfall: String,
+    pub has_access: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UserAccessResponse {
+    pub telegram_id: String,
+    pub groups: Vec<GroupAccess>,
+}
+
+// API endpoint listing how many shares a user is missing for each group
+#[get("/users/{telegram_id}/access")]
+pub async fn get_user_access_handler(
+    pool: web::Data<PgPool>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> Result<ApiResponse<UserAccessResponse>, AppError> {
+    let telegram_id = path.into_inner();
+    let holdings = get_group_holdings(&pool, &telegram_id).await?;
+
+    let groups = holdings
+        .into_iter()
+        .map(|holding| {
+            let has_access = holds_shares(&holding.share_amount, &holding.min_shares);
+            let shortfall = if has_access {
+                BigDecimal::from(0)
+            } else {
+                &holding.min_shares - &holding.share_amount
+            };
+            let buy_url = config.buy_url(&holding.subject_address, holding.chain_type);
+
+            GroupAccess {
+                agent_name: holding.agent_name,
+                chat_group_id: holding.chat_group_id,
+                chain_type: holding.chain_type,
+                subject_address: holding.subject_address,
+                address: holding.address,
+                balance: holding.share_amount.to_string(),
+                required: holding.min_shares.to_string(),
+                shortfall: shortfall.to_string(),
+                has_access,
+                buy_url,
+            }
+        })
+        .collect();
+
+    Ok(ApiResponse::ok(UserAccessResponse { telegram_id, groups }))
+}
+
+#[derive(Deserialize)]
+pub struct UserGroupsQuery {
+    pub chain_type: Option<ChainType>,
+}
+
+#[derive(Serialize)]
+pub struct UserGroupsResponse {
+    pub address: String,
+    pub chain_type: ChainType,
+    pub groups: Vec<QualifyingGroup>,
+}
+
+// API endpoint listing the groups a wallet holds enough shares to join
+#[get("/users/{address}/groups")]
+pub async fn get_user_groups_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<String>,
+    query: web::Query<UserGroupsQuery>,
+) -> Result<ApiResponse<UserGroupsResponse>, AppError> {
+    let chain_type = query.chain_type.unwrap_or_default();
+    let address = chain_type.normalize_address(&path.into_inner());
+    let groups = get_qualifying_groups(&pool, &address, chain_type).await?;
+    Ok(ApiResponse::ok(UserGroupsResponse { address, chain_type, groups }))
+}
+
+#[derive(Deserialize)]
+pub struct PortfolioQuery {
+    pub chain_type: Option<ChainType>,
+}
+
+#[derive(Serialize)]
+pub struct PortfolioHolding {
+    pub subject_address: String,
+    pub share_amount: String,
+    /// Per-share price of the subject's last trade, in native tokens
+    pub last_price: Option<String>,
+    /// What selling the whole holding would return now, net of fees, in native tokens
+    pub estimated_value: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub priced_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct PortfolioResponse {
+    pub address: String,
+    pub chain_type: ChainType,
+    pub native_symbol: &'static str,
+    pub holdings: Vec<PortfolioHolding>,
+    pub total_estimated_value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_value_usd: Option<f64>,
+}
+
+// API endpoint valuing every holding of an address at the latest subject prices
+#[get("/users/{address}/portfolio")]
+pub async fn get_user_portfolio_handler(
+    pool: web::Data<PgPool>,
+    config: web::Data<AppConfig>,
+    oracle: web::Data<PriceOracle>,
+    path: web::Path<String>,
+    query: web::Query<PortfolioQuery>,
+) -> Result<ApiResponse<PortfolioResponse>, AppError> {
+    let chain_type = query.chain_type.unwrap_or_default();
+    let address = chain_type.normalize_address(&path.into_inner());
+
+    let shares: Vec<_> = get_user_shares(&pool, &address, chain_type)
+        .await?
+        .into_iter()
+        .filter(|share| share.share_amount > BigDecimal::from(0))
+        .collect();
+    let subjects: Vec<String> = shares.iter().map(|share| share.subject.clone()).collect();
+    let prices: HashMap<String, _> = get_latest_subject_prices(&pool, chain_type, &subjects)
+        .await?
+        .into_iter()
+        .map(|price| (price.subject.clone(), price))
+        .collect();
+
+    let mut total = BigDecimal::from(0);
+    let holdings = shares
+        .into_iter()
+        .map(|share| {
+            let price = prices.get(&share.subject);
+            let value = price.map(|price| sell_quote(price, &share.share_amount, config.share_price_divisor));
+            if let Some(value) = &value {
+                total += value;
+            }
+            PortfolioHolding {
+                subject_address: share.subject,
+                share_amount: share.share_amount.to_string(),
+                last_price: price.and_then(|price| last_price(price, chain_type.native_decimals())).map(|p| p.to_string()),
+                estimated_value: value.map(|value| value.to_string()),
+                priced_at: price.map(|price| price.traded_at),
+            }
+        })
+        .collect();
+
+    let total_value_usd = oracle
+        .get(chain_type)
+        .and_then(|snapshot| snapshot.native_price_usd)
+        .zip(total.to_string().parse::<f64>().ok())
+        .map(|(usd, total)| usd * total);
+
+    Ok(ApiResponse::ok(PortfolioResponse {
+        address,
+        chain_type,
+        native_symbol: chain_type.native_symbol(),
+        holdings,
+        total_estimated_value: total.normalized().to_string(),
+        total_value_usd,
+    }))
+}
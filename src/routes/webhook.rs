@@ -0,0 +1,145 @@
+use actix_web::{delete, get, post, web};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::db::models::WebhookInfo;
+use crate::db::operations::{create_webhook, delete_webhook, list_webhooks};
+use crate::error::AppError;
+use crate::routes::auth::ApiKey;
+use crate::routes::response::ApiResponse;
+use crate::webhooks::{generate_secret, WebhookEvent};
+
+// Limits keeping the fan-out of a single event small
+const MAX_WEBHOOKS_PER_AGENT: usize = 10;
+const MAX_URL_LENGTH: usize = 2048;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Subscribed events, every event when omitted
+    pub events: Option<Vec<WebhookEvent>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateWebhookResponse {
+    /// Signing secret, only ever returned here
+    pub secret: String,
+    pub webhook: WebhookInfo,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookListResponse {
+    pub agent_name: String,
+    pub webhooks: Vec<WebhookInfo>,
+}
+
+async fn ensure_agent_exists(pool: &PgPool, agent_name: &str) -> Result<(), AppError> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM telegram_bots WHERE agent_name = $1) as "exists!""#,
+        agent_name
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !exists {
+        return Err(AppError::NotFound("Agent not found".to_string()));
+    }
+    Ok(())
+}
+
+// Callback URLs must be absolute http(s) URLs
+fn validate_url(url: &str) -> Result<(), AppError> {
+    if url.len() > MAX_URL_LENGTH {
+        return Err(AppError::BadRequest(format!("url must be at most {} characters", MAX_URL_LENGTH)));
+    }
+    let parsed = Url::parse(url).map_err(|e| AppError::BadRequest(format!("Invalid url: {}", e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AppError::BadRequest("url must be an http or https URL".to_string()));
+    }
+    Ok(())
+}
+
+// Subscribed event names without duplicates, in a stable order
+fn event_names(events: Option<&[WebhookEvent]>) -> Result<Vec<String>, AppError> {
+    let events = events.unwrap_or(&WebhookEvent::ALL);
+    if events.is_empty() {
+        return Err(AppError::BadRequest("events must not be empty".to_string()));
+    }
+    Ok(WebhookEvent::ALL
+        .iter()
+        .filter(|event| events.contains(event))
+        .map(|event| event.as_str().to_string())
+        .collect())
+}
+
+#[post("/agents/{agent_name}/webhooks")]
+async fn create_webhook_handler(
+    api_key: ApiKey,
+    path: web::Path<String>,
+    data: web::Json<CreateWebhookRequest>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<CreateWebhookResponse>, AppError> {
+    let agent_name = path.into_inner();
+    let url = data.url.trim();
+    validate_url(url)?;
+    let events = event_names(data.events.as_deref())?;
+
+    ensure_agent_exists(pool.get_ref(), &agent_name).await?;
+    if list_webhooks(pool.get_ref(), &agent_name).await?.len() >= MAX_WEBHOOKS_PER_AGENT {
+        return Err(AppError::BadRequest(format!("At most {} webhooks are allowed per agent", MAX_WEBHOOKS_PER_AGENT)));
+    }
+
+    let secret = generate_secret();
+    let webhook = create_webhook(pool.get_ref(), &agent_name, url, &secret, &events).await?;
+    info!("Webhook {} of agent {} created with key {}", webhook.id, agent_name, api_key.name);
+    Ok(ApiResponse::ok(CreateWebhookResponse { secret, webhook }))
+}
+
+#[get("/agents/{agent_name}/webhooks")]
+async fn list_webhooks_handler(
+    _api_key: ApiKey,
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<WebhookListResponse>, AppError> {
+    let agent_name = path.into_inner();
+    ensure_agent_exists(pool.get_ref(), &agent_name).await?;
+    let webhooks = list_webhooks(pool.get_ref(), &agent_name).await?;
+    Ok(ApiResponse::ok(WebhookListResponse { agent_name, webhooks }))
+}
+
+#[delete("/agents/{agent_name}/webhooks/{id}")]
+async fn delete_webhook_handler(
+    api_key: ApiKey,
+    path: web::Path<(String, i64)>,
+    pool: web::Data<PgPool>,
+) -> Result<ApiResponse<()>, AppError> {
+    let (agent_name, id) = path.into_inner();
+    if !delete_webhook(pool.get_ref(), &agent_name, id).await? {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+    info!("Webhook {} of agent {} deleted with key {}", id, agent_name, api_key.name);
+    Ok(ApiResponse::done())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_validation() {
+        assert!(validate_url("https://example.com/hooks/alice").is_ok());
+        assert!(validate_url("http://10.0.0.5:8080/hook").is_ok());
+        assert!(validate_url("ftp://example.com/hook").is_err());
+        assert!(validate_url("example.com/hook").is_err());
+    }
+
+    #[test]
+    fn test_event_names() {
+        assert_eq!(event_names(None).unwrap().len(), WebhookEvent::ALL.len());
+        let events = [WebhookEvent::MemberBanned, WebhookEvent::TradeBuy, WebhookEvent::TradeBuy];
+        assert_eq!(event_names(Some(&events)).unwrap(), vec!["trade.buy", "member.banned"]);
+        assert!(event_names(Some(&[])).is_err());
+    }
+}
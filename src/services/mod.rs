@@ -0,0 +1,4 @@
+//! Business logic shared by the HTTP API and the chain syncs, kept apart from
+//! request handling so it can be tested with mocked chains and Telegram.
+
+pub mod verification;
@@ -0,0 +1,436 @@
+//! Member verification: checking a signed challenge, binding the wallet to the
+//! Telegram user and admitting them to the group when they hold its shares.
+//! Agents with `optimistic_verify` admit the member first and check the shares
+//! in the background, see [`VerificationService::admit_optimistically`].
+
+use std::sync::Arc;
+use std::time::Duration;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use teloxide::types::UserId;
+use tracing::{debug, error, info, warn};
+
+use crate::block_chain::{Blockchain, ChainType};
+use crate::bot::api::TelegramApi;
+use crate::bot::errors::record_telegram_error;
+use crate::bot::lanes::{ChatLanes, LaneTurn};
+use crate::db::models::GroupBot;
+use crate::db::operations::{
+    consume_challenge, finish_verification_session, get_group_bot, get_group_subjects, get_verification_session, record_member_verified, record_moderation_event,
+    resolve_pending_verification, schedule_onboarding, set_gated_member, set_group_banned,
+};
+use crate::error::{parse_telegram_id, AppError};
+use crate::metrics;
+use crate::routes::challenge::challenge_message;
+use crate::routes::signature::ChallengeRequest;
+
+// Longest the background balance check of an optimistic admission may take, a member
+// whose balance cannot be read by then is restricted again
+const OPTIMISTIC_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Verifies members signing on one chain
+pub struct VerificationService {
+    chain: Arc<dyn Blockchain>,
+    telegram: Arc<dyn TelegramApi>,
+    pool: PgPool,
+    // Bot lanes grants take their group's turn on, so they stay in order with its updates
+    lanes: Option<ChatLanes>,
+}
+
+impl VerificationService {
+    pub fn new(chain: Box<dyn Blockchain>, telegram: Box<dyn TelegramApi>, pool: PgPool) -> Self {
+        Self { chain: chain.into(), telegram: telegram.into(), pool, lanes: None }
+    }
+
+    /// Grant access during the group's turn on the bot lanes, after the joins it is handling
+    pub fn with_lanes(mut self, lanes: ChatLanes) -> Self {
+        self.lanes = Some(lanes);
+        self
+    }
+
+    /// Check the signed challenge and unmute the member if they hold shares, returns whether they were admitted
+    pub async fn verify(&self, request: &ChallengeRequest) -> Result<bool, AppError> {
+        let chain_type = self.chain.chain_type();
+        // The challenge is the Telegram user id of the member being verified
+        parse_telegram_id(&request.challenge)?;
+
+        // Burn the nonce before checking the signature so a signature is never accepted twice
+        if !consume_challenge(&self.pool, &request.nonce, &request.challenge, &request.chat_id, chain_type).await? {
+            return Err(AppError::InvalidSignature("Challenge nonce is invalid, expired or already used".to_string()));
+        }
+
+        // A session must still be pending and belong to the same user and chat
+        if let Some(session_id) = &request.session_id {
+            let valid = match get_verification_session(&self.pool, session_id).await? {
+                Some(session) => {
+                    session.effective_status() == "pending"
+                        && session.telegram_id == request.challenge
+                        && session.chat_id == request.chat_id
+                        && session.chain_type == chain_type
+                },
+                None => false,
+            };
+            if !valid {
+                return Err(AppError::BadRequest("Verification session is invalid or expired".to_string()));
+            }
+        }
+
+        let bot_info = get_verified_group_bot(&self.pool, &request.chat_id, chain_type).await?;
+
+        let user = chain_type.normalize_address(&request.user);
+        let message = challenge_message(&request.challenge, &request.chat_id, &request.nonce);
+        if self.signer_matches(&message, &request.signature, &user) {
+            let admitted = if bot_info.optimistic_verify {
+                self.admit_optimistically(&bot_info, &request.challenge, &user).await
+            } else {
+                self.bind_and_admit(&bot_info, &request.challenge, &user).await
+            };
+            match admitted {
+                Ok(true) => {
+                    self.finish_session(&request.session_id, "completed").await;
+                    return Ok(true);
+                },
+                Ok(false) => {},
+                Err(e) => {
+                    self.finish_session(&request.session_id, "failed").await;
+                    return Err(e);
+                },
+            }
+        }
+
+        self.finish_session(&request.session_id, "failed").await;
+        Ok(false)
+    }
+
+    /// Whether `signature` over `message` was made by the wallet `user` claims
+    pub fn signer_matches(&self, message: &str, signature: &str, user: &str) -> bool {
+        match self.chain.verify_signature(message, signature, user) {
+            Ok(verified_address) if user == verified_address => {
+                debug!("Address matches! Verified: {}, Expected: {}", verified_address, user);
+                true
+            },
+            Ok(verified_address) => {
+                warn!("Address mismatch with signature! Verified: {}, Expected: {}", verified_address, user);
+                false
+            },
+            Err(e) => {
+                warn!("Verify signature failed: {:?}", e);
+                false
+            },
+        }
+    }
+
+    /// See [`bind_and_admit`]
+    pub async fn bind_and_admit(&self, bot_info: &GroupBot, telegram_id: &str, address: &str) -> Result<bool, AppError> {
+        bind_and_admit(&self.pool, self.chain.as_ref(), self.telegram.as_ref(), self.lanes.as_ref(), bot_info, telegram_id, address).await
+    }
+
+    /// Bind the wallet and unmute the member without waiting for the chain, then check
+    /// their balance in the background: a holder is verified as usual, anyone else is
+    /// restricted again within [`OPTIMISTIC_CHECK_TIMEOUT`]. Always admits
+    pub async fn admit_optimistically(&self, bot_info: &GroupBot, telegram_id: &str, address: &str) -> Result<bool, AppError> {
+        let chain_type = self.chain.chain_type();
+        let user_id = parse_telegram_id(telegram_id)?;
+        save_user_mapping(&self.pool, chain_type, telegram_id, address).await;
+        {
+            let _turn = group_turn(self.lanes.as_ref(), bot_info).await;
+            unmute_member(&self.pool, self.telegram.as_ref(), bot_info, user_id).await?;
+        }
+        metrics::OPTIMISTIC_VERIFICATIONS.with_label_values(&[chain_type.as_str(), "granted"]).inc();
+
+        let pool = self.pool.clone();
+        let blockchain = self.chain.clone();
+        let telegram = self.telegram.clone();
+        let lanes = self.lanes.clone();
+        let (bot_info, telegram_id, address) = (bot_info.clone(), telegram_id.to_string(), address.to_string());
+        tokio::spawn(async move {
+            confirm_optimistic_admission(&pool, blockchain.as_ref(), telegram.as_ref(), lanes.as_ref(), &bot_info, &telegram_id, &address).await;
+        });
+        Ok(true)
+    }
+
+    // Record the outcome on the verification session the request came from, if any
+    async fn finish_session(&self, session_id: &Option<String>, status: &str) {
+        if let Some(session_id) = session_id {
+            if let Err(e) = finish_verification_session(&self.pool, session_id, status).await {
+                error!("Failed to update verification session {}: {:?}", session_id, e);
+            }
+        }
+    }
+}
+
+/// Bot of the group a member verifies for
+pub async fn get_verified_group_bot(pool: &PgPool, chat_id: &str, chain_type: ChainType) -> Result<GroupBot, AppError> {
+    get_group_bot(pool, chat_id, chain_type).await?.ok_or_else(|| {
+        warn!("No bot info found for chat_id: {} and chain: {}", chat_id, chain_type);
+        AppError::NotFound(format!("Bot not found for this chat_id in {} chain", chain_type))
+    })
+}
+
+// The group's turn on the bot lanes, None without lanes or when they are shut down
+async fn group_turn(lanes: Option<&ChatLanes>, bot_info: &GroupBot) -> Option<LaneTurn> {
+    let chat_id = bot_info.chat_group_id.parse().ok()?;
+    lanes?.turn(chat_id).await
+}
+
+/// Bind a proven wallet to the Telegram user and unmute them in the group if
+/// the wallet holds the shares the group's subjects require, returns whether they were admitted.
+/// With `lanes` the member is unmuted during the group's turn, see [`crate::bot::lanes`]
+pub async fn bind_and_admit(
+    pool: &PgPool,
+    blockchain: &dyn Blockchain,
+    telegram: &dyn TelegramApi,
+    lanes: Option<&ChatLanes>,
+    bot_info: &GroupBot,
+    telegram_id: &str,
+    address: &str,
+) -> Result<bool, AppError> {
+    let chain_type = blockchain.chain_type();
+    let user_id = parse_telegram_id(telegram_id)?;
+    save_user_mapping(pool, chain_type, telegram_id, address).await;
+
+    let subjects = get_group_subjects(pool, &bot_info.agent_name).await?;
+    let holds = holds_required_shares(blockchain, bot_info, &subjects, address).await;
+    count_gated_member(pool, chain_type, bot_info, telegram_id, address, holds).await;
+    if !holds {
+        return Ok(false);
+    }
+
+    let _turn = group_turn(lanes, bot_info).await;
+    unmute_member(pool, telegram, bot_info, user_id).await?;
+    lift_group_ban(pool, chain_type, bot_info, address).await;
+    finish_admission(pool, bot_info, telegram_id).await;
+    Ok(true)
+}
+
+// Save user address and Telegram ID, moving the address over if it was bound to someone else
+async fn save_user_mapping(pool: &PgPool, chain_type: ChainType, telegram_id: &str, address: &str) {
+    let result = sqlx::query!(
+        "INSERT INTO user_mappings (address, telegram_id, chain_type)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (address, chain_type) DO UPDATE SET telegram_id = $2",
+        address,
+        telegram_id,
+        chain_type.as_str()
+    )
+        .execute(pool)
+        .await;
+
+    if let Err(e) = result {
+        error!("Failed to save user mapping: {:?}", e);
+    }
+}
+
+async fn count_gated_member(pool: &PgPool, chain_type: ChainType, bot_info: &GroupBot, telegram_id: &str, address: &str, holds: bool) {
+    let result = match pool.acquire().await {
+        Ok(mut conn) => set_gated_member(&mut conn, &bot_info.agent_name, address, chain_type, telegram_id, holds).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        error!("Failed to count {} as a member of {}: {:?}", address, bot_info.agent_name, e);
+    }
+}
+
+// Clear the wallet's ban from the group it was admitted to again
+async fn lift_group_ban(pool: &PgPool, chain_type: ChainType, bot_info: &GroupBot, address: &str) {
+    let result = match pool.acquire().await {
+        Ok(mut conn) => set_group_banned(&mut conn, &bot_info.agent_name, address, chain_type, false).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        error!("Failed to lift the ban of {} from {}: {:?}", address, bot_info.agent_name, e);
+    }
+}
+
+// Close the member's pending verification and queue their onboarding once they are admitted
+async fn finish_admission(pool: &PgPool, bot_info: &GroupBot, telegram_id: &str) {
+    if let Err(e) = resolve_pending_verification(pool, telegram_id, &bot_info.chat_group_id, "verified").await {
+        error!("Failed to close pending verification of user {}: {:?}", telegram_id, e);
+    }
+    if let Err(e) = record_member_verified(pool, &bot_info.agent_name, &bot_info.chat_group_id, telegram_id).await {
+        error!("Failed to record verification of user {} for {}: {:?}", telegram_id, bot_info.agent_name, e);
+    }
+
+    match schedule_onboarding(pool, &bot_info.agent_name, telegram_id, &bot_info.chat_group_id).await {
+        Ok(queued) if queued > 0 => info!("Queued {} onboarding messages for user {}", queued, telegram_id),
+        Ok(_) => {},
+        Err(e) => error!("Failed to queue onboarding for user {}: {:?}", telegram_id, e),
+    }
+}
+
+// Settle a member admitted before their balance was known
+async fn confirm_optimistic_admission(
+    pool: &PgPool,
+    blockchain: &dyn Blockchain,
+    telegram: &dyn TelegramApi,
+    lanes: Option<&ChatLanes>,
+    bot_info: &GroupBot,
+    telegram_id: &str,
+    address: &str,
+) {
+    let chain_type = blockchain.chain_type();
+    let holds = match get_group_subjects(pool, &bot_info.agent_name).await {
+        Ok(subjects) => tokio::time::timeout(OPTIMISTIC_CHECK_TIMEOUT, holds_required_shares(blockchain, bot_info, &subjects, address))
+            .await
+            .unwrap_or_else(|_| {
+                warn!("Balance check of optimistically admitted user {} timed out", telegram_id);
+                false
+            }),
+        Err(e) => {
+            error!("Failed to load subjects of {}: {:?}", bot_info.agent_name, e);
+            false
+        }
+    };
+    count_gated_member(pool, chain_type, bot_info, telegram_id, address, holds).await;
+
+    if holds {
+        metrics::OPTIMISTIC_VERIFICATIONS.with_label_values(&[chain_type.as_str(), "confirmed"]).inc();
+        lift_group_ban(pool, chain_type, bot_info, address).await;
+        finish_admission(pool, bot_info, telegram_id).await;
+        return;
+    }
+
+    metrics::OPTIMISTIC_VERIFICATIONS.with_label_values(&[chain_type.as_str(), "reverted"]).inc();
+    let Ok(user_id) = parse_telegram_id(telegram_id) else {
+        return;
+    };
+    let permissions = bot_info.enforcement_mode.restricted_permissions();
+    let _turn = group_turn(lanes, bot_info).await;
+    if let Err(e) = telegram.restrict_chat_member(&bot_info.bot_token, &bot_info.chat_group_id, UserId(user_id), permissions).await {
+        error!("Failed to restrict optimistically admitted user {} again: {:?}", telegram_id, e);
+        record_telegram_error(pool, &bot_info.agent_name, &bot_info.chat_group_id, &e).await;
+        return;
+    }
+    info!("Restricted optimistically admitted user {} of {} again, {} lacks the shares", telegram_id, bot_info.agent_name, address);
+    let details = Some(format!("{} does not hold the shares the group requires", address));
+    if let Err(e) = record_moderation_event(pool, &bot_info.agent_name, &bot_info.chat_group_id, Some(telegram_id), "optimistic_revert", details, None).await {
+        error!("Failed to log moderation event: {:?}", e);
+    }
+}
+
+/// Whether the on-chain balances of `address` satisfy the group's rule over `subjects`.
+/// A balance that cannot be read does not admit
+pub async fn holds_required_shares(blockchain: &dyn Blockchain, bot_info: &GroupBot, subjects: &[String], address: &str) -> bool {
+    let mut balances: Vec<BigDecimal> = Vec::with_capacity(subjects.len());
+    for subject in subjects {
+        match blockchain.get_shares_balance(subject, address).await {
+            Ok(balance) => {
+                debug!("User {} balance for subject {}: {}, group requires {}", address, subject, balance, bot_info.min_shares);
+                balances.push(balance);
+            },
+            Err(e) => {
+                error!("Failed to get shares balance: {:?}", e);
+                return false;
+            }
+        }
+    }
+    bot_info.subject_rule.admits(&balances, &bot_info.min_shares)
+}
+
+// Give a verified member the regular permissions in the group
+async fn unmute_member(pool: &PgPool, telegram: &dyn TelegramApi, bot_info: &GroupBot, user_id: u64) -> Result<(), AppError> {
+    let unmuted = telegram.restore_chat_member(&bot_info.bot_token, &bot_info.chat_group_id, UserId(user_id)).await;
+    if let Err(e) = unmuted {
+        error!("Failed to unmute verified user {}: {:?}", user_id, e);
+        record_telegram_error(pool, &bot_info.agent_name, &bot_info.chat_group_id, &e).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use anyhow::{anyhow, Result};
+    use async_trait::async_trait;
+    use tokio_util::sync::CancellationToken;
+    use crate::bot::api::{MockTelegramApi, TelegramCall};
+    use crate::enforcement::{member_permissions, EnforcementMode, SubjectRule};
+
+    /// Chain whose signatures all recover `signer` and whose balances are fixed
+    struct MockChain {
+        signer: Option<String>,
+        balances: HashMap<String, u64>,
+    }
+
+    #[async_trait]
+    impl Blockchain for MockChain {
+        fn chain_type(&self) -> ChainType {
+            ChainType::Monad
+        }
+
+        async fn sync_events(&self, _pool: &PgPool, _lanes: &ChatLanes, _shutdown: &CancellationToken) -> Result<()> {
+            Ok(())
+        }
+
+        fn verify_signature(&self, _challenge: &str, _signature: &str, _user: &str) -> Result<String, AppError> {
+            self.signer.clone().ok_or_else(|| AppError::InvalidSignature("Malformed signature".to_string()))
+        }
+
+        async fn get_shares_balance(&self, subject: &str, _user: &str) -> Result<BigDecimal> {
+            self.balances.get(subject).map(|balance| BigDecimal::from(*balance)).ok_or_else(|| anyhow!("RPC unavailable"))
+        }
+
+        async fn get_gas_price(&self) -> Result<u128> {
+            Ok(0)
+        }
+    }
+
+    fn chain(signer: Option<&str>, balances: &[(&str, u64)]) -> MockChain {
+        MockChain {
+            signer: signer.map(str::to_string),
+            balances: balances.iter().map(|(subject, balance)| (subject.to_string(), *balance)).collect(),
+        }
+    }
+
+    fn group(subject_rule: SubjectRule, min_shares: u64) -> GroupBot {
+        GroupBot {
+            agent_name: "alice".to_string(),
+            bot_token: "token".to_string(),
+            chat_group_id: "-100".to_string(),
+            subject_address: "0x1".to_string(),
+            min_shares: BigDecimal::from(min_shares),
+            subject_rule,
+            enforcement_mode: EnforcementMode::Mute,
+            optimistic_verify: false,
+        }
+    }
+
+    // Never connected, the tested paths do not reach the database
+    fn pool() -> PgPool {
+        PgPool::connect_lazy("postgres://localhost/alice_ai_test").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signer_must_match_claimed_address() {
+        let service = |signer| VerificationService::new(Box::new(chain(signer, &[])), Box::new(MockTelegramApi::new()), pool());
+
+        assert!(service(Some("0xaa")).signer_matches("message", "signature", "0xaa"));
+        assert!(!service(Some("0xbb")).signer_matches("message", "signature", "0xaa"));
+        assert!(!service(None).signer_matches("message", "signature", "0xaa"));
+    }
+
+    #[tokio::test]
+    async fn test_required_shares_follow_subject_rule() {
+        let subjects = vec!["0x1".to_string(), "0x2".to_string()];
+        let blockchain = chain(None, &[("0x1", 3), ("0x2", 0)]);
+
+        assert!(holds_required_shares(&blockchain, &group(SubjectRule::Any, 2), &subjects, "0xaa").await);
+        assert!(!holds_required_shares(&blockchain, &group(SubjectRule::All, 2), &subjects, "0xaa").await);
+        assert!(!holds_required_shares(&blockchain, &group(SubjectRule::Any, 5), &subjects, "0xaa").await);
+
+        // A balance that cannot be read keeps the member out
+        let unreadable = vec!["0x1".to_string(), "0x3".to_string()];
+        assert!(!holds_required_shares(&blockchain, &group(SubjectRule::Any, 1), &unreadable, "0xaa").await);
+    }
+
+    #[tokio::test]
+    async fn test_unmute_uses_group_bot() {
+        let telegram = MockTelegramApi::new();
+        unmute_member(&pool(), &telegram, &group(SubjectRule::Any, 1), 42).await.unwrap();
+        assert_eq!(telegram.calls(), vec![
+            TelegramCall::Restrict { chat_id: "-100".to_string(), user_id: 42, permissions: member_permissions() },
+        ]);
+    }
+}
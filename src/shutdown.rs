@@ -0,0 +1,16 @@
+//! Cooperative shutdown for the background loops.
+//!
+//! `main` owns a [`CancellationToken`] that is cancelled on Ctrl+C. Every sync
+//! loop and bot task receives a clone and stops at its next checkpoint, so no
+//! loop is abandoned halfway through a database write.
+
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Sleep for `duration` unless shutdown is requested first, returns true on shutdown
+pub async fn sleep_or_shutdown(shutdown: &CancellationToken, duration: Duration) -> bool {
+    tokio::select! {
+        _ = shutdown.cancelled() => true,
+        _ = tokio::time::sleep(duration) => false,
+    }
+}
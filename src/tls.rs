@@ -0,0 +1,25 @@
+use std::fs::File;
+use std::io::BufReader;
+use rustls::ServerConfig;
+
+use crate::error::AppError;
+
+/// Build the rustls server configuration from PEM certificate chain and private key files
+pub fn load_tls_config(cert_path: &str, key_path: &str) -> Result<ServerConfig, AppError> {
+    let cert_file = File::open(cert_path)
+        .map_err(|e| AppError::Config(format!("Cannot open TLS_CERT_PATH {}: {}", cert_path, e)))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::Config(format!("Invalid certificate in {}: {}", cert_path, e)))?;
+
+    let key_file = File::open(key_path)
+        .map_err(|e| AppError::Config(format!("Cannot open TLS_KEY_PATH {}: {}", key_path, e)))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(key_file))
+        .map_err(|e| AppError::Config(format!("Invalid private key in {}: {}", key_path, e)))?
+        .ok_or_else(|| AppError::Config(format!("No private key found in {}", key_path)))?;
+
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| AppError::Config(format!("Invalid TLS certificate/key pair: {}", e)))
+}
@@ -0,0 +1,260 @@
+//! Signed webhook notifications of trade and membership events.
+//!
+//! Agent owners register callback URLs for the events of their subject. An
+//! event is queued with [`enqueue`] in the transaction of the trade or
+//! enforcement that caused it, so a rolled back trade never notifies anyone,
+//! and [`webhook_delivery_loop`] POSTs the queued events as JSON. Every body is
+//! signed with the webhook's secret: `X-Alice-Signature` carries
+//! `sha256=<hex>`, the HMAC-SHA256 of `<X-Alice-Timestamp>.<body>`. Failed
+//! deliveries are retried with exponential backoff until
+//! [`MAX_DELIVERY_ATTEMPTS`] were made.
+
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{PgConnection, PgPool};
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::block_chain::ChainType;
+use crate::db::models::DueWebhookDelivery;
+use crate::db::operations::{enqueue_webhook_deliveries, finish_webhook_delivery, get_due_webhook_deliveries};
+use crate::metrics;
+use crate::shutdown::sleep_or_shutdown;
+
+pub const SIGNATURE_HEADER: &str = "X-Alice-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Alice-Timestamp";
+pub const EVENT_HEADER: &str = "X-Alice-Event";
+pub const DELIVERY_HEADER: &str = "X-Alice-Delivery";
+
+/// Attempts made before a delivery is given up on
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 10;
+
+// Maximum number of deliveries attempted per pass
+const WEBHOOK_BATCH_SIZE: i64 = 50;
+// Wait before the first retry, doubled after every further failure up to the cap
+const RETRY_BASE_DELAY_SECS: i64 = 30;
+const RETRY_MAX_DELAY_SECS: i64 = 3600;
+// Slow receivers must not hold up the deliveries queued behind them
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Event types a webhook can subscribe to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "trade.buy")]
+    TradeBuy,
+    #[serde(rename = "trade.sell")]
+    TradeSell,
+    /// A member was muted or kicked for holding too few shares
+    #[serde(rename = "member.banned")]
+    MemberBanned,
+    /// A banned member bought back in and got their access back
+    #[serde(rename = "member.unbanned")]
+    MemberUnbanned,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] =
+        [WebhookEvent::TradeBuy, WebhookEvent::TradeSell, WebhookEvent::MemberBanned, WebhookEvent::MemberUnbanned];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TradeBuy => "trade.buy",
+            WebhookEvent::TradeSell => "trade.sell",
+            WebhookEvent::MemberBanned => "member.banned",
+            WebhookEvent::MemberUnbanned => "member.unbanned",
+        }
+    }
+
+    pub fn trade(is_buy: bool) -> Self {
+        if is_buy { WebhookEvent::TradeBuy } else { WebhookEvent::TradeSell }
+    }
+}
+
+/// Data of a `trade.buy` or `trade.sell` event, share amounts in whole shares
+#[derive(Clone, Debug, Serialize)]
+pub struct TradeEventData {
+    pub chain_type: ChainType,
+    pub subject: String,
+    pub trader: String,
+    pub share_amount: String,
+    pub eth_amount: String,
+    /// Trader's balance after the trade
+    pub balance: String,
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_number: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub block_time: Option<OffsetDateTime>,
+}
+
+/// Data of a `member.banned` or `member.unbanned` event
+#[derive(Clone, Debug, Serialize)]
+pub struct MemberEventData {
+    pub agent_name: String,
+    pub chat_id: String,
+    pub telegram_id: String,
+    pub chain_type: ChainType,
+    pub address: String,
+    pub subject: String,
+    /// Moderation action taken, e.g. mute, kick, restore or rejoin_link
+    pub action: String,
+    pub balance: String,
+}
+
+// Signed body of a delivery
+#[derive(Debug, Serialize)]
+struct WebhookBody<'a> {
+    id: i64,
+    event: &'a str,
+    agent_name: &'a str,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    data: serde_json::Value,
+}
+
+/// Queue `event` for the webhooks of the agent gating `subject`, within the caller's
+/// transaction. Returns the number of deliveries queued
+pub async fn enqueue<T: Serialize>(
+    conn: &mut PgConnection,
+    chain_type: ChainType,
+    subject: &str,
+    event: WebhookEvent,
+    data: &T,
+) -> anyhow::Result<u64> {
+    let payload = serde_json::to_string(data)?;
+    Ok(enqueue_webhook_deliveries(conn, chain_type, subject, event.as_str(), &payload).await?)
+}
+
+/// A new random signing secret, returned once to the webhook's creator
+pub fn generate_secret() -> String {
+    format!("whsec_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<body>` under `secret`
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Wait before retrying a delivery that failed `attempts` times, `None` once it is out of attempts
+pub fn retry_delay(attempts: i32) -> Option<time::Duration> {
+    if attempts >= MAX_DELIVERY_ATTEMPTS {
+        return None;
+    }
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    let secs = RETRY_BASE_DELAY_SECS.saturating_mul(1 << exponent).min(RETRY_MAX_DELAY_SECS);
+    Some(time::Duration::seconds(secs))
+}
+
+// POST one signed delivery, any 2xx response counts as delivered
+async fn deliver(client: &Client, delivery: &DueWebhookDelivery) -> Result<(), String> {
+    let data = serde_json::from_str(&delivery.payload).map_err(|e| format!("Invalid stored payload: {}", e))?;
+    let body = serde_json::to_string(&WebhookBody {
+        id: delivery.id,
+        event: &delivery.event_type,
+        agent_name: &delivery.agent_name,
+        created_at: delivery.created_at,
+        data,
+    })
+    .map_err(|e| e.to_string())?;
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+
+    let response = client
+        .post(&delivery.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={}", sign(&delivery.secret, timestamp, &body)))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+// Attempt the deliveries that are due and schedule retries of the ones that failed
+pub async fn send_due_webhooks(pool: &PgPool, client: &Client) -> Result<usize, sqlx::Error> {
+    let deliveries = get_due_webhook_deliveries(pool, WEBHOOK_BATCH_SIZE).await?;
+    let count = deliveries.len();
+
+    for delivery in deliveries {
+        let error = deliver(client, &delivery).await.err();
+        let attempts = delivery.attempts + 1;
+        let retry_at = error.as_ref()
+            .and_then(|_| retry_delay(attempts))
+            .map(|delay| OffsetDateTime::now_utc() + delay);
+        let result = match (&error, retry_at) {
+            (None, _) => "delivered",
+            (Some(error), Some(retry_at)) => {
+                warn!("Webhook {} delivery {} failed (attempt {}), retrying at {}: {}", delivery.webhook_id, delivery.id, attempts, retry_at, error);
+                "retried"
+            }
+            (Some(error), None) => {
+                error!("Webhook {} delivery {} failed after {} attempts, giving up: {}", delivery.webhook_id, delivery.id, attempts, error);
+                "failed"
+            }
+        };
+        metrics::WEBHOOK_DELIVERIES.with_label_values(&[&delivery.event_type, result]).inc();
+        finish_webhook_delivery(pool, delivery.id, error, retry_at).await?;
+    }
+
+    Ok(count)
+}
+
+pub async fn webhook_delivery_loop(pool: PgPool, interval_secs: u64, shutdown: CancellationToken) {
+    let client = Client::new();
+    while !shutdown.is_cancelled() {
+        match send_due_webhooks(&pool, &client).await {
+            Ok(count) if count > 0 => info!("Processed {} webhook deliveries", count),
+            Ok(_) => {},
+            Err(e) => error!("Webhook delivery failed: {:?}", e),
+        }
+        sleep_or_shutdown(&shutdown, Duration::from_secs(interval_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        // HMAC-SHA256 of "1700000000.{}" under "secret"
+        let signature = sign("secret", 1_700_000_000, "{}");
+        assert_eq!(signature, "b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163");
+        // The timestamp is signed, a replayed body with a new timestamp does not verify
+        assert_ne!(signature, sign("secret", 1_700_000_001, "{}"));
+        assert_ne!(signature, sign("other", 1_700_000_000, "{}"));
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        assert_eq!(retry_delay(1), Some(time::Duration::seconds(30)));
+        assert_eq!(retry_delay(2), Some(time::Duration::seconds(60)));
+        assert_eq!(retry_delay(4), Some(time::Duration::seconds(240)));
+        assert_eq!(retry_delay(8), Some(time::Duration::seconds(RETRY_MAX_DELAY_SECS)));
+        assert_eq!(retry_delay(MAX_DELIVERY_ATTEMPTS), None);
+    }
+
+    #[test]
+    fn test_event_names() {
+        for event in WebhookEvent::ALL {
+            assert_eq!(serde_json::to_value(event).unwrap(), serde_json::Value::from(event.as_str()));
+        }
+        assert_eq!(WebhookEvent::trade(false), WebhookEvent::TradeSell);
+    }
+}
@@ -0,0 +1,91 @@
+//! Group bans through renames and deletions of agents, against a real database.
+//!
+//! A wallet banned from two agents' groups must keep its bans when one agent is
+//! renamed and stay marked banned in `user_mappings` exactly as long as one of them
+//! is left. Needs a scratch database that the migrations are applied to:
+//!
+//! `TEST_DATABASE_URL=postgres://localhost/alice_ai_test cargo test --test group_bans -- --ignored`
+
+use std::env;
+
+use alice_ai_server::block_chain::ChainType;
+use alice_ai_server::db::operations::{delete_agent_with_export_window, is_group_banned, rename_agent, set_group_banned};
+use alice_ai_server::db::run_migrations;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn pool() -> PgPool {
+    let url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+    let pool = PgPool::connect(&url).await.expect("Failed to connect to the test database");
+    run_migrations(&pool).await.expect("Failed to run migrations");
+    pool
+}
+
+async fn add_agent(pool: &PgPool, agent_name: &str, chat_id: &str) {
+    sqlx::query(
+        "INSERT INTO telegram_bots (agent_name, invite_url, bot_token, chat_group_id, subject_address, chain_type)
+         VALUES ($1, 'https://t.me/+bans', 'token', $2, $3, 'monad')",
+    )
+    .bind(agent_name)
+    .bind(chat_id)
+    .bind(format!("subject_{}", agent_name))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn is_banned(pool: &PgPool, address: &str) -> bool {
+    let (is_banned,): (bool,) = sqlx::query_as("SELECT is_banned FROM user_mappings WHERE address = $1")
+        .bind(address)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    is_banned
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn test_bans_follow_renames_and_deletions() {
+    let pool = pool().await;
+    let suffix = Uuid::new_v4().simple().to_string();
+    let (first, second, renamed) = (format!("bans_a_{}", suffix), format!("bans_b_{}", suffix), format!("bans_c_{}", suffix));
+    let address = suffix.clone();
+
+    add_agent(&pool, &first, "-1007101").await;
+    add_agent(&pool, &second, "-1007102").await;
+    sqlx::query("INSERT INTO user_mappings (address, telegram_id, chain_type) VALUES ($1, '7101', 'monad')")
+        .bind(&address)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let mut conn = pool.acquire().await.unwrap();
+    set_group_banned(&mut conn, &first, &address, ChainType::Monad, true).await.unwrap();
+    set_group_banned(&mut conn, &second, &address, ChainType::Monad, true).await.unwrap();
+    assert!(is_banned(&pool, &address).await);
+
+    // The ban moves along with the renamed agent
+    assert!(rename_agent(&pool, &first, &renamed).await.unwrap().is_some());
+    assert!(is_group_banned(&mut conn, &renamed, &address, ChainType::Monad).await.unwrap());
+    assert!(!is_group_banned(&mut conn, &first, &address, ChainType::Monad).await.unwrap());
+
+    // Deleting one agent leaves the ban from the other group
+    assert!(delete_agent_with_export_window(&pool, &renamed, "test", 0).await.unwrap().is_some());
+    assert!(is_banned(&pool, &address).await);
+
+    // Once the last ban goes with its agent the wallet is no longer banned
+    assert!(delete_agent_with_export_window(&pool, &second, "test", 0).await.unwrap().is_some());
+    assert!(!is_banned(&pool, &address).await);
+
+    for agent_name in [&renamed, &second] {
+        sqlx::query("DELETE FROM agent_deletions WHERE agent_name = $1")
+            .bind(agent_name)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("DELETE FROM user_mappings WHERE address = $1")
+        .bind(&address)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
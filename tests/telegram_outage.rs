@@ -0,0 +1,184 @@
+//! Enforcement through a Telegram outage, against a real database.
+//!
+//! A holder who sold out is walked up their agent's escalation ladder while the
+//! mocked Telegram API times out, rate limits or rejects the bot. The step must
+//! stay queued, be retried by every pass and be applied exactly once after
+//! Telegram recovers. Needs a scratch database that the migrations are applied to:
+//!
+//! `TEST_DATABASE_URL=postgres://localhost/alice_ai_test cargo test --test telegram_outage -- --ignored`
+
+use std::env;
+
+use alice_ai_server::block_chain::ChainType;
+use alice_ai_server::bot::api::{MockTelegramApi, Outage, TelegramCall};
+use alice_ai_server::bot::errors::TelegramErrorKind;
+use alice_ai_server::bot::escalation::run_due_escalations;
+use alice_ai_server::db::models::NewEscalation;
+use alice_ai_server::db::operations::start_escalation;
+use alice_ai_server::db::run_migrations;
+use alice_ai_server::enforcement::EnforcementMode;
+use alice_ai_server::AppConfig;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const LADDER: &str = r#"[{"action":"read_only","delay_secs":0},{"action":"kick","delay_secs":86400}]"#;
+
+async fn pool() -> PgPool {
+    let url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+    let pool = PgPool::connect(&url).await.expect("Failed to connect to the test database");
+    run_migrations(&pool).await.expect("Failed to run migrations");
+    pool
+}
+
+// Only the buy link of warnings is read from the configuration, the required settings get placeholders
+fn config() -> AppConfig {
+    let placeholders = [
+        ("TELEGRAM_BOT_TOKEN", "token"),
+        ("TELEGRAM_GROUP_ID", "-100"),
+        ("SHARES_CONTRACT_ADDRESS", "0x0000000000000000000000000000000000000000"),
+        ("CHAIN_RPC", "http://localhost:8545"),
+        ("DATABASE_URL", "postgres://localhost/alice_ai_test"),
+        ("START_BLOCK", "0"),
+    ];
+    for (key, value) in placeholders {
+        if env::var(key).is_err() {
+            env::set_var(key, value);
+        }
+    }
+    AppConfig::from_env()
+}
+
+/// Agent with a read-only then kick ladder and a verified member holding none of its shares
+struct SoldOut {
+    agent_name: String,
+    chat_id: String,
+    telegram_id: u64,
+    address: String,
+}
+
+async fn seed(pool: &PgPool, telegram_id: u64) -> SoldOut {
+    let suffix = Uuid::new_v4().simple().to_string();
+    let holder = SoldOut {
+        agent_name: format!("outage_{}", suffix),
+        chat_id: format!("-100{}", telegram_id),
+        telegram_id,
+        address: suffix.clone(),
+    };
+
+    sqlx::query(
+        "INSERT INTO telegram_bots (agent_name, invite_url, bot_token, chat_group_id, subject_address, chain_type, escalation_ladder)
+         VALUES ($1, 'https://t.me/+outage', 'token', $2, $3, 'monad', $4)",
+    )
+    .bind(&holder.agent_name)
+    .bind(&holder.chat_id)
+    .bind(format!("subject{}", suffix))
+    .bind(LADDER)
+    .execute(pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO user_mappings (address, telegram_id, chain_type) VALUES ($1, $2, 'monad')")
+        .bind(&holder.address)
+        .bind(telegram_id.to_string())
+        .execute(pool)
+        .await
+        .unwrap();
+
+    let mut conn = pool.acquire().await.unwrap();
+    let started = start_escalation(&mut conn, &NewEscalation {
+        agent_name: holder.agent_name.clone(),
+        chat_id: holder.chat_id.clone(),
+        telegram_id: telegram_id.to_string(),
+        chain_type: ChainType::Monad,
+        address: holder.address.clone(),
+        subject: format!("subject{}", suffix),
+        trace_id: suffix,
+    }, 0).await.unwrap();
+    assert!(started);
+    holder
+}
+
+async fn cleanup(pool: &PgPool, holder: &SoldOut) {
+    for query in [
+        "DELETE FROM enforcement_escalations WHERE agent_name = $1",
+        "DELETE FROM moderation_events WHERE agent_name = $1",
+        "DELETE FROM telegram_errors WHERE agent_name = $1",
+        "DELETE FROM telegram_bots WHERE agent_name = $1",
+    ] {
+        sqlx::query(query).bind(&holder.agent_name).execute(pool).await.unwrap();
+    }
+    sqlx::query("DELETE FROM user_mappings WHERE address = $1")
+        .bind(&holder.address)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+// Step the escalation runs next and whether the member is marked banned
+async fn state(pool: &PgPool, holder: &SoldOut) -> (i32, bool) {
+    let (next_step,): (i32,) = sqlx::query_as("SELECT next_step FROM enforcement_escalations WHERE agent_name = $1")
+        .bind(&holder.agent_name)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    let (is_banned,): (bool,) = sqlx::query_as("SELECT is_banned FROM user_mappings WHERE address = $1")
+        .bind(&holder.address)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    (next_step, is_banned)
+}
+
+// Calls of the mock aimed at the seeded member, other due escalations of the database are ignored
+fn calls_for(calls: Vec<TelegramCall>, holder: &SoldOut) -> Vec<TelegramCall> {
+    calls
+        .into_iter()
+        .filter(|call| matches!(call, TelegramCall::Restrict { user_id, .. } | TelegramCall::Kick { user_id, .. } if *user_id == holder.telegram_id))
+        .collect()
+}
+
+// Passes over due escalations share the database, so the outages run one after another
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn test_escalation_survives_telegram_outage() {
+    let pool = pool().await;
+    let config = config();
+
+    let outages = [
+        (7001, Outage::Timeout, TelegramErrorKind::Network),
+        (7002, Outage::FloodWait(30), TelegramErrorKind::FloodWait),
+        (7003, Outage::Forbidden, TelegramErrorKind::Forbidden),
+    ];
+    for (telegram_id, outage, kind) in outages {
+        let holder = seed(&pool, telegram_id).await;
+        let telegram = MockTelegramApi::new();
+        telegram.set_outage(Some(outage));
+
+        // Every pass during the outage tries the step again and leaves it queued
+        for attempt in 1..=3 {
+            run_due_escalations(&pool, &telegram, &config).await.unwrap();
+            assert_eq!(calls_for(telegram.failed_calls(), &holder).len(), attempt, "{:?}", outage);
+            assert_eq!(state(&pool, &holder).await, (0, false), "{:?}", outage);
+        }
+        assert!(calls_for(telegram.calls(), &holder).is_empty());
+        let (logged_kind, count): (String, i64) = sqlx::query_as("SELECT kind, count::bigint FROM telegram_errors WHERE agent_name = $1")
+            .bind(&holder.agent_name)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!((logged_kind.as_str(), count), (kind.as_str(), 3));
+
+        // Telegram recovers: the step is applied once and the escalation moves on to the kick a day later
+        telegram.set_outage(None);
+        run_due_escalations(&pool, &telegram, &config).await.unwrap();
+        run_due_escalations(&pool, &telegram, &config).await.unwrap();
+        let restricted = TelegramCall::Restrict {
+            chat_id: holder.chat_id.clone(),
+            user_id: telegram_id,
+            permissions: EnforcementMode::Mute.restricted_permissions(),
+        };
+        assert_eq!(calls_for(telegram.calls(), &holder), vec![restricted], "{:?}", outage);
+        assert_eq!(state(&pool, &holder).await, (1, true), "{:?}", outage);
+
+        cleanup(&pool, &holder).await;
+    }
+}